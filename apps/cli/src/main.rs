@@ -1,8 +1,16 @@
 use clap::{Parser, Subcommand};
-use dnx_core::events::{DnxEvent, DnxObserver, LogLevel};
-use dnx_core::session::{DnxSession, SessionConfig};
+use dnx_core::events::{
+    CompositeObserver, DnxEvent, DnxObserver, JsonlObserver, LogLevel, PacketLogger,
+};
+use dnx_core::protocol::GpFlags;
+use dnx_core::protocol::constants::INTEL_VENDOR_ID;
+use dnx_core::session::{DnxSession, Operation, Preamble, SessionConfig, SessionError};
+use dnx_core::state::OverallProgress;
+use dnx_core::{DeviceSelector, NusbTransport, ProfileRegistry, TransportError, UsbBackend};
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
 use tracing::{error, info};
 
 #[derive(Parser, Debug)]
@@ -37,9 +45,9 @@ struct Args {
     #[arg(long)]
     misc_dnx: Option<String>,
 
-    /// General Purpose flags (hex)
+    /// General Purpose flags: hex and/or flag names, e.g. `80000007` or `dnx_os,0x80000007`
     #[arg(long, default_value = "0")]
-    gp_flags: u32,
+    gp_flags: GpFlags,
 
     /// Enable IFWI wipe mode
     #[arg(long)]
@@ -49,10 +57,82 @@ struct Args {
     #[arg(long)]
     config: Option<String>,
 
-    /// Hardware profile to use (e.g., 'eaglespeak', 'blackburn')
+    /// Operation to perform (fw-only, os-only, fw-then-os, wipe); inferred from paths if omitted
+    #[arg(long)]
+    operation: Option<Operation>,
+
+    /// Handshake preambles to send, comma-separated (e.g. 'DnER,IDRQ'); defaults to DnER
+    #[arg(long, value_delimiter = ',')]
+    handshake: Vec<Preamble>,
+
+    /// Hardware profile to use (built-in 'eaglespeak', 'blackburn', or one from profiles.toml)
     #[arg(short, long)]
     profile: Option<String>,
 
+    /// Append every session event as JSON lines to this file
+    #[arg(long)]
+    event_log: Option<String>,
+
+    /// Keep a JSON checkpoint of session progress in this file
+    #[arg(long)]
+    checkpoint: Option<String>,
+
+    /// Write every USB packet, with its full bytes, as JSON lines to this file
+    #[arg(long, value_name = "PATH")]
+    trace: Option<String>,
+
+    /// Print what would be sent and exit without touching USB
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Bulk OUT transfers kept in flight within one write (1 = one at a time)
+    #[arg(long)]
+    queue_depth: Option<usize>,
+
+    /// Open the device with this USB vendor ID (hex, needs --pid)
+    #[arg(long, value_parser = parse_hex_u16, requires = "pid")]
+    vid: Option<u16>,
+
+    /// Open the device with this USB product ID (hex), bypassing the supported-PID list
+    #[arg(long, value_parser = parse_hex_u16)]
+    pid: Option<u16>,
+
+    /// Use the device with this USB serial number when several are attached
+    #[arg(long, conflicts_with = "bus_addr")]
+    serial: Option<String>,
+
+    /// Use the device at this USB bus and address (e.g. 1:5) when several are attached
+    #[arg(long, value_parser = parse_bus_addr, value_name = "BUS:ADDR")]
+    bus_addr: Option<DeviceSelector>,
+
+    /// Consecutive failed ACK reads before giving up (default 20)
+    #[arg(long)]
+    max_read_retries: Option<u32>,
+
+    /// Milliseconds to wait for each ACK before retrying (default 5000)
+    #[arg(long)]
+    read_timeout_ms: Option<u64>,
+
+    /// USB library: auto (nusb, then libusb if it cannot open the device), nusb, libusb
+    #[arg(long)]
+    backend: Option<UsbBackend>,
+
+    /// Refuse to flash images that fail validation
+    #[arg(long)]
+    strict: bool,
+
+    /// Continue an interrupted OS image after the last acknowledged chunk
+    #[arg(long)]
+    resume_os: bool,
+
+    /// Device error ACK to retry by re-sending the last component (e.g. ER04)
+    #[arg(long = "retry-error", value_name = "CODE")]
+    retry_errors: Vec<String>,
+
+    /// Retries allowed per component for --retry-error codes
+    #[arg(long)]
+    max_retries: Option<u32>,
+
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
@@ -65,6 +145,10 @@ enum Commands {
         /// Hardware profile to use
         #[arg(short, long)]
         profile: Option<String>,
+
+        /// Keep running: flash every device that appears until Ctrl-C
+        #[arg(long)]
+        watch: bool,
     },
 
     /// Dump IFWI version information from firmware image
@@ -83,17 +167,87 @@ enum Commands {
         markdown: bool,
     },
 
+    /// Compare the component versions of two IFWI images
+    #[command(name = "ifwi-diff")]
+    IfwiDiff {
+        /// Reference (old) image
+        #[arg(required = true)]
+        file1: String,
+
+        /// Candidate (new) image
+        #[arg(required = true)]
+        file2: String,
+    },
+
     /// Analyze firmware file structure
     Analyze {
         /// Path to firmware file
         #[arg(required = true)]
         file: String,
+
+        /// Output in JSON format
+        #[arg(long, conflicts_with = "markdown")]
+        json: bool,
+
+        /// Output in markdown format
+        #[arg(long)]
+        markdown: bool,
+    },
+
+    /// Dump the OSIP partition table of an OS image
+    Osip {
+        /// Path to OS image file
+        #[arg(required = true)]
+        file: String,
+
+        /// Output in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a hex dump of part of a file
+    Hexdump {
+        /// Path to file
+        #[arg(required = true)]
+        file: String,
+
+        /// Start offset (decimal or 0x-prefixed hex)
+        #[arg(long, default_value = "0", value_parser = parse_offset)]
+        offset: usize,
+
+        /// Number of bytes to dump (decimal or 0x-prefixed hex)
+        #[arg(long, default_value = "256", value_parser = parse_offset)]
+        len: usize,
+    },
+
+    /// List attached devices in DnX mode
+    Devices {
+        /// Output in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Write an example config file, or check the one given with --config
+    Config {
+        /// Print a commented TOML config listing every setting
+        #[arg(long)]
+        generate: bool,
+
+        /// Write the example to this file instead of stdout
+        #[arg(short, long, requires = "generate")]
+        output: Option<String>,
     },
 }
 
 /// CLI observer that prints progress to stderr.
 struct CliObserver {
     verbose: bool,
+    /// Latest overall percentage, shown next to per-component progress.
+    overall: AtomicU8,
+    /// Latest transfer rate in bytes/s (0 = not measured yet).
+    rate: AtomicU64,
+    /// Latest time remaining in seconds (`u64::MAX` = unknown).
+    eta: AtomicU64,
 }
 
 impl DnxObserver for CliObserver {
@@ -105,6 +259,49 @@ impl DnxObserver for CliObserver {
             DnxEvent::DeviceDisconnected => {
                 eprintln!("✗ Device disconnected");
             }
+            DnxEvent::PlatformDetected { platform } => {
+                eprintln!("✓ Platform: {}", platform);
+            }
+            DnxEvent::WaitingForDevice {
+                elapsed_secs,
+                timeout_secs,
+            } => {
+                eprintln!(
+                    "… Still waiting for device ({}s / {}s)",
+                    elapsed_secs, timeout_secs
+                );
+            }
+            DnxEvent::OverallProgress {
+                bytes_sent,
+                bytes_total,
+            } => {
+                if *bytes_sent == 0 {
+                    eprintln!("→ Sending {} in total", dnx_core::format_size(*bytes_total));
+                }
+                let progress = OverallProgress {
+                    bytes_sent: *bytes_sent,
+                    bytes_total: *bytes_total,
+                };
+                self.overall.store(progress.percent(), Ordering::Relaxed);
+            }
+            DnxEvent::Throughput {
+                bytes_per_sec,
+                eta_secs,
+            } => {
+                self.rate.store(*bytes_per_sec, Ordering::Relaxed);
+                self.eta
+                    .store(eta_secs.unwrap_or(u64::MAX), Ordering::Relaxed);
+            }
+            DnxEvent::UnexpectedDevice {
+                vid,
+                pid,
+                expected_pid,
+            } => {
+                eprintln!(
+                    "! Ignoring device {:04X}:{:04X} (waiting for PID {:04X})",
+                    vid, pid, expected_pid
+                );
+            }
             DnxEvent::PhaseChanged { from, to } => {
                 if self.verbose {
                     eprintln!("→ Phase: {} → {}", from, to);
@@ -116,12 +313,25 @@ impl DnxObserver for CliObserver {
                 current,
                 total,
             } => {
-                let pct = if *total > 0 {
-                    (*current * 100) / *total
-                } else {
-                    0
-                };
-                eprint!("\r[{:>3}%] {}: {}", pct, phase, operation);
+                let pct = (*current * 100).checked_div(*total).unwrap_or(0);
+                let mut speed = String::new();
+                let rate = self.rate.load(Ordering::Relaxed);
+                if rate > 0 {
+                    speed = format!(", {}/s", dnx_core::format_size(rate));
+                    let eta = self.eta.load(Ordering::Relaxed);
+                    if eta != u64::MAX {
+                        speed.push_str(&format!(", ~{}s", eta));
+                    }
+                }
+                // Trailing spaces clear leftovers of a longer previous line
+                eprint!(
+                    "\r[{:>3}%] {}: {}{} (overall {}%)   ",
+                    pct,
+                    phase,
+                    operation,
+                    speed,
+                    self.overall.load(Ordering::Relaxed)
+                );
                 if *current == *total {
                     eprintln!(); // Newline when complete
                 }
@@ -142,6 +352,26 @@ impl DnxObserver for CliObserver {
             DnxEvent::Error { code, message } => {
                 eprintln!("✗ Error [{}]: {}", code, message);
             }
+            DnxEvent::Retry {
+                code,
+                component,
+                attempt,
+                max_retries,
+            } => {
+                eprintln!(
+                    "! {} from device, re-sending {} ({}/{})",
+                    code, component, attempt, max_retries
+                );
+            }
+            DnxEvent::RetryExhausted {
+                retries,
+                last_error,
+            } => {
+                eprintln!("✗ Giving up after {} retries: {}", retries, last_error);
+            }
+            DnxEvent::Cancelled => {
+                eprintln!("✗ Cancelled");
+            }
             DnxEvent::Complete => {
                 eprintln!("✓ Operation complete!");
             }
@@ -153,6 +383,35 @@ impl DnxObserver for CliObserver {
     }
 }
 
+/// Parse a USB ID given as hex, with or without a `0x` prefix.
+fn parse_hex_u16(s: &str) -> Result<u16, String> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|e| format!("invalid USB ID '{}': {}", s, e))
+}
+
+/// Parse a byte offset or length, decimal or `0x`-prefixed hex.
+fn parse_offset(s: &str) -> Result<usize, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|e| format!("invalid number '{}': {}", s, e))
+}
+
+fn parse_bus_addr(s: &str) -> Result<DeviceSelector, String> {
+    let (bus, address) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected BUS:ADDR, got '{}'", s))?;
+    let parse = |v: &str| {
+        v.parse::<u8>()
+            .map_err(|e| format!("invalid bus/address '{}': {}", s, e))
+    };
+    Ok(DeviceSelector::BusAddr {
+        bus: parse(bus)?,
+        address: parse(address)?,
+    })
+}
+
 fn cmd_ifwi_version(
     file: &str,
     json: bool,
@@ -175,7 +434,10 @@ fn cmd_ifwi_version(
         println!("  \"hooks_oem\": \"{}\",", versions.valhooks);
         println!("  \"ia32\": \"{}\",", versions.ia32);
         println!("  \"chaabi\": \"{}\",", versions.chaabi);
-        println!("  \"mia\": \"{}\"", versions.mia);
+        println!("  \"mia\": \"{}\",", versions.mia);
+        println!("  \"umip\": \"{}\",", versions.umip);
+        println!("  \"mipd\": \"{}\",", versions.mipd);
+        println!("  \"mipn\": \"{}\"", versions.mipn);
         println!("}}");
     } else if markdown {
         // Markdown table output
@@ -188,7 +450,35 @@ fn cmd_ifwi_version(
     Ok(())
 }
 
-fn cmd_analyze(file: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn cmd_ifwi_diff(file1: &str, file2: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let load = |file: &str| -> Result<dnx_core::FirmwareVersions, Box<dyn std::error::Error>> {
+        let data = std::fs::read(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        Ok(dnx_core::get_image_fw_rev(&data)?)
+    };
+    let old = load(file1)?;
+    let new = load(file2)?;
+
+    let changes = old.diff(&new);
+    if changes.is_empty() {
+        println!("No component versions differ");
+        return Ok(());
+    }
+
+    let show = |v: dnx_core::Version| {
+        if v.is_valid() {
+            v.to_string()
+        } else {
+            "(absent)".to_string()
+        }
+    };
+    println!("{:<14} {:<10} {:<10}", "Component", "Old", "New");
+    for (component, a, b) in changes {
+        println!("{:<14} {:<10} {:<10}", component, show(a), show(b));
+    }
+    Ok(())
+}
+
+fn cmd_analyze(file: &str, json: bool, markdown: bool) -> Result<(), Box<dyn std::error::Error>> {
     let path = Path::new(file);
 
     if !path.exists() {
@@ -199,62 +489,316 @@ fn cmd_analyze(file: &str) -> Result<(), Box<dyn std::error::Error>> {
     let analysis = dnx_core::FirmwareAnalysis::analyze(path)?;
 
     // Print results
-    println!("{}", analysis.to_text());
+    if json {
+        println!("{}", analysis.to_json());
+    } else if markdown {
+        println!("{}", analysis.to_markdown());
+    } else {
+        println!("{}", analysis.to_text());
+    }
 
     Ok(())
 }
 
-fn cmd_download(args: &Args, profile: Option<&String>) -> Result<(), Box<dyn std::error::Error>> {
+fn cmd_osip(file: &str, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new(file);
+
+    if !path.exists() {
+        return Err(format!("File not found: {}", file).into());
+    }
+
+    let image = dnx_core::OsImage::from_path(path)?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&image.partition_entries())?
+        );
+    } else {
+        print!("{}", image.describe());
+    }
+
+    Ok(())
+}
+
+fn cmd_hexdump(file: &str, offset: usize, len: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+    if offset >= data.len() {
+        return Err(format!(
+            "Offset 0x{:X} is past the end of {} bytes",
+            offset,
+            data.len()
+        )
+        .into());
+    }
+    let end = offset.saturating_add(len).min(data.len());
+    print!("{}", dnx_core::hexdump(&data[offset..end], offset));
+    Ok(())
+}
+
+fn cmd_config(
+    generate: bool,
+    output: Option<&str>,
+    config: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if generate {
+        let example = SessionConfig::example_toml();
+        match output {
+            Some(path) => {
+                std::fs::write(path, example)?;
+                eprintln!("✓ Wrote example config to {}", path);
+            }
+            None => print!("{}", example),
+        }
+        return Ok(());
+    }
+
+    let path = config.ok_or("Nothing to do: pass --generate, or --config <file> to check it")?;
+    let config = SessionConfig::load_from_file(path)?;
+    println!("✓ {} is valid ({})", path, config.effective_operation());
+    Ok(())
+}
+
+fn cmd_devices(json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let devices = NusbTransport::list()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+        return Ok(());
+    }
+    if devices.is_empty() {
+        println!("No DnX devices found");
+        return Ok(());
+    }
+    for d in &devices {
+        let location = match d.bus {
+            Some(bus) => format!("{}:{}", bus, d.address),
+            None => format!("?:{}", d.address),
+        };
+        println!(
+            "{:04X}:{:04X}  {:<16}  bus:addr {:<7}  serial {}",
+            d.vid,
+            d.pid,
+            d.platform,
+            location,
+            d.serial.as_deref().unwrap_or("-")
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_download(
+    args: &Args,
+    profile: Option<&String>,
+    watch: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     let mut fw_dnx = args.fw_dnx.clone();
+    let mut fw_image = args.fw_image.clone();
+    let mut os_dnx = args.os_dnx.clone();
     let mut os_image = args.os_image.clone();
+    let mut gp_flags = args.gp_flags.bits();
 
     let effective_profile = profile.or(args.profile.as_ref());
 
-    if let Some(profile) = effective_profile {
-        match profile.as_str() {
-            "eaglespeak" => {
-                fw_dnx = fw_dnx.or(Some("assets/firmware/eaglespeak/dnx_fwr.bin".to_string()));
-                os_image = os_image.or(Some("assets/firmware/eaglespeak/dnx_osr.img".to_string()));
-                info!("Using profile: eaglespeak (Atom Z3580)");
-            }
-            "blackburn" => {
-                fw_dnx = fw_dnx.or(Some("assets/firmware/blackburn/dnx_fwr.bin".to_string()));
-                os_image = os_image.or(Some("assets/firmware/blackburn/dnx_osr.img".to_string()));
-                info!("Using profile: blackburn (Atom Z3530)");
-            }
-            _ => {
-                error!("Unknown profile: {}", profile);
-                return Err(format!(
-                    "Unknown profile '{}'. Available: eaglespeak, blackburn",
-                    profile
-                )
-                .into());
-            }
+    if let Some(name) = effective_profile {
+        let registry = ProfileRegistry::load()?;
+        let profile = registry.get(name)?;
+        fw_dnx = fw_dnx.or_else(|| profile.fw_dnx.clone());
+        fw_image = fw_image.or_else(|| profile.fw_image.clone());
+        os_dnx = os_dnx.or_else(|| profile.os_dnx.clone());
+        os_image = os_image.or_else(|| profile.os_image.clone());
+        if gp_flags == 0 {
+            gp_flags = profile.gp_flags.unwrap_or(0);
         }
+        info!("Using profile: {} ({})", profile.name, profile.description);
     }
 
     // Load config from file or default, then merge CLI overrides
-    let config = SessionConfig::load_or_default(args.config.as_deref())?
+    let mut config = SessionConfig::load_or_default(args.config.as_deref())?
         .merge(
             fw_dnx,
-            args.fw_image.clone(),
-            args.os_dnx.clone(),
+            fw_image,
+            os_dnx,
             os_image,
             args.misc_dnx.clone(),
-            Some(args.gp_flags),
+            Some(gp_flags),
             Some(args.ifwi_wipe),
         )
         .with_defaults();
+    if args.operation.is_some() {
+        config.operation = args.operation;
+    }
+    if !args.handshake.is_empty() {
+        config.handshake = args.handshake.clone();
+    }
+    config.strict |= args.strict;
+    config.dry_run |= args.dry_run;
+    config.resume_os |= args.resume_os;
+    if args.checkpoint.is_some() {
+        config.checkpoint_path = args.checkpoint.clone();
+    }
+    if let Some(pid) = args.pid {
+        config.device_filter = Some((args.vid.unwrap_or(INTEL_VENDOR_ID), pid));
+    }
+    if let Some(serial) = &args.serial {
+        config.device_selector = Some(DeviceSelector::Serial(serial.clone()));
+    } else if let Some(selector) = &args.bus_addr {
+        config.device_selector = Some(selector.clone());
+    }
+    if let Some(depth) = args.queue_depth {
+        config.write_queue_depth = depth;
+    }
+    if let Some(retries) = args.max_read_retries {
+        config.max_read_retries = retries;
+    }
+    if let Some(ms) = args.read_timeout_ms {
+        config.read_timeout_ms = ms;
+    }
+    if let Some(backend) = args.backend {
+        config.backend = backend;
+    }
+    config
+        .error_policy
+        .retryable
+        .extend(args.retry_errors.iter().cloned());
+    if let Some(max) = args.max_retries {
+        config.error_policy.max_retries = max;
+    }
+
+    // Report every configuration problem at once before touching the device
+    if let Err(errors) = config.validate() {
+        for e in &errors {
+            eprintln!("✗ {e}");
+        }
+        return Err(format!("Invalid configuration ({} problem(s))", errors.len()).into());
+    }
 
-    let observer = Arc::new(CliObserver {
+    let mut observer = CompositeObserver::new().with(Arc::new(CliObserver {
         verbose: args.verbose,
-    });
-    let mut session = DnxSession::with_observer(config, observer);
+        overall: AtomicU8::new(0),
+        rate: AtomicU64::new(0),
+        eta: AtomicU64::new(u64::MAX),
+    }));
+    if let Some(path) = &args.event_log {
+        observer = observer.with(Arc::new(JsonlObserver::new(path)?));
+    }
+    if let Some(path) = &args.trace {
+        observer = observer.with(Arc::new(PacketLogger::new(path)?));
+    }
+    let watch_state = Arc::new(WatchState::default());
+    if watch {
+        observer = observer.with(watch_state.clone());
+    }
+    let observer = Arc::new(observer);
+
+    // Print the plan itself rather than relying on the session's log events
+    if config.dry_run {
+        let mut session = DnxSession::with_observer(config, observer);
+        session.load_files()?;
+        println!("{}", session.plan()?);
+        return Ok(());
+    }
+
+    if watch {
+        return cmd_watch(config, observer, watch_state);
+    }
 
+    let mut session = DnxSession::with_observer(config, observer);
     session.run()?;
     Ok(())
 }
 
+/// Counters and flags shared with the Ctrl-C handler in watch mode.
+#[derive(Default)]
+struct WatchState {
+    flashed: AtomicUsize,
+    failed: AtomicUsize,
+    /// A device is connected and being flashed.
+    busy: AtomicBool,
+    stop: AtomicBool,
+}
+
+impl WatchState {
+    fn tally(&self) -> String {
+        format!(
+            "{} flashed, {} failed",
+            self.flashed.load(Ordering::SeqCst),
+            self.failed.load(Ordering::SeqCst)
+        )
+    }
+}
+
+impl DnxObserver for WatchState {
+    fn on_event(&self, event: &DnxEvent) {
+        if let DnxEvent::DeviceConnected { .. } = event {
+            self.busy.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+fn cmd_watch(
+    config: SessionConfig,
+    observer: Arc<CompositeObserver>,
+    state: Arc<WatchState>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let handler_state = state.clone();
+    ctrlc::set_handler(move || {
+        let already_stopping = handler_state.stop.swap(true, Ordering::SeqCst);
+        if !handler_state.busy.load(Ordering::SeqCst) {
+            eprintln!("\nWatch stopped: {}", handler_state.tally());
+            std::process::exit(0);
+        }
+        if already_stopping {
+            eprintln!("\nAborted: {}", handler_state.tally());
+            std::process::exit(130);
+        }
+        eprintln!("\nStopping after the current device (Ctrl-C again to abort)");
+    })?;
+
+    eprintln!("Watching for devices (Ctrl-C to stop)...");
+    while !state.stop.load(Ordering::SeqCst) {
+        state.busy.store(false, Ordering::SeqCst);
+        let mut session = DnxSession::with_observer(config.clone(), observer.clone());
+
+        match session.run_with_summary() {
+            Ok(summary) => {
+                let n = state.flashed.fetch_add(1, Ordering::SeqCst) + 1;
+                eprintln!("✓ Device #{} done: {}", n, summary);
+            }
+            Err(e) if state.busy.load(Ordering::SeqCst) => {
+                state.failed.fetch_add(1, Ordering::SeqCst);
+                eprintln!("✗ Flash failed: {}", e);
+            }
+            // No device within the retry window; keep waiting
+            Err(
+                SessionError::Timeout { .. }
+                | SessionError::Transport(
+                    TransportError::DeviceNotFound { .. } | TransportError::Timeout { .. },
+                ),
+            ) => continue,
+            // Permissions, ambiguity, bad inputs: retrying cannot help
+            Err(e) => {
+                eprintln!("Watch stopped: {}", state.tally());
+                return Err(e.into());
+            }
+        }
+        state.busy.store(false, Ordering::SeqCst);
+
+        eprintln!("{}; unplug the device to continue...", state.tally());
+        while !state.stop.load(Ordering::SeqCst)
+            && !NusbTransport::attached_supported_pids()
+                .unwrap_or_default()
+                .is_empty()
+        {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+    }
+
+    eprintln!("Watch stopped: {}", state.tally());
+    Ok(())
+}
+
 fn main() {
     let args = Args::parse();
 
@@ -282,11 +826,24 @@ fn main() {
             json,
             markdown,
         }) => cmd_ifwi_version(file, *json, *markdown),
-        Some(Commands::Analyze { file }) => cmd_analyze(file),
-        Some(Commands::Download { profile }) => cmd_download(&args, profile.as_ref()),
+        Some(Commands::IfwiDiff { file1, file2 }) => cmd_ifwi_diff(file1, file2),
+        Some(Commands::Analyze {
+            file,
+            json,
+            markdown,
+        }) => cmd_analyze(file, *json, *markdown),
+        Some(Commands::Osip { file, json }) => cmd_osip(file, *json),
+        Some(Commands::Hexdump { file, offset, len }) => cmd_hexdump(file, *offset, *len),
+        Some(Commands::Devices { json }) => cmd_devices(*json),
+        Some(Commands::Config { generate, output }) => {
+            cmd_config(*generate, output.as_deref(), args.config.as_deref())
+        }
+        Some(Commands::Download { profile, watch }) => {
+            cmd_download(&args, profile.as_ref(), *watch)
+        }
         None => {
             // Default behavior: run download
-            cmd_download(&args, args.profile.as_ref())
+            cmd_download(&args, args.profile.as_ref(), false)
         }
     };
 
@@ -0,0 +1,51 @@
+//! `dnx analyze` output format dispatch.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A minimal DnX firmware file in the test's temp directory.
+fn firmware_file(name: &str) -> PathBuf {
+    let mut data = vec![0u8; 0x400];
+    data[0x80..0x84].copy_from_slice(b"$DnX");
+    let path = std::env::temp_dir().join(format!("dnx-cli-{}-{}.bin", name, std::process::id()));
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn analyze(args: &[&str], name: &str) -> std::process::Output {
+    let path = firmware_file(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_dnx"))
+        .arg("analyze")
+        .arg(&path)
+        .args(args)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(path);
+    output
+}
+
+#[test]
+fn analyze_json_emits_filename() {
+    let output = analyze(&["--json"], "json");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"filename\""));
+    serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+}
+
+#[test]
+fn analyze_markdown_emits_table() {
+    let output = analyze(&["--markdown"], "md");
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("| Property | Value |")
+    );
+}
+
+#[test]
+fn analyze_formats_are_exclusive() {
+    let output = analyze(&["--json", "--markdown"], "both");
+    assert!(!output.status.success());
+}
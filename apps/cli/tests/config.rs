@@ -0,0 +1,47 @@
+//! `dnx config` example generation and checking.
+
+use std::process::Command;
+
+fn dnx(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_dnx"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn config_generate_round_trips() {
+    let output = dnx(&["config", "--generate"]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("retry_timeout_secs")
+    );
+
+    let path = std::env::temp_dir().join(format!("dnx-cli-config-{}.toml", std::process::id()));
+    let path = path.to_str().unwrap();
+    assert!(dnx(&["config", "--generate", "-o", path]).status.success());
+    let check = dnx(&["--config", path, "config"]);
+    let _ = std::fs::remove_file(path);
+    assert!(check.status.success());
+    assert!(
+        String::from_utf8(check.stdout)
+            .unwrap()
+            .contains("is valid")
+    );
+}
+
+#[test]
+fn config_check_rejects_out_of_range_values() {
+    let path = std::env::temp_dir().join(format!("dnx-cli-range-{}.toml", std::process::id()));
+    std::fs::write(&path, "read_timeout_ms = 9999999\n").unwrap();
+    let output = dnx(&["--config", path.to_str().unwrap(), "config"]);
+    let _ = std::fs::remove_file(&path);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("read_timeout_ms")
+    );
+}
@@ -3,14 +3,17 @@
 //! Contains the app state (Model), input handling (Controller).
 
 use std::collections::VecDeque;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use dnx_core::events::{DnxEvent, DnxObserver, DnxPhase, LogLevel, PacketDirection};
 use dnx_core::firmware::FirmwareAnalysis;
-use dnx_core::session::{DnxSession, SessionConfig};
+use dnx_core::session::{CancelToken, DnxSession, SessionConfig, SessionError};
+use dnx_core::state::OverallProgress;
+
+use crate::browser::FileBrowser;
 
 /// Maximum log entries to keep.
 const MAX_LOG_ENTRIES: usize = 1000;
@@ -31,6 +34,10 @@ pub struct App {
     pub progress: u8,
     /// Current operation name.
     pub operation: String,
+    /// Bytes written across all phases, against the planned total.
+    pub overall: OverallProgress,
+    /// Latest transfer rate (bytes/s) and time remaining, once measured.
+    pub throughput: Option<(u64, Option<u64>)>,
     /// Log entries.
     pub logs: VecDeque<LogEntry>,
     /// Log scroll position.
@@ -50,12 +57,16 @@ pub struct App {
     pub observer: Arc<TuiObserver>,
     /// Background session thread handle.
     session_thread: Option<JoinHandle<()>>,
+    /// Cancels the running session's device wait.
+    cancel: CancelToken,
     /// Firmware analysis info (cached)
     pub fw_analysis: Option<FirmwareAnalysis>,
     /// Recent packets
     pub packets: VecDeque<PacketInfo>,
     /// Packet scroll position
     pub packet_scroll: usize,
+    /// Open file picker, drawn over the main view.
+    pub browser: Option<FileBrowser>,
 }
 
 /// Which pane is focused.
@@ -144,6 +155,8 @@ impl App {
             phase: DnxPhase::WaitingForDevice,
             progress: 0,
             operation: String::new(),
+            overall: OverallProgress::default(),
+            throughput: None,
             logs: VecDeque::with_capacity(MAX_LOG_ENTRIES),
             log_scroll: 0,
             device_status: DeviceStatus::Disconnected,
@@ -155,9 +168,11 @@ impl App {
             is_running: false,
             observer: Arc::new(TuiObserver::new()),
             session_thread: None,
+            cancel: CancelToken::new(),
             fw_analysis: None,
             packets: VecDeque::with_capacity(100),
             packet_scroll: 0,
+            browser: None,
         }
     }
 
@@ -185,6 +200,12 @@ impl App {
 
     /// Handle keyboard input. Returns true if app should quit.
     pub fn on_key(&mut self, key: KeyEvent) -> bool {
+        // The file picker is modal
+        if self.browser.is_some() {
+            self.handle_browser_key(key);
+            return false;
+        }
+
         // Global shortcuts
         match key.code {
             KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
@@ -200,6 +221,10 @@ impl App {
                     self.current_tab = Tab::Main;
                     return false;
                 }
+                if self.is_running && !self.cancel.is_cancelled() {
+                    self.stop_operation();
+                    return false;
+                }
                 self.should_quit = true;
                 return true;
             }
@@ -241,30 +266,30 @@ impl App {
                     Focus::Status => Focus::Config,
                 };
             }
-            KeyCode::Up => {
-                if self.focus == Focus::Config && self.input_focus > 0 {
-                    self.input_focus -= 1;
-                }
+            KeyCode::Up if self.focus == Focus::Config && self.input_focus > 0 => {
+                self.input_focus -= 1;
             }
-            KeyCode::Down => {
-                if self.focus == Focus::Config && self.input_focus < 3 {
-                    self.input_focus += 1;
-                }
+            KeyCode::Down if self.focus == Focus::Config && self.input_focus < 3 => {
+                self.input_focus += 1;
             }
-            KeyCode::Enter => {
-                if self.focus == Focus::Config && !self.is_running {
-                    self.start_operation();
-                }
+            KeyCode::Enter if self.focus == Focus::Config && !self.is_running => {
+                self.start_operation();
             }
-            KeyCode::Char(c) => {
-                if self.focus == Focus::Config {
-                    self.input_char(c);
-                }
+            KeyCode::Enter if self.focus == Focus::Config => {
+                self.stop_operation();
             }
-            KeyCode::Backspace => {
-                if self.focus == Focus::Config {
-                    self.delete_char();
-                }
+            KeyCode::Char('o')
+                if key.modifiers.contains(KeyModifiers::CONTROL)
+                    && self.focus == Focus::Config
+                    && !self.is_running =>
+            {
+                self.open_browser();
+            }
+            KeyCode::Char(c) if self.focus == Focus::Config => {
+                self.input_char(c);
+            }
+            KeyCode::Backspace if self.focus == Focus::Config => {
+                self.delete_char();
             }
             _ => {}
         }
@@ -275,10 +300,10 @@ impl App {
             KeyCode::Up | KeyCode::Char('k') => {
                 self.log_scroll = self.log_scroll.saturating_sub(1);
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                if self.log_scroll < self.logs.len().saturating_sub(1) {
-                    self.log_scroll += 1;
-                }
+            KeyCode::Down | KeyCode::Char('j')
+                if self.log_scroll < self.logs.len().saturating_sub(1) =>
+            {
+                self.log_scroll += 1;
             }
             KeyCode::PageUp => {
                 self.log_scroll = self.log_scroll.saturating_sub(10);
@@ -301,10 +326,10 @@ impl App {
             KeyCode::Up | KeyCode::Char('k') => {
                 self.packet_scroll = self.packet_scroll.saturating_sub(1);
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                if self.packet_scroll < self.packets.len().saturating_sub(1) {
-                    self.packet_scroll += 1;
-                }
+            KeyCode::Down | KeyCode::Char('j')
+                if self.packet_scroll < self.packets.len().saturating_sub(1) =>
+            {
+                self.packet_scroll += 1;
             }
             KeyCode::PageUp => {
                 self.packet_scroll = self.packet_scroll.saturating_sub(10);
@@ -323,14 +348,85 @@ impl App {
         }
     }
 
+    fn handle_browser_key(&mut self, key: KeyEvent) {
+        let Some(browser) = self.browser.as_mut() else {
+            return;
+        };
+        let result = match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                browser.up();
+                Ok(None)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                browser.down();
+                Ok(None)
+            }
+            KeyCode::Left | KeyCode::Backspace => browser.parent().map(|_| None),
+            KeyCode::Enter | KeyCode::Right => browser.activate(),
+            KeyCode::Esc => {
+                self.browser = None;
+                return;
+            }
+            _ => Ok(None),
+        };
+
+        match result {
+            Ok(Some(path)) => {
+                let field = browser.field;
+                self.browser = None;
+                self.pick_file(field, path);
+            }
+            Ok(None) => {}
+            Err(e) => self.add_log(LogLevel::Warn, format!("Cannot open directory: {}", e)),
+        }
+    }
+
+    /// Open the file picker for the focused path field.
+    fn open_browser(&mut self) {
+        let field = self.input_focus;
+        let Some(current) = self.field_mut(field).map(|f| f.clone()) else {
+            return;
+        };
+        match FileBrowser::open(field, &current) {
+            Ok(browser) => self.browser = Some(browser),
+            Err(e) => self.add_log(LogLevel::Warn, format!("Cannot open file browser: {}", e)),
+        }
+    }
+
+    /// Fill `field` with a path chosen in the file picker.
+    fn pick_file(&mut self, field: usize, path: PathBuf) {
+        if !path.is_file() {
+            self.add_log(
+                LogLevel::Error,
+                format!("File not found: {}", path.display()),
+            );
+            return;
+        }
+        let value = path.to_string_lossy().into_owned();
+        self.add_log(LogLevel::Info, format!("Selected {}", value));
+        if let Some(f) = self.field_mut(field) {
+            *f = value;
+        }
+        if field == 0 {
+            self.analyze_firmware();
+        }
+    }
+
+    /// Path input field by index.
+    fn field_mut(&mut self, index: usize) -> Option<&mut String> {
+        match index {
+            0 => Some(&mut self.fw_dnx_path),
+            1 => Some(&mut self.fw_image_path),
+            2 => Some(&mut self.os_dnx_path),
+            3 => Some(&mut self.os_image_path),
+            _ => None,
+        }
+    }
+
     fn input_char(&mut self, c: char) {
         let is_fw_dnx = self.input_focus == 0;
-        let field = match self.input_focus {
-            0 => &mut self.fw_dnx_path,
-            1 => &mut self.fw_image_path,
-            2 => &mut self.os_dnx_path,
-            3 => &mut self.os_image_path,
-            _ => return,
+        let Some(field) = self.field_mut(self.input_focus) else {
+            return;
         };
         field.push(c);
 
@@ -342,12 +438,8 @@ impl App {
 
     fn delete_char(&mut self) {
         let is_fw_dnx = self.input_focus == 0;
-        let field = match self.input_focus {
-            0 => &mut self.fw_dnx_path,
-            1 => &mut self.fw_image_path,
-            2 => &mut self.os_dnx_path,
-            3 => &mut self.os_image_path,
-            _ => return,
+        let Some(field) = self.field_mut(self.input_focus) else {
+            return;
         };
         field.pop();
 
@@ -357,6 +449,20 @@ impl App {
         }
     }
 
+    /// Ask the running session to stop; it reports `DnxEvent::Cancelled` once it has.
+    fn stop_operation(&mut self) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        self.cancel.cancel();
+        self.add_log(LogLevel::Warn, "Cancelling...");
+    }
+
+    /// Whether a stop has been requested for the running session.
+    pub fn is_stopping(&self) -> bool {
+        self.is_running && self.cancel.is_cancelled()
+    }
+
     fn start_operation(&mut self) {
         if self.is_running {
             return;
@@ -377,55 +483,65 @@ impl App {
         self.is_running = true;
         self.phase = DnxPhase::WaitingForDevice;
         self.progress = 0;
+        self.overall = OverallProgress::default();
+        self.throughput = None;
         self.operation = "Starting...".to_string();
 
-        // Build config from UI fields using the unified API
-        let session_config = SessionConfig::default()
-            .merge(
-                if self.fw_dnx_path.is_empty() {
-                    None
-                } else {
-                    Some(self.fw_dnx_path.clone())
-                },
-                if self.fw_image_path.is_empty() {
-                    None
-                } else {
-                    Some(self.fw_image_path.clone())
-                },
-                if self.os_dnx_path.is_empty() {
-                    None
-                } else {
-                    Some(self.os_dnx_path.clone())
-                },
-                if self.os_image_path.is_empty() {
-                    None
-                } else {
-                    Some(self.os_image_path.clone())
-                },
-                None, // misc_dnx
-                Some(self.config.gp_flags),
-                Some(self.config.ifwi_wipe_enable),
-            )
-            .with_defaults();
+        // Build config from UI fields; empty path fields are left unset
+        let mut builder = SessionConfig::builder()
+            .gp_flags(self.config.gp_flags)
+            .ifwi_wipe(self.config.ifwi_wipe_enable);
+        if !self.fw_dnx_path.is_empty() {
+            builder = builder.fw_dnx(&self.fw_dnx_path);
+        }
+        if !self.fw_image_path.is_empty() {
+            builder = builder.fw_image(&self.fw_image_path);
+        }
+        if !self.os_dnx_path.is_empty() {
+            builder = builder.os_dnx(&self.os_dnx_path);
+        }
+        if !self.os_image_path.is_empty() {
+            builder = builder.os_image(&self.os_image_path);
+        }
+        let session_config = builder.build().with_defaults();
+
+        if let Err(errors) = session_config.validate() {
+            for e in errors {
+                self.add_log(LogLevel::Error, e.to_string());
+            }
+            self.is_running = false;
+            self.operation = "Invalid configuration".to_string();
+            return;
+        }
 
         self.add_log(LogLevel::Info, "Operation started");
 
         // Clone observer for the thread
         let observer = self.observer.clone();
+        self.cancel = CancelToken::new();
+        let cancel = self.cancel.clone();
+        let session_cancel = self.cancel.clone();
 
         // Spawn session thread
         let handle = thread::spawn(move || {
-            let mut session = DnxSession::with_observer(session_config, observer.clone());
-            match session.run() {
-                Ok(_) => {
-                    observer.on_event(&DnxEvent::Complete);
-                }
-                Err(e) => {
-                    observer.on_event(&DnxEvent::Error {
-                        code: 1, // Generic error code
-                        message: format!("Session error: {}", e),
-                    });
-                }
+            let mut session = DnxSession::with_observer(session_config, observer.clone())
+                .with_cancel_token(session_cancel);
+            // The session reports Complete and Cancelled itself, and the
+            // handler already reported a device error ACK with its code
+            let message = match session.run() {
+                Ok(()) | Err(SessionError::Cancelled | SessionError::DeviceError { .. }) => None,
+                Err(_) if cancel.is_cancelled() => None,
+                Err(SessionError::Timeout { secs }) => Some(format!(
+                    "No device found within {}s; is it in DnX mode?",
+                    secs
+                )),
+                Err(e) => Some(format!("Session error: {}", e)),
+            };
+            if let Some(message) = message {
+                observer.on_event(&DnxEvent::Error {
+                    code: 1, // Generic error code
+                    message,
+                });
             }
         });
 
@@ -439,6 +555,18 @@ impl App {
         for event in events {
             self.process_dnx_event(event);
         }
+
+        // Join the session thread once it has returned, without blocking the UI
+        if self
+            .session_thread
+            .as_ref()
+            .is_some_and(|h| h.is_finished())
+            && let Some(handle) = self.session_thread.take()
+            && handle.join().is_err()
+        {
+            self.add_log(LogLevel::Error, "Session thread panicked");
+            self.is_running = false;
+        }
     }
 
     fn process_dnx_event(&mut self, event: DnxEvent) {
@@ -450,10 +578,50 @@ impl App {
                     format!("Device connected: {:04X}:{:04X}", vid, pid),
                 );
             }
+            DnxEvent::PlatformDetected { platform } => {
+                self.add_log(LogLevel::Info, format!("Platform: {}", platform));
+            }
             DnxEvent::DeviceDisconnected => {
                 self.device_status = DeviceStatus::Disconnected;
                 self.add_log(LogLevel::Warn, "Device disconnected");
             }
+            DnxEvent::WaitingForDevice {
+                elapsed_secs,
+                timeout_secs,
+            } => {
+                self.operation = format!(
+                    "Waiting for device ({}s / {}s, Esc to cancel)",
+                    elapsed_secs, timeout_secs
+                );
+            }
+            DnxEvent::OverallProgress {
+                bytes_sent,
+                bytes_total,
+            } => {
+                self.overall = OverallProgress {
+                    bytes_sent,
+                    bytes_total,
+                };
+            }
+            DnxEvent::Throughput {
+                bytes_per_sec,
+                eta_secs,
+            } => {
+                self.throughput = Some((bytes_per_sec, eta_secs));
+            }
+            DnxEvent::UnexpectedDevice {
+                vid,
+                pid,
+                expected_pid,
+            } => {
+                self.add_log(
+                    LogLevel::Warn,
+                    format!(
+                        "Ignoring device {:04X}:{:04X} (waiting for PID {:04X})",
+                        vid, pid, expected_pid
+                    ),
+                );
+            }
             DnxEvent::PhaseChanged { to, .. } => {
                 self.phase = to;
                 self.add_log(LogLevel::Info, format!("Phase: {}", to));
@@ -465,11 +633,7 @@ impl App {
                 ..
             } => {
                 self.operation = operation;
-                self.progress = if total > 0 {
-                    ((current * 100) / total) as u8
-                } else {
-                    0
-                };
+                self.progress = (current * 100).checked_div(total).unwrap_or(0) as u8;
             }
             DnxEvent::Log { level, message } => {
                 self.add_log(level, message);
@@ -481,9 +645,38 @@ impl App {
                 self.add_log(LogLevel::Error, message);
                 self.is_running = false;
             }
+            DnxEvent::Retry {
+                code,
+                component,
+                attempt,
+                max_retries,
+            } => {
+                self.add_log(
+                    LogLevel::Warn,
+                    format!(
+                        "{} from device, re-sending {} ({}/{})",
+                        code, component, attempt, max_retries
+                    ),
+                );
+            }
+            DnxEvent::RetryExhausted {
+                retries,
+                last_error,
+            } => {
+                self.add_log(
+                    LogLevel::Error,
+                    format!("Giving up after {} retries: {}", retries, last_error),
+                );
+            }
+            DnxEvent::Cancelled => {
+                self.is_running = false;
+                self.operation = "Cancelled".to_string();
+                self.add_log(LogLevel::Warn, "Operation cancelled by user");
+            }
             DnxEvent::Complete => {
                 self.is_running = false;
                 self.progress = 100;
+                self.overall.bytes_sent = self.overall.bytes_sent.max(self.overall.bytes_total);
                 self.add_log(LogLevel::Info, "Operation complete!");
             }
             DnxEvent::Packet {
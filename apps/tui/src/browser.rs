@@ -0,0 +1,128 @@
+//! Modal file browser for picking firmware and OS images.
+//!
+//! Lists subdirectories and `.bin`/`.img` files of one directory at a time;
+//! the config field it was opened from receives the chosen path.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File extensions offered for selection (case-insensitive).
+const EXTENSIONS: &[&str] = &["bin", "img"];
+
+/// One row in the browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// Browser state: the directory shown and the highlighted row.
+#[derive(Debug, Clone)]
+pub struct FileBrowser {
+    /// Config field (`App::input_focus`) to fill in.
+    pub field: usize,
+    pub dir: PathBuf,
+    pub entries: Vec<BrowserEntry>,
+    pub selected: usize,
+}
+
+impl FileBrowser {
+    /// Open at the directory of `current` if it has one, else the working directory.
+    pub fn open(field: usize, current: &str) -> io::Result<Self> {
+        let start = Path::new(current)
+            .parent()
+            .filter(|p| p.is_dir())
+            .map(Path::to_path_buf)
+            .map_or_else(std::env::current_dir, Ok)?;
+
+        let mut browser = Self {
+            field,
+            dir: PathBuf::new(),
+            entries: Vec::new(),
+            selected: 0,
+        };
+        browser.change_dir(start)?;
+        Ok(browser)
+    }
+
+    /// Show `dir`, directories first, then matching files, each sorted by name.
+    pub fn change_dir(&mut self, dir: PathBuf) -> io::Result<()> {
+        let dir = dir.canonicalize()?;
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if path.is_dir() {
+                dirs.push(BrowserEntry {
+                    name: format!("{}/", name),
+                    path,
+                    is_dir: true,
+                });
+            } else if has_image_extension(&path) {
+                files.push(BrowserEntry {
+                    name,
+                    path,
+                    is_dir: false,
+                });
+            }
+        }
+        dirs.sort_by(|a, b| a.name.cmp(&b.name));
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.entries = dir
+            .parent()
+            .map(|parent| BrowserEntry {
+                name: "../".to_string(),
+                path: parent.to_path_buf(),
+                is_dir: true,
+            })
+            .into_iter()
+            .chain(dirs)
+            .chain(files)
+            .collect();
+        self.dir = dir;
+        self.selected = 0;
+        Ok(())
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Go to the parent directory, if any.
+    pub fn parent(&mut self) -> io::Result<()> {
+        match self.dir.parent() {
+            Some(parent) => self.change_dir(parent.to_path_buf()),
+            None => Ok(()),
+        }
+    }
+
+    /// Enter the highlighted directory, or return the highlighted file.
+    pub fn activate(&mut self) -> io::Result<Option<PathBuf>> {
+        let Some(entry) = self.entries.get(self.selected).cloned() else {
+            return Ok(None);
+        };
+        if entry.is_dir {
+            self.change_dir(entry.path)?;
+            Ok(None)
+        } else {
+            Ok(Some(entry.path))
+        }
+    }
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
@@ -0,0 +1,113 @@
+//! Size-rotated log file for the TUI's tracing output.
+//!
+//! When the active file would exceed `max_bytes` it is renamed to
+//! `<name>.1` (replacing the previous backup) and a fresh file is started,
+//! so at most two files of `max_bytes` each are kept.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Env var overriding the log file path.
+pub const LOG_PATH_ENV: &str = "DNX_TUI_LOG";
+/// Env var overriding the rotation size in MB.
+pub const LOG_MAX_MB_ENV: &str = "DNX_TUI_LOG_MAX_MB";
+/// Default rotation size.
+pub const DEFAULT_MAX_MB: u64 = 5;
+
+/// Default log location in the per-user log directory.
+pub fn default_path() -> PathBuf {
+    dnx_core::paths::log_dir().join("dnx-tui.log")
+}
+
+/// Append-only log file that rotates by size.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    inner: Mutex<(File, u64)>,
+}
+
+impl RotatingFile {
+    /// Open (or create) `path`, creating parent directories as needed.
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = open_append(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes: max_bytes.max(1),
+            inner: Mutex::new((file, len)),
+        })
+    }
+
+    /// Path of the active log file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut guard = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let (file, len) = &mut *guard;
+
+        if *len > 0 && *len + buf.len() as u64 > self.max_bytes {
+            file.flush()?;
+            std::fs::rename(&self.path, self.backup_path())?;
+            *file = open_append(&self.path)?;
+            *len = 0;
+        }
+
+        let written = file.write(buf)?;
+        *len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut guard = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        guard.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotates_into_one_backup() {
+        let dir = std::env::temp_dir().join(format!("dnx-tui-log-{}", std::process::id()));
+        let log = RotatingFile::open(dir.join("nested").join("tui.log"), 10).unwrap();
+        let backup = log.backup_path();
+
+        (&log).write_all(b"first-").unwrap();
+        (&log).write_all(b"second").unwrap();
+        (&log).write_all(b"third!").unwrap();
+        (&log).flush().unwrap();
+        let active = std::fs::read_to_string(log.path()).unwrap();
+        let rotated = std::fs::read_to_string(&backup).unwrap();
+
+        // Reopening picks up the active file's length
+        let reopened = RotatingFile::open(log.path(), 10).unwrap();
+        (&reopened).write_all(b"fourth").unwrap();
+        let after_reopen = std::fs::read_to_string(&backup).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(active, "third!");
+        assert_eq!(rotated, "second");
+        assert_eq!(after_reopen, "third!");
+    }
+}
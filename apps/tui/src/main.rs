@@ -4,13 +4,18 @@
 //! progress display, log viewer, and status indicators.
 
 mod app;
+mod browser;
 mod event;
+mod logfile;
 mod ui;
 
 use std::io;
 use std::panic;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::Parser;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -21,8 +26,27 @@ use tracing_subscriber::prelude::*;
 
 use app::App;
 use event::{Event, EventHandler};
+use logfile::RotatingFile;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "dnx-tui",
+    version,
+    about = "Terminal UI for Intel DnX Protocol Tool"
+)]
+struct Args {
+    /// Log file path (default: per-user log directory)
+    #[arg(long, env = logfile::LOG_PATH_ENV)]
+    log_file: Option<PathBuf>,
+
+    /// Rotate the log once it reaches this many MB
+    #[arg(long, env = logfile::LOG_MAX_MB_ENV, default_value_t = logfile::DEFAULT_MAX_MB)]
+    log_max_mb: u64,
+}
 
 fn main() -> Result<()> {
+    let args = Args::parse();
+
     // Setup panic hook to restore terminal on crash
     let original_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
@@ -31,20 +55,20 @@ fn main() -> Result<()> {
     }));
 
     // Initialize tracing to file (not stdout, since we're using the terminal)
+    let log_path = args.log_file.unwrap_or_else(logfile::default_path);
+    let log_file = Arc::new(
+        RotatingFile::open(&log_path, args.log_max_mb * 1024 * 1024)
+            .with_context(|| format!("Failed to open log file {}", log_path.display()))?,
+    );
     let file_appender = tracing_subscriber::fmt::layer()
         .with_ansi(false)
-        .with_writer(|| {
-            std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("dnx-tui.log")
-                .unwrap()
-        });
+        .with_writer(log_file.clone());
 
     tracing_subscriber::registry()
         .with(file_appender)
         .with(tracing_subscriber::EnvFilter::from_default_env())
         .init();
+    tracing::info!(path = %log_file.path().display(), "TUI started");
 
     // Setup terminal
     let terminal = setup_terminal()?;
@@ -8,11 +8,15 @@ use ratatui::{
     style::{Color, Modifier, Style},
     symbols,
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Gauge, List, ListItem, Padding, Paragraph, Tabs, Wrap},
+    widgets::{
+        Block, Borders, Clear, Gauge, List, ListItem, ListState, Padding, Paragraph, Tabs, Wrap,
+    },
 };
 
 use crate::app::{App, DeviceStatus, Focus, LogEntry, Tab};
+use crate::browser::FileBrowser;
 use dnx_core::events::{DnxPhase, LogLevel};
+use dnx_core::format_size;
 
 /// Main draw function.
 pub fn draw(frame: &mut Frame, app: &App) {
@@ -37,6 +41,10 @@ pub fn draw(frame: &mut Frame, app: &App) {
         Tab::Help => draw_help_view(frame, chunks[1]),
     }
 
+    if let Some(browser) = &app.browser {
+        draw_file_browser(frame, chunks[1], browser);
+    }
+
     draw_footer(frame, chunks[2], app);
 }
 
@@ -87,7 +95,11 @@ fn draw_footer(frame: &mut Frame, area: Rect, app: &App) {
     let phase = Span::styled(format!(" {} ", app.phase), Style::default().fg(Color::Cyan));
 
     let help = Span::styled(
-        " Ctrl+Q: Quit | Tab: Focus | Enter: Start ",
+        if app.is_running {
+            " Ctrl+Q: Quit | Tab: Focus | Enter: Stop "
+        } else {
+            " Ctrl+Q: Quit | Tab: Focus | Ctrl+O: Browse | Enter: Start "
+        },
         Style::default().fg(Color::DarkGray),
     );
 
@@ -192,16 +204,20 @@ fn draw_config_panel(frame: &mut Frame, area: Rect, app: &App) {
     }
 
     // Start button
-    let button_style = if app.is_running {
+    let button_style = if app.is_stopping() {
         Style::default().fg(Color::DarkGray)
+    } else if app.is_running {
+        Style::default().fg(Color::Red)
     } else if is_focused && app.input_focus > 3 {
         Style::default().fg(Color::Black).bg(Color::Yellow)
     } else {
         Style::default().fg(Color::Green)
     };
 
-    let button_text = if app.is_running {
-        "⟳ Running..."
+    let button_text = if app.is_stopping() {
+        "⟳ Stopping..."
+    } else if app.is_running {
+        "■ Stop"
     } else {
         "▶ Start"
     };
@@ -245,11 +261,7 @@ fn draw_firmware_info(frame: &mut Frame, area: Rect, app: &App) {
 
     let content = if let Some(analysis) = &app.fw_analysis {
         let type_str = format!("{}", analysis.file_type);
-        let size_str = format!(
-            "{} bytes ({:.1} KB)",
-            analysis.size,
-            analysis.size as f64 / 1024.0
-        );
+        let size_str = format!("{} bytes ({})", analysis.size, format_size(analysis.size));
 
         let token_str = if let Some(t) = &analysis.token {
             format!("{} - {}", t.marker, t.platform)
@@ -258,7 +270,7 @@ fn draw_firmware_info(frame: &mut Frame, area: Rect, app: &App) {
         };
 
         let chaabi_str = if let Some(c) = &analysis.chaabi {
-            format!("{} bytes ({:.1} KB)", c.size, c.size as f64 / 1024.0)
+            format!("{} bytes ({})", c.size, format_size(c.size as u64))
         } else {
             "N/A".to_string()
         };
@@ -323,11 +335,31 @@ fn draw_progress(frame: &mut Frame, area: Rect, app: &App) {
         _ => Color::Cyan,
     };
 
-    let label = if app.operation.is_empty() {
+    let mut label = if app.operation.is_empty() {
         format!("{}%", app.progress)
     } else {
         format!("{}: {}%", app.operation, app.progress)
     };
+    // The gauge follows overall progress so it does not restart every phase;
+    // the label keeps the per-component percentage.
+    let percent = if app.overall.bytes_total > 0 {
+        label.push_str(&format!(
+            " ({} of {})",
+            format_size(app.overall.bytes_sent.min(app.overall.bytes_total)),
+            format_size(app.overall.bytes_total)
+        ));
+        app.overall.percent()
+    } else {
+        app.progress
+    };
+    if let Some((rate, eta)) = app.throughput
+        && rate > 0
+    {
+        label.push_str(&format!(", {}/s", format_size(rate)));
+        if let Some(eta) = eta {
+            label.push_str(&format!(", ~{}s", eta));
+        }
+    }
 
     let gauge = Gauge::default()
         .block(
@@ -337,7 +369,7 @@ fn draw_progress(frame: &mut Frame, area: Rect, app: &App) {
                 .title(" Progress "),
         )
         .gauge_style(Style::default().fg(color).bg(Color::Black))
-        .percent(app.progress as u16)
+        .percent(percent as u16)
         .label(label);
 
     frame.render_widget(gauge, area);
@@ -397,15 +429,20 @@ fn draw_logs_view(frame: &mut Frame, area: Rect, app: &App) {
 }
 
 fn draw_protocol_view(frame: &mut Frame, area: Rect, app: &App) {
+    let visible = area.height.saturating_sub(2) as usize;
+    // Keep the view full when scrolled to the newest packets
+    let first = app
+        .packet_scroll
+        .min(app.packets.len().saturating_sub(visible));
     let items: Vec<ListItem> = app
         .packets
         .iter()
-        .skip(app.packet_scroll)
-        .take(area.height.saturating_sub(2) as usize)
+        .skip(first)
+        .take(visible)
         .map(|p| {
             let (dir_icon, color) = match p.direction {
-                dnx_core::events::PacketDirection::Tx => ("->", Color::Yellow),
-                dnx_core::events::PacketDirection::Rx => ("<-", Color::Green),
+                dnx_core::events::PacketDirection::Tx => ("TX ->", Color::Yellow),
+                dnx_core::events::PacketDirection::Rx => ("RX <-", Color::Green),
             };
 
             ListItem::new(Line::from(vec![
@@ -441,6 +478,54 @@ fn draw_protocol_view(frame: &mut Frame, area: Rect, app: &App) {
     frame.render_widget(list, area);
 }
 
+fn draw_file_browser(frame: &mut Frame, area: Rect, browser: &FileBrowser) {
+    let popup = centered_rect(area, 70, 80);
+    frame.render_widget(Clear, popup);
+
+    let items: Vec<ListItem> = browser
+        .entries
+        .iter()
+        .map(|e| {
+            let color = if e.is_dir { Color::Cyan } else { Color::White };
+            ListItem::new(Span::styled(e.name.clone(), Style::default().fg(color)))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(format!(" {} ", browser.dir.display()))
+                .title_bottom(" ↑↓: Move | Enter: Open/Select | ←: Up | Esc: Cancel "),
+        )
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::Yellow))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default().with_selected(Some(browser.selected));
+    frame.render_stateful_widget(list, popup, &mut state);
+}
+
+/// `percent_x` by `percent_y` rectangle centered in `area`.
+fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
 fn draw_help_view(frame: &mut Frame, area: Rect) {
     let help_text = vec![
         "",
@@ -449,21 +534,24 @@ fn draw_help_view(frame: &mut Frame, area: Rect) {
         "  KEYBOARD SHORTCUTS:",
         "",
         "  Ctrl+Q, Ctrl+C, Esc    Quit application",
+        "  Enter/Esc (running)    Stop the running operation",
         "  F1                     Show this help",
         "  F2                     View full logs",
+        "  F3                     View USB packets (TX/RX)",
         "  Tab                    Switch focus between panels",
         "  Up/Down                Navigate input fields",
         "  Enter                  Start DnX operation",
+        "  Ctrl+O                 Browse for the focused path",
         "",
-        "  IN LOGS VIEW:",
+        "  IN LOGS/PROTOCOL VIEW:",
         "",
-        "  j/k, Up/Down           Scroll logs",
+        "  j/k, Up/Down           Scroll logs or packets",
         "  Page Up/Down           Scroll by page",
         "  Home/End               Go to start/end",
         "",
         "  USAGE:",
         "",
-        "  1. Fill in the file paths (use Tab/Arrow keys)",
+        "  1. Fill in the file paths (type them or press Ctrl+O)",
         "  2. Press Enter to start the operation",
         "  3. Watch the progress and logs",
         "",
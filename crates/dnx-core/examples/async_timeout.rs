@@ -0,0 +1,58 @@
+//! OS-only session driven by `run_async` under a `tokio::time::timeout`.
+//!
+//! Writes a tiny synthetic OS DnX and OSIP image, then waits for a device
+//! for at most the given number of seconds (default 3). Without a device
+//! attached the timeout fires and drops the session future, which stops the
+//! wait and cancels any in-flight USB transfer.
+//!
+//! Run with: `cargo run -p dnx-core --features async --example async_timeout [secs]`
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use dnx_core::events::{DnxEvent, DnxObserver};
+use dnx_core::protocol::constants::OSIP_PARTITIONTABLE_SIZE;
+use dnx_core::session::{DnxSession, Operation, SessionConfig};
+
+/// Prints every event except packet dumps.
+struct PrintObserver;
+
+impl DnxObserver for PrintObserver {
+    fn on_event(&self, event: &DnxEvent) {
+        if !matches!(event, DnxEvent::Packet { .. }) {
+            println!("{:?}", event);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let secs = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 3,
+    };
+
+    let dir = std::env::temp_dir().join("dnx-async-timeout");
+    std::fs::create_dir_all(&dir)?;
+    let os_dnx = dir.join("dnx_osr.bin");
+    std::fs::write(&os_dnx, [0xA6u8; 4096])?;
+    let mut image = vec![0u8; OSIP_PARTITIONTABLE_SIZE + 1024];
+    image[0..4].copy_from_slice(b"$OS$");
+    let os_image = dir.join("dnx_osr.img");
+    std::fs::write(&os_image, &image)?;
+
+    let config = SessionConfig {
+        os_dnx_path: Some(os_dnx.to_string_lossy().into_owned()),
+        os_image_path: Some(os_image.to_string_lossy().into_owned()),
+        operation: Some(Operation::OsOnly),
+        ..Default::default()
+    };
+    let mut session = DnxSession::with_observer(config, Arc::new(PrintObserver));
+
+    match tokio::time::timeout(Duration::from_secs(secs), session.run_async()).await {
+        Ok(Ok(summary)) => println!("{}", summary),
+        Ok(Err(e)) => println!("Session failed: {:#}", e),
+        Err(_) => println!("No device within {}s; session cancelled", secs),
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! Simulated throughput benchmark.
+//!
+//! Runs an OS-only session against a `MockTransport` with configurable
+//! latency, bandwidth, and injected read errors, sweeping chunk sizes and
+//! printing how long each run takes, then sweeps the write queue depth.
+//!
+//! Run with: `cargo run -p dnx-core --example benchmark`
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dnx_core::events::NullObserver;
+use dnx_core::protocol::constants::*;
+use dnx_core::session::{DnxSession, Operation, SessionConfig};
+use dnx_core::transport::MockTransport;
+
+const IMAGE_SIZE: usize = 8 * 1024 * 1024;
+const BANDWIDTH_BPS: u64 = 40 * 1024 * 1024;
+const LATENCY: Duration = Duration::from_micros(500);
+const ERROR_RATE: f64 = 0.02;
+
+fn main() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join("dnx-benchmark");
+    std::fs::create_dir_all(&dir)?;
+
+    let os_dnx = dir.join("os_dnx.bin");
+    std::fs::write(&os_dnx, vec![0xA5u8; 64 * 1024])?;
+
+    // OSIP header ($OS$, no partitions) followed by the image payload
+    let mut image = vec![0u8; OSIP_PARTITIONTABLE_SIZE + IMAGE_SIZE];
+    image[0..4].copy_from_slice(b"$OS$");
+    let os_image = dir.join("os_image.bin");
+    std::fs::write(&os_image, &image)?;
+
+    println!(
+        "image={} KB  bandwidth={} MB/s  latency={:?}  error_rate={:.0}%",
+        IMAGE_SIZE / 1024,
+        BANDWIDTH_BPS / 1024 / 1024,
+        LATENCY,
+        ERROR_RATE * 100.0
+    );
+    println!(
+        "{:>10} {:>8} {:>10} {:>12}",
+        "chunk", "chunks", "time", "throughput"
+    );
+
+    for chunk_size in [16 * 1024, 32 * 1024, 64 * 1024, ONE28_K, 256 * 1024] {
+        let elapsed = run(&os_dnx, &os_image, chunk_size, None)?;
+        print_row(&format!("{} KB", chunk_size / 1024), chunk_size, elapsed);
+    }
+
+    // Same link, 128 KB chunks, modelled as 4 KB transfers with a queue of N
+    println!();
+    println!(
+        "{:>10} {:>8} {:>10} {:>12}",
+        "depth", "chunks", "time", "throughput"
+    );
+    for depth in [1, 2, 4, 8] {
+        let elapsed = run(&os_dnx, &os_image, ONE28_K, Some(depth))?;
+        print_row(&depth.to_string(), ONE28_K, elapsed);
+    }
+
+    Ok(())
+}
+
+/// Run one OS-only session, optionally modelling a write queue depth.
+fn run(
+    os_dnx: &Path,
+    os_image: &Path,
+    chunk_size: usize,
+    queue_depth: Option<usize>,
+) -> anyhow::Result<Duration> {
+    let chunks = IMAGE_SIZE.div_ceil(chunk_size);
+
+    let mut mock = MockTransport::new()
+        .with_latency(LATENCY)
+        .with_bandwidth(BANDWIDTH_BPS)
+        .with_error_rate(ERROR_RATE)
+        .with_seed(chunk_size as u64);
+    if let Some(depth) = queue_depth {
+        mock = mock.with_write_queue_depth(depth);
+    }
+    mock.queue_ack_u32(BULK_ACK_DXBL);
+    mock.queue_ack_u64(BULK_ACK_ROSIP, 5);
+    for _ in 0..chunks {
+        mock.queue_ack_u32(BULK_ACK_RIMG);
+    }
+    mock.queue_ack_u32(BULK_ACK_DONE);
+
+    let config = SessionConfig {
+        os_dnx_path: Some(os_dnx.to_string_lossy().into_owned()),
+        os_image_path: Some(os_image.to_string_lossy().into_owned()),
+        operation: Some(Operation::OsOnly),
+        chunk_size,
+        ..Default::default()
+    };
+    let mut session = DnxSession::with_observer(config, Arc::new(NullObserver));
+
+    let start = Instant::now();
+    session.run_with_transport(&mock)?;
+    Ok(start.elapsed())
+}
+
+fn print_row(label: &str, chunk_size: usize, elapsed: Duration) {
+    println!(
+        "{:>10} {:>8} {:>9.2}s {:>8.2} MB/s",
+        label,
+        IMAGE_SIZE.div_ceil(chunk_size),
+        elapsed.as_secs_f64(),
+        IMAGE_SIZE as f64 / 1024.0 / 1024.0 / elapsed.as_secs_f64()
+    );
+}
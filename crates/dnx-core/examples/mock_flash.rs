@@ -0,0 +1,114 @@
+//! Full firmware + OS session against a scripted `MockTransport`.
+//!
+//! Builds synthetic FW and OS inputs, queues the ACKs a device would send,
+//! runs the session with a printing observer and reports the summary.
+//! No hardware is needed.
+//!
+//! Run with: `cargo run -p dnx-core --example mock_flash`
+
+use std::sync::Arc;
+
+use dnx_core::events::{DnxEvent, DnxObserver, PacketDirection};
+use dnx_core::payload::FirmwareImage;
+use dnx_core::protocol::constants::*;
+use dnx_core::protocol::header::FwUpdateProfileHeader;
+use dnx_core::protocol::{DnxHeader, xor8};
+use dnx_core::session::{DnxSession, Operation, SessionConfig};
+use dnx_core::transport::{MockTransport, UsbTransport};
+
+/// Prints every event except packet dumps, which are summarised.
+struct PrintObserver;
+
+impl DnxObserver for PrintObserver {
+    fn on_event(&self, event: &DnxEvent) {
+        match event {
+            DnxEvent::Packet {
+                direction: PacketDirection::Tx,
+                length,
+                ..
+            } => println!("  -> {} bytes", length),
+            DnxEvent::Packet { .. } => {}
+            DnxEvent::Progress {
+                operation,
+                current,
+                total,
+                ..
+            } => println!("  {} {}/{}", operation, current, total),
+            other => println!("{:?}", other),
+        }
+    }
+}
+
+/// DnX header, D0 profile header (no PSFW/SSFW), LOFW and HIFW.
+fn fw_image() -> Vec<u8> {
+    let body = FwUpdateProfileHeader::D0_SIZE + 2 * ONE28_K;
+    let mut data = DnxHeader::new(body as u32, 0).to_bytes().to_vec();
+    data.resize(DnxHeader::SIZE + body, 0x5A);
+    data
+}
+
+/// OSIP with one 8-block image, followed by the image.
+fn os_image() -> Vec<u8> {
+    let blocks = 8u32;
+    let mut data = vec![0u8; OSIP_PARTITIONTABLE_SIZE + blocks as usize * 512];
+    data[0..4].copy_from_slice(b"$OS$");
+    data[0x08] = 1; // pointers
+    data[0x09] = 1; // images
+    data[0x0A] = 0x38; // header size
+    data[0x24..0x28].copy_from_slice(&50u32.to_le_bytes()); // destination LBA
+    data[0x30..0x34].copy_from_slice(&blocks.to_le_bytes());
+    data[0x07] = xor8(&data[..0x38]);
+    data
+}
+
+fn main() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join("dnx-mock-flash");
+    std::fs::create_dir_all(&dir)?;
+    let write = |name: &str, data: &[u8]| -> anyhow::Result<String> {
+        let path = dir.join(name);
+        std::fs::write(&path, data)?;
+        Ok(path.to_string_lossy().into_owned())
+    };
+
+    let ifwi = fw_image();
+    let components = FirmwareImage::from_bytes(ifwi.clone())?.components().len();
+    println!("FW image: {} bytes, {} components", ifwi.len(), components);
+
+    let config = SessionConfig {
+        fw_dnx_path: Some(write("dnx_fwr.bin", &[0xA5; 4096])?),
+        fw_image_path: Some(write("ifwi.bin", &ifwi)?),
+        os_dnx_path: Some(write("dnx_osr.bin", &[0xA6; 4096])?),
+        os_image_path: Some(write("dnx_osr.img", &os_image())?),
+        operation: Some(Operation::FwThenOs),
+        strict: true,
+        ..Default::default()
+    };
+
+    // What a virgin device asks for: FW stage, then the OS stage
+    let mock = MockTransport::new();
+    for ack in [b"DFRM", b"DXBL"] {
+        mock.queue_ack(ack);
+    }
+    mock.queue_ack_u64(BULK_ACK_READY_UPH_SIZE, 5);
+    for ack in [b"RUPH", b"LOFW", b"HIFW", b"HLT$", b"DORM", b"DXBL"] {
+        mock.queue_ack(ack);
+    }
+    mock.queue_ack_u64(BULK_ACK_ROSIP, 5);
+    for ack in [b"RIMG", b"EOIU", b"DONE"] {
+        mock.queue_ack(ack);
+    }
+
+    let mut session = DnxSession::with_observer(config, Arc::new(PrintObserver));
+    let summary = session.run_with_transport(&mock)?;
+
+    let sent: usize = mock.get_writes().iter().map(Vec::len).sum();
+    println!("{}", summary);
+    println!(
+        "{:04X}:{:04X} received {} bytes in {} writes",
+        mock.vendor_id(),
+        mock.product_id(),
+        sent,
+        mock.get_writes().len()
+    );
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Transparent decompression of firmware and OS images.
+//!
+//! gzip and xz are detected by magic bytes, falling back to the file
+//! extension. Decoding requires the `compression` feature.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use tracing::info;
+
+/// gzip magic bytes.
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+/// xz magic bytes.
+const XZ_MAGIC: &[u8] = &[0xFD, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Supported compression formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+}
+
+impl Compression {
+    /// Detect the format from magic bytes, then from the file extension.
+    pub fn detect(data: &[u8], path: Option<&Path>) -> Option<Self> {
+        if data.starts_with(GZIP_MAGIC) {
+            return Some(Compression::Gzip);
+        }
+        if data.starts_with(XZ_MAGIC) {
+            return Some(Compression::Xz);
+        }
+        match path?.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Xz => write!(f, "xz"),
+        }
+    }
+}
+
+/// Decompress `data` if it is compressed, returning the format that was undone.
+pub fn decompress(
+    data: Vec<u8>,
+    path: Option<&Path>,
+) -> io::Result<(Vec<u8>, Option<Compression>)> {
+    match Compression::detect(&data, path) {
+        Some(format) => Ok((decode(&data, format)?, Some(format))),
+        None => Ok((data, None)),
+    }
+}
+
+/// Read a file, decompressing it if needed.
+pub fn read_file(path: &Path) -> io::Result<(Vec<u8>, Option<Compression>)> {
+    let data = std::fs::read(path)?;
+    let compressed_len = data.len();
+    let (data, format) = decompress(data, Some(path))?;
+    if let Some(format) = format {
+        info!(
+            path = %path.display(),
+            format = %format,
+            compressed = compressed_len,
+            decompressed = data.len(),
+            "Decompressed file"
+        );
+    }
+    Ok((data, format))
+}
+
+#[cfg(feature = "compression")]
+fn decode(data: &[u8], format: Compression) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut out = Vec::new();
+    match format {
+        Compression::Gzip => {
+            flate2::read::MultiGzDecoder::new(data).read_to_end(&mut out)?;
+        }
+        Compression::Xz => {
+            lzma_rs::xz_decompress(&mut io::BufReader::new(data), &mut out)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decode(_data: &[u8], format: Compression) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} input requires dnx-core's `compression` feature", format),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Compression::detect(&[0x1F, 0x8B, 0x08], None),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::detect(XZ_MAGIC, None), Some(Compression::Xz));
+        assert_eq!(
+            Compression::detect(b"xx", Some(Path::new("ifwi.bin.xz"))),
+            Some(Compression::Xz)
+        );
+        assert_eq!(Compression::detect(b"$OS$", Some(Path::new("a.img"))), None);
+
+        let (data, format) = decompress(b"$OS$".to_vec(), None).unwrap();
+        assert_eq!((data.as_slice(), format), (&b"$OS$"[..], None));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_decompress_roundtrip() {
+        use std::io::Write;
+
+        let payload = b"$DnX firmware payload".repeat(64);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&payload).unwrap();
+        let (data, format) = decompress(gz.finish().unwrap(), None).unwrap();
+        assert_eq!(format, Some(Compression::Gzip));
+        assert_eq!(data, payload);
+
+        let mut xz = Vec::new();
+        lzma_rs::xz_compress(&mut io::BufReader::new(&payload[..]), &mut xz).unwrap();
+        let (data, format) = decompress(xz, None).unwrap();
+        assert_eq!(format, Some(Compression::Xz));
+        assert_eq!(data, payload);
+    }
+}
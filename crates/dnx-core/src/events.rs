@@ -4,9 +4,19 @@
 //! tight coupling to the core logic.
 
 use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::Platform;
+use crate::size::format_size;
 
 /// Log level for events.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LogLevel {
     Trace,
     Debug,
@@ -16,9 +26,10 @@ pub enum LogLevel {
 }
 
 /// DnX state machine phases.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum DnxPhase {
     /// Waiting for device connection.
+    #[default]
     WaitingForDevice,
     /// Initial handshake (sending preamble).
     Handshake,
@@ -49,12 +60,34 @@ impl fmt::Display for DnxPhase {
 }
 
 /// Events emitted by the DnX session.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum DnxEvent {
     /// Device connected.
     DeviceConnected { vid: u16, pid: u16 },
     /// Device disconnected (might re-enumerate with different PID).
     DeviceDisconnected,
+    /// A supported device other than the expected post-reset PID appeared.
+    UnexpectedDevice {
+        vid: u16,
+        pid: u16,
+        expected_pid: u16,
+    },
+    /// Still polling for a device.
+    WaitingForDevice {
+        elapsed_secs: u64,
+        timeout_secs: u64,
+    },
+    /// Bytes written across all phases; first reported with `bytes_sent: 0`
+    /// before anything is sent. `bytes_sent` may slightly exceed
+    /// `bytes_total` because of protocol framing.
+    OverallProgress { bytes_sent: u64, bytes_total: u64 },
+    /// Recent transfer rate, sent after `OverallProgress` once measurable.
+    /// `eta_secs` is `None` while the rate is zero.
+    Throughput {
+        bytes_per_sec: u64,
+        eta_secs: Option<u64>,
+    },
     /// Phase changed.
     PhaseChanged { from: DnxPhase, to: DnxPhase },
     /// Progress update for current operation.
@@ -70,19 +103,70 @@ pub enum DnxEvent {
     AckReceived { ack: String },
     /// Error occurred.
     Error { code: u32, message: String },
+    /// A retryable device error; the last component is being re-sent.
+    Retry {
+        code: String,
+        component: String,
+        attempt: u32,
+        max_retries: u32,
+    },
+    /// Transient read errors hit the retry cap in a row; the session is
+    /// giving up.
+    RetryExhausted { retries: u32, last_error: String },
+    /// The device identified its platform with an `MFLD`/`CLVT` ACK.
+    PlatformDetected { platform: Platform },
     /// USB Packet sent/received.
     Packet {
         direction: PacketDirection,
         packet_type: String,
         length: usize,
+        /// First bytes of the packet, serialized as a hex string.
+        #[serde(with = "hex_bytes", default)]
         data: Option<Vec<u8>>,
     },
+    /// The session stopped because its cancel token fired.
+    Cancelled,
     /// All operations completed successfully.
     Complete,
 }
 
+/// Serde helper encoding `Option<Vec<u8>>` as a hex string.
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(data: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(bytes) => s.serialize_some(
+                &bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>(),
+            ),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        let Some(hex) = Option::<String>::deserialize(d)? else {
+            return Ok(None);
+        };
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("odd-length hex string"));
+        }
+        // Checked up front so pairs below are ASCII and never split a char
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(D::Error::custom("invalid hex string"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(D::Error::custom))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+    }
+}
+
 /// USB packet direction.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PacketDirection {
     Tx, // Transmit (Host -> Device)
     Rx, // Receive (Device -> Host)
@@ -103,6 +187,12 @@ impl fmt::Display for PacketDirection {
 pub trait DnxObserver: Send + Sync {
     /// Called when an event occurs.
     fn on_event(&self, event: &DnxEvent);
+
+    /// Called with the complete bytes of every packet sent or received.
+    ///
+    /// `DnxEvent::Packet` only carries a short preview; implement this to
+    /// capture full payloads without putting them on every event.
+    fn on_packet(&self, _direction: PacketDirection, _packet_type: &str, _data: &[u8]) {}
 }
 
 /// No-op observer that discards all events.
@@ -114,6 +204,132 @@ impl DnxObserver for NullObserver {
     }
 }
 
+/// Observer that forwards every event to several observers.
+#[derive(Default)]
+pub struct CompositeObserver {
+    observers: Vec<Arc<dyn DnxObserver>>,
+}
+
+impl CompositeObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an observer to the fan-out list.
+    pub fn with(mut self, observer: Arc<dyn DnxObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+}
+
+impl DnxObserver for CompositeObserver {
+    fn on_event(&self, event: &DnxEvent) {
+        for observer in &self.observers {
+            observer.on_event(event);
+        }
+    }
+
+    fn on_packet(&self, direction: PacketDirection, packet_type: &str, data: &[u8]) {
+        for observer in &self.observers {
+            observer.on_packet(direction, packet_type, data);
+        }
+    }
+}
+
+/// Observer that appends each event as one JSON object per line.
+///
+/// Lines look like `{"ts_ms":1700000000000,"event":{"type":"device_connected",...}}`.
+pub struct JsonlObserver {
+    file: Mutex<File>,
+}
+
+#[derive(Serialize)]
+struct JsonlRecord<'a> {
+    ts_ms: u128,
+    event: &'a DnxEvent,
+}
+
+impl JsonlObserver {
+    /// Open `path` in append mode, creating it if needed.
+    pub fn new<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl DnxObserver for JsonlObserver {
+    fn on_event(&self, event: &DnxEvent) {
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let Ok(mut line) = serde_json::to_string(&JsonlRecord { ts_ms, event }) else {
+            return;
+        };
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.write_all(line.as_bytes()).and_then(|_| file.flush()) {
+            tracing::warn!(error = %e, "Failed to write JSONL event");
+        }
+    }
+}
+
+/// Observer that writes a full protocol trace, one JSON object per packet.
+///
+/// Lines look like
+/// `{"ts_ms":1700000000000,"direction":"Tx","packet_type":"Cmd/Hdr","length":4,"data":"446e4552"}`.
+pub struct PacketLogger {
+    file: Mutex<File>,
+}
+
+#[derive(Serialize)]
+struct PacketRecord<'a> {
+    ts_ms: u128,
+    direction: PacketDirection,
+    packet_type: &'a str,
+    length: usize,
+    data: String,
+}
+
+impl PacketLogger {
+    /// Create (or truncate) the trace file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(Self {
+            file: Mutex::new(File::create(path)?),
+        })
+    }
+}
+
+impl DnxObserver for PacketLogger {
+    fn on_event(&self, _event: &DnxEvent) {}
+
+    fn on_packet(&self, direction: PacketDirection, packet_type: &str, data: &[u8]) {
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let record = PacketRecord {
+            ts_ms,
+            direction,
+            packet_type,
+            length: data.len(),
+            data: data.iter().map(|b| format!("{:02x}", b)).collect(),
+        };
+        let Ok(mut line) = serde_json::to_string(&record) else {
+            return;
+        };
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.write_all(line.as_bytes()).and_then(|_| file.flush()) {
+            tracing::warn!(error = %e, "Failed to write packet trace");
+        }
+    }
+}
+
 /// Observer that logs events using tracing.
 pub struct TracingObserver;
 
@@ -126,6 +342,44 @@ impl DnxObserver for TracingObserver {
             DnxEvent::DeviceDisconnected => {
                 tracing::warn!("Device disconnected");
             }
+            DnxEvent::WaitingForDevice {
+                elapsed_secs,
+                timeout_secs,
+            } => {
+                tracing::info!(
+                    "Still waiting for device ({}s / {}s)",
+                    elapsed_secs,
+                    timeout_secs
+                );
+            }
+            DnxEvent::UnexpectedDevice {
+                vid,
+                pid,
+                expected_pid,
+            } => {
+                tracing::warn!(
+                    vid = %format!("{:04X}", vid),
+                    pid = %format!("{:04X}", pid),
+                    expected_pid = %format!("{:04X}", expected_pid),
+                    "Unexpected device"
+                );
+            }
+            DnxEvent::OverallProgress {
+                bytes_sent,
+                bytes_total,
+            } => {
+                if *bytes_sent == 0 {
+                    tracing::info!(total = %format_size(*bytes_total), "Transfer planned");
+                } else {
+                    tracing::trace!(sent = bytes_sent, total = bytes_total, "Overall progress");
+                }
+            }
+            DnxEvent::Throughput {
+                bytes_per_sec,
+                eta_secs,
+            } => {
+                tracing::trace!(bytes_per_sec, eta_secs, "Throughput");
+            }
             DnxEvent::PhaseChanged { from, to } => {
                 tracing::info!(from = %from, to = %to, "Phase changed");
             }
@@ -135,11 +389,7 @@ impl DnxObserver for TracingObserver {
                 current,
                 total,
             } => {
-                let pct = if *total > 0 {
-                    (*current * 100) / *total
-                } else {
-                    0
-                };
+                let pct = (*current * 100).checked_div(*total).unwrap_or(0);
                 tracing::debug!(phase = %phase, operation = %operation, progress = %format!("{}%", pct), "Progress");
             }
             DnxEvent::Log { level, message } => match level {
@@ -155,6 +405,23 @@ impl DnxObserver for TracingObserver {
             DnxEvent::Error { code, message } => {
                 tracing::error!(code = code, "Error: {}", message);
             }
+            DnxEvent::Retry {
+                code,
+                component,
+                attempt,
+                max_retries,
+            } => {
+                tracing::warn!(code = %code, component = %component, "Retry {}/{}", attempt, max_retries);
+            }
+            DnxEvent::RetryExhausted {
+                retries,
+                last_error,
+            } => {
+                tracing::error!("Giving up after {} retries: {}", retries, last_error);
+            }
+            DnxEvent::PlatformDetected { platform } => {
+                tracing::info!(platform = %platform, "Platform detected");
+            }
             DnxEvent::Packet {
                 direction,
                 packet_type,
@@ -168,9 +435,145 @@ impl DnxObserver for TracingObserver {
                     "USB Packet"
                 );
             }
+            DnxEvent::Cancelled => {
+                tracing::warn!("Session cancelled");
+            }
             DnxEvent::Complete => {
                 tracing::info!("Operation complete");
             }
         }
     }
 }
+
+/// Observer that keeps every event, for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct EventRecorder(Mutex<Vec<DnxEvent>>);
+
+#[cfg(test)]
+impl EventRecorder {
+    /// Recorded events `f` picks out, in order.
+    pub(crate) fn collect<T>(&self, f: impl FnMut(&DnxEvent) -> Option<T>) -> Vec<T> {
+        self.0.lock().unwrap().iter().filter_map(f).collect()
+    }
+}
+
+#[cfg(test)]
+impl DnxObserver for EventRecorder {
+    fn on_event(&self, event: &DnxEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_observer_appends_lines() {
+        let path = std::env::temp_dir().join(format!("dnx-events-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let jsonl = Arc::new(JsonlObserver::new(&path).unwrap());
+        let observer = CompositeObserver::new()
+            .with(Arc::new(NullObserver))
+            .with(jsonl);
+        observer.on_event(&DnxEvent::DeviceConnected {
+            vid: 0x8086,
+            pid: 0x0A14,
+        });
+        observer.on_event(&DnxEvent::PhaseChanged {
+            from: DnxPhase::Handshake,
+            to: DnxPhase::FirmwareDownload,
+        });
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0]["ts_ms"].as_u64().unwrap() > 0);
+        assert_eq!(lines[0]["event"]["type"], "device_connected");
+        assert_eq!(lines[0]["event"]["pid"], 0x0A14);
+        assert_eq!(lines[1]["event"]["to"], "FirmwareDownload");
+    }
+
+    /// Counts events and packet bytes it is given.
+    #[derive(Default)]
+    struct Counter {
+        events: Mutex<usize>,
+        packet_bytes: Mutex<usize>,
+    }
+
+    impl DnxObserver for Counter {
+        fn on_event(&self, _event: &DnxEvent) {
+            *self.events.lock().unwrap() += 1;
+        }
+
+        fn on_packet(&self, _direction: PacketDirection, _packet_type: &str, data: &[u8]) {
+            *self.packet_bytes.lock().unwrap() += data.len();
+        }
+    }
+
+    #[test]
+    fn test_composite_observer_fans_out() {
+        let (a, b) = (Arc::new(Counter::default()), Arc::new(Counter::default()));
+        let observer = CompositeObserver::new()
+            .with(a.clone())
+            .with(Arc::new(TracingObserver))
+            .with(b.clone());
+
+        observer.on_event(&DnxEvent::Complete);
+        observer.on_event(&DnxEvent::Cancelled);
+        observer.on_packet(PacketDirection::Tx, "Data", &[0u8; 100]);
+
+        for counter in [a, b] {
+            assert_eq!(*counter.events.lock().unwrap(), 2);
+            assert_eq!(*counter.packet_bytes.lock().unwrap(), 100);
+        }
+    }
+
+    #[test]
+    fn test_event_serde_roundtrip() {
+        let events = [
+            DnxEvent::Packet {
+                direction: PacketDirection::Tx,
+                packet_type: "Cmd/Hdr".to_string(),
+                length: 4,
+                data: Some(b"DnER".to_vec()),
+            },
+            DnxEvent::Log {
+                level: LogLevel::Warn,
+                message: "hello".to_string(),
+            },
+            DnxEvent::Complete,
+        ];
+
+        let json = serde_json::to_string(&events[0]).unwrap();
+        assert!(json.contains("\"data\":\"446e4552\""), "{}", json);
+
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            let back: DnxEvent = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&back).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_packet_data_rejects_bad_hex() {
+        let packet = |data: &str| {
+            let json = format!(
+                r#"{{"type":"packet","direction":"Tx","packet_type":"Data","length":2,"data":"{data}"}}"#
+            );
+            serde_json::from_str::<DnxEvent>(&json)
+        };
+        assert!(packet("446e").is_ok());
+        // Four bytes, with "é" straddling the first pair
+        assert!(packet("aéb").is_err());
+        assert!(packet("+1").is_err());
+        assert!(packet("4").is_err());
+    }
+}
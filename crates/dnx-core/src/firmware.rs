@@ -3,10 +3,22 @@
 //! Provides unified firmware analysis API for CLI, TUI, and xtask.
 
 use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
 use crate::fuph::FuphHeader;
 use crate::ifwi_version::{self, FirmwareVersions};
+use crate::payload::FirmwareImage;
+use crate::payload::os::osii_entries;
+use crate::protocol::constants::{
+    OSII_SIZE, OSIP_BLOCK_SIZE, OSIP_ENTRIES_OFFSET, OSIP_PARTITIONTABLE_SIZE,
+};
+use crate::size::format_size;
+use crate::util::first_positions;
 
 /// Firmware file type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -36,16 +48,17 @@ impl fmt::Display for FirmwareType {
 }
 
 /// Magic marker found in firmware
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MarkerInfo {
     pub name: String,
+    #[serde(serialize_with = "serialize_hex")]
     pub pattern: Vec<u8>,
     pub position: usize,
     pub description: String,
 }
 
 /// RSA signature information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RsaSignature {
     pub offset: usize,
     pub size: usize,
@@ -54,7 +67,7 @@ pub struct RsaSignature {
 }
 
 /// Validation check result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidationCheck {
     pub name: String,
     pub passed: bool,
@@ -62,7 +75,7 @@ pub struct ValidationCheck {
 }
 
 /// Token information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TokenInfo {
     pub marker: String,
     pub offset: usize,
@@ -71,7 +84,7 @@ pub struct TokenInfo {
 }
 
 /// Chaabi information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ChaabiInfo {
     pub offset: usize,
     pub size: usize,
@@ -80,7 +93,7 @@ pub struct ChaabiInfo {
 }
 
 /// Complete firmware analysis result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FirmwareAnalysis {
     /// Source file path
     pub path: PathBuf,
@@ -89,6 +102,7 @@ pub struct FirmwareAnalysis {
     /// File size in bytes
     pub size: u64,
     /// Detected firmware type
+    #[serde(rename = "type", serialize_with = "serialize_display")]
     pub file_type: FirmwareType,
     /// SHA256 hash of file
     pub sha256: String,
@@ -106,15 +120,43 @@ pub struct FirmwareAnalysis {
     pub fuph: Option<FuphHeader>,
     /// Validation checks
     pub validations: Vec<ValidationCheck>,
-    /// Raw data (for further analysis)
-    #[allow(dead_code)]
-    data: Vec<u8>,
+    /// SHA256 of each identified component, as (name, hex digest)
+    #[serde(serialize_with = "serialize_component_hashes")]
+    pub component_hashes: Vec<(String, String)>,
 }
 
 impl FirmwareAnalysis {
-    /// Analyze a firmware file
+    /// Analyze a firmware file, decompressing .gz/.xz input.
+    ///
+    /// Same as [`analyze_mmap`](Self::analyze_mmap); the report keeps no
+    /// copy of the file.
     pub fn analyze(path: &Path) -> std::io::Result<Self> {
-        let data = std::fs::read(path)?;
+        Self::analyze_mmap(path)
+    }
+
+    /// Analyze a memory-mapped file, so a large image is paged in from the
+    /// file instead of being copied onto the heap. Compressed input has to
+    /// be decompressed into memory first.
+    pub fn analyze_mmap(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // Safety: the map is only read, and dropped before returning. Like any
+        // mapping, it faults if another process truncates the file meanwhile.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        if crate::compression::Compression::detect(&map, Some(path)).is_some() {
+            let (data, _) = crate::compression::read_file(path)?;
+            return Ok(Self::analyze_slice(path, &data));
+        }
+        Ok(Self::analyze_slice(path, &map))
+    }
+
+    /// Analyze firmware already loaded in memory. `path` is only used for naming.
+    pub fn analyze_bytes(path: &Path, data: Vec<u8>) -> Self {
+        Self::analyze_slice(path, &data)
+    }
+
+    /// Analyze borrowed bytes, e.g. a caller's memory map, without copying
+    /// them. `path` is only used for naming.
+    pub fn analyze_slice(path: &Path, data: &[u8]) -> Self {
         let size = data.len() as u64;
         let filename = path
             .file_name()
@@ -122,33 +164,37 @@ impl FirmwareAnalysis {
             .unwrap_or_default();
 
         // Compute SHA256
-        let sha256 = compute_sha256(&data);
+        let sha256 = sha256_hex(data);
 
-        // Detect file type
-        let file_type = detect_file_type(&data);
-
-        // Find magic markers
-        let markers = find_markers(&data);
+        // Find magic markers, and the file type from the same scan
+        let markers = find_markers(data);
+        let has = |name: &str| markers.iter().any(|m| m.name == name);
+        let file_type = classify(data, has("ANDROID!"), has("$FIP"));
 
         // Extract RSA signature info (for DnX firmware)
-        let rsa_signature = extract_rsa_signature(&data);
+        let rsa_signature = extract_rsa_signature(data);
 
         // Extract token info
-        let token = extract_token_info(&data, &markers);
+        let token = extract_token_info(data, &markers);
 
         // Extract Chaabi info
-        let chaabi = extract_chaabi_info(&data, &markers);
+        let chaabi = extract_chaabi_info(data, &markers);
 
         // Try to extract IFWI versions
-        let versions = ifwi_version::get_image_fw_rev(&data).ok();
+        let versions = ifwi_version::get_image_fw_rev(data).ok();
 
         // Try to parse FUPH header
-        let fuph = FuphHeader::parse(&data);
+        let fuph = FuphHeader::parse(data);
 
         // Run validation checks
-        let validations = run_validations(&data, &markers);
+        let validations =
+            run_validations(data, &markers, file_type, fuph.as_ref(), versions.as_ref());
 
-        Ok(Self {
+        // Hash each identified component
+        let component_hashes =
+            compute_component_hashes(data, file_type, token.as_ref(), chaabi.as_ref());
+
+        Self {
             path: path.to_path_buf(),
             filename,
             size,
@@ -161,8 +207,8 @@ impl FirmwareAnalysis {
             versions,
             fuph,
             validations,
-            data,
-        })
+            component_hashes,
+        }
     }
 
     /// Check if all validations passed
@@ -184,9 +230,9 @@ impl FirmwareAnalysis {
         out.push_str(&format!("Firmware Analysis: {}\n", self.filename));
         out.push_str(&format!("{}\n", "=".repeat(50)));
         out.push_str(&format!(
-            "File size: {} bytes ({:.2} KB)\n",
+            "File size: {} bytes ({})\n",
             self.size,
-            self.size as f64 / 1024.0
+            format_size(self.size)
         ));
         out.push_str(&format!("Type: {}\n", self.file_type));
         out.push_str(&format!("SHA256: {}...\n", &self.sha256[..32]));
@@ -225,9 +271,9 @@ impl FirmwareAnalysis {
             out.push_str("\nChaabi:\n");
             out.push_str(&format!("  Offset: 0x{:X}\n", chaabi.offset));
             out.push_str(&format!(
-                "  Size: {} bytes ({:.1} KB)\n",
+                "  Size: {} bytes ({})\n",
                 chaabi.size,
-                chaabi.size as f64 / 1024.0
+                format_size(chaabi.size as u64)
             ));
         }
 
@@ -237,6 +283,13 @@ impl FirmwareAnalysis {
             out.push_str(&format!("  IFWI: {}\n", v.ifwi));
             out.push_str(&format!("  SCU: {}\n", v.scu));
             out.push_str(&format!("  Chaabi: {}\n", v.chaabi));
+            out.push_str(&format!(
+                "  MIP: umip {} / mipd {} / mipn {}\n",
+                v.umip, v.mipd, v.mipn
+            ));
+            for warning in &v.warnings {
+                out.push_str(&format!("  ⚠ {}\n", warning));
+            }
         }
 
         // Validations
@@ -249,37 +302,22 @@ impl FirmwareAnalysis {
         out
     }
 
-    /// Format as JSON
+    /// Format as pretty-printed JSON, with `valid` and `validation_summary` added.
     pub fn to_json(&self) -> String {
-        let mut out = String::from("{\n");
-        out.push_str(&format!("  \"filename\": \"{}\",\n", self.filename));
-        out.push_str(&format!("  \"size\": {},\n", self.size));
-        out.push_str(&format!("  \"type\": \"{}\",\n", self.file_type));
-        out.push_str(&format!("  \"sha256\": \"{}\",\n", self.sha256));
-        out.push_str(&format!("  \"valid\": {},\n", self.is_valid()));
-
-        // Markers
-        out.push_str("  \"markers\": [\n");
-        for (i, m) in self.markers.iter().enumerate() {
-            out.push_str(&format!(
-                "    {{\"name\": \"{}\", \"position\": {}}}",
-                m.name, m.position
-            ));
-            if i < self.markers.len() - 1 {
-                out.push(',');
-            }
-            out.push('\n');
+        #[derive(Serialize)]
+        struct Report<'a> {
+            #[serde(flatten)]
+            analysis: &'a FirmwareAnalysis,
+            valid: bool,
+            validation_summary: String,
         }
-        out.push_str("  ],\n");
 
-        // Validations
-        out.push_str(&format!(
-            "  \"validation_summary\": \"{}\"\n",
-            self.validation_summary()
-        ));
-        out.push_str("}\n");
-
-        out
+        let report = Report {
+            analysis: self,
+            valid: self.is_valid(),
+            validation_summary: self.validation_summary(),
+        };
+        serde_json::to_string_pretty(&report).expect("analysis is always serializable")
     }
 
     /// Format as markdown table
@@ -313,8 +351,11 @@ impl FirmwareAnalysis {
     }
 }
 
+/// RSA signature block of a DnX/IFWI image.
+pub const RSA_SIGNATURE_RANGE: Range<usize> = 0x88..0x188;
+
 /// Compare two firmware files
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FirmwareComparison {
     pub file1: String,
     pub file2: String,
@@ -325,7 +366,7 @@ pub struct FirmwareComparison {
     pub diff_regions: Vec<DiffRegion>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DiffRegion {
     pub start: usize,
     pub end: usize,
@@ -336,8 +377,45 @@ pub struct DiffRegion {
 impl FirmwareComparison {
     /// Compare two firmware files
     pub fn compare(path1: &Path, path2: &Path) -> std::io::Result<Self> {
-        let data1 = std::fs::read(path1)?;
-        let data2 = std::fs::read(path2)?;
+        Self::compare_with_progress(path1, path2, |_, _| {})
+    }
+
+    /// Compare two firmware files in fixed-size windows, calling
+    /// `progress(bytes_compared, total)` after each one.
+    ///
+    /// Only one window per file is held in memory, so multi-hundred-MB OS
+    /// images can be compared. `total` is the length of the shorter file.
+    pub fn compare_with_progress(
+        path1: &Path,
+        path2: &Path,
+        progress: impl FnMut(u64, u64),
+    ) -> std::io::Result<Self> {
+        Self::compare_with_mask_and_progress(path1, path2, &[], progress)
+    }
+
+    /// Compare two firmware files, skipping bytes inside any `ignore` range.
+    ///
+    /// Ignored bytes count neither as differences nor towards the compared
+    /// length used for `diff_percentage`.
+    pub fn compare_with_mask(
+        path1: &Path,
+        path2: &Path,
+        ignore: &[Range<usize>],
+    ) -> std::io::Result<Self> {
+        Self::compare_with_mask_and_progress(path1, path2, ignore, |_, _| {})
+    }
+
+    /// [`FirmwareComparison::compare_with_mask`] with progress reporting.
+    pub fn compare_with_mask_and_progress(
+        path1: &Path,
+        path2: &Path,
+        ignore: &[Range<usize>],
+        mut progress: impl FnMut(u64, u64),
+    ) -> std::io::Result<Self> {
+        let mut reader1 = std::fs::File::open(path1)?;
+        let mut reader2 = std::fs::File::open(path2)?;
+        let len1 = reader1.metadata()?.len();
+        let len2 = reader2.metadata()?.len();
 
         let file1 = path1
             .file_name()
@@ -348,32 +426,41 @@ impl FirmwareComparison {
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_default();
 
-        let size_match = data1.len() == data2.len();
+        let size_match = len1 == len2;
+        let total = len1.min(len2);
 
-        // Compare RSA signatures
-        let rsa_match = if data1.len() >= 0x188 && data2.len() >= 0x188 {
-            data1[0x88..0x188] == data2[0x88..0x188]
-        } else {
-            false
+        let mut buf1 = vec![0u8; COMPARE_WINDOW];
+        let mut buf2 = vec![0u8; COMPARE_WINDOW];
+        let mut diff = DiffTracker {
+            ignore: ignore.to_vec(),
+            ..Default::default()
         };
+        let mut rsa_match = false;
+        let mut offset = 0u64;
 
-        // Count differences
-        let min_len = data1.len().min(data2.len());
-        let diff_count = data1[..min_len]
-            .iter()
-            .zip(data2[..min_len].iter())
-            .filter(|(a, b)| a != b)
-            .count();
+        while offset < total {
+            let want = (total - offset).min(COMPARE_WINDOW as u64) as usize;
+            reader1.read_exact(&mut buf1[..want])?;
+            reader2.read_exact(&mut buf2[..want])?;
+
+            // Compare RSA signatures (always inside the first window)
+            if offset == 0 && want >= RSA_SIGNATURE_RANGE.end {
+                rsa_match = buf1[RSA_SIGNATURE_RANGE] == buf2[RSA_SIGNATURE_RANGE];
+            }
 
-        let diff_percentage = if min_len > 0 {
-            (diff_count as f64 / min_len as f64) * 100.0
+            diff.feed(offset as usize, &buf1[..want], &buf2[..want]);
+            offset += want as u64;
+            progress(offset, total);
+        }
+
+        let diff_count = diff.count;
+        let compared = total as usize - diff.ignored(total as usize);
+        let diff_percentage = if compared > 0 {
+            (diff_count as f64 / compared as f64) * 100.0
         } else {
             0.0
         };
 
-        // Find diff regions
-        let diff_regions = find_diff_regions(&data1, &data2);
-
         Ok(Self {
             file1,
             file2,
@@ -381,10 +468,28 @@ impl FirmwareComparison {
             rsa_match,
             diff_count,
             diff_percentage,
-            diff_regions,
+            diff_regions: diff.finish(total as usize),
         })
     }
 
+    /// Ranges that differ between two legitimately signed builds of the
+    /// same image: the RSA signature and, if present, the FUPH trailer.
+    pub fn signature_ranges(path: &Path) -> std::io::Result<Vec<Range<usize>>> {
+        let mut file = std::fs::File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        // The FUPH search only looks at the last 48 bytes
+        let tail_len = len.min(64);
+        file.seek(SeekFrom::Start((len - tail_len) as u64))?;
+        let mut tail = vec![0u8; tail_len];
+        file.read_exact(&mut tail)?;
+
+        let mut ranges = vec![RSA_SIGNATURE_RANGE];
+        if let Some(fuph) = FuphHeader::parse(&tail) {
+            ranges.push(len - fuph.header_len..len);
+        }
+        Ok(ranges)
+    }
+
     /// Format comparison as text
     pub fn to_text(&self) -> String {
         let mut out = String::new();
@@ -429,85 +534,264 @@ impl FirmwareComparison {
 
         out
     }
+
+    /// Format comparison as pretty-printed JSON, listing every diff region.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("comparison is always serializable")
+    }
+
+    /// Format comparison as markdown, with a table of every diff region.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("## {} vs {}\n\n", self.file1, self.file2));
+        out.push_str("| Property | Value |\n");
+        out.push_str("|----------|-------|\n");
+        out.push_str(&format!(
+            "| Size match | {} |\n",
+            if self.size_match { "✅" } else { "❌" }
+        ));
+        out.push_str(&format!(
+            "| RSA match | {} |\n",
+            if self.rsa_match { "✅" } else { "❌" }
+        ));
+        out.push_str(&format!(
+            "| Different bytes | {} ({:.3}%) |\n",
+            self.diff_count, self.diff_percentage
+        ));
+
+        if !self.diff_regions.is_empty() {
+            out.push_str("\n### Diff regions\n\n");
+            out.push_str("| Start | End | Size | Description |\n");
+            out.push_str("|-------|-----|------|-------------|\n");
+            for r in &self.diff_regions {
+                out.push_str(&format!(
+                    "| 0x{:05X} | 0x{:05X} | {} | {} |\n",
+                    r.start, r.end, r.size, r.description
+                ));
+            }
+        }
+
+        out
+    }
 }
 
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
-fn compute_sha256(data: &[u8]) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-
-    // Simple hash for now (replace with proper SHA256 if crypto is added)
-    let mut hasher = DefaultHasher::new();
-    data.hash(&mut hasher);
-    let h1 = hasher.finish();
-    data[..data.len().min(1024)].hash(&mut hasher);
-    let h2 = hasher.finish();
-    format!(
-        "{:016x}{:016x}{:016x}{:016x}",
-        h1,
-        h2,
-        h1 ^ h2,
-        h1.wrapping_add(h2)
+fn serialize_display<T: fmt::Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&hex_string(bytes))
+}
+
+/// Component hashes as `[{"name": ..., "sha256": ...}]`.
+fn serialize_component_hashes<S: Serializer>(
+    hashes: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct ComponentHash<'a> {
+        name: &'a str,
+        sha256: &'a str,
+    }
+
+    serializer.collect_seq(
+        hashes
+            .iter()
+            .map(|(name, sha256)| ComponentHash { name, sha256 }),
     )
 }
 
-fn detect_file_type(data: &[u8]) -> FirmwareType {
-    // Check for $DnX marker
-    if data.len() > 0x84 && &data[0x80..0x84] == b"$DnX" {
-        // Check for $OS$ header
-        if data.len() >= 4 && &data[0..4] == b"$OS$" {
-            return FirmwareType::DnxOsRecovery;
+/// Hex-encoded SHA256 digest.
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex_string(&Sha256::digest(data))
+}
+
+/// Lowercase hex encoding.
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn compute_component_hashes(
+    data: &[u8],
+    file_type: FirmwareType,
+    token: Option<&TokenInfo>,
+    chaabi: Option<&ChaabiInfo>,
+) -> Vec<(String, String)> {
+    let mut ranges: Vec<(String, usize, usize)> = Vec::new();
+
+    // Same boundaries `xtask firmware extract` uses for DnX binaries
+    ranges.push(("Header".to_string(), 0, 0x188));
+    if let Some(t) = token {
+        ranges.push(("IFWI".to_string(), 0, t.offset));
+        ranges.push(("Token".to_string(), t.offset, t.offset + t.size));
+    }
+    if let Some(c) = chaabi {
+        ranges.push(("Chaabi".to_string(), c.offset, c.offset + c.size));
+    }
+
+    // Full IFWI images carry the FW update layout (PSFW/SSFW/VEDFW...)
+    if file_type == FirmwareType::Ifwi
+        && let Ok(image) = FirmwareImage::from_bytes(data.to_vec())
+    {
+        for (component, range) in image.components() {
+            ranges.push((component.name().to_string(), range.start, range.end));
         }
-        return FirmwareType::DnxFirmware;
     }
 
-    // Check for $OS$ header
-    if data.len() >= 4 && &data[0..4] == b"$OS$" {
+    ranges
+        .into_iter()
+        .filter_map(|(name, start, end)| {
+            let end = end.min(data.len());
+            (start < end).then(|| (name, sha256_hex(&data[start..end])))
+        })
+        .collect()
+}
+
+/// Offset of the `$DnX` marker in a DnX FW binary.
+const DNX_MARKER_OFFSET: usize = 0x80;
+/// Size of the CDPH trailer written when none is supplied.
+const CDPH_TRAILER_SIZE: usize = 0x200;
+
+/// The sections of a `dnx_fwr.bin`, in file order.
+///
+/// Boundaries match `xtask firmware extract`: the IFWI region runs up to the
+/// token, the token up to `CH00 - 0x80`, the Chaabi FW up to `CDPH`, and the
+/// trailer from `CDPH` to the end of the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssembleParams {
+    /// DnX header, signature and IFWI region (starts with the `$DnX` block).
+    pub ifwi: Vec<u8>,
+    /// Chaabi token (`$CHT`/`DTKN` block); may be empty.
+    pub token: Vec<u8>,
+    /// Chaabi FW, from `CH00 - 0x80` up to the `CDPH` marker.
+    pub chaabi: Vec<u8>,
+    /// CDPH trailer; its last 24 bytes are the header sent before the Chaabi FW.
+    pub cdph: Vec<u8>,
+}
+
+/// Split a DnX FW binary into its sections. `None` without `CH00` and `CDPH`.
+pub fn extract_components(data: &[u8]) -> Option<AssembleParams> {
+    let [ch00, cdph, dtkn, cht] = first_positions(data, [b"CH00", b"CDPH", b"DTKN", b"$CHT"]);
+    let (ch00, cdph) = (ch00?, cdph?);
+    let chaabi_start = ch00.checked_sub(0x80)?;
+    if chaabi_start > cdph {
+        return None;
+    }
+
+    let token_start = match (dtkn, cht) {
+        (Some(dtkn), _) if dtkn < chaabi_start => dtkn,
+        (_, Some(cht)) if cht < ch00 && cht >= 0x80 => cht - 0x80,
+        _ => chaabi_start,
+    };
+
+    Some(AssembleParams {
+        ifwi: data[..token_start].to_vec(),
+        token: data[token_start..chaabi_start].to_vec(),
+        chaabi: data[chaabi_start..cdph].to_vec(),
+        cdph: data[cdph..].to_vec(),
+    })
+}
+
+/// Lay out DnX FW sections into a `dnx_fwr.bin`.
+///
+/// The `$DnX` marker is written at 0x80 (padding the IFWI region if it is
+/// shorter), and an empty trailer becomes a zeroed 512-byte `CDPH` block.
+/// The 24-byte DnX header checksum is not stored in the file; it is derived
+/// from the file size when flashing (see [`crate::DnxHeader`]).
+pub fn assemble_fw(params: &AssembleParams) -> Vec<u8> {
+    let mut ifwi = params.ifwi.clone();
+    if ifwi.len() < DNX_MARKER_OFFSET + 4 {
+        ifwi.resize(DNX_MARKER_OFFSET + 4, 0);
+    }
+    ifwi[DNX_MARKER_OFFSET..DNX_MARKER_OFFSET + 4].copy_from_slice(b"$DnX");
+
+    let mut cdph = params.cdph.clone();
+    if cdph.is_empty() {
+        cdph.resize(CDPH_TRAILER_SIZE, 0);
+    }
+    if cdph.len() < 4 {
+        cdph.resize(4, 0);
+    }
+    cdph[..4].copy_from_slice(b"CDPH");
+
+    let mut out =
+        Vec::with_capacity(ifwi.len() + params.token.len() + params.chaabi.len() + cdph.len());
+    out.extend_from_slice(&ifwi);
+    out.extend_from_slice(&params.token);
+    out.extend_from_slice(&params.chaabi);
+    out.extend_from_slice(&cdph);
+    out
+}
+
+/// Classify a buffer's firmware type from its signatures alone.
+///
+/// Cheap and allocation-free; use [`FirmwareAnalysis::analyze_bytes`] for the full report.
+pub fn detect_type(data: &[u8]) -> FirmwareType {
+    let [android, fip] = first_positions(data, [b"ANDROID!", b"$FIP"]);
+    classify(data, android.is_some(), fip.is_some())
+}
+
+/// `detect_type`, given whether `ANDROID!` and `$FIP` occur anywhere.
+fn classify(data: &[u8], has_android: bool, has_fip: bool) -> FirmwareType {
+    // $OS$ at offset 0 is an OS recovery image, whatever else it contains
+    if data.starts_with(b"$OS$") {
         return FirmwareType::DnxOsRecovery;
     }
 
-    // Check for ANDROID!
-    if data.windows(8).any(|w| w == b"ANDROID!") {
+    // Check for $DnX marker
+    if data.get(0x80..0x84) == Some(b"$DnX".as_slice()) {
+        return FirmwareType::DnxFirmware;
+    }
+
+    if has_android {
         return FirmwareType::AndroidBoot;
     }
 
-    // Check for $FIP (full IFWI)
-    if data.windows(4).any(|w| w == b"$FIP") {
+    // $FIP means a full IFWI
+    if has_fip {
         return FirmwareType::Ifwi;
     }
 
     FirmwareType::Unknown
 }
 
+/// Markers reported by the analysis, as (pattern, description).
+const MARKERS: [(&[u8], &str); 11] = [
+    (b"$DnX", "DnX signature marker"),
+    (b"$FIP", "FIP version block"),
+    (b"$CHT", "TNG A0 Token marker"),
+    (b"DTKN", "TNG B0+ Token marker"),
+    (b"ChPr", "TNG B0/ANN Token marker"),
+    (b"CH00", "Chaabi FW start"),
+    (b"CDPH", "Chaabi FW end"),
+    (b"IFWI", "IFWI chunk marker"),
+    (b"$OS$", "OS DnX header"),
+    (b"ANDROID!", "Android boot image"),
+    (b"$MN2", "Manifest 2"),
+];
+
+/// First occurrence of each of `MARKERS`, in one pass over `data`.
 fn find_markers(data: &[u8]) -> Vec<MarkerInfo> {
-    let patterns: &[(&str, &[u8], &str)] = &[
-        ("$DnX", b"$DnX", "DnX signature marker"),
-        ("$FIP", b"$FIP", "FIP version block"),
-        ("$CHT", b"$CHT", "TNG A0 Token marker"),
-        ("DTKN", b"DTKN", "TNG B0+ Token marker"),
-        ("ChPr", b"ChPr", "TNG B0/ANN Token marker"),
-        ("CH00", b"CH00", "Chaabi FW start"),
-        ("CDPH", b"CDPH", "Chaabi FW end"),
-        ("IFWI", b"IFWI", "IFWI chunk marker"),
-        ("$OS$", b"$OS$", "OS DnX header"),
-        ("ANDROID!", b"ANDROID!", "Android boot image"),
-        ("$MN2", b"$MN2", "Manifest 2"),
-    ];
-
-    let mut markers = Vec::new();
-    for (name, pattern, desc) in patterns {
-        if let Some(pos) = data.windows(pattern.len()).position(|w| w == *pattern) {
-            markers.push(MarkerInfo {
-                name: name.to_string(),
+    let positions = first_positions(data, MARKERS.map(|(pattern, _)| pattern));
+    let mut markers: Vec<MarkerInfo> = MARKERS
+        .iter()
+        .zip(positions)
+        .filter_map(|((pattern, desc), pos)| {
+            Some(MarkerInfo {
+                name: String::from_utf8_lossy(pattern).into_owned(),
                 pattern: pattern.to_vec(),
-                position: pos,
+                position: pos?,
                 description: desc.to_string(),
-            });
-        }
-    }
+            })
+        })
+        .collect();
 
     markers.sort_by_key(|m| m.position);
     markers
@@ -520,7 +804,7 @@ fn extract_rsa_signature(data: &[u8]) -> Option<RsaSignature> {
 
     // RSA signature is at 0x88-0x188 (256 bytes)
     let rsa_data = &data[0x88..0x188];
-    let hash = compute_sha256(rsa_data);
+    let hash = sha256_hex(rsa_data);
 
     Some(RsaSignature {
         offset: 0x88,
@@ -579,7 +863,17 @@ fn extract_chaabi_info(_data: &[u8], markers: &[MarkerInfo]) -> Option<ChaabiInf
     })
 }
 
-fn run_validations(data: &[u8], markers: &[MarkerInfo]) -> Vec<ValidationCheck> {
+fn run_validations(
+    data: &[u8],
+    markers: &[MarkerInfo],
+    file_type: FirmwareType,
+    fuph: Option<&FuphHeader>,
+    versions: Option<&FirmwareVersions>,
+) -> Vec<ValidationCheck> {
+    if file_type == FirmwareType::DnxOsRecovery {
+        return run_os_validations(data);
+    }
+
     let mut checks = Vec::new();
 
     // Check $DnX signature
@@ -629,42 +923,186 @@ fn run_validations(data: &[u8], markers: &[MarkerInfo]) -> Vec<ValidationCheck>
         message: format!("{} bytes", data.len()),
     });
 
+    // Check the FUPH trailer, when the image has one
+    if let Some(fuph) = fuph {
+        let passed = fuph.is_valid(data.len());
+        checks.push(ValidationCheck {
+            name: "FUPH".to_string(),
+            passed,
+            message: if passed {
+                format!(
+                    "Components ({}) fit in the image",
+                    format_size(fuph.total_size() as u64)
+                )
+            } else {
+                "Component sizes do not fit in the image".to_string()
+            },
+        });
+    }
+
+    // Never fails: the block checksum rule is unconfirmed on real images, so
+    // mismatches are listed as warnings only
+    if let Some(versions) = versions {
+        checks.push(ValidationCheck {
+            name: "FIP Checksums".to_string(),
+            passed: true,
+            message: if versions.warnings.is_empty() {
+                "All version blocks verify".to_string()
+            } else {
+                format!(
+                    "{} block(s) do not verify (unconfirmed rule, not an error)",
+                    versions.warnings.len()
+                )
+            },
+        });
+    }
+
+    checks
+}
+
+/// Checks for OS recovery images (OSIP header instead of DnX/Chaabi markers).
+fn run_os_validations(data: &[u8]) -> Vec<ValidationCheck> {
+    let mut checks = Vec::new();
+
+    let has_sig = data.starts_with(b"$OS$");
+    checks.push(ValidationCheck {
+        name: "OSIP Signature".to_string(),
+        passed: has_sig,
+        message: if has_sig {
+            "$OS$ found at offset 0"
+        } else {
+            "$OS$ not found"
+        }
+        .to_string(),
+    });
+
+    let num_pointers = data.get(0x08).copied().unwrap_or(0) as usize;
+    let header_size = data
+        .get(0x0A..0x0C)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+        .unwrap_or(0);
+    let header_ok = (OSIP_ENTRIES_OFFSET..=OSIP_PARTITIONTABLE_SIZE).contains(&header_size)
+        && header_size <= data.len();
+
+    // XOR of every header byte, including the checksum byte, must be zero
+    let xor = if header_ok {
+        crate::protocol::xor8(&data[..header_size])
+    } else {
+        0xFF
+    };
+    checks.push(ValidationCheck {
+        name: "OSIP Checksum".to_string(),
+        passed: xor == 0,
+        message: if !header_ok {
+            format!("Invalid header size {}", header_size)
+        } else if xor == 0 {
+            format!("Header checksum OK ({} bytes)", header_size)
+        } else {
+            format!("Header checksum mismatch (xor=0x{:02X})", xor)
+        },
+    });
+
+    let partitions = osii_entries(data);
+    let partitions_ok = num_pointers > 0
+        && header_ok
+        && OSIP_ENTRIES_OFFSET + num_pointers * OSII_SIZE <= header_size
+        && partitions.len() == num_pointers
+        && partitions.iter().all(|&(_, blocks)| blocks > 0)
+        && OSIP_PARTITIONTABLE_SIZE as u64
+            + partitions
+                .iter()
+                .map(|&(_, b)| b * OSIP_BLOCK_SIZE)
+                .sum::<u64>()
+            <= data.len() as u64;
+    checks.push(ValidationCheck {
+        name: "OSIP Partitions".to_string(),
+        passed: partitions_ok,
+        message: format!(
+            "{} partition(s): {}",
+            num_pointers,
+            partitions
+                .iter()
+                .map(|(lba, blocks)| format!("lba {} + {} blocks", lba, blocks))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    });
+
+    let size_ok = data.len() > OSIP_PARTITIONTABLE_SIZE;
+    checks.push(ValidationCheck {
+        name: "File Size".to_string(),
+        passed: size_ok,
+        message: format!("{} bytes", data.len()),
+    });
+
     checks
 }
 
-fn find_diff_regions(data1: &[u8], data2: &[u8]) -> Vec<DiffRegion> {
-    let min_len = data1.len().min(data2.len());
-    let mut regions = Vec::new();
-    let mut in_diff = false;
-    let mut diff_start = 0;
+/// Window size used when streaming two files through a comparison.
+const COMPARE_WINDOW: usize = 1024 * 1024;
+
+/// Differing bytes and regions, accumulated window by window.
+#[derive(Default)]
+struct DiffTracker {
+    count: usize,
+    regions: Vec<DiffRegion>,
+    diff_start: Option<usize>,
+    /// Offsets treated as equal.
+    ignore: Vec<Range<usize>>,
+}
+
+impl DiffTracker {
+    /// Compare two equally long windows starting at file offset `base`.
+    fn feed(&mut self, base: usize, data1: &[u8], data2: &[u8]) {
+        for (i, (a, b)) in data1.iter().zip(data2).enumerate() {
+            let pos = base + i;
+            if a != b && !self.ignore.iter().any(|r| r.contains(&pos)) {
+                self.count += 1;
+                self.diff_start.get_or_insert(pos);
+            } else if let Some(start) = self.diff_start.take() {
+                self.push(start, pos);
+            }
+        }
+    }
 
-    for i in 0..min_len {
-        if data1[i] != data2[i] {
-            if !in_diff {
-                diff_start = i;
-                in_diff = true;
+    /// Bytes below `len` covered by the ignore ranges (overlaps counted once).
+    fn ignored(&self, len: usize) -> usize {
+        let mut ranges: Vec<_> = self
+            .ignore
+            .iter()
+            .map(|r| r.start.min(len)..r.end.min(len))
+            .filter(|r| !r.is_empty())
+            .collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut covered = 0;
+        let mut end = 0;
+        for r in ranges {
+            let start = r.start.max(end);
+            if r.end > start {
+                covered += r.end - start;
+                end = r.end;
             }
-        } else if in_diff {
-            regions.push(DiffRegion {
-                start: diff_start,
-                end: i - 1,
-                size: i - diff_start,
-                description: describe_region(diff_start),
-            });
-            in_diff = false;
         }
+        covered
     }
 
-    if in_diff {
-        regions.push(DiffRegion {
-            start: diff_start,
-            end: min_len - 1,
-            size: min_len - diff_start,
-            description: describe_region(diff_start),
-        });
+    /// Close any open region at `len` (the compared length) and return all regions.
+    fn finish(mut self, len: usize) -> Vec<DiffRegion> {
+        if let Some(start) = self.diff_start.take() {
+            self.push(start, len);
+        }
+        self.regions
     }
 
-    regions
+    fn push(&mut self, start: usize, end_exclusive: usize) {
+        self.regions.push(DiffRegion {
+            start,
+            end: end_exclusive - 1,
+            size: end_exclusive - start,
+            description: describe_region(start),
+        });
+    }
 }
 
 fn describe_region(offset: usize) -> String {
@@ -691,7 +1129,110 @@ mod tests {
     fn test_detect_file_type() {
         let mut data = vec![0u8; 0x100];
         data[0x80..0x84].copy_from_slice(b"$DnX");
-        assert_eq!(detect_file_type(&data), FirmwareType::DnxFirmware);
+        assert_eq!(detect_type(&data), FirmwareType::DnxFirmware);
+    }
+
+    #[test]
+    fn test_detect_type_variants() {
+        let mut os = vec![0u8; 0x100];
+        os[0..4].copy_from_slice(b"$OS$");
+        assert_eq!(detect_type(&os), FirmwareType::DnxOsRecovery);
+
+        let mut ifwi = vec![0u8; 0x100];
+        ifwi[0x40..0x44].copy_from_slice(b"$FIP");
+        assert_eq!(detect_type(&ifwi), FirmwareType::Ifwi);
+
+        let mut boot = vec![0u8; 0x100];
+        boot[0..8].copy_from_slice(b"ANDROID!");
+        assert_eq!(detect_type(&boot), FirmwareType::AndroidBoot);
+
+        assert_eq!(detect_type(&[0u8; 0x100]), FirmwareType::Unknown);
+    }
+
+    #[test]
+    fn test_detect_type_os_marker_wins() {
+        // Both $OS$ at 0 and $DnX at 0x80
+        let mut both = vec![0u8; 0x100];
+        both[0..4].copy_from_slice(b"$OS$");
+        both[0x80..0x84].copy_from_slice(b"$DnX");
+        assert_eq!(detect_type(&both), FirmwareType::DnxOsRecovery);
+
+        // Only $OS$, buffer too short to hold the $DnX marker
+        assert_eq!(detect_type(b"$OS$"), FirmwareType::DnxOsRecovery);
+
+        // $DnX exactly filling the buffer
+        let mut exact = vec![0u8; 0x84];
+        exact[0x80..0x84].copy_from_slice(b"$DnX");
+        assert_eq!(detect_type(&exact), FirmwareType::DnxFirmware);
+        assert_eq!(detect_type(&[]), FirmwareType::Unknown);
+    }
+
+    #[test]
+    fn test_component_hashes() {
+        let mut data = vec![0u8; 0x400];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        let analysis = FirmwareAnalysis::analyze_slice(Path::new("fw.bin"), &data);
+
+        let (name, hash) = &analysis.component_hashes[0];
+        assert_eq!(name, "Header");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(*hash, sha256_hex(&data[..0x188]));
+        assert!(analysis.to_json().contains("\"component_hashes\""));
+    }
+
+    #[test]
+    fn test_analyze_file_matches_slice() {
+        let mut data = vec![0u8; 0x4000];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        data[0x1000..0x1004].copy_from_slice(b"$CH$");
+        data[0x2000..0x2004].copy_from_slice(b"UPH$");
+        let path = std::env::temp_dir().join(format!("dnx-analyze-{}.bin", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+
+        let from_file = FirmwareAnalysis::analyze(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let from_slice = FirmwareAnalysis::analyze_slice(&path, &data);
+
+        assert_eq!(from_file.sha256, sha256_hex(&data));
+        assert!(!from_file.markers.is_empty());
+        assert_eq!(from_file.to_json(), from_slice.to_json());
+    }
+
+    #[test]
+    fn test_analyze_mmap_matches_eager_read() {
+        let mut data = vec![0u8; 0x4000];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        data[0x2000..0x2004].copy_from_slice(b"UPH$");
+        let path = std::env::temp_dir().join(format!("dnx-mmap-{}.bin", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        let mapped = FirmwareAnalysis::analyze_mmap(&path).unwrap();
+        let eager = FirmwareAnalysis::analyze_bytes(&path, std::fs::read(&path).unwrap());
+
+        // An empty file maps to an empty slice
+        std::fs::write(&path, b"").unwrap();
+        let empty = FirmwareAnalysis::analyze_mmap(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(mapped.to_json(), eager.to_json());
+        assert_eq!(empty.size, 0);
+        assert_eq!(empty.sha256, sha256_hex(b""));
+    }
+
+    #[test]
+    fn test_sha256_hex_known_vector() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let zeros = vec![0u8; 4096];
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("zero.bin"), zeros.clone());
+        assert_eq!(
+            analysis.sha256,
+            "ad7facb2586fc6e966c004d7d1d16b024f5805ff7cb47c7a85dabd8b48892ca7"
+        );
+        let rsa = analysis.rsa_signature.unwrap();
+        assert_eq!(rsa.hash, sha256_hex(&zeros[0x88..0x188]));
     }
 
     #[test]
@@ -705,4 +1246,254 @@ mod tests {
         assert_eq!(markers[0].name, "$DnX");
         assert_eq!(markers[1].name, "CH00");
     }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut data = vec![0u8; 0x400];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        data[0x200..0x204].copy_from_slice(b"CH00");
+        data[0x300..0x304].copy_from_slice(b"CDPH");
+        let mut analysis = FirmwareAnalysis::analyze_bytes(Path::new("fw.bin"), data);
+        analysis.versions = Some(FirmwareVersions {
+            ifwi: ifwi_version::Version::new(0x94, 0x171),
+            ..Default::default()
+        });
+
+        let json: serde_json::Value = serde_json::from_str(&analysis.to_json()).unwrap();
+        let names: Vec<_> = json["markers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["name"].as_str().unwrap())
+            .collect();
+        let expected: Vec<_> = analysis.markers.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, expected);
+        assert_eq!(names.len(), 3);
+
+        assert_eq!(json["type"], "DnX Firmware");
+        assert_eq!(json["sha256"], analysis.sha256.as_str());
+        assert_eq!(json["versions"]["ifwi"], "0094.0171");
+        assert_eq!(json["valid"], analysis.is_valid());
+        assert_eq!(
+            json["component_hashes"][0]["sha256"],
+            analysis.component_hashes[0].1.as_str()
+        );
+    }
+
+    fn check_names(analysis: &FirmwareAnalysis) -> Vec<&str> {
+        analysis
+            .validations
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_fw_validations() {
+        let mut data = vec![0u8; 0x800];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        data[0x400..0x404].copy_from_slice(b"CH00");
+        data[0x600..0x604].copy_from_slice(b"CDPH");
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("dnx_fwr.bin"), data);
+
+        assert_eq!(
+            check_names(&analysis),
+            ["DnX Signature", "Chaabi Marker", "CDPH Marker", "File Size"]
+        );
+        assert!(analysis.is_valid());
+    }
+
+    #[test]
+    fn test_fuph_validation() {
+        let mut data = vec![0u8; 0x800];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        let start = data.len() - crate::fuph::FUPH_HDR_LEN;
+        let ifwi = start + crate::fuph::FUPH_IFWI_OFFSET;
+        data[start..start + 4].copy_from_slice(crate::fuph::FUPH_MAGIC);
+        data[ifwi..ifwi + 4].copy_from_slice(&0x40u32.to_le_bytes());
+
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("ifwi.bin"), data.clone());
+        let fuph = analysis.validations.iter().find(|c| c.name == "FUPH");
+        assert!(fuph.is_some_and(|c| c.passed));
+
+        data[ifwi..ifwi + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("ifwi.bin"), data);
+        let fuph = analysis.validations.iter().find(|c| c.name == "FUPH");
+        assert!(fuph.is_some_and(|c| !c.passed));
+    }
+
+    #[test]
+    fn test_fip_checksum_validation() {
+        let mut data = vec![0u8; 0x800];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        data[0x100..0x104].copy_from_slice(b"$FIP");
+        // scuc version 0094.0171 with a checksum byte that does not fold to zero
+        let scuc = 0x104 + 7 * 8;
+        data[scuc..scuc + 5].copy_from_slice(&[0x71, 0x01, 0x94, 0x00, 0x00]);
+
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("ifwi.bin"), data);
+        let check = analysis
+            .validations
+            .iter()
+            .find(|c| c.name == "FIP Checksums")
+            .unwrap();
+        assert!(check.passed);
+        assert!(check.message.starts_with("1 block(s) do not verify"));
+        let warnings = analysis.versions.unwrap().warnings;
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("scuc"));
+    }
+
+    #[test]
+    fn test_os_validations() {
+        // One partition at LBA 1, 2 blocks long
+        let mut data = vec![0u8; 3 * 512];
+        data[0..4].copy_from_slice(b"$OS$");
+        data[0x08] = 1;
+        data[0x0A] = 0x38;
+        data[0x24] = 1;
+        data[0x30] = 2;
+        data[0x07] = data[..0x38].iter().fold(0, |acc, b| acc ^ b);
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("dnx_osr.img"), data.clone());
+
+        assert_eq!(analysis.file_type, FirmwareType::DnxOsRecovery);
+        assert_eq!(
+            check_names(&analysis),
+            [
+                "OSIP Signature",
+                "OSIP Checksum",
+                "OSIP Partitions",
+                "File Size"
+            ]
+        );
+        assert!(analysis.is_valid(), "{:?}", analysis.validations);
+
+        // Corrupt the checksum and overrun the file with the partition
+        data[0x07] ^= 1;
+        data[0x30] = 6;
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("dnx_osr.img"), data);
+        assert!(!analysis.validations[1].passed);
+        assert!(!analysis.validations[2].passed);
+    }
+
+    #[test]
+    fn test_compare_with_mask() {
+        let dir = std::env::temp_dir().join(format!("dnx-compare-mask-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut data1 = vec![0u8; 0x400];
+        let start = data1.len() - crate::fuph::FUPH_HDR_LEN;
+        data1[start..start + 4].copy_from_slice(crate::fuph::FUPH_MAGIC);
+        let mut data2 = data1.clone();
+        data2[RSA_SIGNATURE_RANGE].fill(0xAA);
+        data2[start + crate::fuph::FUPH_IFWI_OFFSET] = 0x10;
+        let (path1, path2) = (dir.join("a.bin"), dir.join("b.bin"));
+        std::fs::write(&path1, &data1).unwrap();
+        std::fs::write(&path2, &data2).unwrap();
+
+        let unmasked = FirmwareComparison::compare(&path1, &path2).unwrap();
+        let ranges = FirmwareComparison::signature_ranges(&path1).unwrap();
+        let masked = FirmwareComparison::compare_with_mask(&path1, &path2, &ranges).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unmasked.diff_count, 0x100 + 1);
+        assert_eq!(ranges, [RSA_SIGNATURE_RANGE, start..0x400]);
+        assert_eq!(masked.diff_count, 0);
+        assert_eq!(masked.diff_percentage, 0.0);
+        assert!(masked.diff_regions.is_empty());
+        assert!(!masked.rsa_match);
+
+        let json: serde_json::Value = serde_json::from_str(&unmasked.to_json()).unwrap();
+        assert_eq!(json["diff_count"], 0x101);
+        let regions = json["diff_regions"].as_array().unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0]["start"], 0x88);
+        assert_eq!(regions[0]["description"], "RSA Signature");
+        assert!(
+            unmasked
+                .to_markdown()
+                .contains("| 0x00088 | 0x00187 | 256 | RSA Signature |")
+        );
+    }
+
+    #[test]
+    fn test_compare_streams_across_windows() {
+        let dir = std::env::temp_dir().join(format!("dnx-compare-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let len = COMPARE_WINDOW + 100;
+        let data1 = vec![0u8; len];
+        let mut data2 = data1.clone();
+        data2[COMPARE_WINDOW - 2..COMPARE_WINDOW + 3].fill(0xFF);
+        data2[0x100] = 1;
+        data2.push(0);
+        let (path1, path2) = (dir.join("a.bin"), dir.join("b.bin"));
+        std::fs::write(&path1, &data1).unwrap();
+        std::fs::write(&path2, &data2).unwrap();
+
+        let mut calls = Vec::new();
+        let cmp = FirmwareComparison::compare_with_progress(&path1, &path2, |done, total| {
+            calls.push((done, total))
+        })
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            calls,
+            [
+                (COMPARE_WINDOW as u64, len as u64),
+                (len as u64, len as u64)
+            ]
+        );
+        assert!(!cmp.size_match);
+        assert!(!cmp.rsa_match);
+        assert_eq!(cmp.diff_count, 6);
+        assert_eq!(cmp.diff_regions.len(), 2);
+        assert_eq!(cmp.diff_regions[1].start, COMPARE_WINDOW - 2);
+        assert_eq!(cmp.diff_regions[1].size, 5);
+    }
+
+    /// Synthetic FW binary: 0x400-byte IFWI, `$CHT` token, Chaabi FW, CDPH trailer.
+    fn synthetic_fw() -> Vec<u8> {
+        let mut data = vec![0xA5u8; 0x400];
+        data[0x80..0x84].copy_from_slice(b"$DnX");
+        let mut token = vec![0x11u8; 0x100];
+        token[0x80..0x84].copy_from_slice(b"$CHT");
+        let mut chaabi = vec![0x22u8; 0x300];
+        chaabi[0x80..0x84].copy_from_slice(b"CH00");
+        let mut cdph = vec![0u8; 0x200];
+        cdph[..4].copy_from_slice(b"CDPH");
+        cdph[0x1F0] = 0x5A;
+        data.extend(token);
+        data.extend(chaabi);
+        data.extend(cdph);
+        data
+    }
+
+    #[test]
+    fn test_extract_assemble_round_trip() {
+        let data = synthetic_fw();
+        let parts = extract_components(&data).unwrap();
+        assert_eq!(parts.ifwi.len(), 0x400);
+        assert_eq!(parts.token.len(), 0x100);
+        assert_eq!(parts.chaabi.len(), 0x300);
+        assert_eq!(parts.cdph.len(), 0x200);
+        assert_eq!(assemble_fw(&parts), data);
+
+        let analysis = FirmwareAnalysis::analyze_bytes(Path::new("dnx_fwr.bin"), data);
+        assert_eq!(analysis.file_type, FirmwareType::DnxFirmware);
+        assert_eq!(analysis.token.unwrap().offset, 0x400);
+    }
+
+    #[test]
+    fn test_assemble_writes_markers() {
+        let image = assemble_fw(&AssembleParams {
+            ifwi: vec![1; 0x10],
+            token: Vec::new(),
+            chaabi: vec![2; 0x100],
+            cdph: Vec::new(),
+        });
+        assert_eq!(&image[0x80..0x84], b"$DnX");
+        assert_eq!(&image[0x184..0x188], b"CDPH");
+        assert_eq!(image.len(), 0x84 + 0x100 + CDPH_TRAILER_SIZE);
+        assert!(extract_components(&image).is_none());
+    }
 }
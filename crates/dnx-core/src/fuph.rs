@@ -5,11 +5,19 @@
 
 use std::fmt;
 
+use serde::Serialize;
+
+use crate::size::format_size;
+
+pub use crate::protocol::header::DnxHeader;
+
 /// FUPH Header magic string
 pub const FUPH_MAGIC: &[u8] = b"UPH$";
 
 /// FUPH Header size (standard)
 pub const FUPH_HDR_LEN: usize = 36;
+/// FUPH Header size on MFD-era images (no VEDFW)
+pub const FUPH_MFD_HDR_LEN: usize = 28;
 
 /// FUPH Header offsets
 pub const FUPH_MIP_OFFSET: usize = 0x04;
@@ -21,7 +29,7 @@ pub const FUPH_SUCP_OFFSET: usize = 0x18;
 pub const FUPH_VEDFW_OFFSET: usize = 0x1c;
 
 /// DNX Header length
-pub const DNX_HDR_LEN: usize = 24;
+pub const DNX_HDR_LEN: usize = DnxHeader::SIZE;
 
 /// DNX Header offsets
 pub const DNX_SIZE_OFFSET: usize = 0;
@@ -48,7 +56,25 @@ pub mod requests {
 }
 
 /// FUPH Header attributes - sizes of firmware components
-#[derive(Debug, Clone, Default)]
+///
+/// The trailer starts with `UPH$` followed by one size dword (in units of
+/// 4 bytes) per component, so its length tells which components it covers:
+///
+/// | Offset | 36-byte (standard) | 28-byte (MFD) |
+/// |--------|--------------------|---------------|
+/// | 0x00   | `UPH$`             | `UPH$`        |
+/// | 0x04   | MIP                | MIP           |
+/// | 0x08   | IFWI               | IFWI          |
+/// | 0x0C   | PSFW1              | PSFW1         |
+/// | 0x10   | PSFW2              | PSFW2         |
+/// | 0x14   | SSFW               | SSFW          |
+/// | 0x18   | SuCP               | SuCP          |
+/// | 0x1C   | VEDFW              | -             |
+/// | 0x20   | reserved           | -             |
+///
+/// Components whose dword lies past the end of a shorter header (SuCP on
+/// some early images) are reported as 0.
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct FuphHeader {
     /// Header length (28 or 36 bytes)
     pub header_len: usize,
@@ -81,15 +107,17 @@ impl FuphHeader {
         let fuph_start = data.len() - header_len;
         let fuph_data = &data[fuph_start..];
 
-        // Sizes are stored as DWORDs (multiply by 4 to get bytes)
+        // Sizes are stored as DWORDs (multiply by 4 to get bytes). A field
+        // is only part of the header if its dword fits inside `header_len`.
         let read_size = |offset: usize| -> u32 {
-            if offset + 4 <= fuph_data.len() {
+            if offset + 4 <= header_len {
                 u32::from_le_bytes([
                     fuph_data[offset],
                     fuph_data[offset + 1],
                     fuph_data[offset + 2],
                     fuph_data[offset + 3],
-                ]) * 4
+                ])
+                .saturating_mul(4)
             } else {
                 0
             }
@@ -103,23 +131,38 @@ impl FuphHeader {
             psfw2_size: read_size(FUPH_PSFW2_OFFSET),
             ssfw_size: read_size(FUPH_SSFW_OFFSET),
             sucp_size: read_size(FUPH_SUCP_OFFSET),
-            vedfw_size: if header_len >= FUPH_HDR_LEN {
-                read_size(FUPH_VEDFW_OFFSET)
-            } else {
-                0
-            },
+            vedfw_size: read_size(FUPH_VEDFW_OFFSET),
         })
     }
 
+    /// Component sizes in image order: MIP, IFWI, PSFW1, PSFW2, SSFW, SuCP, VEDFW.
+    pub fn sizes(&self) -> [u32; 7] {
+        [
+            self.mip_size,
+            self.ifwi_size,
+            self.psfw1_size,
+            self.psfw2_size,
+            self.ssfw_size,
+            self.sucp_size,
+            self.vedfw_size,
+        ]
+    }
+
     /// Total firmware size
     pub fn total_size(&self) -> u32 {
-        self.mip_size
-            + self.ifwi_size
-            + self.psfw1_size
-            + self.psfw2_size
-            + self.ssfw_size
-            + self.sucp_size
-            + self.vedfw_size
+        self.sizes().into_iter().fold(0, u32::saturating_add)
+    }
+
+    /// Whether the trailer is consistent with an image of `total_image_len` bytes.
+    ///
+    /// The trailer carries no checksum of its own, so this is the check the
+    /// SCU driver relies on: no size dword overflows when scaled to bytes,
+    /// and the components plus the trailer fit in the image.
+    pub fn is_valid(&self, total_image_len: usize) -> bool {
+        let sizes = self.sizes();
+        let components: u64 = sizes.iter().map(|&s| s as u64).sum();
+        // `parse` saturates a dword too large to scale to bytes
+        !sizes.contains(&u32::MAX) && components + self.header_len as u64 <= total_image_len as u64
     }
 }
 
@@ -128,121 +171,57 @@ impl fmt::Display for FuphHeader {
         writeln!(f, "FUPH Header (len={}):", self.header_len)?;
         writeln!(
             f,
-            "  MIP:    {:>8} bytes ({:.2} KB)",
+            "  MIP:    {:>8} bytes ({})",
             self.mip_size,
-            self.mip_size as f64 / 1024.0
+            format_size(self.mip_size as u64)
         )?;
         writeln!(
             f,
-            "  IFWI:   {:>8} bytes ({:.2} KB)",
+            "  IFWI:   {:>8} bytes ({})",
             self.ifwi_size,
-            self.ifwi_size as f64 / 1024.0
+            format_size(self.ifwi_size as u64)
         )?;
         writeln!(
             f,
-            "  PSFW1:  {:>8} bytes ({:.2} KB)",
+            "  PSFW1:  {:>8} bytes ({})",
             self.psfw1_size,
-            self.psfw1_size as f64 / 1024.0
+            format_size(self.psfw1_size as u64)
         )?;
         writeln!(
             f,
-            "  PSFW2:  {:>8} bytes ({:.2} KB)",
+            "  PSFW2:  {:>8} bytes ({})",
             self.psfw2_size,
-            self.psfw2_size as f64 / 1024.0
+            format_size(self.psfw2_size as u64)
         )?;
         writeln!(
             f,
-            "  SSFW:   {:>8} bytes ({:.2} KB)",
+            "  SSFW:   {:>8} bytes ({})",
             self.ssfw_size,
-            self.ssfw_size as f64 / 1024.0
+            format_size(self.ssfw_size as u64)
         )?;
         writeln!(
             f,
-            "  SUCP:   {:>8} bytes ({:.2} KB)",
+            "  SUCP:   {:>8} bytes ({})",
             self.sucp_size,
-            self.sucp_size as f64 / 1024.0
+            format_size(self.sucp_size as u64)
         )?;
         writeln!(
             f,
-            "  VEDFW:  {:>8} bytes ({:.2} KB)",
+            "  VEDFW:  {:>8} bytes ({})",
             self.vedfw_size,
-            self.vedfw_size as f64 / 1024.0
+            format_size(self.vedfw_size as u64)
         )?;
         writeln!(
             f,
-            "  Total:  {:>8} bytes ({:.2} MB)",
+            "  Total:  {:>8} bytes ({})",
             self.total_size(),
-            self.total_size() as f64 / 1024.0 / 1024.0
+            format_size(self.total_size() as u64)
         )
     }
 }
 
-/// DNX Header structure for firmware update
-#[derive(Debug, Clone)]
-pub struct DnxHeader {
-    /// DNX payload size
-    pub size: u32,
-    /// General Purpose flags
-    pub gp_flags: u32,
-    /// Reserved fields
-    pub reserved: [u32; 3],
-    /// XOR checksum (size ^ gp_flags)
-    pub xor_checksum: u32,
-}
-
-impl DnxHeader {
-    /// Create a new DNX header
-    pub fn new(size: u32, gp_flags: u32) -> Self {
-        Self {
-            size,
-            gp_flags,
-            reserved: [0; 3],
-            xor_checksum: size ^ gp_flags,
-        }
-    }
-
-    /// Parse DNX header from bytes
-    pub fn parse(data: &[u8]) -> Option<Self> {
-        if data.len() < DNX_HDR_LEN {
-            return None;
-        }
-
-        let read_u32 = |offset: usize| -> u32 {
-            u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ])
-        };
-
-        Some(Self {
-            size: read_u32(DNX_SIZE_OFFSET),
-            gp_flags: read_u32(DNX_GP_FLAG_OFFSET),
-            reserved: [read_u32(8), read_u32(12), read_u32(16)],
-            xor_checksum: read_u32(DNX_XOR_CHK_OFFSET),
-        })
-    }
-
-    /// Serialize to bytes
-    pub fn to_bytes(&self) -> [u8; DNX_HDR_LEN] {
-        let mut bytes = [0u8; DNX_HDR_LEN];
-        bytes[0..4].copy_from_slice(&self.size.to_le_bytes());
-        bytes[4..8].copy_from_slice(&self.gp_flags.to_le_bytes());
-        bytes[8..12].copy_from_slice(&self.reserved[0].to_le_bytes());
-        bytes[12..16].copy_from_slice(&self.reserved[1].to_le_bytes());
-        bytes[16..20].copy_from_slice(&self.reserved[2].to_le_bytes());
-        bytes[20..24].copy_from_slice(&self.xor_checksum.to_le_bytes());
-        bytes
-    }
-
-    /// Validate XOR checksum
-    pub fn is_valid(&self) -> bool {
-        self.xor_checksum == (self.size ^ self.gp_flags)
-    }
-}
-
-/// Find FUPH header length by scanning backwards for "UPH$" magic
+/// Find FUPH header length by scanning backwards for the "UPH$" magic that
+/// starts the header.
 fn find_fuph_header_len(data: &[u8]) -> Option<usize> {
     const SKIP_BYTES: usize = 8;
     const FUPH_MAX_LEN: usize = 36;
@@ -256,15 +235,14 @@ fn find_fuph_header_len(data: &[u8]) -> Option<usize> {
     let mut cnt = 0usize;
 
     while cnt <= FUPH_MAX_LEN {
-        if offset < 4 {
-            break;
-        }
-
-        if &data[offset - 4..offset] == FUPH_MAGIC {
+        if &data[offset..offset + 4] == FUPH_MAGIC {
             return Some(cnt + SKIP_BYTES);
         }
 
-        offset = offset.saturating_sub(4);
+        if offset < 4 {
+            break;
+        }
+        offset -= 4;
         cnt += 4;
     }
 
@@ -280,17 +258,88 @@ mod tests {
         let header = DnxHeader::new(109812, 0);
         assert_eq!(header.size, 109812);
         assert_eq!(header.gp_flags, 0);
-        assert_eq!(header.xor_checksum, 109812);
+        assert_eq!(header.checksum, 109812);
         assert!(header.is_valid());
     }
 
+    /// Image ending in a `header_len`-byte FUPH whose dwords are 1, 2, 3, ...
+    fn fixture(header_len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; 0x100];
+        data.extend_from_slice(FUPH_MAGIC);
+        for i in 1..header_len / 4 {
+            data.extend_from_slice(&(i as u32).to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_36_byte_header() {
+        let fuph = FuphHeader::parse(&fixture(FUPH_HDR_LEN)).unwrap();
+        assert_eq!(fuph.header_len, FUPH_HDR_LEN);
+        assert_eq!(fuph.sizes(), [4, 8, 12, 16, 20, 24, 28]);
+    }
+
+    #[test]
+    fn test_parse_28_byte_header() {
+        let fuph = FuphHeader::parse(&fixture(FUPH_MFD_HDR_LEN)).unwrap();
+        assert_eq!(fuph.header_len, FUPH_MFD_HDR_LEN);
+        assert_eq!(fuph.sizes(), [4, 8, 12, 16, 20, 24, 0]);
+
+        // Without SuCP
+        let fuph = FuphHeader::parse(&fixture(24)).unwrap();
+        assert_eq!(fuph.sizes(), [4, 8, 12, 16, 20, 0, 0]);
+    }
+
+    #[test]
+    fn test_parse_trailer() {
+        let mut data = vec![0xAAu8; 0x100];
+        let start = data.len() - FUPH_HDR_LEN;
+        data[start..].fill(0);
+        data[start..start + 4].copy_from_slice(FUPH_MAGIC);
+        data[start + FUPH_PSFW1_OFFSET..start + FUPH_PSFW1_OFFSET + 4]
+            .copy_from_slice(&0x40u32.to_le_bytes());
+        data[start + FUPH_VEDFW_OFFSET..start + FUPH_VEDFW_OFFSET + 4]
+            .copy_from_slice(&8u32.to_le_bytes());
+
+        let fuph = FuphHeader::parse(&data).unwrap();
+        assert_eq!(fuph.header_len, FUPH_HDR_LEN);
+        assert_eq!(fuph.psfw1_size, 0x100);
+        assert_eq!(fuph.vedfw_size, 0x20);
+    }
+
+    #[test]
+    fn test_is_valid_rejects_corrupt_size() {
+        let mut data = vec![0u8; 0x1000];
+        let start = data.len() - FUPH_HDR_LEN;
+        data[start..start + 4].copy_from_slice(FUPH_MAGIC);
+        // 0x100 dwords = 1 KB of IFWI
+        data[start + FUPH_IFWI_OFFSET..start + FUPH_IFWI_OFFSET + 4]
+            .copy_from_slice(&0x100u32.to_le_bytes());
+
+        let fuph = FuphHeader::parse(&data).unwrap();
+        assert!(fuph.is_valid(data.len()));
+        // The same trailer does not fit in a smaller image
+        assert!(!fuph.is_valid(0x400));
+
+        // A corrupted size runs past the end of the image
+        data[start + FUPH_SSFW_OFFSET..start + FUPH_SSFW_OFFSET + 4]
+            .copy_from_slice(&0x0010_0000u32.to_le_bytes());
+        assert!(!FuphHeader::parse(&data).unwrap().is_valid(data.len()));
+
+        // One too large to scale to bytes at all
+        data[start + FUPH_SSFW_OFFSET..start + FUPH_SSFW_OFFSET + 4]
+            .copy_from_slice(&0xDEAD_BEEFu32.to_le_bytes());
+        assert!(!FuphHeader::parse(&data).unwrap().is_valid(usize::MAX));
+    }
+
     #[test]
     fn test_dnx_header_roundtrip() {
         let header = DnxHeader::new(12345, 0x80000000);
         let bytes = header.to_bytes();
-        let parsed = DnxHeader::parse(&bytes).unwrap();
+        assert_eq!(bytes.len(), DNX_HDR_LEN);
+        let parsed = DnxHeader::from_bytes(&bytes).unwrap();
         assert_eq!(header.size, parsed.size);
         assert_eq!(header.gp_flags, parsed.gp_flags);
-        assert_eq!(header.xor_checksum, parsed.xor_checksum);
+        assert_eq!(header.checksum, parsed.checksum);
     }
 }
@@ -8,6 +8,10 @@
 use std::fmt;
 use std::io::{self, Read};
 
+use serde::{Serialize, Serializer};
+
+use crate::protocol::checksum::xor8;
+
 /// FIP_PATTERN: "$FIP" little-endian (inversed)
 const FIP_PATTERN: u32 = 0x50494624;
 
@@ -34,14 +38,21 @@ impl fmt::Display for Version {
     }
 }
 
+/// Serialized in its display form, e.g. `"0094.0171"`.
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 /// Complete firmware versions extracted from IFWI image
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct FirmwareVersions {
     /// IFWI overall version
     pub ifwi: Version,
     /// SCU (System Control Unit) version
     pub scu: Version,
-    /// SCU Bootstrap version
+    /// SCU Bootstrap version (reported by the device; not stored in the FIP)
     pub scu_bootstrap: Version,
     /// IA32 firmware version
     pub ia32: Version,
@@ -51,19 +62,105 @@ pub struct FirmwareVersions {
     pub chaabi: Version,
     /// mIA version
     pub mia: Version,
+    /// UMIP (user MIP) version
+    pub umip: Version,
+    /// MIP data version
+    pub mipd: Version,
+    /// MIP N version
+    pub mipn: Version,
+    /// SPAT version
+    pub spat: Version,
+    /// SPCT version
+    pub spct: Version,
+    /// RPCH version
+    pub rpch: Version,
+    /// HVM version
+    pub hvm: Version,
+    /// Video decoder firmware version
+    pub ved: Version,
+    /// Video encoder firmware version
+    pub vec: Version,
+    /// MOS (main OS) version
+    pub mos: Version,
+    /// POS (provisioning OS) version
+    pub pos: Version,
+    /// COS (charging OS) version
+    pub cos: Version,
+    /// Chaabi extension versions, CH01 to CH15
+    pub chxx: [Version; 15],
+    /// DnX version
+    pub dnx: Version,
+    /// Version blocks whose checksum byte did not verify (informational;
+    /// the checksum rule is unconfirmed)
+    pub warnings: Vec<String>,
 }
 
 impl FirmwareVersions {
+    /// Components as (label, version), in FIP order after the IFWI version.
+    ///
+    /// CHxx blocks are only listed when set; most images leave them empty.
+    pub fn components(&self) -> Vec<(String, Version)> {
+        self.rows(false)
+    }
+
+    /// Every component whose version differs from `other`, as
+    /// (label, self's version, other's version). A component missing from
+    /// one image shows up as `0000.0000` on that side.
+    pub fn diff(&self, other: &Self) -> Vec<(String, Version, Version)> {
+        let old = std::iter::once(("IFWI".to_string(), self.ifwi)).chain(self.rows(true));
+        let new = std::iter::once(("IFWI".to_string(), other.ifwi)).chain(other.rows(true));
+        old.zip(new)
+            .filter(|((_, a), (_, b))| a != b)
+            .map(|((label, a), (_, b))| (label, a, b))
+            .collect()
+    }
+
+    /// Labelled component versions; `all` keeps unset optional ones.
+    fn rows(&self, all: bool) -> Vec<(String, Version)> {
+        let mut rows: Vec<(String, Version)> = [
+            ("SCU", self.scu),
+            ("Hooks/OEM", self.valhooks),
+            ("IA32", self.ia32),
+            ("Chaabi", self.chaabi),
+            ("mIA", self.mia),
+            ("UMIP", self.umip),
+            ("MIPD", self.mipd),
+            ("MIPN", self.mipn),
+            ("SPAT", self.spat),
+            ("SPCT", self.spct),
+            ("RPCH", self.rpch),
+            ("HVM", self.hvm),
+            ("VED", self.ved),
+            ("VEC", self.vec),
+            ("MOS", self.mos),
+            ("POS", self.pos),
+            ("COS", self.cos),
+        ]
+        .into_iter()
+        .map(|(label, v)| (label.to_string(), v))
+        .collect();
+        if all || self.scu_bootstrap.is_valid() {
+            rows.insert(1, ("SCU Bootstrap".to_string(), self.scu_bootstrap));
+        }
+        rows.extend(
+            self.chxx
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| all || v.is_valid())
+                .map(|(i, v)| (format!("CH{:02}", i + 1), *v)),
+        );
+        rows.push(("DnX".to_string(), self.dnx));
+        rows
+    }
+
     /// Pretty print the firmware versions
     pub fn dump(&self) {
         println!("Image FW versions:");
-        println!("       ifwi: {}", self.ifwi);
+        println!("{:>13}: {}", "IFWI", self.ifwi);
         println!("---- components ----");
-        println!("        scu: {}", self.scu);
-        println!("  hooks/oem: {}", self.valhooks);
-        println!("       ia32: {}", self.ia32);
-        println!("     chaabi: {}", self.chaabi);
-        println!("        mIA: {}", self.mia);
+        for (label, version) in self.components() {
+            println!("{:>13}: {}", label, version);
+        }
     }
 
     /// Format as markdown table
@@ -72,15 +169,51 @@ impl FirmwareVersions {
         out.push_str("| Component | Version |\n");
         out.push_str("|-----------|----------|\n");
         out.push_str(&format!("| IFWI | {} |\n", self.ifwi));
-        out.push_str(&format!("| SCU | {} |\n", self.scu));
-        out.push_str(&format!("| Hooks/OEM | {} |\n", self.valhooks));
-        out.push_str(&format!("| IA32 | {} |\n", self.ia32));
-        out.push_str(&format!("| Chaabi | {} |\n", self.chaabi));
-        out.push_str(&format!("| mIA | {} |\n", self.mia));
+        for (label, version) in self.components() {
+            out.push_str(&format!("| {} | {} |\n", label, version));
+        }
         out
     }
 }
 
+/// One version block from a FIP header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FipComponent {
+    /// Component name as used in the FIP header (e.g. "scuc", "ch01").
+    pub name: &'static str,
+    /// Component version.
+    pub version: Version,
+    /// Per-block checksum byte.
+    pub checksum: u8,
+    /// Size field (CHxx blocks only).
+    pub size: Option<u16>,
+    /// Destination field (CHxx blocks only).
+    pub dest: Option<u16>,
+    /// Whether the block's bytes XOR to zero.
+    pub checksum_valid: bool,
+}
+
+/// Every version block from a single `$FIP` header.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedFip {
+    /// Byte offset of the `$FIP` signature in the image.
+    pub offset: usize,
+    /// All blocks in header order.
+    pub components: Vec<FipComponent>,
+}
+
+impl ParsedFip {
+    /// Look up a component by name.
+    pub fn get(&self, name: &str) -> Option<&FipComponent> {
+        self.components.iter().find(|c| c.name == name)
+    }
+
+    /// Look up a component version by name.
+    pub fn version(&self, name: &str) -> Option<Version> {
+        self.get(name).map(|c| c.version)
+    }
+}
+
 /// FIP version block structure (8 bytes)
 #[derive(Debug, Clone, Copy, Default)]
 #[repr(C, packed)]
@@ -99,6 +232,28 @@ impl FipVersionBlock {
             minor: self.minor,
         }
     }
+
+    /// The checksum byte makes the whole block XOR to zero.
+    ///
+    /// The rule mirrors the OSIP header checksum and has not been confirmed
+    /// against a real FIP block, so a mismatch is only reported as a warning.
+    fn checksum_valid(&self) -> bool {
+        let (minor, major, reserved16) = (self.minor, self.major, self.reserved16);
+        let words = [minor, major, reserved16];
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        xor8(&bytes) ^ self.checksum ^ self.reserved8 == 0
+    }
+
+    fn component(self, name: &'static str) -> FipComponent {
+        FipComponent {
+            name,
+            version: self.as_version(),
+            checksum: self.checksum,
+            size: None,
+            dest: None,
+            checksum_valid: self.checksum_valid(),
+        }
+    }
 }
 
 /// FIP version block with size/dest (12 bytes, for CHxx components)
@@ -114,6 +269,36 @@ struct FipVersionBlockChxx {
     dest: u16,
 }
 
+impl FipVersionBlockChxx {
+    /// As [`FipVersionBlock::checksum_valid`], over all 12 bytes.
+    fn checksum_valid(&self) -> bool {
+        let (minor, major, reserved16, size, dest) = (
+            self.minor,
+            self.major,
+            self.reserved16,
+            self.size,
+            self.dest,
+        );
+        let words = [minor, major, reserved16, size, dest];
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        xor8(&bytes) ^ self.checksum ^ self.reserved8 == 0
+    }
+
+    fn component(self, name: &'static str) -> FipComponent {
+        FipComponent {
+            name,
+            version: Version {
+                major: self.major,
+                minor: self.minor,
+            },
+            checksum: self.checksum,
+            size: Some(self.size),
+            dest: Some(self.dest),
+            checksum_valid: self.checksum_valid(),
+        }
+    }
+}
+
 /// FIP Header structure (complete)
 /// Total size: 4 + 8*18 + 12*15 + 8*4 = 4 + 144 + 180 + 32 = 360 bytes
 #[derive(Debug, Clone, Copy)]
@@ -158,6 +343,50 @@ struct FipHeader {
     ifwi_rev: FipVersionBlock,
 }
 
+impl FipHeader {
+    fn components(&self) -> Vec<FipComponent> {
+        let h = *self;
+        vec![
+            h.umip_rev.component("umip"),
+            h.spat_rev.component("spat"),
+            h.spct_rev.component("spct"),
+            h.rpch_rev.component("rpch"),
+            h.ch00_rev.component("ch00"),
+            h.mipd_rev.component("mipd"),
+            h.mipn_rev.component("mipn"),
+            h.scuc_rev.component("scuc"),
+            h.hvm_rev.component("hvm"),
+            h.mia_rev.component("mia"),
+            h.ia32_rev.component("ia32"),
+            h.oem_rev.component("oem"),
+            h.ved_rev.component("ved"),
+            h.vec_rev.component("vec"),
+            h.mos_rev.component("mos"),
+            h.pos_rev.component("pos"),
+            h.cos_rev.component("cos"),
+            h.ch01_rev.component("ch01"),
+            h.ch02_rev.component("ch02"),
+            h.ch03_rev.component("ch03"),
+            h.ch04_rev.component("ch04"),
+            h.ch05_rev.component("ch05"),
+            h.ch06_rev.component("ch06"),
+            h.ch07_rev.component("ch07"),
+            h.ch08_rev.component("ch08"),
+            h.ch09_rev.component("ch09"),
+            h.ch10_rev.component("ch10"),
+            h.ch11_rev.component("ch11"),
+            h.ch12_rev.component("ch12"),
+            h.ch13_rev.component("ch13"),
+            h.ch14_rev.component("ch14"),
+            h.ch15_rev.component("ch15"),
+            h.dnx_rev.component("dnx"),
+            h.reserved0_rev.component("reserved0"),
+            h.reserved1_rev.component("reserved1"),
+            h.ifwi_rev.component("ifwi"),
+        ]
+    }
+}
+
 impl Default for FipHeader {
     fn default() -> Self {
         // Safety: FipHeader is all primitive types, zeroed is valid
@@ -194,98 +423,91 @@ impl std::error::Error for IfwiError {}
 /// Extract firmware versions from IFWI image data
 pub fn get_image_fw_rev(data: &[u8]) -> Result<FirmwareVersions, IfwiError> {
     let mut versions = FirmwareVersions::default();
-    let mut offset = 0;
-    let fip_size = std::mem::size_of::<FipHeader>();
-    let mut magic_found = false;
 
-    while offset + fip_size <= data.len() {
-        // Scan for FIP magic
-        while offset + 4 <= data.len() {
-            let magic = u32::from_le_bytes([
-                data[offset],
-                data[offset + 1],
-                data[offset + 2],
-                data[offset + 3],
-            ]);
-
-            if magic == FIP_PATTERN {
-                magic_found = true;
-                break;
+    // Later headers override earlier ones, but null fields never overwrite
+    let merge = |target: &mut Version, fip: &ParsedFip, name: &str| {
+        if let Some(v) = fip.version(name) {
+            if v.minor != 0 {
+                target.minor = v.minor;
+            }
+            if v.major != 0 {
+                target.major = v.major;
             }
-            offset += 4;
-        }
-
-        if !magic_found {
-            break;
-        }
-
-        if offset + fip_size > data.len() {
-            break;
-        }
-
-        // Parse FIP header
-        // Safety: We verified there's enough data, and FipHeader is packed C struct
-        let fip: FipHeader =
-            unsafe { std::ptr::read_unaligned(data[offset..].as_ptr() as *const FipHeader) };
-
-        // Update versions (don't update if null)
-        let scuc = fip.scuc_rev.as_version();
-        if scuc.minor != 0 {
-            versions.scu.minor = scuc.minor;
-        }
-        if scuc.major != 0 {
-            versions.scu.major = scuc.major;
         }
-
-        let ia32 = fip.ia32_rev.as_version();
-        if ia32.minor != 0 {
-            versions.ia32.minor = ia32.minor;
+    };
+
+    for fip in parse_fips(data) {
+        for c in fip.components.iter().filter(|c| !c.checksum_valid) {
+            versions.warnings.push(format!(
+                "FIP at 0x{:X}: {} block checksum mismatch (version {}, checksum 0x{:02X})",
+                fip.offset, c.name, c.version, c.checksum
+            ));
         }
-        if ia32.major != 0 {
-            versions.ia32.major = ia32.major;
+        merge(&mut versions.scu, &fip, "scuc");
+        merge(&mut versions.ia32, &fip, "ia32");
+        merge(&mut versions.valhooks, &fip, "oem");
+        merge(&mut versions.ifwi, &fip, "ifwi");
+        merge(&mut versions.chaabi, &fip, "ch00");
+        merge(&mut versions.mia, &fip, "mia");
+        merge(&mut versions.umip, &fip, "umip");
+        merge(&mut versions.mipd, &fip, "mipd");
+        merge(&mut versions.mipn, &fip, "mipn");
+        merge(&mut versions.spat, &fip, "spat");
+        merge(&mut versions.spct, &fip, "spct");
+        merge(&mut versions.rpch, &fip, "rpch");
+        merge(&mut versions.hvm, &fip, "hvm");
+        merge(&mut versions.ved, &fip, "ved");
+        merge(&mut versions.vec, &fip, "vec");
+        merge(&mut versions.mos, &fip, "mos");
+        merge(&mut versions.pos, &fip, "pos");
+        merge(&mut versions.cos, &fip, "cos");
+        merge(&mut versions.dnx, &fip, "dnx");
+        for (i, version) in versions.chxx.iter_mut().enumerate() {
+            merge(version, &fip, &format!("ch{:02}", i + 1));
         }
+    }
 
-        let oem = fip.oem_rev.as_version();
-        if oem.minor != 0 {
-            versions.valhooks.minor = oem.minor;
-        }
-        if oem.major != 0 {
-            versions.valhooks.major = oem.major;
-        }
+    if !versions.ifwi.is_valid() && !versions.scu.is_valid() {
+        return Err(IfwiError::FipNotFound);
+    }
 
-        let ifwi = fip.ifwi_rev.as_version();
-        if ifwi.minor != 0 {
-            versions.ifwi.minor = ifwi.minor;
-        }
-        if ifwi.major != 0 {
-            versions.ifwi.major = ifwi.major;
-        }
+    Ok(versions)
+}
 
-        let ch00 = fip.ch00_rev.as_version();
-        if ch00.minor != 0 {
-            versions.chaabi.minor = ch00.minor;
-        }
-        if ch00.major != 0 {
-            versions.chaabi.major = ch00.major;
-        }
+/// Parse every complete `$FIP` header in the image, in file order.
+pub fn parse_fips(data: &[u8]) -> Vec<ParsedFip> {
+    let fip_size = std::mem::size_of::<FipHeader>();
+    let mut fips = Vec::new();
+    let mut offset = 0;
 
-        let mia = fip.mia_rev.as_version();
-        if mia.minor != 0 {
-            versions.mia.minor = mia.minor;
-        }
-        if mia.major != 0 {
-            versions.mia.major = mia.major;
+    while offset + fip_size <= data.len() {
+        let magic = u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]);
+        if magic == FIP_PATTERN {
+            // Safety: bounds checked above, and FipHeader is a packed C struct
+            let fip: FipHeader =
+                unsafe { std::ptr::read_unaligned(data[offset..].as_ptr() as *const FipHeader) };
+            fips.push(ParsedFip {
+                offset,
+                components: fip.components(),
+            });
         }
-
         offset += 4;
-        magic_found = false;
     }
 
-    if !versions.ifwi.is_valid() && !versions.scu.is_valid() {
-        return Err(IfwiError::FipNotFound);
-    }
+    fips
+}
 
-    Ok(versions)
+/// Parse the first `$FIP` header in the image, exposing all version blocks.
+pub fn parse_fip(data: &[u8]) -> Result<ParsedFip, IfwiError> {
+    parse_fips(data)
+        .into_iter()
+        .next()
+        .ok_or(IfwiError::FipNotFound)
 }
 
 /// Check IFWI file and print versions
@@ -313,6 +535,141 @@ mod tests {
         assert_eq!(format!("{}", v), "0094.0171");
     }
 
+    #[test]
+    fn test_parse_fip_all_blocks() {
+        let fip_size = std::mem::size_of::<FipHeader>();
+        let mut data = vec![0u8; 0x40 + fip_size];
+        data[0x40..0x44].copy_from_slice(b"$FIP");
+        // umip block: minor=0x0002, major=0x0001
+        data[0x44..0x48].copy_from_slice(&[0x02, 0x00, 0x01, 0x00]);
+        // ifwi block is the last 8 bytes
+        let ifwi = 0x40 + fip_size - 8;
+        data[ifwi..ifwi + 4].copy_from_slice(&[0x71, 0x01, 0x94, 0x00]);
+
+        let fip = parse_fip(&data).unwrap();
+        assert_eq!(fip.offset, 0x40);
+        assert_eq!(fip.components.len(), 36);
+        assert_eq!(fip.version("umip"), Some(Version::new(1, 2)));
+        assert_eq!(fip.version("ifwi"), Some(Version::new(0x0094, 0x0171)));
+        assert!(fip.get("ch01").unwrap().size.is_some());
+        assert!(parse_fip(&[0u8; 16]).is_err());
+    }
+
+    #[test]
+    fn test_mip_versions() {
+        let fip_size = std::mem::size_of::<FipHeader>();
+        let mut data = vec![0u8; fip_size];
+        data[0..4].copy_from_slice(b"$FIP");
+        let block = |name: &str| {
+            let index = FipHeader::default()
+                .components()
+                .iter()
+                .position(|c| c.name == name)
+                .unwrap();
+            4 + index * 8
+        };
+        for (name, minor) in [("umip", 1u8), ("mipd", 2), ("mipn", 3), ("scuc", 4)] {
+            let off = block(name);
+            data[off..off + 4].copy_from_slice(&[minor, 0x00, 0x10, 0x00]);
+        }
+
+        let versions = get_image_fw_rev(&data).unwrap();
+        assert_eq!(versions.umip, Version::new(0x10, 1));
+        assert_eq!(versions.mipd, Version::new(0x10, 2));
+        assert_eq!(versions.mipn, Version::new(0x10, 3));
+        assert!(versions.to_markdown().contains("| MIPD | 0010.0002 |"));
+    }
+
+    #[test]
+    fn test_extended_versions() {
+        let fip_size = std::mem::size_of::<FipHeader>();
+        let mut data = vec![0u8; fip_size];
+        data[0..4].copy_from_slice(b"$FIP");
+        // 17 plain blocks, then CH01..CH15 at 12 bytes each, then dnx
+        let plain = |index: usize| 4 + index * 8;
+        let chxx = |n: usize| plain(17) + (n - 1) * 12;
+        let dnx = chxx(16);
+        for (off, minor) in [
+            (plain(7), 1u8),
+            (plain(12), 2),
+            (plain(14), 3),
+            (chxx(3), 4),
+            (dnx, 5),
+        ] {
+            data[off..off + 4].copy_from_slice(&[minor, 0x00, 0x20, 0x00]);
+        }
+
+        let versions = get_image_fw_rev(&data).unwrap();
+        assert_eq!(versions.scu, Version::new(0x20, 1));
+        assert_eq!(versions.ved, Version::new(0x20, 2));
+        assert_eq!(versions.mos, Version::new(0x20, 3));
+        assert_eq!(versions.chxx[2], Version::new(0x20, 4));
+        assert_eq!(versions.dnx, Version::new(0x20, 5));
+
+        let markdown = versions.to_markdown();
+        assert!(markdown.contains("| VED | 0020.0002 |"));
+        assert!(markdown.contains("| CH03 | 0020.0004 |"));
+        assert!(!markdown.contains("CH01"));
+        assert!(!markdown.contains("SCU Bootstrap"));
+    }
+
+    #[test]
+    fn test_block_checksums() {
+        let fip_size = std::mem::size_of::<FipHeader>();
+        let mut data = vec![0u8; fip_size];
+        data[0..4].copy_from_slice(b"$FIP");
+        // scuc (block 7) with a checksum that folds the block to zero
+        let scuc = 4 + 7 * 8;
+        data[scuc..scuc + 5].copy_from_slice(&[0x71, 0x01, 0x94, 0x00, 0x71 ^ 0x01 ^ 0x94]);
+        // ch02 (12-byte block) including its size and dest fields
+        let ch02 = 4 + 17 * 8 + 12;
+        data[ch02..ch02 + 12].copy_from_slice(&[1, 0, 2, 0, 1 ^ 2 ^ 0x10, 0, 0, 0, 0x10, 0, 0, 0]);
+
+        let fip = parse_fip(&data).unwrap();
+        assert!(fip.components.iter().all(|c| c.checksum_valid));
+        assert!(get_image_fw_rev(&data).unwrap().warnings.is_empty());
+
+        data[scuc + 4] ^= 0xFF;
+        let versions = get_image_fw_rev(&data).unwrap();
+        assert_eq!(versions.scu, Version::new(0x94, 0x171));
+        assert_eq!(versions.warnings.len(), 1);
+        assert!(versions.warnings[0].contains("scuc block checksum mismatch"));
+    }
+
+    #[test]
+    fn test_versions_diff() {
+        let fip_size = std::mem::size_of::<FipHeader>();
+        let image = |scu_minor: u8| {
+            let mut data = vec![0u8; fip_size];
+            data[0..4].copy_from_slice(b"$FIP");
+            let scuc = 4 + 7 * 8;
+            data[scuc..scuc + 4].copy_from_slice(&[scu_minor, 0x00, 0x94, 0x00]);
+            let ia32 = 4 + 10 * 8;
+            data[ia32..ia32 + 4].copy_from_slice(&[0x02, 0x00, 0x01, 0x00]);
+            get_image_fw_rev(&data).unwrap()
+        };
+        let old = image(0x70);
+        let new = image(0x71);
+
+        assert_eq!(
+            old.diff(&new),
+            [(
+                "SCU".to_string(),
+                Version::new(0x94, 0x70),
+                Version::new(0x94, 0x71)
+            )]
+        );
+        assert!(old.diff(&old).is_empty());
+
+        // A component only one side has is reported against 0000.0000
+        let mut with_ch05 = new.clone();
+        with_ch05.chxx[4] = Version::new(1, 0);
+        assert_eq!(
+            new.diff(&with_ch05),
+            [("CH05".to_string(), Version::default(), Version::new(1, 0))]
+        );
+    }
+
     #[test]
     fn test_fip_pattern() {
         assert_eq!(FIP_PATTERN, 0x50494624);
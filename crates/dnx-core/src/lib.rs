@@ -11,6 +11,8 @@
 //! - **Transport**: USB communication abstraction (nusb, mock)
 //! - **State**: State machine and ACK handlers
 //! - **Events**: Observer pattern for UI decoupling
+//! - **Plan**: Device-independent description of what a session will send
+//! - **Profiles**: Named board configurations (built-in and `profiles.toml`)
 //! - **Session**: High-level orchestrator
 //! - **IFWI Version**: Extract firmware version info from IFWI images
 //! - **FUPH**: Firmware Update Payload Header parsing
@@ -20,34 +22,56 @@
 //! ```no_run
 //! use dnx_core::session::{DnxSession, SessionConfig};
 //!
-//! let config = SessionConfig {
-//!     fw_dnx_path: Some("dnx_fwr.bin".to_string()),
-//!     fw_image_path: Some("ifwi.bin".to_string()),
-//!     ..Default::default()
-//! };
+//! let config = SessionConfig::builder()
+//!     .fw_dnx("dnx_fwr.bin")
+//!     .fw_image("ifwi.bin")
+//!     .build();
 //!
 //! let mut session = DnxSession::new(config);
 //! session.run().expect("DnX failed");
 //! ```
 
+pub mod compression;
 pub mod events;
 pub mod firmware;
 pub mod fuph;
 pub mod ifwi_version;
+pub mod paths;
 pub mod payload;
+pub mod plan;
+pub mod profiles;
 pub mod protocol;
 pub mod session;
+pub mod size;
 pub mod state;
 pub mod transport;
+pub mod util;
 
 // Re-exports for convenience
-pub use events::{DnxEvent, DnxObserver, DnxPhase, LogLevel, TracingObserver};
-pub use firmware::{FirmwareAnalysis, FirmwareComparison, FirmwareType};
-pub use fuph::{DnxHeader, FuphHeader};
+pub use events::{
+    CompositeObserver, DnxEvent, DnxObserver, DnxPhase, JsonlObserver, LogLevel, PacketLogger,
+    TracingObserver,
+};
+pub use firmware::{
+    AssembleParams, FirmwareAnalysis, FirmwareComparison, FirmwareType, RSA_SIGNATURE_RANGE,
+    assemble_fw, extract_components,
+};
+pub use fuph::FuphHeader;
 pub use ifwi_version::{
-    FirmwareVersions, Version, check_ifwi_file, check_ifwi_path, get_image_fw_rev,
+    FipComponent, FirmwareVersions, ParsedFip, Version, check_ifwi_file, check_ifwi_path,
+    get_image_fw_rev, parse_fip, parse_fips,
 };
 pub use payload::{ChunkState, FirmwareImage, OsChunkState, OsImage};
-pub use protocol::AckCode;
-pub use session::{DnxSession, SessionConfig};
-pub use transport::{MockTransport, NusbTransport, TransportError, UsbTransport};
+pub use plan::{FlashPlan, PlanStep};
+pub use profiles::{Profile, ProfileRegistry};
+pub use protocol::{AckCode, DnxHeader, Preamble};
+pub use session::{
+    Checkpoint, DnxSession, Operation, SessionConfig, SessionConfigBuilder, SessionError,
+    SessionSummary,
+};
+pub use size::format_size;
+pub use transport::{
+    DeviceSelector, DeviceTransport, MockTransport, NusbTransport, TransportError, UsbBackend,
+    UsbTransport,
+};
+pub use util::{first_positions, hexdump};
@@ -0,0 +1,31 @@
+//! Per-user locations for files the tools write (logs, state).
+
+use std::path::PathBuf;
+
+/// Application data directory.
+///
+/// `%LOCALAPPDATA%\dnx` on Windows, `~/Library/Application Support/dnx` on
+/// macOS, `$XDG_STATE_HOME/dnx` (or `~/.local/state/dnx`) elsewhere. Falls
+/// back to the current directory if no home can be determined.
+pub fn data_dir() -> PathBuf {
+    let env = |key: &str| {
+        std::env::var_os(key)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+
+    let base = if cfg!(windows) {
+        env("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|h| h.join("Library").join("Application Support"))
+    } else {
+        env("XDG_STATE_HOME").or_else(|| env("HOME").map(|h| h.join(".local").join("state")))
+    };
+
+    base.map_or_else(|| PathBuf::from("."), |b| b.join("dnx"))
+}
+
+/// Directory holding log files.
+pub fn log_dir() -> PathBuf {
+    data_dir().join("logs")
+}
@@ -3,10 +3,13 @@
 //! Handles Intel Medfield firmware images including:
 //! - DnX header parsing
 //! - FW Update Profile Header extraction
-//! - 128KB chunk iteration for PSFW1/PSFW2/SSFW/VEDFW
+//! - 128KB chunk iteration for PSFW1/PSFW2/SSFW/SuCP/VEDFW
 //!
 //! Reference: xFSTK `dldrstate.cpp` StartFw(), FwHandlePSFW1, etc.
 
+use std::ops::Range;
+
+use crate::fuph::FuphHeader;
 use crate::protocol::constants::ONE28_K;
 use crate::protocol::header::{DnxHeader, FwUpdateProfileHeader, HeaderError};
 use thiserror::Error;
@@ -48,12 +51,31 @@ pub enum FwComponent {
     Psfw2,
     /// Secondary Security FW (Extended)
     Ssfw,
-    /// ROM Patch
-    RomPatch,
+    /// SCU patch (SuCP). xFSTK calls this slot the ROM patch.
+    Sucp,
     /// Video Encoder/Decoder FW
     VedFw,
 }
 
+impl FwComponent {
+    /// Short display name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FwComponent::DnxHeader => "DnX Header",
+            FwComponent::ProfileHeaderSize => "Profile Header Size",
+            FwComponent::ProfileHeader => "Profile Header",
+            FwComponent::Mip => "MIP",
+            FwComponent::Lofw => "LOFW",
+            FwComponent::Hifw => "HIFW",
+            FwComponent::Psfw1 => "PSFW1",
+            FwComponent::Psfw2 => "PSFW2",
+            FwComponent::Ssfw => "SSFW",
+            FwComponent::Sucp => "SuCP",
+            FwComponent::VedFw => "VEDFW",
+        }
+    }
+}
+
 /// Parsed firmware image with lazy component access.
 #[derive(Debug)]
 pub struct FirmwareImage {
@@ -61,6 +83,8 @@ pub struct FirmwareImage {
     data: Vec<u8>,
     /// Detected profile header size
     profile_header_size: usize,
+    /// MIP size from the FUPH trailer; 0 when the image has no usable FUPH
+    mip_size: usize,
     /// Offsets for various components (lazy parsed)
     psfw1_offset: usize,
     psfw1_size: usize,
@@ -68,8 +92,8 @@ pub struct FirmwareImage {
     psfw2_size: usize,
     ssfw_offset: usize,
     ssfw_size: usize,
-    rom_patch_offset: usize,
-    rom_patch_size: usize,
+    sucp_offset: usize,
+    sucp_size: usize,
     vedfw_offset: usize,
     vedfw_size: usize,
 }
@@ -94,43 +118,128 @@ impl FirmwareImage {
         let profile =
             FwUpdateProfileHeader::from_firmware_image(&data[header_start..], profile_header_size)?;
 
-        let psfw1_size = profile.psfw1_size().unwrap_or(0) as usize;
-        let psfw2_size = profile.psfw2_size().unwrap_or(0) as usize;
-        let ssfw_size = profile.ssfw_size().unwrap_or(0) as usize;
-        let rom_patch_size = profile.rom_patch_size().unwrap_or(0) as usize;
-
-        // Calculate offsets
-        // Layout: DnxHeader | ProfileHeader | LOFW (128K) | HIFW (128K) | PSFW1 | PSFW2 | SSFW | RomPatch | VEDFW
+        // Layout: DnxHeader | ProfileHeader | LOFW (128K) | HIFW (128K) | PSFW1 | PSFW2 | SSFW | SuCP | VEDFW
         let base = header_start + profile_header_size;
-        let lofw_hifw_size = ONE28_K * 2; // 256KB for LOFW + HIFW
+        let (mip_size, psfw1_offset, [psfw1_size, psfw2_size, ssfw_size, sucp_size, vedfw_size]) =
+            match Self::fuph_layout(&data, base) {
+                Some(layout) => layout,
+                None => {
+                    // No usable FUPH: components follow LOFW/HIFW back to back
+                    // and VEDFW runs to the end of the image.
+                    let sizes = [
+                        profile.psfw1_size().unwrap_or(0) as usize,
+                        profile.psfw2_size().unwrap_or(0) as usize,
+                        profile.ssfw_size().unwrap_or(0) as usize,
+                        profile.sucp_size().unwrap_or(0) as usize,
+                    ];
+                    let psfw1_offset = base + 2 * ONE28_K;
+                    let vedfw_offset = psfw1_offset + sizes.iter().sum::<usize>();
+                    let vedfw_size = data.len().saturating_sub(vedfw_offset);
+                    (
+                        0,
+                        psfw1_offset,
+                        [sizes[0], sizes[1], sizes[2], sizes[3], vedfw_size],
+                    )
+                }
+            };
 
-        let psfw1_offset = base + lofw_hifw_size;
         let psfw2_offset = psfw1_offset + psfw1_size;
         let ssfw_offset = psfw2_offset + psfw2_size;
-        let rom_patch_offset = ssfw_offset + ssfw_size;
-        let vedfw_offset = rom_patch_offset + rom_patch_size;
-        let vedfw_size = data.len().saturating_sub(vedfw_offset);
+        let sucp_offset = ssfw_offset + ssfw_size;
+        let vedfw_offset = sucp_offset + sucp_size;
 
         Ok(Self {
             data,
             profile_header_size,
+            mip_size,
             psfw1_offset,
             psfw1_size,
             psfw2_offset,
             psfw2_size,
             ssfw_offset,
             ssfw_size,
-            rom_patch_offset,
-            rom_patch_size,
+            sucp_offset,
+            sucp_size,
             vedfw_offset,
             vedfw_size,
         })
     }
 
-    fn detect_profile_header_size(_data: &[u8]) -> usize {
-        // Try to detect based on known patterns
-        // For now, default to D0 size
-        FwUpdateProfileHeader::D0_SIZE
+    /// Read and parse a firmware image, decompressing .gz/.xz input.
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, FirmwareError> {
+        let (data, _) = crate::compression::read_file(path.as_ref())?;
+        Self::from_bytes(data)
+    }
+
+    /// Component layout from a FUPH trailer, if the image has a consistent one.
+    ///
+    /// FUPH lists MIP, IFWI, PSFW1, PSFW2, SSFW, SuCP and VEDFW sizes in image
+    /// order; MIP + IFWI start at `base` and hold LOFW/HIFW. Returns the MIP
+    /// size, the PSFW1 offset and the PSFW1..VEDFW sizes.
+    fn fuph_layout(data: &[u8], base: usize) -> Option<(usize, usize, [usize; 5])> {
+        let fuph = FuphHeader::parse(data)?;
+        let leading = fuph.mip_size as usize + fuph.ifwi_size as usize;
+        let sizes = [
+            fuph.psfw1_size,
+            fuph.psfw2_size,
+            fuph.ssfw_size,
+            fuph.sucp_size,
+            fuph.vedfw_size,
+        ]
+        .map(|s| s as usize);
+
+        let psfw1_offset = base + leading;
+        let end = psfw1_offset + sizes.iter().sum::<usize>();
+        (leading >= 2 * ONE28_K && end <= data.len() - fuph.header_len).then_some((
+            fuph.mip_size as usize,
+            psfw1_offset,
+            sizes,
+        ))
+    }
+
+    /// Pick the profile header size (D0 0x24, C0 0x20, old MFD 0x1C) whose
+    /// component sizes lay out the image.
+    ///
+    /// A candidate whose components end exactly at the end of the image wins;
+    /// otherwise the only candidate that stays in bounds. Ambiguous images
+    /// fall back to D0.
+    fn detect_profile_header_size(data: &[u8]) -> usize {
+        const CANDIDATES: [usize; 3] = [
+            FwUpdateProfileHeader::D0_SIZE,
+            FwUpdateProfileHeader::C0_SIZE,
+            FwUpdateProfileHeader::OLD_MFD_SIZE,
+        ];
+
+        let layout_end = |size: usize| -> Option<usize> {
+            let profile =
+                FwUpdateProfileHeader::from_firmware_image(data.get(DnxHeader::SIZE..)?, size)
+                    .ok()?;
+            [
+                profile.psfw1_size(),
+                profile.psfw2_size(),
+                profile.ssfw_size(),
+                profile.sucp_size(),
+                profile.vedfw_size(),
+            ]
+            .into_iter()
+            .flatten()
+            .try_fold(DnxHeader::SIZE + size + 2 * ONE28_K, |end, s| {
+                end.checked_add(s as usize)
+            })
+        };
+        let ends: Vec<_> = CANDIDATES.iter().map(|&s| (s, layout_end(s))).collect();
+
+        if let Some(&(size, _)) = ends.iter().find(|(_, end)| *end == Some(data.len())) {
+            return size;
+        }
+        let fitting: Vec<_> = ends
+            .iter()
+            .filter(|(_, end)| end.is_some_and(|e| e <= data.len()))
+            .collect();
+        match fitting.as_slice() {
+            [(size, _)] => *size,
+            _ => FwUpdateProfileHeader::D0_SIZE,
+        }
     }
 
     /// Get DnX header bytes.
@@ -138,6 +247,14 @@ impl FirmwareImage {
         &self.data[..DnxHeader::SIZE]
     }
 
+    /// MIP region sent on DMIP: the first `mip_size` bytes after the profile
+    /// header, per the FUPH trailer. `None` without a usable FUPH or MIP.
+    pub fn mip_bytes(&self) -> Option<&[u8]> {
+        let start = DnxHeader::SIZE + self.profile_header_size;
+        let end = (start + self.mip_size).min(self.data.len());
+        (start < end).then(|| &self.data[start..end])
+    }
+
     /// Get profile header size as u32 for sending.
     pub fn profile_header_size_bytes(&self) -> [u8; 4] {
         (self.profile_header_size as u32).to_le_bytes()
@@ -172,7 +289,7 @@ impl FirmwareImage {
             FwComponent::Psfw1 => (self.psfw1_bytes(), ONE28_K),
             FwComponent::Psfw2 => (self.psfw2_bytes(), ONE28_K),
             FwComponent::Ssfw => (self.ssfw_bytes(), ONE28_K),
-            FwComponent::RomPatch => (self.rom_patch_bytes(), ONE28_K),
+            FwComponent::Sucp => (self.sucp_bytes(), ONE28_K),
             FwComponent::VedFw => (self.vedfw_bytes(), ONE28_K),
             _ => (&[] as &[u8], ONE28_K),
         };
@@ -206,13 +323,18 @@ impl FirmwareImage {
         &self.data[self.ssfw_offset..end]
     }
 
-    /// Get ROM Patch bytes.
-    pub fn rom_patch_bytes(&self) -> &[u8] {
-        if self.rom_patch_offset >= self.data.len() || self.rom_patch_size == 0 {
+    /// Get SuCP (SCU patch) bytes.
+    pub fn sucp_bytes(&self) -> &[u8] {
+        if self.sucp_offset >= self.data.len() || self.sucp_size == 0 {
             return &[];
         }
-        let end = (self.rom_patch_offset + self.rom_patch_size).min(self.data.len());
-        &self.data[self.rom_patch_offset..end]
+        let end = (self.sucp_offset + self.sucp_size).min(self.data.len());
+        &self.data[self.sucp_offset..end]
+    }
+
+    /// Alias of `sucp_bytes`, under xFSTK's name for the slot.
+    pub fn rom_patch_bytes(&self) -> &[u8] {
+        self.sucp_bytes()
     }
 
     /// Get VEDFW bytes.
@@ -224,6 +346,43 @@ impl FirmwareImage {
         &self.data[self.vedfw_offset..end]
     }
 
+    /// Byte ranges of every non-empty component sent to the device, clamped
+    /// to the image size.
+    ///
+    /// The MIP overlaps the start of LOFW: it is sent on its own on DMIP.
+    pub fn components(&self) -> Vec<(FwComponent, Range<usize>)> {
+        let header_end = DnxHeader::SIZE + self.profile_header_size;
+        let lofw_end = header_end + ONE28_K;
+        let ranges = [
+            (
+                FwComponent::ProfileHeader,
+                DnxHeader::SIZE,
+                self.profile_header_size,
+            ),
+            (FwComponent::Mip, header_end, self.mip_size),
+            (FwComponent::Lofw, header_end, ONE28_K),
+            (FwComponent::Hifw, lofw_end, ONE28_K),
+            (FwComponent::Psfw1, self.psfw1_offset, self.psfw1_size),
+            (FwComponent::Psfw2, self.psfw2_offset, self.psfw2_size),
+            (FwComponent::Ssfw, self.ssfw_offset, self.ssfw_size),
+            (FwComponent::Sucp, self.sucp_offset, self.sucp_size),
+            (FwComponent::VedFw, self.vedfw_offset, self.vedfw_size),
+        ];
+
+        ranges
+            .into_iter()
+            .filter_map(|(component, start, size)| {
+                let end = (start + size).min(self.data.len());
+                (start < end).then_some((component, start..end))
+            })
+            .collect()
+    }
+
+    /// Total bytes sent for this image: the sum of all component sizes.
+    pub fn total_transfer_size(&self) -> usize {
+        self.components().iter().map(|(_, range)| range.len()).sum()
+    }
+
     /// Get raw data.
     pub fn raw_data(&self) -> &[u8] {
         &self.data
@@ -373,11 +532,9 @@ impl ChunkState {
 
     /// Progress as percentage.
     pub fn progress_pct(&self) -> u8 {
-        if self.total == 0 {
-            100
-        } else {
-            ((self.current * 100) / self.total) as u8
-        }
+        (self.current * 100)
+            .checked_div(self.total)
+            .map_or(100, |pct| pct as u8)
     }
 }
 
@@ -399,6 +556,28 @@ mod tests {
         assert_eq!(chunks[2].len(), 300 * 1024 - 2 * ONE28_K);
     }
 
+    #[test]
+    fn test_components_clamped_to_image() {
+        // Header + profile + LOFW + 1KB of HIFW, no PSFW/SSFW sizes set
+        let len = DnxHeader::SIZE + FwUpdateProfileHeader::D0_SIZE + ONE28_K + 1024;
+        let image = FirmwareImage::from_bytes(vec![0u8; len]).unwrap();
+        let components = image.components();
+
+        let names: Vec<_> = components.iter().map(|(c, _)| *c).collect();
+        assert_eq!(
+            names,
+            vec![
+                FwComponent::ProfileHeader,
+                FwComponent::Lofw,
+                FwComponent::Hifw
+            ]
+        );
+        assert_eq!(components[2].1.len(), 1024);
+
+        let sum: usize = components.iter().map(|(_, r)| r.len()).sum();
+        assert_eq!(image.total_transfer_size(), sum);
+    }
+
     #[test]
     fn test_chunk_state() {
         let data = vec![1u8; 300 * 1024];
@@ -419,4 +598,104 @@ mod tests {
         assert!(state.next_chunk(&data).is_none());
         assert!(state.is_done());
     }
+
+    /// Image whose components exactly fill it for `header_size`; PSFW1 is 0x11.
+    fn exact_fit_image(header_size: usize, vedfw: usize) -> Vec<u8> {
+        let (psfw1, psfw2, ssfw, sucp) = (0x100, 0x80, 0x60, 0x40);
+        let base = DnxHeader::SIZE + header_size + 2 * ONE28_K;
+        let mut data = vec![0u8; base + psfw1 + psfw2 + ssfw + sucp + vedfw];
+        let profile = DnxHeader::SIZE;
+        for (offset, size) in [(0x0C, psfw1), (0x10, psfw2), (0x14, ssfw), (0x18, sucp)] {
+            data[profile + offset..profile + offset + 4]
+                .copy_from_slice(&(size as u32).to_le_bytes());
+        }
+        if header_size > FwUpdateProfileHeader::OLD_MFD_SIZE {
+            data[profile + 0x1C..profile + 0x20].copy_from_slice(&(vedfw as u32).to_le_bytes());
+        }
+        data[base..base + psfw1].fill(0x11);
+        data
+    }
+
+    #[test]
+    fn test_detect_profile_header_size() {
+        for (header_size, vedfw) in [
+            (FwUpdateProfileHeader::D0_SIZE, 0x20),
+            (FwUpdateProfileHeader::C0_SIZE, 0x20),
+            (FwUpdateProfileHeader::OLD_MFD_SIZE, 0),
+        ] {
+            let image = FirmwareImage::from_bytes(exact_fit_image(header_size, vedfw)).unwrap();
+            assert_eq!(image.profile_header_bytes().len(), header_size);
+            assert_eq!(image.mip_bytes(), None);
+            assert_eq!(image.psfw1_bytes(), &[0x11; 0x100][..]);
+            assert_eq!(image.vedfw_bytes().len(), vedfw);
+        }
+
+        // Trailing bytes no candidate accounts for: ambiguous, so D0
+        let mut data = exact_fit_image(FwUpdateProfileHeader::C0_SIZE, 0x20);
+        data.extend_from_slice(&[0u8; 0x10]);
+        let image = FirmwareImage::from_bytes(data).unwrap();
+        assert_eq!(
+            image.profile_header_bytes().len(),
+            FwUpdateProfileHeader::D0_SIZE
+        );
+    }
+
+    #[test]
+    fn test_layout_from_fuph_trailer() {
+        use crate::fuph::{FUPH_HDR_LEN, FUPH_MAGIC};
+
+        // MIP + IFWI is 0x100 bytes longer than LOFW + HIFW
+        let (mip, ifwi) = (0x100, 2 * ONE28_K);
+        let (psfw1, ssfw, sucp, vedfw) = (0x100, 0x80, 0x40, 0x20);
+        let base = DnxHeader::SIZE + FwUpdateProfileHeader::D0_SIZE;
+        let vedfw_start = base + mip + ifwi + psfw1 + ssfw + sucp;
+        let mut data = vec![0u8; vedfw_start + vedfw + FUPH_HDR_LEN];
+        data[base..base + mip].fill(0x3D);
+        data[base + mip + ifwi..base + mip + ifwi + psfw1].fill(0x11);
+        data[vedfw_start..vedfw_start + vedfw].fill(0xED);
+
+        let fuph = vedfw_start + vedfw;
+        data[fuph..fuph + 4].copy_from_slice(FUPH_MAGIC);
+        for (i, size) in [mip, ifwi, psfw1, 0, ssfw, sucp, vedfw]
+            .into_iter()
+            .enumerate()
+        {
+            let offset = fuph + 4 + i * 4;
+            data[offset..offset + 4].copy_from_slice(&(size as u32 / 4).to_le_bytes());
+        }
+
+        let image = FirmwareImage::from_bytes(data).unwrap();
+        assert_eq!(image.mip_bytes(), Some(&[0x3D; 0x100][..]));
+        assert_eq!(image.psfw1_bytes(), &[0x11; 0x100][..]);
+        assert!(image.psfw2_bytes().is_empty());
+        assert_eq!(image.vedfw_bytes(), &[0xED; 0x20][..]);
+    }
+
+    #[test]
+    fn test_layout_with_sucp() {
+        let psfw1 = 0x100;
+        let ssfw = 0x80;
+        let sucp = 0x40;
+        let vedfw = 0x20;
+
+        let base = DnxHeader::SIZE + FwUpdateProfileHeader::D0_SIZE + 2 * ONE28_K;
+        let mut data = vec![0u8; base + psfw1 + ssfw + sucp + vedfw];
+        let profile = DnxHeader::SIZE;
+        data[profile + 0x0C..profile + 0x10].copy_from_slice(&(psfw1 as u32).to_le_bytes());
+        data[profile + 0x14..profile + 0x18].copy_from_slice(&(ssfw as u32).to_le_bytes());
+        data[profile + 0x18..profile + 0x1C].copy_from_slice(&(sucp as u32).to_le_bytes());
+        let sucp_start = base + psfw1 + ssfw;
+        data[sucp_start..sucp_start + sucp].fill(0x5C);
+        data[sucp_start + sucp..].fill(0xED);
+
+        let image = FirmwareImage::from_bytes(data).unwrap();
+        assert_eq!(image.sucp_bytes(), &[0x5C; 0x40][..]);
+        assert_eq!(image.vedfw_bytes(), &[0xED; 0x20][..]);
+        assert!(
+            image
+                .components()
+                .iter()
+                .any(|(c, r)| *c == FwComponent::Sucp && r.start == sucp_start)
+        );
+    }
 }
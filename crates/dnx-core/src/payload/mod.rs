@@ -6,4 +6,4 @@ pub mod firmware;
 pub mod os;
 
 pub use firmware::{ChunkIterator, ChunkState, FirmwareError, FirmwareImage, FwComponent};
-pub use os::{OsChunkIterator, OsChunkState, OsImage, OsImageError};
+pub use os::{OsChunkIterator, OsChunkState, OsImage, OsImageError, PartitionEntry};
@@ -3,8 +3,13 @@
 //! Handles OS recovery images with OSIP (OS Image Package) structure.
 //! Reference: xFSTK `dldrstate.cpp` OsHandleROSIP, OsHandleRIMG
 
-use crate::protocol::constants::OSIP_PARTITIONTABLE_SIZE;
+use crate::protocol::checksum::xor8;
+use crate::protocol::constants::{
+    OSII_BLOCKS_OFFSET, OSII_SIZE, OSIP_BLOCK_SIZE, OSIP_ENTRIES_OFFSET, OSIP_PARTITIONTABLE_SIZE,
+};
 use crate::protocol::header::{HeaderError, OsipHeader};
+use crate::size::format_size;
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -19,6 +24,10 @@ pub enum OsImageError {
     Io(#[from] std::io::Error),
     #[error("Partition {index} out of range")]
     PartitionOutOfRange { index: usize },
+    #[error("Image truncated: partition table needs {expected} bytes, file has {actual}")]
+    Truncated { expected: usize, actual: usize },
+    #[error("OSIP header checksum mismatch (xor=0x{xor:02X})")]
+    ChecksumMismatch { xor: u8 },
 }
 
 /// OSIP signature constant.
@@ -39,8 +48,18 @@ pub struct OsImage {
 }
 
 impl OsImage {
-    /// Parse OS image from raw bytes.
+    /// Parse OS image from raw bytes. A non-standard signature is only logged.
     pub fn from_bytes(data: Vec<u8>) -> Result<Self, OsImageError> {
+        Self::parse(data, false)
+    }
+
+    /// Like [`OsImage::from_bytes`], but reject a signature that is neither
+    /// `$OS$` nor zero.
+    pub fn from_bytes_strict(data: Vec<u8>) -> Result<Self, OsImageError> {
+        Self::parse(data, true)
+    }
+
+    fn parse(data: Vec<u8>, strict: bool) -> Result<Self, OsImageError> {
         if data.len() < OSIP_PARTITIONTABLE_SIZE {
             return Err(OsImageError::FileTooSmall {
                 actual: data.len(),
@@ -53,27 +72,24 @@ impl OsImage {
         // Validate signature if present (some images may not have it)
         // $OS$ = 0x24534F24
         if osip.signature != 0 && osip.signature != OSIP_SIGNATURE {
-            // Not a critical error, just log it
+            if strict {
+                return Err(OsImageError::InvalidSignature {
+                    actual: osip.signature,
+                });
+            }
             tracing::warn!(
                 signature = format!("0x{:08X}", osip.signature),
                 "Non-standard OSIP signature"
             );
         }
 
-        let num_partitions = osip.num_pointers as usize;
-
-        // Parse partition entries
-        let mut partitions = Vec::with_capacity(num_partitions);
-        for i in 0..num_partitions {
-            if let Some(size) = osip.os_partition_size(i) {
-                // Offset calculation: each partition entry is at offset 0x30 + i * 0x18
-                // The actual data offset would need to be read from the entry
-                // For simplicity, we assume sequential layout after OSIP header
-                let offset =
-                    OSIP_PARTITIONTABLE_SIZE + partitions.iter().map(|(_, s)| *s).sum::<usize>();
-                partitions.push((offset, size as usize));
-            }
-        }
+        // Images follow the OSIP in entry order; the OSII LBA is the eMMC
+        // destination and says nothing about where an image sits in the file.
+        let partitions: Vec<_> = partition_entries(&data)
+            .iter()
+            .map(|e| (e.file_offset as usize, e.size() as usize))
+            .collect();
+        let num_partitions = partitions.len();
 
         Ok(Self {
             data,
@@ -83,6 +99,73 @@ impl OsImage {
         })
     }
 
+    /// Read and parse an OS image, decompressing .gz/.xz input.
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, OsImageError> {
+        let (data, _) = crate::compression::read_file(path.as_ref())?;
+        Self::from_bytes(data)
+    }
+
+    /// Check the image is consistent with its OSIP.
+    ///
+    /// The file must hold the partition table followed by every OSII image
+    /// (the LBA is the eMMC destination, not a file offset). If the OSIP
+    /// declares a header size (u16 at 0x0A), its bytes must XOR to 0.
+    pub fn validate(&self) -> Result<(), OsImageError> {
+        let expected = OSIP_PARTITIONTABLE_SIZE
+            + osii_entries(&self.data)
+                .iter()
+                .map(|&(_, blocks)| (blocks * OSIP_BLOCK_SIZE) as usize)
+                .sum::<usize>();
+        if expected > self.data.len() {
+            return Err(OsImageError::Truncated {
+                expected,
+                actual: self.data.len(),
+            });
+        }
+
+        let header_size = u16::from_le_bytes([self.data[0x0A], self.data[0x0B]]) as usize;
+        if (0x20..=OSIP_PARTITIONTABLE_SIZE).contains(&header_size) {
+            let xor = xor8(&self.data[..header_size]);
+            if xor != 0 {
+                return Err(OsImageError::ChecksumMismatch { xor });
+            }
+        }
+        Ok(())
+    }
+
+    /// OSII entries of the partition table.
+    pub fn partition_entries(&self) -> Vec<PartitionEntry> {
+        partition_entries(&self.data)
+    }
+
+    /// Format the partition table for display.
+    pub fn describe(&self) -> String {
+        let entries = self.partition_entries();
+        let mut out = format!(
+            "OSIP: {} partition(s), {}\n",
+            entries.len(),
+            format_size(self.data.len() as u64)
+        );
+        out.push_str(&format!(
+            "{:>3}  {:>10}  {:>10}  {:>12}  {:>10}  {:>10}  {:>4}  {:>7}\n",
+            "#", "Offset", "LBA", "Size", "Load", "Entry", "Attr", "Rev"
+        ));
+        for e in &entries {
+            out.push_str(&format!(
+                "{:>3}  0x{:08X}  0x{:08X}  {:>12}  0x{:08X}  0x{:08X}  0x{:02X}  {:>7}\n",
+                e.index,
+                e.file_offset,
+                e.lba,
+                format_size(e.size()),
+                e.load_address,
+                e.entry_point,
+                e.attributes,
+                format!("{}.{}", e.revision_major, e.revision_minor)
+            ));
+        }
+        out
+    }
+
     /// Get OSIP header bytes (512 bytes).
     pub fn osip_bytes(&self) -> &[u8] {
         &self.data[..OSIP_PARTITIONTABLE_SIZE.min(self.data.len())]
@@ -133,6 +216,20 @@ impl OsImage {
         &self.data[OSIP_PARTITIONTABLE_SIZE..]
     }
 
+    /// Total bytes sent for this image: the OSIP header plus the image data,
+    /// or only the `partitions` selected (by OSIP index) when given.
+    pub fn total_transfer_size(&self, partitions: Option<&[usize]>) -> usize {
+        let images = match partitions {
+            None => self.image_data().len(),
+            Some(partitions) => partitions
+                .iter()
+                .filter_map(|&index| self.partition(index).ok())
+                .map(<[u8]>::len)
+                .sum(),
+        };
+        self.osip_bytes().len() + images
+    }
+
     /// Get chunk iterator for entire image (excluding OSIP header).
     pub fn image_chunks(&self, chunk_size: usize) -> OsChunkIterator<'_> {
         OsChunkIterator::new(self.image_data(), chunk_size)
@@ -154,6 +251,72 @@ impl OsImage {
     }
 }
 
+/// One OSII entry of the OSIP partition table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PartitionEntry {
+    pub index: usize,
+    pub revision_major: u16,
+    pub revision_minor: u16,
+    /// eMMC destination, in 512-byte blocks.
+    pub lba: u32,
+    /// DDR address the image is loaded to.
+    pub load_address: u32,
+    pub entry_point: u32,
+    /// Image size in 512-byte blocks.
+    pub blocks: u32,
+    pub attributes: u8,
+    /// Where the image data starts in the file (images follow the OSIP in order).
+    pub file_offset: u64,
+}
+
+impl PartitionEntry {
+    /// Image size in bytes.
+    pub fn size(&self) -> u64 {
+        self.blocks as u64 * OSIP_BLOCK_SIZE
+    }
+}
+
+/// Parse the OSII entries declared by the u8 pointer count at 0x08.
+pub fn partition_entries(data: &[u8]) -> Vec<PartitionEntry> {
+    let num_pointers = data.get(0x08).copied().unwrap_or(0) as usize;
+    let mut file_offset = OSIP_PARTITIONTABLE_SIZE as u64;
+    let mut entries = Vec::with_capacity(num_pointers);
+    for index in 0..num_pointers {
+        let Some(entry) = data
+            .get(OSIP_ENTRIES_OFFSET + index * OSII_SIZE..)
+            .and_then(|rest| rest.get(..OSII_SIZE))
+        else {
+            break;
+        };
+        let u16_at = |off: usize| u16::from_le_bytes([entry[off], entry[off + 1]]);
+        let u32_at = |off: usize| {
+            u32::from_le_bytes([entry[off], entry[off + 1], entry[off + 2], entry[off + 3]])
+        };
+        let parsed = PartitionEntry {
+            index,
+            revision_minor: u16_at(0x00),
+            revision_major: u16_at(0x02),
+            lba: u32_at(0x04),
+            load_address: u32_at(0x08),
+            entry_point: u32_at(0x0C),
+            blocks: u32_at(OSII_BLOCKS_OFFSET),
+            attributes: entry[0x14],
+            file_offset,
+        };
+        file_offset += parsed.size();
+        entries.push(parsed);
+    }
+    entries
+}
+
+/// `(lba, blocks)` of each OSII entry, as declared by the u8 pointer count at 0x08.
+pub fn osii_entries(data: &[u8]) -> Vec<(u64, u64)> {
+    partition_entries(data)
+        .iter()
+        .map(|e| (e.lba as u64, e.blocks as u64))
+        .collect()
+}
+
 /// OS image chunk iterator.
 #[derive(Debug)]
 pub struct OsChunkIterator<'a> {
@@ -198,11 +361,9 @@ impl<'a> OsChunkIterator<'a> {
 
     /// Progress percentage.
     pub fn progress_pct(&self) -> u8 {
-        if self.total_chunks == 0 {
-            100
-        } else {
-            ((self.current_chunk * 100) / self.total_chunks) as u8
-        }
+        (self.current_chunk * 100)
+            .checked_div(self.total_chunks)
+            .map_or(100, |pct| pct as u8)
     }
 
     /// Reset iterator.
@@ -282,11 +443,9 @@ impl OsChunkState {
     }
 
     pub fn progress_pct(&self) -> u8 {
-        if self.total == 0 {
-            100
-        } else {
-            ((self.current * 100) / self.total) as u8
-        }
+        (self.current * 100)
+            .checked_div(self.total)
+            .map_or(100, |pct| pct as u8)
     }
 }
 
@@ -327,4 +486,124 @@ mod tests {
         assert!(state.next_chunk(&data).is_none());
         assert!(state.is_done());
     }
+
+    /// OSIP with one 4-block OSII entry (destination LBA 50), `len` bytes long.
+    fn single_partition_image(len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        data[0..4].copy_from_slice(b"$OS$");
+        data[0x08] = 1;
+        data[0x24..0x28].copy_from_slice(&50u32.to_le_bytes());
+        data[0x30..0x34].copy_from_slice(&4u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_partition_entries() {
+        let mut data = single_partition_image(5 * 512);
+        data[0x08] = 2;
+        data[0x22..0x24].copy_from_slice(&1u16.to_le_bytes());
+        data[0x28..0x2C].copy_from_slice(&0x0110_0000u32.to_le_bytes());
+        data[0x2C..0x30].copy_from_slice(&0x0110_1000u32.to_le_bytes());
+        data[0x34] = 0x0E;
+        data[0x48..0x4C].copy_from_slice(&2u32.to_le_bytes());
+
+        let entries = partition_entries(&data);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0],
+            PartitionEntry {
+                index: 0,
+                revision_major: 1,
+                revision_minor: 0,
+                lba: 50,
+                load_address: 0x0110_0000,
+                entry_point: 0x0110_1000,
+                blocks: 4,
+                attributes: 0x0E,
+                file_offset: 0x200,
+            }
+        );
+        assert_eq!(entries[1].file_offset, 0x200 + 4 * 512);
+        assert_eq!(entries[1].size(), 1024);
+
+        let image = OsImage::from_bytes(data).unwrap();
+        assert!(image.describe().contains("0x01100000"));
+        assert_eq!(image.total_transfer_size(None), 5 * 512);
+    }
+
+    #[test]
+    fn test_partition_slices() {
+        // Two OSII entries whose eMMC destinations are out of file order
+        let mut data = single_partition_image(0x200 + 6 * 512);
+        data[0x08] = 2;
+        data[0x3C..0x40].copy_from_slice(&10u32.to_le_bytes());
+        data[0x48..0x4C].copy_from_slice(&2u32.to_le_bytes());
+        data[0x200..0x200 + 4 * 512].fill(0xA0);
+        data[0x200 + 4 * 512..].fill(0xA1);
+
+        let image = OsImage::from_bytes(data).unwrap();
+        assert_eq!(image.num_partitions(), 2);
+        assert_eq!(image.partition(0).unwrap(), [0xA0; 4 * 512]);
+        assert_eq!(image.partition(1).unwrap(), [0xA1; 2 * 512]);
+        assert!(matches!(
+            image.partition(2),
+            Err(OsImageError::PartitionOutOfRange { index: 2 })
+        ));
+
+        assert_eq!(image.total_transfer_size(None), 0x200 + 6 * 512);
+        assert_eq!(image.total_transfer_size(Some(&[1])), 0x200 + 2 * 512);
+        assert_eq!(image.total_transfer_size(Some(&[1, 0])), 0x200 + 6 * 512);
+    }
+
+    #[test]
+    fn test_signature_strictness() {
+        let mut data = single_partition_image(0x200 + 4 * 512);
+        data[0..4].copy_from_slice(b"MZ\x90\x00");
+
+        assert!(OsImage::from_bytes(data.clone()).is_ok());
+        assert!(matches!(
+            OsImage::from_bytes_strict(data.clone()),
+            Err(OsImageError::InvalidSignature {
+                actual: 0x0090_5A4D
+            })
+        ));
+
+        // A zero signature is accepted either way
+        data[0..4].fill(0);
+        assert!(OsImage::from_bytes_strict(data).is_ok());
+    }
+
+    #[test]
+    fn test_validate_size() {
+        let image = OsImage::from_bytes(single_partition_image(5 * 512)).unwrap();
+        assert!(image.validate().is_ok());
+
+        let image = OsImage::from_bytes(single_partition_image(3 * 512)).unwrap();
+        assert!(matches!(
+            image.validate(),
+            Err(OsImageError::Truncated {
+                expected: 2560,
+                actual: 1536
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validate_checksum() {
+        let mut data = single_partition_image(5 * 512);
+        data[0x0A] = 0x38;
+        data[0x07] = xor8(&data[..0x38]);
+        assert!(
+            OsImage::from_bytes(data.clone())
+                .unwrap()
+                .validate()
+                .is_ok()
+        );
+
+        data[0x07] ^= 0x01;
+        assert!(matches!(
+            OsImage::from_bytes(data).unwrap().validate(),
+            Err(OsImageError::ChecksumMismatch { xor: 0x01 })
+        ));
+    }
 }
@@ -0,0 +1,206 @@
+//! Flash plan - what a session intends to send, derived from the inputs only.
+//!
+//! Used for dry runs, previews and tests. No device or ACKs are involved.
+
+use std::fmt;
+
+use crate::events::DnxPhase;
+use crate::payload::{FirmwareImage, FwComponent, OsImage};
+use crate::protocol::constants::ONE28_K;
+use crate::state::machine::Operation;
+
+/// One payload the session will send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanStep {
+    pub phase: DnxPhase,
+    pub component: String,
+    pub size: usize,
+    pub chunk_count: usize,
+}
+
+/// Ordered list of payloads for an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashPlan {
+    pub operation: Operation,
+    pub chunk_size: usize,
+    pub steps: Vec<PlanStep>,
+    /// Input problems that would not stop a non-strict session.
+    pub warnings: Vec<String>,
+}
+
+impl FlashPlan {
+    /// Build the plan from already-loaded inputs.
+    ///
+    /// `chunk_size` applies to the OS image; FW components use the fixed
+    /// 128 KB protocol chunks. 0 means the 128 KB default.
+    pub fn build(
+        operation: Operation,
+        chunk_size: usize,
+        fw_dnx: Option<&[u8]>,
+        fw_image: Option<&FirmwareImage>,
+        os_dnx: Option<&[u8]>,
+        os_image: Option<&OsImage>,
+    ) -> Self {
+        let chunk_size = if chunk_size == 0 { ONE28_K } else { chunk_size };
+        let mut plan = Self {
+            operation,
+            chunk_size,
+            steps: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        if operation.includes_fw() {
+            let phase = DnxPhase::FirmwareDownload;
+            if let Some(data) = fw_dnx {
+                plan.push(phase, "FW DnX", data.len(), None);
+            }
+            if let Some(image) = fw_image.filter(|_| operation != Operation::Wipe) {
+                for (component, range) in image.components() {
+                    let chunk = match component {
+                        FwComponent::Psfw1
+                        | FwComponent::Psfw2
+                        | FwComponent::Ssfw
+                        | FwComponent::Sucp
+                        | FwComponent::VedFw => Some(ONE28_K),
+                        _ => None,
+                    };
+                    plan.push(phase, component.name(), range.len(), chunk);
+                }
+            }
+        }
+
+        if operation.includes_os() {
+            let phase = DnxPhase::OsDownload;
+            if let Some(data) = os_dnx {
+                plan.push(phase, "OS DnX", data.len(), None);
+            }
+            if let Some(image) = os_image {
+                if let Err(e) = image.validate() {
+                    plan.warnings.push(format!("OS image: {}", e));
+                }
+                plan.push(phase, "OSIP", image.osip_bytes().len(), None);
+                plan.push(
+                    phase,
+                    "OS Image",
+                    image.image_data().len(),
+                    Some(chunk_size),
+                );
+            }
+        }
+
+        plan
+    }
+
+    /// Add a step; `chunk_size` of `None` means a single transfer.
+    fn push(&mut self, phase: DnxPhase, component: &str, size: usize, chunk_size: Option<usize>) {
+        self.steps.push(PlanStep {
+            phase,
+            component: component.to_string(),
+            size,
+            chunk_count: chunk_size.map_or(1, |c| size.div_ceil(c.max(1))),
+        });
+    }
+
+    /// Total bytes across all steps.
+    pub fn total_bytes(&self) -> usize {
+        self.steps.iter().map(|s| s.size).sum()
+    }
+
+    /// Total number of chunks across all steps.
+    pub fn total_chunks(&self) -> usize {
+        self.steps.iter().map(|s| s.chunk_count).sum()
+    }
+}
+
+impl fmt::Display for FlashPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Plan: {} (chunk size {} bytes)",
+            self.operation, self.chunk_size
+        )?;
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "  {:>2}. [{}] {}: {} bytes in {} chunk(s)",
+                i + 1,
+                step.phase,
+                step.component,
+                step.size,
+                step.chunk_count
+            )?;
+        }
+        write!(
+            f,
+            "Total: {} bytes in {} chunk(s)",
+            self.total_bytes(),
+            self.total_chunks()
+        )?;
+        for warning in &self.warnings {
+            write!(f, "\nWarning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_image(image_len: usize) -> OsImage {
+        let mut data = vec![0u8; 0x200 + image_len];
+        data[0..4].copy_from_slice(b"$OS$");
+        OsImage::from_bytes(data).unwrap()
+    }
+
+    #[test]
+    fn test_plan_os_only() {
+        let image = os_image(300 * 1024);
+        let plan = FlashPlan::build(
+            Operation::OsOnly,
+            0,
+            Some(&[0u8; 10]),
+            None,
+            Some(&[0u8; 64]),
+            Some(&image),
+        );
+
+        let steps: Vec<_> = plan
+            .steps
+            .iter()
+            .map(|s| (s.component.as_str(), s.size, s.chunk_count))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ("OS DnX", 64, 1),
+                ("OSIP", 0x200, 1),
+                ("OS Image", 300 * 1024, 3)
+            ]
+        );
+        assert!(plan.steps.iter().all(|s| s.phase == DnxPhase::OsDownload));
+        assert_eq!(plan.total_chunks(), 5);
+        assert!(plan.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_plan_fw_then_os_chunk_size() {
+        let image = os_image(300 * 1024);
+        let plan = FlashPlan::build(
+            Operation::FwThenOs,
+            64 * 1024,
+            Some(&[0u8; 100]),
+            None,
+            None,
+            Some(&image),
+        );
+
+        assert_eq!(plan.steps[0].component, "FW DnX");
+        assert_eq!(plan.steps[0].phase, DnxPhase::FirmwareDownload);
+        assert_eq!(plan.steps.last().unwrap().chunk_count, 5);
+        assert!(
+            plan.to_string()
+                .contains("OS Image: 307200 bytes in 5 chunk(s)")
+        );
+    }
+}
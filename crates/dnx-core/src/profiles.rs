@@ -0,0 +1,188 @@
+//! Hardware profiles: named sets of images and flags for known boards.
+//!
+//! Built-in profiles cover the boards shipped under `assets/firmware`. More
+//! can be added without code changes in a `profiles.toml`:
+//!
+//! ```toml
+//! [myboard]
+//! description = "My board (Atom Z3560)"
+//! fw_dnx = "images/myboard/dnx_fwr.bin"
+//! os_image = "images/myboard/dnx_osr.img"
+//! gp_flags = 0x80000007
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Profiles file looked up in the working directory by `ProfileRegistry::load`.
+pub const PROFILES_FILE: &str = "profiles.toml";
+
+/// A named board configuration. Paths are relative to the working directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Set from the table key when loaded from TOML.
+    #[serde(skip)]
+    pub name: String,
+    pub description: String,
+    pub fw_dnx: Option<String>,
+    pub fw_image: Option<String>,
+    pub os_dnx: Option<String>,
+    pub os_image: Option<String>,
+    pub gp_flags: Option<u32>,
+}
+
+/// Lookup of a profile name that is not registered.
+#[derive(Debug, Error)]
+#[error("Unknown profile '{name}'. Available: {}", available.join(", "))]
+pub struct UnknownProfile {
+    pub name: String,
+    pub available: Vec<String>,
+}
+
+/// All known profiles, by name.
+#[derive(Debug, Clone)]
+pub struct ProfileRegistry {
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl ProfileRegistry {
+    /// Only the built-in profiles.
+    pub fn builtin() -> Self {
+        let board = |name: &str, description: &str| Profile {
+            name: name.to_string(),
+            description: description.to_string(),
+            fw_dnx: Some(format!("assets/firmware/{}/dnx_fwr.bin", name)),
+            os_image: Some(format!("assets/firmware/{}/dnx_osr.img", name)),
+            ..Default::default()
+        };
+
+        Self {
+            profiles: [
+                board("eaglespeak", "Eaglespeak (Atom Z3580)"),
+                board("blackburn", "Blackburn (Atom Z3530)"),
+            ]
+            .into_iter()
+            .map(|p| (p.name.clone(), p))
+            .collect(),
+        }
+    }
+
+    /// Built-in profiles plus `profiles.toml` from the working directory, if present.
+    pub fn load() -> Result<Self> {
+        let mut registry = Self::builtin();
+        if Path::new(PROFILES_FILE).exists() {
+            registry.load_file(PROFILES_FILE)?;
+        }
+        Ok(registry)
+    }
+
+    /// Add the profiles in a TOML file, replacing built-ins of the same name.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        self.load_str(&content)
+            .with_context(|| format!("Invalid profiles file {}", path.display()))
+    }
+
+    /// Add the profiles in TOML `content`, replacing any of the same name.
+    pub fn load_str(&mut self, content: &str) -> Result<()> {
+        let profiles: BTreeMap<String, Profile> = toml::from_str(content)?;
+        for (name, mut profile) in profiles {
+            profile.name = name.clone();
+            self.profiles.insert(name, profile);
+        }
+        Ok(())
+    }
+
+    /// Look up a profile by name.
+    pub fn get(&self, name: &str) -> Result<&Profile, UnknownProfile> {
+        self.profiles.get(name).ok_or_else(|| UnknownProfile {
+            name: name.to_string(),
+            available: self.names().map(str::to_owned).collect(),
+        })
+    }
+
+    /// Registered profile names, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Registered profiles, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &Profile> {
+        self.profiles.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_profiles() {
+        let registry = ProfileRegistry::builtin();
+        let eaglespeak = registry.get("eaglespeak").unwrap();
+        assert_eq!(
+            eaglespeak.fw_dnx.as_deref(),
+            Some("assets/firmware/eaglespeak/dnx_fwr.bin")
+        );
+        assert_eq!(
+            eaglespeak.os_image.as_deref(),
+            Some("assets/firmware/eaglespeak/dnx_osr.img")
+        );
+
+        let err = registry.get("nope").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile 'nope'. Available: blackburn, eaglespeak"
+        );
+    }
+
+    #[test]
+    fn test_load_custom_profiles_file() {
+        let dir = std::env::temp_dir().join(format!("dnx-profiles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(PROFILES_FILE);
+        std::fs::write(
+            &path,
+            r#"
+[myboard]
+description = "My board"
+fw_dnx = "fw.bin"
+fw_image = "ifwi.bin"
+gp_flags = 0x80000007
+
+[blackburn]
+os_image = "custom/os.img"
+"#,
+        )
+        .unwrap();
+
+        let mut registry = ProfileRegistry::builtin();
+        registry.load_file(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let myboard = registry.get("myboard").unwrap();
+        assert_eq!(myboard.name, "myboard");
+        assert_eq!(myboard.fw_dnx.as_deref(), Some("fw.bin"));
+        assert_eq!(myboard.fw_image.as_deref(), Some("ifwi.bin"));
+        assert_eq!(myboard.os_image, None);
+        assert_eq!(myboard.gp_flags, Some(0x8000_0007));
+
+        // A file entry replaces the built-in of the same name
+        let blackburn = registry.get("blackburn").unwrap();
+        assert_eq!(blackburn.os_image.as_deref(), Some("custom/os.img"));
+        assert_eq!(blackburn.fw_dnx, None);
+
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            ["blackburn", "eaglespeak", "myboard"]
+        );
+        assert!(registry.load_str("[bad]\ngp_flags = \"x\"").is_err());
+    }
+}
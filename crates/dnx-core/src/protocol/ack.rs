@@ -5,40 +5,70 @@
 
 use std::fmt;
 
+use super::Preamble;
+use super::constants::*;
+
 /// Parsed ACK code from device.
+///
+/// The bytes are stored big-endian in the low `len` bytes of `value`, so
+/// `"ROSIP"` from the wire and the `BULK_ACK_ROSIP` constant compare equal.
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct AckCode {
-    /// Raw bytes of the ACK (up to 8 bytes, stored as u64)
+    /// Raw bytes of the ACK (up to 8 bytes, right-aligned in a u64)
     value: u64,
     /// Number of significant bytes
     len: u8,
 }
 
 impl AckCode {
-    /// Create ACK code from raw bytes.
+    /// Create ACK code from raw bytes (first 8 at most), big-endian.
     pub fn from_bytes(bytes: &[u8]) -> Self {
-        let len = bytes.len().min(8) as u8;
-        let mut value: u64 = 0;
-        for (i, &b) in bytes.iter().take(8).enumerate() {
-            value |= (b as u64) << ((7 - i) * 8);
+        let bytes = &bytes[..bytes.len().min(8)];
+        let value = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+        Self {
+            value,
+            len: bytes.len() as u8,
         }
-        // Shift to align to MSB based on actual length
-        let shift = (8 - len) * 8;
-        value >>= shift;
-        Self { value, len }
     }
 
     /// Create ACK from a 4-byte u32 constant.
     pub const fn from_u32(v: u32) -> Self {
-        Self {
-            value: v as u64,
-            len: 4,
+        Self::from_u64_len(v as u64, 4)
+    }
+
+    /// Create a `len`-byte ACK from the low bytes of `v` (bytes above `len` are dropped).
+    pub const fn from_u64_len(v: u64, len: u8) -> Self {
+        let len = if len > 8 { 8 } else { len };
+        let value = if len == 8 {
+            v
+        } else {
+            v & ((1u64 << (len as u32 * 8)) - 1)
+        };
+        Self { value, len }
+    }
+
+    /// The ACK for a constant registered in `ALL_ACKS`, at its wire width.
+    pub const fn from_constant(v: u64) -> Option<Self> {
+        let mut i = 0;
+        while i < ALL_ACKS.len() {
+            if ALL_ACKS[i].value == v {
+                return Some(ALL_ACKS[i].code());
+            }
+            i += 1;
         }
+        None
     }
 
     /// Create ACK from a u64 constant (variable length).
-    /// Determines length by counting significant bytes.
+    ///
+    /// Registered constants get their wire width (see `from_constant`).
+    /// Other values are sized by counting significant bytes, so an
+    /// unregistered code with leading NUL bytes must be built with
+    /// [`AckCode::from_u64_len`].
     pub const fn from_u64(v: u64) -> Self {
+        if let Some(ack) = Self::from_constant(v) {
+            return ack;
+        }
         // Count significant bytes by checking highest set byte
         let len = if v > 0x00FF_FFFF_FFFF_FFFF {
             8
@@ -57,14 +87,17 @@ impl AckCode {
         } else {
             1
         };
-        Self { value: v, len }
+        Self::from_u64_len(v, len)
+    }
+
+    /// The `len` significant bytes, in wire order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.value.to_be_bytes()[8 - self.len as usize..].to_vec()
     }
 
     /// Get ASCII representation if printable.
     pub fn as_ascii(&self) -> String {
-        let bytes = self.value.to_be_bytes();
-        let start = 8 - self.len as usize;
-        bytes[start..]
+        self.to_bytes()
             .iter()
             .map(|&b| {
                 if b.is_ascii_graphic() || b == b' ' {
@@ -91,38 +124,76 @@ impl AckCode {
         self.len == 0
     }
 
-    /// Check if this matches a u32 constant (common 4-byte ACKs).
+    /// Check if this is exactly a 4-byte ACK constant (DONE, DFRM, ...).
+    ///
+    /// Longer ACKs never match, even when they start or end with `expected`
+    /// (e.g. `RUPHS` is not `RUPH`).
     pub fn matches_u32(&self, expected: u32) -> bool {
-        if self.len < 4 {
-            return false;
-        }
-        // Check the first 4 bytes (prefix)
-        let shift = (self.len - 4) * 8;
-        ((self.value >> shift) & 0xFFFFFFFF) == expected as u64
+        self.len == 4 && self.value == expected as u64
+    }
+
+    /// Check if this is exactly `bytes`, length included.
+    pub fn matches_exact(&self, bytes: &[u8]) -> bool {
+        bytes.len() == self.len as usize && *self == Self::from_bytes(bytes)
     }
 
     /// Check if this matches a u64 constant (5+ byte ACKs like RUPHS, PSFW1).
+    ///
+    /// Both value and length must agree, so a leading NUL byte does not
+    /// make a 6-byte read equal to a 5-byte code.
     pub fn matches_u64(&self, expected: u64) -> bool {
-        self.value == expected
+        *self == Self::from_u64(expected)
     }
 
     /// Check if this is an error code (starts with 'ER').
     pub fn is_error(&self) -> bool {
-        let be = self.value.to_be_bytes();
-        let start = 8 - self.len as usize;
-        self.len >= 4 && be[start] == b'E' && be[start + 1] == b'R'
+        self.len >= 4 && self.to_bytes().starts_with(b"ER")
+    }
+
+    /// Canonical name of a recognized ACK or preamble, e.g. `"RUPHS"`.
+    pub fn known_name(&self) -> Option<&'static str> {
+        if let Some(def) = AckDef::lookup(self) {
+            return Some(def.name);
+        }
+        Preamble::ALL
+            .iter()
+            .find(|p| *self == Self::from_bytes(&p.to_bytes()))
+            .map(Preamble::name)
+    }
+
+    /// What the device means by this error ACK, if known.
+    ///
+    /// `None` for non-error ACKs and for error codes in `ALL_ACKS` whose
+    /// meaning is not documented.
+    pub fn error_description(&self) -> Option<&'static str> {
+        if !self.is_error() {
+            return None;
+        }
+        ERROR_DESCRIPTIONS
+            .iter()
+            .find(|(code, _)| self.len == 4 && self.matches_u32(*code))
+            .map(|(_, description)| *description)
     }
 }
 
+/// Meanings of the error ACKs that are documented. Extend as codes are
+/// confirmed against xFSTK or device behaviour.
+const ERROR_DESCRIPTIONS: &[(u32, &str)] = &[
+    (
+        BULK_ACK_INVALID_PING,
+        "invalid ping: the handshake preamble was not recognized",
+    ),
+    (BULK_ACK_ERRR, "generic device error"),
+];
+
 impl fmt::Debug for AckCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "AckCode({:0width$X} '{}')",
-            self.value,
-            self.as_ascii(),
-            width = (self.len * 2) as usize
-        )
+        let hex: String = self
+            .to_bytes()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        write!(f, "AckCode({} '{}')", hex, self.as_ascii())
     }
 }
 
@@ -132,6 +203,159 @@ impl fmt::Display for AckCode {
     }
 }
 
+/// Broad grouping of an ACK code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AckCategory {
+    /// Initial DnX handshake (DFRM, DxxM, DXBL).
+    Handshake,
+    /// Firmware download stage.
+    Firmware,
+    /// OS recovery stage.
+    Os,
+    /// Device-reported error.
+    Error,
+    /// SoC/platform identifier.
+    Platform,
+}
+
+impl fmt::Display for AckCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AckCategory::Handshake => "handshake",
+            AckCategory::Firmware => "fw",
+            AckCategory::Os => "os",
+            AckCategory::Error => "error",
+            AckCategory::Platform => "platform",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Registry entry describing a known ACK constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckDef {
+    /// Mnemonic as sent on the wire (non-printable bytes spelled out, e.g. `IFW1`).
+    pub name: &'static str,
+    /// Constant value, right-aligned as in `protocol::constants`.
+    pub value: u64,
+    /// Length in bytes on the wire.
+    pub len: u8,
+    pub category: AckCategory,
+}
+
+impl AckDef {
+    const fn new(name: &'static str, value: u64, len: u8, category: AckCategory) -> Self {
+        Self {
+            name,
+            value,
+            len,
+            category,
+        }
+    }
+
+    /// The ACK as it would be parsed off the wire.
+    pub const fn code(&self) -> AckCode {
+        AckCode::from_u64_len(self.value, self.len)
+    }
+
+    /// Check whether a received ACK is this one.
+    pub fn matches(&self, ack: &AckCode) -> bool {
+        if self.len == 4 {
+            ack.matches_u32(self.value as u32)
+        } else {
+            ack.matches_u64(self.value)
+        }
+    }
+
+    /// Whether `bytes` is a truncated known ACK that needs more data.
+    ///
+    /// An exact match is never partial, even if a longer ACK extends it
+    /// (e.g. `RUPH` vs `RUPHS`).
+    pub fn is_partial(bytes: &[u8]) -> bool {
+        !bytes.is_empty()
+            && ALL_ACKS
+                .iter()
+                .all(|def| def.code() != AckCode::from_bytes(bytes))
+            && ALL_ACKS.iter().any(|def| {
+                let wire = def.code().to_bytes();
+                wire.len() > bytes.len() && wire.starts_with(bytes)
+            })
+    }
+
+    /// Look up the registry entry for a received ACK.
+    pub fn lookup(ack: &AckCode) -> Option<&'static AckDef> {
+        ALL_ACKS.iter().find(|def| def.matches(ack))
+    }
+}
+
+/// Every ACK constant defined in `protocol::constants`.
+pub const ALL_ACKS: &[AckDef] = &[
+    // Handshake
+    AckDef::new("DFRM", BULK_ACK_DFRM as u64, 4, AckCategory::Handshake),
+    AckDef::new("DxxM", BULK_ACK_DxxM as u64, 4, AckCategory::Handshake),
+    AckDef::new("DXBL", BULK_ACK_DXBL as u64, 4, AckCategory::Handshake),
+    // Firmware download
+    AckDef::new("RUPHS", BULK_ACK_READY_UPH_SIZE, 5, AckCategory::Firmware),
+    AckDef::new("RUPH", BULK_ACK_READY_UPH as u64, 4, AckCategory::Firmware),
+    AckDef::new("RESET", BULK_ACK_GPP_RESET, 5, AckCategory::Firmware),
+    AckDef::new("DMIP", BULK_ACK_DMIP as u64, 4, AckCategory::Firmware),
+    AckDef::new("LOFW", BULK_ACK_LOFW as u64, 4, AckCategory::Firmware),
+    AckDef::new("HIFW", BULK_ACK_HIFW as u64, 4, AckCategory::Firmware),
+    AckDef::new("PSFW1", BULK_ACK_PSFW1, 5, AckCategory::Firmware),
+    AckDef::new("PSFW2", BULK_ACK_PSFW2, 5, AckCategory::Firmware),
+    AckDef::new("SSFW", BULK_ACK_SSFW as u64, 4, AckCategory::Firmware),
+    AckDef::new(
+        "HLT$",
+        BULK_ACK_UPDATE_SUCCESSFUL as u64,
+        4,
+        AckCategory::Firmware,
+    ),
+    AckDef::new("SuCP", BULK_ACK_PATCH as u64, 4, AckCategory::Firmware),
+    AckDef::new("RTBD", BULK_ACK_RTBD as u64, 4, AckCategory::Firmware),
+    AckDef::new("VEDFW", BULK_ACK_VEDFW, 5, AckCategory::Firmware),
+    AckDef::new("SSBS", BULK_ACK_SSBS as u64, 4, AckCategory::Firmware),
+    AckDef::new("IFW1", BULK_ACK_IFW1 as u64, 4, AckCategory::Firmware),
+    AckDef::new("IFW2", BULK_ACK_IFW2 as u64, 4, AckCategory::Firmware),
+    AckDef::new("IFW3", BULK_ACK_IFW3 as u64, 4, AckCategory::Firmware),
+    AckDef::new("HLT0", BULK_ACK_HLT0 as u64, 4, AckCategory::Firmware),
+    AckDef::new("DCFI00", BULK_ACK_DCFI00, 6, AckCategory::Firmware),
+    AckDef::new("DIFWI", BULK_ACK_DIFWI, 5, AckCategory::Firmware),
+    // Platform identifiers
+    AckDef::new("MFLD", BULK_ACK_MFLD as u64, 4, AckCategory::Platform),
+    AckDef::new("CLVT", BULK_ACK_CLVT as u64, 4, AckCategory::Platform),
+    // OS recovery
+    AckDef::new("DORM", BULK_ACK_DORM as u64, 4, AckCategory::Os),
+    AckDef::new("OSIP Sz", BULK_ACK_OSIPSZ, 7, AckCategory::Os),
+    AckDef::new("ROSIP", BULK_ACK_ROSIP, 5, AckCategory::Os),
+    AckDef::new("DONE", BULK_ACK_DONE as u64, 4, AckCategory::Os),
+    AckDef::new("RIMG", BULK_ACK_RIMG as u64, 4, AckCategory::Os),
+    AckDef::new("EOIU", BULK_ACK_EOIU as u64, 4, AckCategory::Os),
+    // Errors
+    AckDef::new("ER00", BULK_ACK_INVALID_PING as u64, 4, AckCategory::Error),
+    AckDef::new("ER01", BULK_ACK_ER01 as u64, 4, AckCategory::Error),
+    AckDef::new("ER02", BULK_ACK_ER02 as u64, 4, AckCategory::Error),
+    AckDef::new("ER03", BULK_ACK_ER03 as u64, 4, AckCategory::Error),
+    AckDef::new("ER04", BULK_ACK_ER04 as u64, 4, AckCategory::Error),
+    AckDef::new("ER10", BULK_ACK_ER10 as u64, 4, AckCategory::Error),
+    AckDef::new("ER11", BULK_ACK_ER11 as u64, 4, AckCategory::Error),
+    AckDef::new("ER12", BULK_ACK_ER12 as u64, 4, AckCategory::Error),
+    AckDef::new("ER13", BULK_ACK_ER13 as u64, 4, AckCategory::Error),
+    AckDef::new("ER15", BULK_ACK_ER15 as u64, 4, AckCategory::Error),
+    AckDef::new("ER16", BULK_ACK_ER16 as u64, 4, AckCategory::Error),
+    AckDef::new("ER17", BULK_ACK_ER17 as u64, 4, AckCategory::Error),
+    AckDef::new("ER18", BULK_ACK_ER18 as u64, 4, AckCategory::Error),
+    AckDef::new("ER20", BULK_ACK_ER20 as u64, 4, AckCategory::Error),
+    AckDef::new("ER21", BULK_ACK_ER21 as u64, 4, AckCategory::Error),
+    AckDef::new("ER22", BULK_ACK_ER22 as u64, 4, AckCategory::Error),
+    AckDef::new("ER25", BULK_ACK_ER25 as u64, 4, AckCategory::Error),
+    AckDef::new("ERRR", BULK_ACK_ERRR as u64, 4, AckCategory::Error),
+];
+
+/// All known ACK definitions.
+pub fn all_acks() -> &'static [AckDef] {
+    ALL_ACKS
+}
+
 /// Macro to create constant AckCodes.
 #[macro_export]
 macro_rules! ack {
@@ -143,7 +367,54 @@ macro_rules! ack {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::protocol::constants::*;
+
+    #[test]
+    fn test_ack_formatting() {
+        let ack = AckCode::from_bytes(b"DFRM");
+        assert_eq!(format!("{:?}", ack), "AckCode(4446524D 'DFRM')");
+        assert_eq!(ack.to_string(), "DFRM");
+
+        let ack = AckCode::from_bytes(b"ROSIP");
+        assert_eq!(format!("{:?}", ack), "AckCode(524F534950 'ROSIP')");
+        assert_eq!(ack, AckCode::from_u64(BULK_ACK_ROSIP));
+
+        let ack = AckCode::from_bytes(b"OSIP Sz");
+        assert_eq!(format!("{:?}", ack), "AckCode(4F53495020537A 'OSIP Sz')");
+        assert_eq!(ack.to_string(), "OSIP Sz");
+        assert_eq!(ack, AckCode::from_u64(BULK_ACK_OSIPSZ));
+
+        let ack = AckCode::from_bytes(&[0, 0x41, 0x42, 0x43, 0x44]);
+        assert_eq!(format!("{:?}", ack), "AckCode(0041424344 '.ABCD')");
+        assert_eq!(format!("{:?}", AckCode::from_bytes(&[])), "AckCode( '')");
+    }
+
+    #[test]
+    fn test_constructors_agree() {
+        for def in ALL_ACKS {
+            let ack = def.code();
+            assert_eq!(AckCode::from_bytes(&ack.to_bytes()), ack, "{}", def.name);
+            if ack
+                .to_bytes()
+                .iter()
+                .all(|b| b.is_ascii_graphic() || *b == b' ')
+            {
+                assert_eq!(AckCode::from_bytes(ack.as_ascii().as_bytes()), ack);
+            }
+            if def.len == 4 {
+                assert_eq!(AckCode::from_u32(def.value as u32), ack);
+            } else {
+                assert_eq!(AckCode::from_u64(def.value), ack);
+            }
+        }
+
+        let padded = AckCode::from_u64_len(0x41_4243, 4);
+        assert_eq!(padded, AckCode::from_bytes(b"\0ABC"));
+        assert_eq!(padded.as_ascii(), ".ABC");
+        assert_eq!(
+            AckCode::from_u64_len(0x4142_4344, 2),
+            AckCode::from_bytes(b"CD")
+        );
+    }
 
     #[test]
     fn test_4byte_ack() {
@@ -160,6 +431,61 @@ mod tests {
         assert_eq!(ack.as_ascii(), "RUPHS");
     }
 
+    #[test]
+    fn test_6_and_7_byte_acks() {
+        for (bytes, value, len) in [
+            (&b"DCFI00"[..], BULK_ACK_DCFI00, 6),
+            (&b"OSIP Sz"[..], BULK_ACK_OSIPSZ, 7),
+        ] {
+            let ack = AckCode::from_bytes(bytes);
+            assert_eq!(ack.len(), len);
+            assert_eq!(ack, AckCode::from_u64(value));
+            assert!(ack.matches_u64(value));
+            assert_eq!(ack.as_ascii().as_bytes(), bytes);
+        }
+
+        let osip_sz = AckCode::from_bytes(b"OSIP Sz");
+        assert!(!osip_sz.matches_u64(BULK_ACK_ROSIP));
+        assert!(!AckCode::from_bytes(b"OSIP S").matches_u64(BULK_ACK_OSIPSZ));
+        assert!(!AckCode::from_bytes(b"\0RUPHS").matches_u64(BULK_ACK_READY_UPH_SIZE));
+        assert_eq!(AckDef::lookup(&osip_sz).map(|d| d.name), Some("OSIP Sz"));
+
+        // Reads longer than 8 bytes keep the first 8; empty reads don't panic
+        assert_eq!(AckCode::from_bytes(b"OSIP Sz\0\0").len(), 8);
+        assert!(AckCode::from_bytes(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_known_name() {
+        assert_eq!(AckCode::from_bytes(b"DONE").known_name(), Some("DONE"));
+        assert_eq!(AckCode::from_bytes(b"RUPHS").known_name(), Some("RUPHS"));
+        assert_eq!(AckCode::from_bytes(b"ROSIP").known_name(), Some("ROSIP"));
+        assert_eq!(
+            AckCode::from_bytes(b"OSIP Sz").known_name(),
+            Some("OSIP Sz")
+        );
+        assert_eq!(
+            AckCode::from_bytes(&Preamble::Dner.to_bytes()).known_name(),
+            Some("DnER")
+        );
+        assert_eq!(AckCode::from_bytes(b"XYZW").known_name(), None);
+        assert_eq!(AckCode::from_bytes(b"\0RUPHS").known_name(), None);
+    }
+
+    #[test]
+    fn test_from_constant_uses_registry_width() {
+        for def in ALL_ACKS {
+            assert_eq!(
+                AckCode::from_constant(def.value),
+                Some(def.code()),
+                "{}",
+                def.name
+            );
+        }
+        assert_eq!(AckCode::from_constant(0x1234), None);
+        assert_eq!(AckCode::from_u64(0x1234).len(), 2);
+    }
+
     #[test]
     fn test_from_bytes() {
         let bytes = b"DONE";
@@ -173,4 +499,107 @@ mod tests {
         assert!(ack.is_error());
         assert_eq!(ack.as_ascii(), "ER01");
     }
+
+    #[test]
+    fn test_error_description() {
+        let describe = |ack: u32| AckCode::from_u32(ack).error_description();
+        assert_eq!(
+            describe(BULK_ACK_INVALID_PING),
+            Some("invalid ping: the handshake preamble was not recognized")
+        );
+        assert_eq!(describe(BULK_ACK_ERRR), Some("generic device error"));
+
+        // Known codes without a documented meaning, and non-errors
+        assert_eq!(describe(BULK_ACK_ER10), None);
+        assert_eq!(describe(BULK_ACK_DONE), None);
+
+        // Every described code is a registered error ACK
+        for (code, _) in ERROR_DESCRIPTIONS {
+            let def = AckDef::lookup(&AckCode::from_u32(*code)).unwrap();
+            assert_eq!(def.category, AckCategory::Error, "{}", def.name);
+        }
+    }
+
+    #[test]
+    fn test_ack_registry_consistent() {
+        for (i, def) in ALL_ACKS.iter().enumerate() {
+            assert_eq!(def.name.len(), def.len as usize, "{}", def.name);
+            assert_eq!(AckCode::from_u64(def.value).len(), def.len, "{}", def.name);
+            assert_eq!(AckDef::lookup(&def.code()), Some(def), "{}", def.name);
+
+            for other in &ALL_ACKS[i + 1..] {
+                assert_ne!(def.name, other.name);
+                assert_ne!(def.value, other.value, "{} / {}", def.name, other.name);
+            }
+        }
+        assert_eq!(
+            AckDef::lookup(&AckCode::from_bytes(b"MFLD")).map(|d| d.category),
+            Some(AckCategory::Platform)
+        );
+    }
+
+    #[test]
+    fn test_ack_registry_no_collisions() {
+        for (i, def) in ALL_ACKS.iter().enumerate() {
+            for other in &ALL_ACKS[i + 1..] {
+                assert_ne!(
+                    (def.value, def.len),
+                    (other.value, other.len),
+                    "{} collides with {}",
+                    def.name,
+                    other.name
+                );
+            }
+
+            // No ACK of any other width matches, whatever the check order
+            for other in ALL_ACKS.iter().filter(|d| d.len != def.len) {
+                assert!(
+                    !def.matches(&other.code()),
+                    "{} matches {}",
+                    def.name,
+                    other.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_acks_never_match_4_byte_checks() {
+        let four_byte: Vec<_> = ALL_ACKS.iter().filter(|d| d.len == 4).collect();
+        for long in ALL_ACKS.iter().filter(|d| d.len > 4) {
+            let ack = long.code();
+            for def in &four_byte {
+                assert!(
+                    !ack.matches_u32(def.value as u32),
+                    "{} / {}",
+                    long.name,
+                    def.name
+                );
+            }
+            // Neither the leading nor the trailing 4 bytes count
+            let bytes = ack.to_bytes();
+            for window in [&bytes[..4], &bytes[bytes.len() - 4..]] {
+                let prefix = AckCode::from_bytes(window);
+                assert!(!ack.matches_u32(prefix.value() as u32), "{}", long.name);
+                assert!(prefix.matches_u32(prefix.value() as u32));
+            }
+        }
+
+        // ROSIP ends in 'OSIP'; RUPHS starts with 'RUPH'
+        assert!(!AckCode::from_u64(BULK_ACK_ROSIP).matches_u32(u32::from_be_bytes(*b"OSIP")));
+        assert!(!AckCode::from_u64(BULK_ACK_READY_UPH_SIZE).matches_u32(BULK_ACK_READY_UPH));
+        assert_eq!(
+            AckDef::lookup(&AckCode::from_bytes(b"RUPHS")).map(|d| d.name),
+            Some("RUPHS")
+        );
+    }
+
+    #[test]
+    fn test_matches_exact() {
+        let ack = AckCode::from_bytes(b"RUPHS");
+        assert!(ack.matches_exact(b"RUPHS"));
+        assert!(!ack.matches_exact(b"RUPH"));
+        assert!(!ack.matches_exact(b"\0RUPHS"));
+        assert!(AckCode::from_bytes(b"DONE").matches_exact(b"DONE"));
+    }
 }
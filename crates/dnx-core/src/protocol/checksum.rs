@@ -0,0 +1,24 @@
+//! Checksums used by DnX headers and OSIP tables.
+
+/// DnX header checksum: `size ^ gp_flags`.
+pub fn dnx_xor_checksum(size: u32, gp_flags: u32) -> u32 {
+    size ^ gp_flags
+}
+
+/// XOR of all bytes (OSIP header checksum; a valid header folds to 0).
+pub fn xor8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |acc, b| acc ^ b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(dnx_xor_checksum(109812, 0), 109812);
+        assert_eq!(dnx_xor_checksum(0x1234, 0x20), 0x1214);
+        assert_eq!(xor8(&[0x24, 0x4F, 0x53, 0x24]), 0x24 ^ 0x4F ^ 0x53 ^ 0x24);
+        assert_eq!(xor8(&[]), 0);
+    }
+}
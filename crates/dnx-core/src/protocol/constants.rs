@@ -51,6 +51,15 @@ pub const FW_UPDATE_PROFILE_OLD_HDR_SIZE_MFD: usize = 0x1C;
 /// OSIP Partition Table Size
 pub const OSIP_PARTITIONTABLE_SIZE: usize = 0x200;
 
+/// OSIP layout: `$OS$`, header checksum at 0x07, pointer count at 0x08,
+/// header size (u16) at 0x0A, then 0x18-byte OSII entries from 0x20.
+pub const OSIP_ENTRIES_OFFSET: usize = 0x20;
+pub const OSII_SIZE: usize = 0x18;
+/// Offset of the u32 image size (in blocks) within an OSII entry.
+pub const OSII_BLOCKS_OFFSET: usize = 0x10;
+/// OSII LBAs and sizes are in 512-byte blocks.
+pub const OSIP_BLOCK_SIZE: u64 = 512;
+
 // ============================================================================
 // Preambles (Host -> Device)
 // ============================================================================
@@ -221,5 +230,5 @@ pub const OSIP_NUM_POINTERS_OFFSET: usize = 0x08;
 /// Calculate offset for OS partition N size
 #[inline]
 pub const fn get_os_n_size_offset(n: usize) -> usize {
-    (n * 0x18) + 0x30
+    OSIP_ENTRIES_OFFSET + n * OSII_SIZE + OSII_BLOCKS_OFFSET
 }
@@ -0,0 +1,108 @@
+//! General Purpose (GP) flags carried in the DnX header.
+
+use std::fmt;
+use std::str::FromStr;
+
+bitflags::bitflags! {
+    /// GP flags passed to the device in the DnX header.
+    ///
+    /// Only [`GpFlags::DNX_OS`] has a known effect on the host side; the
+    /// low bits and bit 31 are what xFSTK's Medfield/Moorefield scripts pass
+    /// (`0x80000007`), but their individual meanings are not documented.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct GpFlags: u32 {
+        /// The usual xFSTK value for Medfield/Moorefield, `0x80000007`.
+        const XFSTK_DEFAULT = 0x8000_0007;
+        /// Take the misc (DnX OS) path on a non-virgin part (DxxM).
+        const DNX_OS = 0x20;
+    }
+}
+
+impl GpFlags {
+    /// Wrap a raw value, keeping bits that have no name.
+    pub fn from_u32(bits: u32) -> Self {
+        Self::from_bits_retain(bits)
+    }
+
+    /// Set bits outside every named flag.
+    pub fn unknown_bits(self) -> u32 {
+        self.bits() & !Self::all().bits()
+    }
+}
+
+impl fmt::Display for GpFlags {
+    /// Named flags in lowercase, comma-separated, then any unnamed bits as
+    /// hex; parses back with [`FromStr`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = self
+            .iter_names()
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect();
+        let unknown = self.unknown_bits();
+        if unknown != 0 || parts.is_empty() {
+            parts.push(format!("0x{:X}", unknown));
+        }
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+impl FromStr for GpFlags {
+    type Err = String;
+
+    /// Comma- or `|`-separated flag names (case-insensitive) and hex values,
+    /// with or without a `0x` prefix, e.g. `dnx_os,0x80000007`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut flags = GpFlags::empty();
+        for token in s.split([',', '|']).map(str::trim) {
+            let named = GpFlags::all()
+                .iter_names()
+                .find(|(name, _)| name.eq_ignore_ascii_case(token));
+            if let Some((_, flag)) = named {
+                flags |= flag;
+                continue;
+            }
+            let digits = token.trim_start_matches("0x").trim_start_matches("0X");
+            let bits = u32::from_str_radix(digits, 16).map_err(|_| {
+                let names: Vec<String> = GpFlags::all()
+                    .iter_names()
+                    .map(|(name, _)| name.to_ascii_lowercase())
+                    .collect();
+                format!(
+                    "unknown GP flag '{}' (expected hex or one of: {})",
+                    token,
+                    names.join(", ")
+                )
+            })?;
+            flags |= GpFlags::from_u32(bits);
+        }
+        Ok(flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gp_flags_parse_and_display() {
+        assert_eq!("dnx_os".parse::<GpFlags>(), Ok(GpFlags::DNX_OS));
+        assert_eq!("80000007".parse::<GpFlags>(), Ok(GpFlags::XFSTK_DEFAULT));
+        assert_eq!(
+            "DNX_OS | 0x80000007".parse::<GpFlags>().unwrap().bits(),
+            0x8000_0027
+        );
+        assert_eq!("0".parse::<GpFlags>(), Ok(GpFlags::empty()));
+        assert!(
+            "dnx_os,foo"
+                .parse::<GpFlags>()
+                .unwrap_err()
+                .contains("'foo'")
+        );
+
+        let flags = GpFlags::from_u32(0x8000_0127);
+        assert_eq!(flags.unknown_bits(), 0x100);
+        assert_eq!(flags.to_string(), "xfstk_default,dnx_os,0x100");
+        assert_eq!(flags.to_string().parse::<GpFlags>(), Ok(flags));
+        assert_eq!(GpFlags::empty().to_string(), "0x0");
+    }
+}
@@ -1,9 +1,11 @@
 //! Data structure headers for DnX protocol.
 
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt};
 use std::io::Cursor;
 use thiserror::Error;
 
+use super::checksum::dnx_xor_checksum;
+
 #[derive(Error, Debug)]
 pub enum HeaderError {
     #[error("Buffer too small: expected {expected}, got {actual}")]
@@ -16,32 +18,42 @@ pub enum HeaderError {
 
 /// DnX Header (24 bytes / 0x18)
 ///
-/// Sent at the start of FW/OS download to specify size and checksum.
-#[derive(Debug, Clone, Copy, Default)]
+/// Sent ahead of the FW DnX binary. Layout follows xFSTK `InitDnxHdr`:
+/// size, GP flags, three reserved words, then `size ^ gp_flags`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[repr(C)]
 pub struct DnxHeader {
     pub size: u32,
+    pub gp_flags: u32,
+    pub reserved: [u32; 3],
     pub checksum: u32,
-    pub reserved: [u32; 4],
 }
 
 impl DnxHeader {
     pub const SIZE: usize = 24;
 
-    pub fn new(size: u32, checksum: u32) -> Self {
+    /// Build a header for `size` bytes, computing the checksum.
+    pub fn new(size: u32, gp_flags: u32) -> Self {
         Self {
             size,
-            checksum,
-            reserved: [0; 4],
+            gp_flags,
+            reserved: [0; 3],
+            checksum: dnx_xor_checksum(size, gp_flags),
         }
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(Self::SIZE);
-        buf.write_u32::<LittleEndian>(self.size).unwrap();
-        buf.write_u32::<LittleEndian>(self.checksum).unwrap();
-        for &r in &self.reserved {
-            buf.write_u32::<LittleEndian>(r).unwrap();
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0u8; Self::SIZE];
+        let words = [
+            self.size,
+            self.gp_flags,
+            self.reserved[0],
+            self.reserved[1],
+            self.reserved[2],
+            self.checksum,
+        ];
+        for (dst, word) in buf.chunks_exact_mut(4).zip(words) {
+            dst.copy_from_slice(&word.to_le_bytes());
         }
         buf
     }
@@ -56,15 +68,20 @@ impl DnxHeader {
         let mut cursor = Cursor::new(data);
         Ok(Self {
             size: cursor.read_u32::<LittleEndian>()?,
-            checksum: cursor.read_u32::<LittleEndian>()?,
+            gp_flags: cursor.read_u32::<LittleEndian>()?,
             reserved: [
                 cursor.read_u32::<LittleEndian>()?,
                 cursor.read_u32::<LittleEndian>()?,
                 cursor.read_u32::<LittleEndian>()?,
-                cursor.read_u32::<LittleEndian>()?,
             ],
+            checksum: cursor.read_u32::<LittleEndian>()?,
         })
     }
+
+    /// Whether the checksum matches `size ^ gp_flags`.
+    pub fn is_valid(&self) -> bool {
+        self.checksum == dnx_xor_checksum(self.size, self.gp_flags)
+    }
 }
 
 /// FW Update Profile Header (variable size: 0x1C / 0x20 / 0x24)
@@ -114,8 +131,8 @@ impl FwUpdateProfileHeader {
         self.read_u32_at(0x14)
     }
 
-    /// Get ROM Patch size from header.
-    pub fn rom_patch_size(&self) -> Option<u32> {
+    /// Get SuCP (SCU patch) size from header; FUPH `sucp_size`.
+    pub fn sucp_size(&self) -> Option<u32> {
         if self.size > 0x18 {
             self.read_u32_at(0x18)
         } else {
@@ -123,6 +140,20 @@ impl FwUpdateProfileHeader {
         }
     }
 
+    /// Alias of `sucp_size`, under xFSTK's name for the slot.
+    pub fn rom_patch_size(&self) -> Option<u32> {
+        self.sucp_size()
+    }
+
+    /// Get VEDFW size from header; absent on old Medfield (0x1C) headers.
+    pub fn vedfw_size(&self) -> Option<u32> {
+        if self.size > 0x1C {
+            self.read_u32_at(0x1C)
+        } else {
+            None
+        }
+    }
+
     fn read_u32_at(&self, offset: usize) -> Option<u32> {
         if self.data.len() >= offset + 4 {
             let mut cursor = Cursor::new(&self.data[offset..]);
@@ -139,7 +170,9 @@ impl FwUpdateProfileHeader {
 
 /// OSIP (OS Image Package) Partition Table Header.
 ///
-/// 512 bytes (0x200).
+/// 512 bytes (0x200): `$OS$`, revision and checksum bytes, the u8 pointer
+/// count at 0x08, the u16 header size at 0x0A, then 0x18-byte OSII entries
+/// from 0x20.
 #[derive(Debug, Clone)]
 pub struct OsipHeader {
     pub data: Vec<u8>,
@@ -160,8 +193,10 @@ impl OsipHeader {
         }
         let mut cursor = Cursor::new(data);
         let signature = cursor.read_u32::<LittleEndian>()?;
-        let header_size = cursor.read_u32::<LittleEndian>()?;
-        let num_pointers = cursor.read_u32::<LittleEndian>()?;
+        cursor.set_position(0x08);
+        let num_pointers = cursor.read_u8()? as u32;
+        cursor.set_position(0x0A);
+        let header_size = cursor.read_u16::<LittleEndian>()? as u32;
 
         Ok(Self {
             data: data[..Self::SIZE].to_vec(),
@@ -171,15 +206,24 @@ impl OsipHeader {
         })
     }
 
-    /// Get size of OS partition N.
+    /// OSII entry N, if the pointer count declares it.
+    pub fn os_partition(&self, n: usize) -> Option<crate::payload::PartitionEntry> {
+        crate::payload::os::partition_entries(&self.data)
+            .into_iter()
+            .nth(n)
+    }
+
+    /// Get size of OS partition N, in 512-byte blocks.
     pub fn os_partition_size(&self, n: usize) -> Option<u32> {
-        let offset = (n * 0x18) + 0x30;
-        if self.data.len() >= offset + 4 {
-            let mut cursor = Cursor::new(&self.data[offset..]);
-            cursor.read_u32::<LittleEndian>().ok()
-        } else {
-            None
-        }
+        self.os_partition(n).map(|entry| entry.blocks)
+    }
+
+    /// Get the eMMC start block of OS partition N.
+    ///
+    /// This is where the device writes the image, not where it sits in the
+    /// file: recovery images store the images back to back after the OSIP.
+    pub fn os_partition_lba(&self, n: usize) -> Option<u32> {
+        self.os_partition(n).map(|entry| entry.lba)
     }
 
     pub fn to_bytes(&self) -> &[u8] {
@@ -190,15 +234,65 @@ impl OsipHeader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::constants::get_os_n_size_offset;
 
     #[test]
     fn test_dnx_header_roundtrip() {
-        let header = DnxHeader::new(0x12345678, 0xDEADBEEF);
+        let header = DnxHeader::new(0x12345678, 0x80000020);
         let bytes = header.to_bytes();
-        assert_eq!(bytes.len(), DnxHeader::SIZE);
+        assert_eq!(&bytes[4..8], &0x80000020u32.to_le_bytes());
+        assert_eq!(&bytes[20..24], &(0x12345678u32 ^ 0x80000020).to_le_bytes());
 
         let parsed = DnxHeader::from_bytes(&bytes).unwrap();
-        assert_eq!(parsed.size, 0x12345678);
-        assert_eq!(parsed.checksum, 0xDEADBEEF);
+        assert_eq!(parsed, header);
+        assert!(parsed.is_valid());
+    }
+
+    #[test]
+    fn test_dnx_header_matches_xfstk_layout() {
+        // Built the way xFSTK's InitDnxHdr (and the old inline DxxM code) did
+        let (size, gp_flags) = (109812u32, 0x8000_0007u32);
+        let mut expected = [0u8; 24];
+        expected[0..4].copy_from_slice(&size.to_le_bytes());
+        expected[4..8].copy_from_slice(&gp_flags.to_le_bytes());
+        expected[20..24].copy_from_slice(&(size ^ gp_flags).to_le_bytes());
+
+        assert_eq!(DnxHeader::new(size, gp_flags).to_bytes(), expected);
+        // The FUPH module re-exports the same type
+        assert_eq!(
+            crate::fuph::DnxHeader::new(size, gp_flags).to_bytes(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_osip_header_fields() {
+        let mut data = vec![0u8; OsipHeader::SIZE];
+        data[0..4].copy_from_slice(b"$OS$");
+        data[0x08] = 2;
+        data[0x09] = 2;
+        data[0x0A..0x0C].copy_from_slice(&0x50u16.to_le_bytes());
+        data[0x24..0x28].copy_from_slice(&0x32u32.to_le_bytes());
+        data[0x30..0x34].copy_from_slice(&4u32.to_le_bytes());
+        data[0x3C..0x40].copy_from_slice(&0x10u32.to_le_bytes());
+        data[0x48..0x4C].copy_from_slice(&2u32.to_le_bytes());
+
+        let osip = OsipHeader::from_bytes(&data).unwrap();
+        assert_eq!(osip.signature, 0x24534F24);
+        assert_eq!(osip.num_pointers, 2);
+        assert_eq!(osip.header_size, 0x50);
+        assert_eq!(osip.os_partition_lba(0), Some(0x32));
+        assert_eq!(osip.os_partition_size(0), Some(4));
+        assert_eq!(osip.os_partition_lba(1), Some(0x10));
+        assert_eq!(osip.os_partition_size(1), Some(2));
+        // Entries past the pointer count are not read
+        assert_eq!(osip.os_partition_size(2), None);
+        assert_eq!(osip.os_partition_size(21), None);
+        assert_eq!(
+            osip.os_partition_size(1),
+            Some(u32::from_le_bytes(
+                data[get_os_n_size_offset(1)..][..4].try_into().unwrap()
+            ))
+        );
     }
 }
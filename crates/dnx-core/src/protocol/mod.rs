@@ -1,9 +1,17 @@
 //! Protocol module - DnX protocol definitions.
 
 pub mod ack;
+pub mod checksum;
 pub mod constants;
+pub mod gp_flags;
 pub mod header;
+pub mod platform;
+pub mod preamble;
 
-pub use ack::AckCode;
+pub use ack::{ALL_ACKS, AckCategory, AckCode, AckDef, all_acks};
+pub use checksum::{dnx_xor_checksum, xor8};
 pub use constants::*;
+pub use gp_flags::GpFlags;
 pub use header::{DnxHeader, FwUpdateProfileHeader, HeaderError, OsipHeader};
+pub use platform::{Platform, platform_family, platform_name};
+pub use preamble::Preamble;
@@ -0,0 +1,71 @@
+//! Platform names for device product IDs and platform-identity ACKs.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::constants::{
+    D0_FW_UPDATE_PROFILE_HDR_SIZE, FW_UPDATE_PROFILE_OLD_HDR_SIZE_MFD, MEDFIELD_FW_PID,
+    MEDFIELD_PRODUCT_ID, MOOREFIELD_ALT_PID, MOOREFIELD_PRODUCT_ID,
+};
+
+/// Platform a device reports with an identity ACK (`MFLD`/`CLVT`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Platform {
+    Medfield,
+    Clovertrail,
+}
+
+impl Platform {
+    /// FW update profile header size to announce when no FW image says
+    /// otherwise: the old 0x1C header on Medfield, D0 (0x24) elsewhere.
+    pub fn profile_header_size(&self) -> usize {
+        match self {
+            Platform::Medfield => FW_UPDATE_PROFILE_OLD_HDR_SIZE_MFD,
+            Platform::Clovertrail => D0_FW_UPDATE_PROFILE_HDR_SIZE,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Medfield => write!(f, "Medfield"),
+            Platform::Clovertrail => write!(f, "Clovertrail"),
+        }
+    }
+}
+
+/// SoC family a DnX product ID belongs to, if known.
+pub fn platform_family(pid: u16) -> Option<&'static str> {
+    match pid {
+        MEDFIELD_PRODUCT_ID | MEDFIELD_FW_PID => Some("Medfield"),
+        MOOREFIELD_PRODUCT_ID | MOOREFIELD_ALT_PID => Some("Moorefield"),
+        _ => None,
+    }
+}
+
+/// Human-readable name for a PID, e.g. "Moorefield 0A2C".
+pub fn platform_name(pid: u16) -> String {
+    format!("{} {:04X}", platform_family(pid).unwrap_or("Unknown"), pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::constants::SUPPORTED_PIDS;
+
+    #[test]
+    fn test_every_supported_pid_has_a_platform() {
+        for &pid in SUPPORTED_PIDS {
+            assert!(
+                platform_family(pid).is_some(),
+                "no platform for {:04X}",
+                pid
+            );
+        }
+        assert_eq!(platform_name(MOOREFIELD_PRODUCT_ID), "Moorefield 0A2C");
+        assert_eq!(platform_name(MEDFIELD_PRODUCT_ID), "Medfield E004");
+        assert_eq!(platform_name(0x1234), "Unknown 1234");
+    }
+}
@@ -0,0 +1,92 @@
+//! Host -> device preambles sent to open a DnX handshake.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::constants::{PREAMBLE_BMRQ, PREAMBLE_DNER, PREAMBLE_IDRQ};
+
+/// A handshake preamble.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Preamble {
+    /// Download Execute ROM.
+    Dner,
+    /// ID Request.
+    Idrq,
+    /// Boot Mode Request.
+    Bmrq,
+}
+
+impl Preamble {
+    /// Every preamble, in handshake order.
+    pub const ALL: [Preamble; 3] = [Preamble::Dner, Preamble::Idrq, Preamble::Bmrq];
+
+    /// Wire value of the preamble.
+    pub fn value(&self) -> u32 {
+        match self {
+            Preamble::Dner => PREAMBLE_DNER,
+            Preamble::Idrq => PREAMBLE_IDRQ,
+            Preamble::Bmrq => PREAMBLE_BMRQ,
+        }
+    }
+
+    /// Mnemonic as it appears on the wire, e.g. `DnER`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preamble::Dner => "DnER",
+            Preamble::Idrq => "IDRQ",
+            Preamble::Bmrq => "BMRQ",
+        }
+    }
+
+    /// Little-endian bytes as written to the bulk OUT endpoint.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        self.value().to_le_bytes()
+    }
+
+    /// Default handshake: `DnER` alone, the same for every device.
+    ///
+    /// Moorefield 0A2C/0A65 were seen failing with EPROTO on an unsolicited
+    /// IDRQ, so devices that need IDRQ/BMRQ opt in via
+    /// `SessionConfig::handshake`.
+    pub const DEFAULT_SEQUENCE: &'static [Preamble] = &[Preamble::Dner];
+}
+
+impl fmt::Display for Preamble {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Preamble {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "DNER" => Ok(Preamble::Dner),
+            "IDRQ" => Ok(Preamble::Idrq),
+            "BMRQ" => Ok(Preamble::Bmrq),
+            _ => Err(format!(
+                "unknown preamble '{}' (expected DnER, IDRQ, BMRQ)",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preamble_bytes_and_parse() {
+        for p in [Preamble::Dner, Preamble::Idrq, Preamble::Bmrq] {
+            assert_eq!(p.to_bytes(), p.to_string().as_bytes());
+            assert_eq!(p.to_string().parse::<Preamble>(), Ok(p));
+        }
+        assert_eq!("dner".parse::<Preamble>(), Ok(Preamble::Dner));
+        assert!("XXXX".parse::<Preamble>().is_err());
+    }
+}
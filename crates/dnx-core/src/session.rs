@@ -1,20 +1,223 @@
 //! DnX Session - High-level orchestrator for the download process.
 
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use tracing::{info, instrument, warn};
 
-use crate::events::{DnxEvent, DnxObserver, DnxPhase, PacketDirection, TracingObserver};
-use crate::protocol::constants::PREAMBLE_DNER;
+use crate::events::{DnxEvent, DnxObserver, DnxPhase, LogLevel, PacketDirection, TracingObserver};
+use crate::payload::{FirmwareError, OsImageError};
+use crate::plan::FlashPlan;
+pub use crate::protocol::Preamble;
+use crate::protocol::constants::INTEL_VENDOR_ID;
+use crate::protocol::{AckCode, GpFlags};
 use crate::state::handlers::{HandleResult, HandlerContext, handle_ack};
+pub use crate::state::machine::ErrorPolicy;
+pub use crate::state::machine::Operation;
 use crate::state::machine::StateMachineContext;
-use crate::transport::{NusbTransport, TransportError, UsbTransport};
+use crate::transport::{
+    DeviceSelector, DeviceTransport, TransportError, UsbBackend, UsbTransport, is_supported_device,
+};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[cfg(feature = "async")]
+mod async_run;
+mod builder;
+mod checkpoint;
+
+pub use builder::SessionConfigBuilder;
+pub use checkpoint::Checkpoint;
+
+/// How often `wait_for_device` reports that it is still waiting.
+const WAIT_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the device wait loops look for the device.
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pause after a reset so the device has actually dropped off the bus.
+const RESET_SETTLE: Duration = Duration::from_secs(2);
+
+/// Device wait used when `retry_timeout_secs` is unset.
+const DEFAULT_RETRY_TIMEOUT_SECS: u64 = 300;
+
+fn default_retry_timeout_secs() -> u64 {
+    DEFAULT_RETRY_TIMEOUT_SECS
+}
+
+/// Minimum time between checkpoint writes while the session runs.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Shared flag that stops a running session, including while it waits for
+/// a device or for the next ACK.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation; every clone observes it.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Why waiting for a device ended without one.
+#[derive(Debug, Error)]
+pub enum WaitError {
+    #[error("Timeout waiting for device after {secs}s")]
+    Timeout { secs: u64 },
+    #[error("Cancelled while waiting for device")]
+    Cancelled,
+    #[error("Permission denied opening device {vid:04X}:{pid:04X}. {hint}")]
+    PermissionDenied { vid: u16, pid: u16, hint: String },
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+}
+
+/// Read retries allowed when `SessionConfig::max_read_retries` is 0.
+const DEFAULT_MAX_READ_RETRIES: u32 = 20;
+/// ACK read timeout used when `SessionConfig::read_timeout_ms` is 0.
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_millis(5000);
+/// Bytes of each packet copied into `DnxEvent::Packet` when
+/// `SessionConfig::packet_capture_len` is unset.
+pub const DEFAULT_PACKET_CAPTURE_LEN: usize = 32;
+/// First and largest delay between retried reads.
+const READ_BACKOFF_BASE: Duration = Duration::from_millis(50);
+const READ_BACKOFF_MAX: Duration = Duration::from_secs(2);
+
+/// Why a session failed, for callers that branch on the kind of failure.
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+    #[error(transparent)]
+    Firmware(#[from] FirmwareError),
+    #[error(transparent)]
+    OsImage(#[from] OsImageError),
+    /// The device answered with an error ACK (`ER**`) that was not retried.
+    #[error("Device error: {ack} ({description})")]
+    DeviceError {
+        ack: String,
+        /// What the code means, or "undocumented code".
+        description: &'static str,
+    },
+    /// No device showed up within the retry window.
+    #[error("Timeout waiting for device after {secs}s")]
+    Timeout { secs: u64 },
+    #[error("Permission denied opening device {vid:04X}:{pid:04X}. {hint}")]
+    PermissionDenied { vid: u16, pid: u16, hint: String },
+    #[error("Cannot read {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Giving up after {retries} consecutive read errors (last: {last})")]
+    TooManyRetries { retries: u32, last: TransportError },
+    #[error("Session cancelled")]
+    Cancelled,
+    #[error("Operation '{operation}' requires {input}")]
+    MissingInput {
+        operation: Operation,
+        input: &'static str,
+    },
+    #[error("Invalid configuration: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidConfig(Vec<ConfigError>),
+    /// A handler stopped the session, e.g. the device asked for a missing input.
+    #[error("{0}")]
+    Protocol(String),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<WaitError> for SessionError {
+    fn from(e: WaitError) -> Self {
+        match e {
+            WaitError::Timeout { secs } => Self::Timeout { secs },
+            WaitError::Cancelled => Self::Cancelled,
+            WaitError::PermissionDenied { vid, pid, hint } => {
+                Self::PermissionDenied { vid, pid, hint }
+            }
+            WaitError::Transport(e) => Self::Transport(e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for SessionError {
+    /// Recover the typed error behind an internal `anyhow` error.
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<SessionError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<WaitError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<TransportError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<FirmwareError>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<OsImageError>() {
+            Ok(e) => e.into(),
+            Err(e) => Self::Other(e),
+        }
+    }
+}
+
+/// A problem found by [`SessionConfig::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConfigError {
+    #[error("No FW or OS input files are set")]
+    NoInputs,
+    #[error("Operation '{operation}' requires {input}")]
+    MissingInput {
+        operation: Operation,
+        input: &'static str,
+    },
+    #[error("Cannot read {path}: {reason}")]
+    Unreadable { path: String, reason: String },
+    #[error("FW image {path} is invalid: {reason}")]
+    InvalidFirmwareImage { path: String, reason: String },
+    #[error("OS image {path} is invalid: {reason}")]
+    InvalidOsImage { path: String, reason: String },
+    #[error("GP flags 0x{flags:08X} set unrecognized bits 0x{unknown:08X}")]
+    UnknownGpFlags { flags: u32, unknown: u32 },
+    #[error("USB backend '{0}' is not available in this build")]
+    BackendUnavailable(UsbBackend),
+    #[error("{field} = {value} is above the maximum of {max}")]
+    OutOfRange {
+        field: &'static str,
+        value: u64,
+        max: u64,
+    },
+}
 
 /// Configuration for a DnX session.
+///
+/// Valid input combinations:
+/// - FW stage: `fw_dnx_path` is required; `fw_image_path` is only needed when
+///   the device asks for the update profile (RUPH/LOFW/HIFW).
+/// - OS stage: `os_image_path` is required; `os_dnx_path` is only needed when
+///   the device asks for an OS DnX (DXBL).
+///
+/// A FW image without a FW DnX, or an OS DnX without an OS image, is rejected
+/// by [`SessionConfig::validate_operation`].
+///
+/// Build one with [`SessionConfig::builder`] rather than a struct literal.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SessionConfig {
     /// Path to FW DnX binary.
@@ -28,21 +231,120 @@ pub struct SessionConfig {
     /// Path to Misc DnX binary.
     pub misc_dnx_path: Option<String>,
     /// GP flags.
+    #[serde(default)]
     pub gp_flags: u32,
     /// Enable IFWI wipe.
+    #[serde(default)]
     pub ifwi_wipe_enable: bool,
-    /// Retry timeout in seconds.
+    /// Seconds to wait for the device (0 = 300 s default).
+    #[serde(default = "default_retry_timeout_secs")]
     pub retry_timeout_secs: u64,
+    /// Chunk size in bytes for IFWI/OS transfers (0 = 128 KB default).
+    #[serde(default)]
+    pub chunk_size: usize,
+    /// Requested operation. Inferred from the provided paths when unset.
+    pub operation: Option<Operation>,
+    /// Open exactly this `(vid, pid)`, bypassing the supported-PID allowlist.
+    #[serde(default)]
+    pub device_filter: Option<(u16, u16)>,
+    /// Which device to use when several are attached.
+    #[serde(default)]
+    pub device_selector: Option<DeviceSelector>,
+    /// Restrict the bulk endpoint search to this USB interface number.
+    pub interface_hint: Option<u8>,
+    /// USB library used to open and drive the device.
+    #[serde(default)]
+    pub backend: UsbBackend,
+    /// PID the device must come back with after `RESET` (e.g. 0x0A14 on Medfield).
+    /// Any supported PID is accepted when unset.
+    pub expected_reenumerated_pid: Option<u16>,
+    /// OS partitions to flash, by OSIP index. Unset = the whole image.
+    pub os_partitions: Option<Vec<usize>>,
+    /// Which device errors to retry, and how often.
+    #[serde(default)]
+    pub error_policy: ErrorPolicy,
+    /// Consecutive failed ACK reads before aborting (0 = default of 20).
+    /// Also read as `max_consecutive_errors`.
+    #[serde(default, alias = "max_consecutive_errors")]
+    pub max_read_retries: u32,
+    /// Time to wait for each ACK read before retrying (0 = default of 5000 ms).
+    #[serde(default)]
+    pub read_timeout_ms: u64,
+    /// After a disconnect during the OS image, continue after the last chunk
+    /// the device acknowledged instead of resending the whole image. Only for
+    /// devices that keep their write position across re-enumeration.
+    #[serde(default)]
+    pub resume_os: bool,
+    /// Bulk OUT transfers kept in flight within one write (0/1 = one at a
+    /// time). Writes never overlap each other: each waits for its ACK.
+    #[serde(default)]
+    pub write_queue_depth: usize,
+    /// Treat image validation problems as errors instead of warnings.
+    #[serde(default)]
+    pub strict: bool,
+    /// Reject an OS image whose OSIP signature is not `$OS$` (or zero) at load
+    /// time, instead of warning. Implied by `strict`.
+    #[serde(default)]
+    pub strict_os_validation: bool,
+    /// Bytes of each packet copied into `DnxEvent::Packet` (unset = 32,
+    /// `Some(0)` = none, `Some(usize::MAX)` = whole packets). Observers that
+    /// need every byte regardless should implement `DnxObserver::on_packet`.
+    #[serde(default)]
+    pub packet_capture_len: Option<usize>,
+    /// Load, validate and plan, then stop without opening a device.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Preambles sent to open the handshake. Empty = `DnER` alone, for every device.
+    #[serde(default)]
+    pub handshake: Vec<Preamble>,
+    /// JSON file the session keeps updated with its progress (see [`Checkpoint`]).
+    #[serde(default)]
+    pub checkpoint_path: Option<String>,
 }
 
 impl SessionConfig {
-    /// Load configuration from a TOML file
+    /// Start building a config from the defaults.
+    pub fn builder() -> SessionConfigBuilder {
+        SessionConfigBuilder::new()
+    }
+
+    /// Load configuration from a TOML file. Missing settings take their
+    /// defaults; out-of-range numbers (see [`SessionConfig::range_errors`])
+    /// are rejected.
     pub fn load_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         let content = std::fs::read_to_string(path)?;
         let config: SessionConfig = toml::from_str(&content)?;
+        let errors = config.range_errors();
+        if !errors.is_empty() {
+            let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            return Err(anyhow!("{}: {}", path.display(), errors.join("; ")));
+        }
         Ok(config)
     }
 
+    /// A commented TOML file listing every setting, for `dnx config --generate`.
+    pub fn example_toml() -> &'static str {
+        include_str!("session/example_config.toml")
+    }
+
+    /// Numeric settings above their maximum (a day of waiting, 16 MB
+    /// chunks, and so on).
+    pub fn range_errors(&self) -> Vec<ConfigError> {
+        let settings = [
+            ("retry_timeout_secs", self.retry_timeout_secs, 86_400),
+            ("chunk_size", self.chunk_size as u64, 16 << 20),
+            ("max_read_retries", self.max_read_retries as u64, 1000),
+            ("read_timeout_ms", self.read_timeout_ms, 600_000),
+            ("write_queue_depth", self.write_queue_depth as u64, 64),
+        ];
+        settings
+            .into_iter()
+            .filter(|&(_, value, max)| value > max)
+            .map(|(field, value, max)| ConfigError::OutOfRange { field, value, max })
+            .collect()
+    }
+
     /// Save configuration to a TOML file
     pub fn save_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
         let content = toml::to_string_pretty(self)?;
@@ -58,19 +360,162 @@ impl SessionConfig {
         }
     }
 
-    /// Apply sensible defaults for unset values.
+    /// Apply sensible defaults for unset values: a 300 s device wait and
+    /// 128 KB chunks. Values already set are kept, so call this last, after
+    /// loading the file and applying overrides.
     pub fn with_defaults(mut self) -> Self {
         if self.retry_timeout_secs == 0 {
-            self.retry_timeout_secs = 300;
+            self.retry_timeout_secs = DEFAULT_RETRY_TIMEOUT_SECS;
+        }
+        if self.chunk_size == 0 {
+            self.chunk_size = crate::protocol::constants::ONE28_K;
         }
         self
     }
 
+    /// Resolve the operation, inferring it from the provided paths if not set.
+    pub fn effective_operation(&self) -> Operation {
+        self.operation.unwrap_or_else(|| {
+            Operation::infer(
+                self.fw_dnx_path.is_some() || self.fw_image_path.is_some(),
+                self.os_dnx_path.is_some() || self.os_image_path.is_some(),
+                self.ifwi_wipe_enable,
+            )
+        })
+    }
+
+    /// Check that the inputs required by the operation are present.
+    pub fn validate_operation(&self) -> Result<Operation> {
+        let op = self.effective_operation();
+        let missing = |input| SessionError::MissingInput {
+            operation: op,
+            input,
+        };
+        if op.includes_fw() && self.fw_dnx_path.is_none() {
+            return Err(missing("a FW DnX path").into());
+        }
+        if op.includes_os() && self.os_image_path.is_none() {
+            return Err(missing("an OS image path").into());
+        }
+        Ok(op)
+    }
+
+    /// Check everything that can be checked without a device: an operation
+    /// with its required inputs, readable files, parseable FW and OS images
+    /// and known GP flag bits. Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        self.check(true)
+    }
+
+    /// `validate`, parsing the FW and OS images only if `parse_images` is
+    /// set; a session parses them once while loading instead.
+    fn check(&self, parse_images: bool) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        let inputs = [
+            &self.fw_dnx_path,
+            &self.fw_image_path,
+            &self.os_dnx_path,
+            &self.os_image_path,
+        ];
+        if inputs.iter().all(|p| p.is_none()) {
+            errors.push(ConfigError::NoInputs);
+        } else if let Err(e) = self.validate_operation()
+            && let Some(SessionError::MissingInput { operation, input }) = e.downcast_ref()
+        {
+            errors.push(ConfigError::MissingInput {
+                operation: *operation,
+                input,
+            });
+        }
+
+        let readable = |path: &String, errors: &mut Vec<ConfigError>| {
+            let result = std::fs::metadata(path).and_then(|m| {
+                if m.is_file() {
+                    std::fs::File::open(path).map(|_| ())
+                } else {
+                    Err(std::io::Error::other("not a file"))
+                }
+            });
+            match result {
+                Ok(()) => true,
+                Err(e) => {
+                    errors.push(ConfigError::Unreadable {
+                        path: path.clone(),
+                        reason: e.to_string(),
+                    });
+                    false
+                }
+            }
+        };
+
+        for path in [&self.fw_dnx_path, &self.os_dnx_path, &self.misc_dnx_path]
+            .into_iter()
+            .flatten()
+        {
+            readable(path, &mut errors);
+        }
+        if let Some(path) = &self.fw_image_path
+            && readable(path, &mut errors)
+            && parse_images
+            && let Err(e) = crate::payload::FirmwareImage::from_path(path)
+        {
+            errors.push(ConfigError::InvalidFirmwareImage {
+                path: path.clone(),
+                reason: e.to_string(),
+            });
+        }
+        if let Some(path) = &self.os_image_path
+            && readable(path, &mut errors)
+            && parse_images
+            && let Err(e) = crate::payload::OsImage::from_path(path)
+        {
+            errors.push(ConfigError::InvalidOsImage {
+                path: path.clone(),
+                reason: e.to_string(),
+            });
+        }
+
+        let unknown = GpFlags::from_u32(self.gp_flags).unknown_bits();
+        if unknown != 0 {
+            errors.push(ConfigError::UnknownGpFlags {
+                flags: self.gp_flags,
+                unknown,
+            });
+        }
+
+        if !self.backend.is_available() {
+            errors.push(ConfigError::BackendUnavailable(self.backend));
+        }
+        errors.extend(self.range_errors());
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Preambles to send: `handshake`, or the fixed default when it is empty.
+    pub fn effective_handshake(&self) -> &[Preamble] {
+        if self.handshake.is_empty() {
+            Preamble::DEFAULT_SEQUENCE
+        } else {
+            &self.handshake
+        }
+    }
+
     /// Merge CLI-style overrides into this config.
-    /// Only overwrites fields that have explicit values (Some).
+    ///
+    /// Precedence is override, then this config (e.g. loaded from a file),
+    /// then defaults: a `Some` override replaces the field, `None` leaves it
+    /// as is. `gp_flags` of 0 and `ifwi_wipe` of `false` count as unset, so
+    /// an override can enable a wipe but not disable one from the file.
+    ///
+    /// New code should use [`SessionConfigBuilder`] instead.
     #[allow(clippy::too_many_arguments)]
     pub fn merge(
-        mut self,
+        self,
         fw_dnx: Option<String>,
         fw_image: Option<String>,
         os_dnx: Option<String>,
@@ -79,30 +524,57 @@ impl SessionConfig {
         gp_flags: Option<u32>,
         ifwi_wipe: Option<bool>,
     ) -> Self {
+        let mut builder = SessionConfigBuilder::from(self);
         if let Some(v) = fw_dnx {
-            self.fw_dnx_path = Some(v);
+            builder = builder.fw_dnx(v);
         }
         if let Some(v) = fw_image {
-            self.fw_image_path = Some(v);
+            builder = builder.fw_image(v);
         }
         if let Some(v) = os_dnx {
-            self.os_dnx_path = Some(v);
+            builder = builder.os_dnx(v);
         }
         if let Some(v) = os_image {
-            self.os_image_path = Some(v);
+            builder = builder.os_image(v);
         }
         if let Some(v) = misc_dnx {
-            self.misc_dnx_path = Some(v);
+            builder = builder.misc_dnx(v);
         }
         if let Some(v) = gp_flags
             && v != 0
         {
-            self.gp_flags = v;
+            builder = builder.gp_flags(v);
         }
         if ifwi_wipe == Some(true) {
-            self.ifwi_wipe_enable = true;
+            builder = builder.ifwi_wipe(true);
         }
-        self
+        builder.build()
+    }
+}
+
+/// Outcome of a completed session run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSummary {
+    pub operation: Operation,
+    /// VID/PID of the last device the session talked to.
+    pub device: Option<(u16, u16)>,
+    /// Number of device re-enumerations handled.
+    pub reenumerations: u32,
+    pub elapsed: Duration,
+}
+
+impl std::fmt::Display for SessionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.operation)?;
+        if let Some((vid, pid)) = self.device {
+            write!(f, " on {:04X}:{:04X}", vid, pid)?;
+        }
+        write!(
+            f,
+            " in {:.1}s ({} re-enumeration(s))",
+            self.elapsed.as_secs_f64(),
+            self.reenumerations
+        )
     }
 }
 
@@ -110,11 +582,15 @@ impl SessionConfig {
 pub struct DnxSession<O: DnxObserver> {
     config: SessionConfig,
     observer: Arc<O>,
+    cancel: CancelToken,
     // Loaded file data
     fw_dnx_data: Option<Vec<u8>>,
     fw_image: Option<crate::payload::FirmwareImage>,
     os_dnx_data: Option<Vec<u8>>,
+    misc_dnx_data: Option<Vec<u8>>,
     os_image: Option<crate::payload::OsImage>,
+    /// When the checkpoint file was last written.
+    checkpoint_saved: Mutex<Option<std::time::Instant>>,
 }
 
 impl DnxSession<TracingObserver> {
@@ -130,65 +606,316 @@ impl<O: DnxObserver + 'static> DnxSession<O> {
         Self {
             config,
             observer,
+            cancel: CancelToken::default(),
             fw_dnx_data: None,
             fw_image: None,
             os_dnx_data: None,
+            misc_dnx_data: None,
             os_image: None,
+            checkpoint_saved: Mutex::new(None),
         }
     }
 
-    /// Load all required files.
-    fn load_files(&mut self) -> Result<()> {
+    /// Use `token` to cancel this session from another thread.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Token that cancels this session when triggered.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Load all required files. Called by `run`; call it directly before `plan`.
+    pub fn load_files(&mut self) -> Result<()> {
         if let Some(path) = &self.config.fw_dnx_path {
             info!(path = %path, "Loading FW DnX");
-            self.fw_dnx_data = Some(std::fs::read(path)?);
+            self.fw_dnx_data = Some(self.read_input(path)?);
         }
         if let Some(path) = &self.config.fw_image_path {
             info!(path = %path, "Loading FW Image");
-            let data = std::fs::read(path)?;
+            let data = self.read_input(path)?;
             self.fw_image = Some(crate::payload::FirmwareImage::from_bytes(data)?);
         }
         if let Some(path) = &self.config.os_dnx_path {
             info!(path = %path, "Loading OS DnX");
-            self.os_dnx_data = Some(std::fs::read(path)?);
+            self.os_dnx_data = Some(self.read_input(path)?);
         }
         if let Some(path) = &self.config.os_image_path {
             info!(path = %path, "Loading OS Image");
-            let data = std::fs::read(path)?;
-            self.os_image = Some(crate::payload::OsImage::from_bytes(data)?);
+            let data = self.read_input(path)?;
+            let image = if self.config.strict || self.config.strict_os_validation {
+                crate::payload::OsImage::from_bytes_strict(data)
+                    .with_context(|| format!("Invalid OS image {}", path))?
+            } else {
+                crate::payload::OsImage::from_bytes(data)?
+            };
+            if let Err(e) = image.validate() {
+                if self.config.strict {
+                    return Err(anyhow!("OS image {} failed validation: {}", path, e));
+                }
+                warn!(path = %path, error = %e, "OS image failed validation");
+                self.observer.on_event(&DnxEvent::Log {
+                    level: LogLevel::Warn,
+                    message: format!("OS image {}: {}", path, e),
+                });
+            }
+            self.os_image = Some(image);
+        }
+        if let Some(path) = &self.config.misc_dnx_path {
+            info!(path = %path, "Loading Misc DnX");
+            self.misc_dnx_data = Some(self.read_input(path)?);
         }
         Ok(())
     }
 
-    /// Run the complete DnX session.
-    #[instrument(skip(self))]
-    pub fn run(&mut self) -> Result<()> {
+    /// Read an input file, decompressing it and logging if it was compressed.
+    fn read_input(&self, path: &str) -> Result<Vec<u8>> {
+        let (data, format) =
+            crate::compression::read_file(std::path::Path::new(path)).map_err(|source| {
+                SessionError::Io {
+                    path: path.to_string(),
+                    source,
+                }
+            })?;
+        if let Some(format) = format {
+            self.observer.on_event(&DnxEvent::Log {
+                level: LogLevel::Info,
+                message: format!(
+                    "Decompressed {} input {} ({} bytes)",
+                    format,
+                    path,
+                    data.len()
+                ),
+            });
+        }
+        Ok(data)
+    }
+
+    /// Describe what the session will send, without touching USB.
+    ///
+    /// Requires `load_files` to have been called.
+    pub fn plan(&self) -> Result<FlashPlan> {
+        let operation = self.config.validate_operation()?;
+        if operation.includes_fw() && self.fw_dnx_data.is_none() {
+            return Err(anyhow!("FW DnX not loaded; call load_files() first"));
+        }
+        if operation.includes_os() && self.os_image.is_none() {
+            return Err(anyhow!("OS image not loaded; call load_files() first"));
+        }
+
+        Ok(FlashPlan::build(
+            operation,
+            self.config.chunk_size,
+            self.fw_dnx_data.as_deref(),
+            self.fw_image.as_ref(),
+            self.os_dnx_data.as_deref(),
+            self.os_image.as_ref(),
+        ))
+    }
+
+    /// Load and validate the inputs and report the plan as log events,
+    /// without waiting for or touching a device.
+    fn dry_run(&mut self) -> Result<SessionSummary> {
+        let start = std::time::Instant::now();
+        self.config
+            .check(false)
+            .map_err(SessionError::InvalidConfig)?;
+        let operation = self.config.validate_operation()?;
+        self.load_files()?;
+        let plan = self.plan()?;
+
+        let log = |level, message: String| {
+            self.observer.on_event(&DnxEvent::Log { level, message });
+        };
+        for (i, step) in plan.steps.iter().enumerate() {
+            log(
+                LogLevel::Info,
+                format!(
+                    "Plan {}: [{}] {}: {} bytes in {} chunk(s)",
+                    i + 1,
+                    step.phase,
+                    step.component,
+                    step.size,
+                    step.chunk_count
+                ),
+            );
+        }
+        for warning in &plan.warnings {
+            log(LogLevel::Warn, warning.clone());
+        }
+        log(
+            LogLevel::Info,
+            format!(
+                "Dry run of {}: {} bytes in {} chunk(s) would be sent",
+                operation,
+                plan.total_bytes(),
+                plan.total_chunks()
+            ),
+        );
+
+        Ok(SessionSummary {
+            operation,
+            device: None,
+            reenumerations: 0,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Validate the config, load files, and build the initial state.
+    fn prepare(&mut self) -> Result<StateMachineContext> {
+        // Images are parsed by load_files, so check everything else here
+        self.config
+            .check(false)
+            .map_err(SessionError::InvalidConfig)?;
+        let operation = self.config.validate_operation()?;
+        info!(operation = %operation, "Starting session");
+
         // Load files
         self.load_files()?;
+        self.report_previous_checkpoint();
+
+        let state = self.new_state()?;
+        self.observer.on_event(&state.progress.event());
+        Ok(state)
+    }
+
+    /// Log how far an earlier session with the same inputs got, if it left
+    /// a checkpoint behind.
+    fn report_previous_checkpoint(&self) {
+        let Some(path) = &self.config.checkpoint_path else {
+            return;
+        };
+        match Checkpoint::load(path) {
+            Ok(Some(previous)) if previous.matches(&self.config) => {
+                let message = format!(
+                    "Previous session reached {} ({}/{} bytes, last ACK {})",
+                    previous.phase,
+                    previous.bytes_sent,
+                    previous.bytes_total,
+                    previous.last_ack.as_deref().unwrap_or("none")
+                );
+                info!("{}", message);
+                self.observer.on_event(&DnxEvent::Log {
+                    level: LogLevel::Info,
+                    message,
+                });
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Ignoring checkpoint: {:#}", e),
+        }
+    }
 
+    /// Initial state-machine state for this config and the loaded inputs,
+    /// to drive with [`DnxSession::step`].
+    pub fn new_state(&self) -> Result<StateMachineContext> {
+        let operation = self.config.validate_operation()?;
         let mut state = StateMachineContext::new();
+        state.operation = operation;
         state.gp_flags = self.config.gp_flags;
-        state.ifwi_wipe_enable = self.config.ifwi_wipe_enable;
+        state.ifwi_wipe_enable = self.config.ifwi_wipe_enable || operation == Operation::Wipe;
+        state.chunk_size = self.config.chunk_size;
+        state.os_partitions = self.config.os_partitions.clone();
+        state.error_policy = self.config.error_policy.clone();
+        state.resume_os = self.config.resume_os;
+        state.progress.bytes_total = self.transfer_size(operation) as u64;
+        Ok(state)
+    }
+
+    /// Feed one ACK to the state machine and perform the resulting action.
+    ///
+    /// This is the low-level entry point under `run`: no device discovery,
+    /// handshake, read retries or re-enumeration. Call `load_files` first;
+    /// `state` carries the protocol state between steps.
+    pub fn step<T: UsbTransport>(
+        &self,
+        transport: &T,
+        state: &mut StateMachineContext,
+        ack: &AckCode,
+    ) -> Result<HandleResult> {
+        state.last_ack = Some(*ack);
+        let mut ctx = HandlerContext {
+            transport,
+            observer: self.observer.as_ref(),
+            state,
+            fw_dnx_data: self.fw_dnx_data.as_deref(),
+            fw_image: self.fw_image.as_ref(),
+            os_dnx_data: self.os_dnx_data.as_deref(),
+            misc_dnx_data: self.misc_dnx_data.as_deref(),
+            os_image: self.os_image.as_ref(),
+        };
+        handle_ack(ack, &mut ctx)
+    }
+
+    /// Bytes the session will send for `operation` with the loaded inputs.
+    fn transfer_size(&self, operation: Operation) -> usize {
+        let mut total = 0;
+        if operation.includes_fw() {
+            total += self.fw_dnx_data.as_ref().map_or(0, Vec::len);
+            if operation != Operation::Wipe {
+                total += self
+                    .fw_image
+                    .as_ref()
+                    .map_or(0, crate::payload::FirmwareImage::total_transfer_size);
+            }
+        }
+        if operation.includes_os() {
+            total += self.os_dnx_data.as_ref().map_or(0, Vec::len);
+            let partitions = self.config.os_partitions.as_deref();
+            total += self
+                .os_image
+                .as_ref()
+                .map_or(0, |os| os.total_transfer_size(partitions));
+        }
+        total
+    }
+
+    /// Run the complete DnX session.
+    pub fn run(&mut self) -> Result<(), SessionError> {
+        self.run_with_summary().map(|_| ())
+    }
+
+    /// Run the complete DnX session and report what happened.
+    #[instrument(skip(self))]
+    pub fn run_with_summary(&mut self) -> Result<SessionSummary, SessionError> {
+        if self.config.dry_run {
+            return Ok(self.dry_run()?);
+        }
+        let start = std::time::Instant::now();
+        let mut state = self.prepare()?;
+        let mut reenumerating = false;
+        let mut summary = SessionSummary {
+            operation: state.operation,
+            device: None,
+            reenumerations: 0,
+            elapsed: Duration::ZERO,
+        };
 
         loop {
-            // Emit starting event
-            self.observer.on_event(&DnxEvent::PhaseChanged {
-                from: DnxPhase::WaitingForDevice,
-                to: DnxPhase::WaitingForDevice,
-            });
+            if reenumerating {
+                self.enter_phase(&mut state, DnxPhase::WaitingForDevice);
+            }
 
             // Wait for device
-            let transport = self.wait_for_device()?;
+            let transport = match self.config.expected_reenumerated_pid {
+                Some(pid) if reenumerating => self.wait_for_reenumerated(pid)?,
+                _ if reenumerating => self.wait_for_selected(self.reenumerated_selector())?,
+                _ => self.wait_for_device()?,
+            }
+            .with_write_queue_depth(self.config.write_queue_depth);
 
             self.observer.on_event(&DnxEvent::DeviceConnected {
                 vid: transport.vendor_id(),
                 pid: transport.product_id(),
             });
+            summary.device = Some((transport.vendor_id(), transport.product_id()));
 
             // Wrap transport with observer
             let obs_transport = ObservableTransport {
                 inner: &transport,
                 observer: &self.observer,
+                capture_len: self.packet_capture_len(),
             };
 
             // Run state machine
@@ -196,50 +923,293 @@ impl<O: DnxObserver + 'static> DnxSession<O> {
 
             match result {
                 Ok(HandleResult::Complete) => break,
+                Ok(HandleResult::NeedReEnumerate) if state.is_complete() => {
+                    info!("Requested operation complete, not waiting for re-enumeration");
+                    break;
+                }
                 Ok(HandleResult::NeedReEnumerate) => {
                     info!("Device resetting, waiting for re-enumeration...");
-                    thread::sleep(Duration::from_secs(2)); // Wait for device to actually disconnect
+                    state.reset_transfers();
+                    self.reset_device(&transport);
+                    thread::sleep(RESET_SETTLE); // Wait for device to actually disconnect
+                    reenumerating = true;
+                    summary.reenumerations += 1;
                     continue; // Loop back to wait_for_device
                 }
                 Ok(_) => break, // Other results end the session normally
-                Err(e) => return Err(e),
+                Err(e) => return Err(e.into()),
             }
         }
 
-        Ok(())
+        self.finish(&mut state);
+        summary.elapsed = start.elapsed();
+        Ok(summary)
     }
 
-    fn wait_for_device(&self) -> Result<NusbTransport> {
-        info!("Waiting for device...");
-        let timeout = Duration::from_secs(self.config.retry_timeout_secs.max(60));
+    /// Run the session over an already-open transport.
+    ///
+    /// Re-enumeration is assumed to happen on the same transport, which makes
+    /// this suitable for `MockTransport`-driven tests and benchmarks.
+    pub fn run_with_transport<T: UsbTransport>(
+        &mut self,
+        transport: &T,
+    ) -> Result<SessionSummary, SessionError> {
         let start = std::time::Instant::now();
-        let mut poll_count = 0u64;
+        let mut state = self.prepare()?;
+        let mut summary = SessionSummary {
+            operation: state.operation,
+            device: Some((transport.vendor_id(), transport.product_id())),
+            reenumerations: 0,
+            elapsed: Duration::ZERO,
+        };
 
         loop {
-            poll_count += 1;
-
-            match NusbTransport::open() {
-                Ok(t) => {
-                    info!(
-                        vid = format!("{:04X}", t.vendor_id()),
-                        pid = format!("{:04X}", t.product_id()),
-                        "Device found after {} polls",
-                        poll_count
-                    );
-                    return Ok(t);
+            self.observer.on_event(&DnxEvent::DeviceConnected {
+                vid: transport.vendor_id(),
+                pid: transport.product_id(),
+            });
+
+            let obs_transport = ObservableTransport {
+                inner: transport,
+                observer: &self.observer,
+                capture_len: self.packet_capture_len(),
+            };
+
+            match self.run_state_machine(&obs_transport, &mut state)? {
+                HandleResult::NeedReEnumerate if !state.is_complete() => {
+                    summary.reenumerations += 1;
+                    state.reset_transfers();
+                    self.reset_device(transport);
+                    self.enter_phase(&mut state, DnxPhase::WaitingForDevice);
                 }
-                Err(TransportError::DeviceNotFound { .. }) => {
-                    if start.elapsed() > timeout {
-                        return Err(anyhow!(
-                            "Timeout waiting for device after {}s",
-                            timeout.as_secs()
-                        ));
-                    }
-                    // Fast polling: 100ms instead of 1s
-                    thread::sleep(Duration::from_millis(100));
+                _ => break,
+            }
+        }
+
+        self.finish(&mut state);
+        summary.elapsed = start.elapsed();
+        Ok(summary)
+    }
+
+    fn packet_capture_len(&self) -> usize {
+        self.config
+            .packet_capture_len
+            .unwrap_or(DEFAULT_PACKET_CAPTURE_LEN)
+    }
+
+    /// USB-reset the device so it re-enumerates sooner.
+    ///
+    /// Some Moorefield boards come back much faster after a host-side reset
+    /// than after a plain disconnect. Failures (e.g. on Windows, where nusb
+    /// has no reset) are logged and otherwise ignored.
+    fn reset_device<T: UsbTransport>(&self, transport: &T) {
+        if let Err(e) = transport.reset() {
+            warn!("USB reset failed: {}", e);
+            self.observer.on_event(&DnxEvent::Log {
+                level: LogLevel::Warn,
+                message: format!("USB reset failed: {}", e),
+            });
+        }
+    }
+
+    /// Report a phase change if `to` differs from the current phase.
+    fn enter_phase(&self, state: &mut StateMachineContext, to: DnxPhase) {
+        if let Some(event) = state.enter_phase(to) {
+            self.observer.on_event(&event);
+        }
+    }
+
+    /// Emit the terminal transition and the single `Complete` event.
+    fn finish(&self, state: &mut StateMachineContext) {
+        self.enter_phase(state, DnxPhase::Complete);
+        self.save_checkpoint(state, true);
+        self.observer.on_event(&DnxEvent::Complete);
+    }
+
+    /// Write the checkpoint file, if configured. Unless `force`d, at most
+    /// once per `CHECKPOINT_INTERVAL`. Failures are logged, never fatal.
+    fn save_checkpoint(&self, state: &StateMachineContext, force: bool) {
+        let Some(path) = &self.config.checkpoint_path else {
+            return;
+        };
+        let mut saved = self.checkpoint_saved.lock().unwrap();
+        let now = std::time::Instant::now();
+        if !force && saved.is_some_and(|at| now.duration_since(at) < CHECKPOINT_INTERVAL) {
+            return;
+        }
+        *saved = Some(now);
+        if let Err(e) = Checkpoint::capture(&self.config, state).save(path) {
+            warn!("Failed to write checkpoint: {:#}", e);
+        }
+    }
+
+    /// Poll for the device until it appears, the timeout passes or the
+    /// session is cancelled.
+    pub fn wait_for_device(&self) -> Result<DeviceTransport, WaitError> {
+        self.wait_for_selected(self.config.device_selector.as_ref())
+    }
+
+    /// The selector that still names the device after it re-enumerated.
+    ///
+    /// A serial number survives a reset, a bus address does not.
+    fn reenumerated_selector(&self) -> Option<&DeviceSelector> {
+        self.config
+            .device_selector
+            .as_ref()
+            .filter(|s| matches!(s, DeviceSelector::Serial(_)))
+    }
+
+    fn wait_for_selected(
+        &self,
+        selector: Option<&DeviceSelector>,
+    ) -> Result<DeviceTransport, WaitError> {
+        match selector {
+            Some(selector) => info!("Waiting for device ({})...", selector),
+            None => info!("Waiting for device..."),
+        }
+        let mut wait = DeviceWait::new(&self.config);
+        let t = self.poll_until_open(&mut wait, self.selected_opener(selector))?;
+        Ok(self.opened_device(t, &wait))
+    }
+
+    /// Opens the device named by `selector` among those passing the filter.
+    fn selected_opener<'a>(
+        &'a self,
+        selector: Option<&'a DeviceSelector>,
+    ) -> impl FnMut() -> Result<DeviceTransport, TransportError> + 'a {
+        move || {
+            DeviceTransport::open_selected(
+                self.config.backend,
+                self.config.device_filter,
+                selector,
+                self.config.interface_hint,
+            )
+        }
+    }
+
+    /// Report the device `wait` ended with, warning if it is not supported.
+    fn opened_device(&self, t: DeviceTransport, wait: &DeviceWait) -> DeviceTransport {
+        if !is_supported_device(t.vendor_id(), t.product_id()) {
+            let msg = format!(
+                "Using unrecognized device {:04X}:{:04X} (device filter override)",
+                t.vendor_id(),
+                t.product_id()
+            );
+            warn!("{}", msg);
+            self.observer.on_event(&DnxEvent::Log {
+                level: LogLevel::Warn,
+                message: msg,
+            });
+        }
+        info!(
+            vid = format!("{:04X}", t.vendor_id()),
+            pid = format!("{:04X}", t.product_id()),
+            "Device found after {} polls",
+            wait.polls
+        );
+        t
+    }
+
+    /// Call `open` every `DEVICE_POLL_INTERVAL` while it reports
+    /// `DeviceNotFound`, emitting `WaitingForDevice` every few seconds.
+    fn poll_until_open(
+        &self,
+        wait: &mut DeviceWait,
+        mut open: impl FnMut() -> Result<DeviceTransport, TransportError>,
+    ) -> Result<DeviceTransport, WaitError> {
+        loop {
+            if let Some(t) = self.poll_once(wait, &mut open)? {
+                return Ok(t);
+            }
+            thread::sleep(DEVICE_POLL_INTERVAL);
+        }
+    }
+
+    /// One round of a device wait: `Ok(None)` means poll again.
+    fn poll_once(
+        &self,
+        wait: &mut DeviceWait,
+        open: &mut impl FnMut() -> Result<DeviceTransport, TransportError>,
+    ) -> Result<Option<DeviceTransport>, WaitError> {
+        if self.cancel.is_cancelled() {
+            self.observer.on_event(&DnxEvent::Cancelled);
+            return Err(WaitError::Cancelled);
+        }
+        wait.polls += 1;
+        match open() {
+            Ok(t) => return Ok(Some(t)),
+            Err(TransportError::DeviceNotFound { .. }) => {}
+            Err(TransportError::PermissionDenied { vid, pid, hint }) => {
+                return Err(WaitError::PermissionDenied { vid, pid, hint });
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let elapsed = wait.start.elapsed();
+        if elapsed > wait.timeout {
+            return Err(WaitError::Timeout {
+                secs: wait.timeout.as_secs(),
+            });
+        }
+        if elapsed >= wait.next_report {
+            self.observer.on_event(&DnxEvent::WaitingForDevice {
+                elapsed_secs: elapsed.as_secs(),
+                timeout_secs: wait.timeout.as_secs(),
+            });
+            wait.next_report += WAIT_PROGRESS_INTERVAL;
+        }
+        Ok(None)
+    }
+
+    /// Wait for the device to come back with `expected_pid` after a reset.
+    ///
+    /// Other supported devices on the bus are reported once via
+    /// `DnxEvent::UnexpectedDevice` and otherwise ignored.
+    fn wait_for_reenumerated(&self, expected_pid: u16) -> Result<DeviceTransport, WaitError> {
+        info!(
+            expected_pid = format!("{:04X}", expected_pid),
+            "Waiting for re-enumerated device..."
+        );
+        self.poll_until_open(
+            &mut DeviceWait::new(&self.config),
+            self.reenumerated_opener(expected_pid),
+        )
+    }
+
+    /// Opens the device once it is back with `expected_pid`.
+    fn reenumerated_opener(
+        &self,
+        expected_pid: u16,
+    ) -> impl FnMut() -> Result<DeviceTransport, TransportError> + '_ {
+        let mut reported = Vec::new();
+
+        move || {
+            let opened = DeviceTransport::open_selected(
+                self.config.backend,
+                Some((INTEL_VENDOR_ID, expected_pid)),
+                self.reenumerated_selector(),
+                self.config.interface_hint,
+            );
+            if !matches!(opened, Err(TransportError::DeviceNotFound { .. })) {
+                return opened;
+            }
+
+            for pid in DeviceTransport::attached_supported_pids(self.config.backend)? {
+                if pid != expected_pid && !reported.contains(&pid) {
+                    warn!(
+                        pid = format!("{:04X}", pid),
+                        expected_pid = format!("{:04X}", expected_pid),
+                        "Ignoring unexpected device"
+                    );
+                    self.observer.on_event(&DnxEvent::UnexpectedDevice {
+                        vid: INTEL_VENDOR_ID,
+                        pid,
+                        expected_pid,
+                    });
+                    reported.push(pid);
                 }
-                Err(e) => return Err(e.into()),
             }
+            opened
         }
     }
 
@@ -248,33 +1218,22 @@ impl<O: DnxObserver + 'static> DnxSession<O> {
         transport: &T,
         state: &mut StateMachineContext,
     ) -> Result<HandleResult> {
-        // Send initial preamble only if we are starting fresh or after a reset that returns to DnX mode
-        if !state.gpp_reset {
-            self.observer.on_event(&DnxEvent::PhaseChanged {
-                from: DnxPhase::WaitingForDevice,
-                to: DnxPhase::Handshake,
-            });
-
-            // Initial handshake: send DnER.
-            // Most devices (including Moorefield 0A2C/0A65) respond to this.
-            transport.write(&PREAMBLE_DNER.to_le_bytes())?;
-            info!(preamble = "DnER", "Sent handshake preamble");
-
-            // We used to send IDRQ immediately for Moorefield here, but it caused
-            // "hardware fault or protocol violation" (EPROTO) on some devices.
-            // We'll now wait for the first response in the main loop instead.
-        } else {
-            // After reset, we might just wait for the first ACK from the new stage
-            info!("Resuming state machine after reset");
-            state.gpp_reset = false;
-        }
+        self.start_state_machine(transport, state)?;
+        let (max_read_retries, read_timeout) = self.read_limits();
+        let mut read_retries = 0u32;
 
         // Main loop
         loop {
-            let ack = match transport.read_ack() {
-                Ok(a) => a,
-                Err(TransportError::Timeout { .. }) => {
-                    continue;
+            if self.cancel.is_cancelled() {
+                self.observer.on_event(&DnxEvent::Cancelled);
+                self.save_checkpoint(state, true);
+                return Err(SessionError::Cancelled.into());
+            }
+
+            let ack = match transport.read_ack_with_timeout(read_timeout) {
+                Ok(a) => {
+                    read_retries = 0;
+                    a
                 }
                 Err(TransportError::Disconnected) => {
                     self.observer.on_event(&DnxEvent::DeviceDisconnected);
@@ -282,64 +1241,181 @@ impl<O: DnxObserver + 'static> DnxSession<O> {
                     return Ok(HandleResult::NeedReEnumerate);
                 }
                 Err(e) => {
-                    // Intel xFSTK uses extensive retries.
-                    // We shouldn't fail immediately on transient read errors.
-                    // Log it as a debug/warn but keep trying.
-                    warn!(error = ?e, "Transient read error, retrying...");
-                    thread::sleep(Duration::from_millis(50));
+                    read_retries += 1;
+                    thread::sleep(self.read_retry_delay(
+                        state,
+                        read_retries,
+                        max_read_retries,
+                        e,
+                    )?);
                     continue;
                 }
             };
 
-            let mut ctx = HandlerContext {
-                transport,
-                observer: self.observer.as_ref(),
-                state,
-                fw_dnx_data: self.fw_dnx_data.as_deref(),
-                fw_image: self.fw_image.as_ref(),
-                os_dnx_data: self.os_dnx_data.as_deref(),
-                os_image: self.os_image.as_ref(),
+            let result = self.step(transport, state, &ack)?;
+            if let Some(done) = self.after_step(state, &ack, result) {
+                return done;
+            }
+        }
+    }
+
+    /// Send the handshake, or pick up where the last stage left off after a
+    /// reset, before reading the first ACK.
+    fn start_state_machine<T: UsbTransport>(
+        &self,
+        transport: &T,
+        state: &mut StateMachineContext,
+    ) -> Result<()> {
+        // Send initial preamble only if we are starting fresh or after a reset that returns to DnX mode
+        if !state.gpp_reset {
+            self.enter_phase(state, DnxPhase::Handshake);
+
+            // Initial handshake: DnER by default. IDRQ/BMRQ are opt-in since an
+            // unsolicited IDRQ caused EPROTO on some Moorefield devices.
+            for preamble in self.config.effective_handshake() {
+                transport.write(&preamble.to_bytes())?;
+                info!(preamble = %preamble, "Sent handshake preamble");
+            }
+        } else {
+            // After reset, we might just wait for the first ACK from the new stage
+            info!("Resuming state machine after reset");
+            state.gpp_reset = false;
+            let phase = if state.operation.includes_os() {
+                DnxPhase::OsDownload
+            } else {
+                DnxPhase::FirmwareDownload
             };
+            self.enter_phase(state, phase);
+        }
+        Ok(())
+    }
 
-            let result = handle_ack(&ack, &mut ctx)?;
+    /// Read retry cap and per-read timeout, with defaults for unset values.
+    fn read_limits(&self) -> (u32, Duration) {
+        let max_read_retries = match self.config.max_read_retries {
+            0 => DEFAULT_MAX_READ_RETRIES,
+            n => n,
+        };
+        let read_timeout = match self.config.read_timeout_ms {
+            0 => DEFAULT_READ_TIMEOUT,
+            ms => Duration::from_millis(ms),
+        };
+        (max_read_retries, read_timeout)
+    }
 
-            match result {
-                HandleResult::Continue => {}
-                HandleResult::FwDone => {
-                    self.observer.on_event(&DnxEvent::PhaseChanged {
-                        from: DnxPhase::FirmwareDownload,
-                        to: DnxPhase::OsDownload,
-                    });
-                }
-                HandleResult::OsDone => {
-                    self.observer.on_event(&DnxEvent::PhaseChanged {
-                        from: DnxPhase::OsDownload,
-                        to: DnxPhase::Complete,
-                    });
-                }
-                HandleResult::Complete => {
-                    self.observer.on_event(&DnxEvent::Complete);
-                    return Ok(HandleResult::Complete);
-                }
-                HandleResult::Error(msg) => {
-                    return Err(anyhow!(msg));
-                }
-                HandleResult::NeedReEnumerate => {
-                    self.observer.on_event(&DnxEvent::PhaseChanged {
-                        from: DnxPhase::FirmwareDownload,
-                        to: DnxPhase::DeviceReset,
-                    });
-                    self.observer.on_event(&DnxEvent::DeviceDisconnected);
-                    return Ok(HandleResult::NeedReEnumerate);
-                }
+    /// Backoff before retry number `read_retries` after a failed ACK read,
+    /// or the error ending the session once `max_read_retries` is exceeded.
+    fn read_retry_delay(
+        &self,
+        state: &mut StateMachineContext,
+        read_retries: u32,
+        max_read_retries: u32,
+        e: TransportError,
+    ) -> Result<Duration> {
+        // Intel xFSTK uses extensive retries, so transient read errors and
+        // timeouts are retried with backoff before giving up.
+        if read_retries > max_read_retries {
+            self.observer.on_event(&DnxEvent::RetryExhausted {
+                retries: max_read_retries,
+                last_error: e.to_string(),
+            });
+            self.enter_phase(state, DnxPhase::Error);
+            self.save_checkpoint(state, true);
+            return Err(SessionError::TooManyRetries {
+                retries: max_read_retries,
+                last: e,
             }
+            .into());
+        }
+        let delay = READ_BACKOFF_BASE
+            .saturating_mul(1 << (read_retries - 1).min(16))
+            .min(READ_BACKOFF_MAX);
+        let msg = format!(
+            "Read error ({}/{}): {}; retrying in {}ms",
+            read_retries,
+            max_read_retries,
+            e,
+            delay.as_millis()
+        );
+        warn!("{}", msg);
+        self.observer.on_event(&DnxEvent::Log {
+            level: LogLevel::Warn,
+            message: msg,
+        });
+        Ok(delay)
+    }
+
+    /// Act on the result of one step and checkpoint; `Some` ends the read
+    /// loop with it.
+    fn after_step(
+        &self,
+        state: &mut StateMachineContext,
+        ack: &AckCode,
+        result: HandleResult,
+    ) -> Option<Result<HandleResult>> {
+        let outcome = self.step_outcome(state, ack, result);
+        self.save_checkpoint(state, outcome.is_some());
+        outcome
+    }
 
-            if !state.should_continue() {
-                break;
+    fn step_outcome(
+        &self,
+        state: &mut StateMachineContext,
+        ack: &AckCode,
+        result: HandleResult,
+    ) -> Option<Result<HandleResult>> {
+        match result {
+            HandleResult::Continue => {}
+            HandleResult::FwDone if state.operation.includes_os() => {
+                self.enter_phase(state, DnxPhase::OsDownload);
+            }
+            // Completion is reported once, when the session finishes
+            HandleResult::FwDone | HandleResult::OsDone => {}
+            HandleResult::Complete => {
+                return Some(Ok(HandleResult::Complete));
+            }
+            HandleResult::Error(msg) => {
+                self.enter_phase(state, DnxPhase::Error);
+                let error = if ack.is_error() {
+                    SessionError::DeviceError {
+                        ack: ack.as_ascii(),
+                        description: ack.error_description().unwrap_or("undocumented code"),
+                    }
+                } else {
+                    SessionError::Protocol(msg)
+                };
+                return Some(Err(error.into()));
+            }
+            HandleResult::NeedReEnumerate => {
+                self.enter_phase(state, DnxPhase::DeviceReset);
+                self.observer.on_event(&DnxEvent::DeviceDisconnected);
+                return Some(Ok(HandleResult::NeedReEnumerate));
             }
         }
 
-        Ok(HandleResult::Complete)
+        (!state.should_continue()).then_some(Ok(HandleResult::Complete))
+    }
+}
+
+/// Deadline and progress reporting for one device wait.
+struct DeviceWait {
+    start: std::time::Instant,
+    timeout: Duration,
+    next_report: Duration,
+    polls: u64,
+}
+
+impl DeviceWait {
+    fn new(config: &SessionConfig) -> Self {
+        Self {
+            start: std::time::Instant::now(),
+            timeout: Duration::from_secs(match config.retry_timeout_secs {
+                0 => DEFAULT_RETRY_TIMEOUT_SECS,
+                secs => secs,
+            }),
+            next_report: WAIT_PROGRESS_INTERVAL,
+            polls: 0,
+        }
     }
 }
 
@@ -347,6 +1423,28 @@ impl<O: DnxObserver + 'static> DnxSession<O> {
 struct ObservableTransport<'a, T: UsbTransport, O: DnxObserver> {
     inner: &'a T,
     observer: &'a Arc<O>,
+    /// Bytes of each packet copied into `DnxEvent::Packet`.
+    capture_len: usize,
+}
+
+impl<T: UsbTransport, O: DnxObserver> ObservableTransport<'_, T, O> {
+    fn capture(&self, data: &[u8]) -> Option<Vec<u8>> {
+        (self.capture_len > 0).then(|| data[..data.len().min(self.capture_len)].to_vec())
+    }
+
+    fn observe_read(&self, res: &Result<Vec<u8>, TransportError>) {
+        if let Ok(data) = res
+            && !data.is_empty()
+        {
+            self.observer.on_packet(PacketDirection::Rx, "Data", data);
+            self.observer.on_event(&DnxEvent::Packet {
+                direction: PacketDirection::Rx,
+                packet_type: "Data".to_string(),
+                length: data.len(),
+                data: self.capture(data),
+            });
+        }
+    }
 }
 
 impl<'a, T: UsbTransport, O: DnxObserver> UsbTransport for ObservableTransport<'a, T, O> {
@@ -354,11 +1452,13 @@ impl<'a, T: UsbTransport, O: DnxObserver> UsbTransport for ObservableTransport<'
         let res = self.inner.write(data);
         if res.is_ok() {
             let packet_type = if data.len() < 32 { "Cmd/Hdr" } else { "Data" };
+            self.observer
+                .on_packet(PacketDirection::Tx, packet_type, data);
             self.observer.on_event(&DnxEvent::Packet {
                 direction: PacketDirection::Tx,
                 packet_type: packet_type.to_string(),
                 length: data.len(),
-                data: Some(data.iter().take(32).cloned().collect()),
+                data: self.capture(data),
             });
         }
         res
@@ -366,19 +1466,24 @@ impl<'a, T: UsbTransport, O: DnxObserver> UsbTransport for ObservableTransport<'
 
     fn read(&self, max_len: usize) -> Result<Vec<u8>, TransportError> {
         let res = self.inner.read(max_len);
-        if let Ok(data) = &res
-            && !data.is_empty()
-        {
-            self.observer.on_event(&DnxEvent::Packet {
-                direction: PacketDirection::Rx,
-                packet_type: "Data".to_string(),
-                length: data.len(),
-                data: Some(data.iter().take(32).cloned().collect()),
-            });
-        }
+        self.observe_read(&res);
+        res
+    }
+
+    fn read_with_timeout(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        let res = self.inner.read_with_timeout(max_len, timeout);
+        self.observe_read(&res);
         res
     }
 
+    fn reset(&self) -> Result<(), TransportError> {
+        self.inner.reset()
+    }
+
     fn is_connected(&self) -> bool {
         self.inner.is_connected()
     }
@@ -391,3 +1496,914 @@ impl<'a, T: UsbTransport, O: DnxObserver> UsbTransport for ObservableTransport<'
         self.inner.product_id()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventRecorder;
+    use crate::protocol::constants::*;
+    use crate::transport::MockTransport;
+
+    /// Temporary directory removed on drop, so failing tests clean up too.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("dnx-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = std::path::Path;
+
+        fn deref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    /// Queue the ACKs of a whole OS-only session: DXBL, ROSIP, `rimgs`
+    /// RIMGs, then DONE.
+    fn queue_os_session(mock: &MockTransport, rimgs: usize) {
+        mock.queue_ack_u32(BULK_ACK_DXBL);
+        mock.queue_ack_u64(BULK_ACK_ROSIP, 5);
+        for _ in 0..rimgs {
+            mock.queue_ack_u32(BULK_ACK_RIMG);
+        }
+        mock.queue_ack_u32(BULK_ACK_DONE);
+    }
+
+    /// Every `PhaseChanged` transition `recorder` saw.
+    fn phases(recorder: &EventRecorder) -> Vec<(DnxPhase, DnxPhase)> {
+        recorder.collect(|e| match e {
+            DnxEvent::PhaseChanged { from, to } => Some((*from, *to)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_minimal_toml_loads_with_defaults() {
+        let dir = TempDir::new("minimal");
+        let path = dir.join("dnx.toml");
+        std::fs::write(&path, "os_image_path = \"os.img\"\n").unwrap();
+        let config = SessionConfig::load_from_file(&path).unwrap();
+
+        assert_eq!(config.os_image_path.as_deref(), Some("os.img"));
+        assert_eq!(config.fw_dnx_path, None);
+        assert_eq!(config.retry_timeout_secs, 300);
+        assert_eq!(config.gp_flags, 0);
+        assert!(!config.ifwi_wipe_enable);
+        assert_eq!(config.error_policy, ErrorPolicy::default());
+        assert_eq!(config.backend, UsbBackend::Auto);
+        assert_eq!(config.effective_operation(), Operation::OsOnly);
+    }
+
+    #[test]
+    fn test_load_rejects_out_of_range_values() {
+        let dir = TempDir::new("range");
+        let path = dir.join("dnx.toml");
+        std::fs::write(
+            &path,
+            "retry_timeout_secs = 100000\nwrite_queue_depth = 1000\n",
+        )
+        .unwrap();
+        let err = SessionConfig::load_from_file(&path)
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("retry_timeout_secs = 100000"), "{err}");
+        assert!(err.contains("write_queue_depth = 1000"), "{err}");
+        assert_eq!(
+            SessionConfig::builder()
+                .chunk_size(32 << 20)
+                .build()
+                .range_errors(),
+            [ConfigError::OutOfRange {
+                field: "chunk_size",
+                value: 32 << 20,
+                max: 16 << 20,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_example_toml_covers_every_setting() {
+        let example = SessionConfig::example_toml();
+        let config: SessionConfig = toml::from_str(example).unwrap();
+        assert_eq!(config.retry_timeout_secs, 300);
+        assert!(config.range_errors().is_empty());
+
+        // Uncommenting the first sample of each setting must still parse
+        let mut seen = std::collections::HashSet::new();
+        let uncommented: String = example
+            .lines()
+            .map(|line| {
+                let sample = line.strip_prefix("# ").unwrap_or_default();
+                match sample.split_once(" = ") {
+                    Some((key, _))
+                        if key.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+                            && seen.insert(key.to_string()) =>
+                    {
+                        sample
+                    }
+                    _ => line,
+                }
+            })
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let full: SessionConfig = toml::from_str(&uncommented).unwrap();
+        assert_eq!(full.operation, Some(Operation::FwThenOs));
+        assert_eq!(
+            full.device_selector,
+            Some(DeviceSelector::Serial("ABC123".into()))
+        );
+        assert!(full.range_errors().is_empty());
+
+        // Every field has an entry, including ones unset by default.
+        // Aliases share their field's entry.
+        for key in config_fields() {
+            if *key == "max_consecutive_errors" {
+                continue;
+            }
+            assert!(
+                seen.contains(*key) || config_has_key(example, key),
+                "new SessionConfig field {key}: add it to the example"
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_consecutive_errors_sets_read_retries() {
+        let config: SessionConfig = toml::from_str("max_consecutive_errors = 5").unwrap();
+        assert_eq!(config.max_read_retries, 5);
+    }
+
+    /// Every key `SessionConfig` reads, as serde knows them.
+    fn config_fields() -> &'static [&'static str] {
+        use serde::de::{Error, Visitor, value};
+
+        /// Deserializer that only records the fields of the struct asked for.
+        struct Fields(&'static [&'static str]);
+
+        impl<'de> serde::Deserializer<'de> for &mut Fields {
+            type Error = value::Error;
+
+            fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, value::Error> {
+                Err(value::Error::custom("not a struct"))
+            }
+
+            fn deserialize_struct<V: Visitor<'de>>(
+                self,
+                _name: &'static str,
+                fields: &'static [&'static str],
+                _visitor: V,
+            ) -> Result<V::Value, value::Error> {
+                self.0 = fields;
+                Err(value::Error::custom("fields recorded"))
+            }
+
+            serde::forward_to_deserialize_any! {
+                bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+                bytes byte_buf option unit unit_struct newtype_struct seq tuple
+                tuple_struct map enum identifier ignored_any
+            }
+        }
+
+        let mut fields = Fields(&[]);
+        SessionConfig::deserialize(&mut fields).unwrap_err();
+        fields.0
+    }
+
+    /// Whether `toml` sets `key` (uncommented) at the start of a line.
+    fn config_has_key(toml: &str, key: &str) -> bool {
+        toml.lines()
+            .any(|line| line.starts_with(&format!("{key} = ")) || line == format!("[{key}]"))
+    }
+
+    #[test]
+    fn test_merge_overlays_only_some_values() {
+        let file = SessionConfig::builder()
+            .fw_dnx("file_fw_dnx.bin")
+            .os_image("file_os.img")
+            .gp_flags(0x80)
+            .ifwi_wipe(true)
+            .build();
+
+        let merged = file.clone().merge(
+            Some("cli_fw_dnx.bin".into()),
+            None,
+            None,
+            None,
+            Some("misc.bin".into()),
+            Some(0),
+            Some(false),
+        );
+        assert_eq!(merged.fw_dnx_path.as_deref(), Some("cli_fw_dnx.bin"));
+        assert_eq!(merged.misc_dnx_path.as_deref(), Some("misc.bin"));
+        // None, 0 and false leave the file's values alone
+        assert_eq!(merged.fw_image_path, None);
+        assert_eq!(merged.os_image_path.as_deref(), Some("file_os.img"));
+        assert_eq!(merged.gp_flags, 0x80);
+        assert!(merged.ifwi_wipe_enable);
+
+        let untouched = file.clone().merge(None, None, None, None, None, None, None);
+        assert_eq!(
+            toml::to_string(&untouched).unwrap(),
+            toml::to_string(&file).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_with_defaults_fills_only_unset_values() {
+        let config = SessionConfig::default().with_defaults();
+        assert_eq!(config.retry_timeout_secs, 300);
+        assert_eq!(config.chunk_size, crate::protocol::constants::ONE28_K);
+
+        let config = SessionConfig::builder()
+            .retry_timeout(Duration::from_secs(30))
+            .chunk_size(4096)
+            .build()
+            .with_defaults();
+        assert_eq!(config.retry_timeout_secs, 30);
+        assert_eq!(config.chunk_size, 4096);
+    }
+
+    #[test]
+    fn test_effective_handshake() {
+        assert_eq!(
+            SessionConfig::default().effective_handshake(),
+            [Preamble::Dner]
+        );
+        let config = SessionConfig::builder()
+            .handshake(vec![Preamble::Idrq, Preamble::Bmrq])
+            .build();
+        assert_eq!(
+            config.effective_handshake(),
+            [Preamble::Idrq, Preamble::Bmrq]
+        );
+    }
+
+    #[test]
+    fn test_device_wait_uses_configured_timeout() {
+        let timeout = |secs| {
+            let config = SessionConfig::builder()
+                .retry_timeout(Duration::from_secs(secs))
+                .build();
+            DeviceWait::new(&config).timeout
+        };
+        assert_eq!(timeout(0), Duration::from_secs(DEFAULT_RETRY_TIMEOUT_SECS));
+        assert_eq!(timeout(5), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_validate_operation_inputs() {
+        let config = |fw_dnx: bool, fw_image: bool, os_dnx: bool, os_image: bool| SessionConfig {
+            fw_dnx_path: fw_dnx.then(|| "fw_dnx.bin".to_string()),
+            fw_image_path: fw_image.then(|| "ifwi.bin".to_string()),
+            os_dnx_path: os_dnx.then(|| "os_dnx.bin".to_string()),
+            os_image_path: os_image.then(|| "os.img".to_string()),
+            ..Default::default()
+        };
+        let missing = |c: SessionConfig| match c.validate_operation() {
+            Err(e) => match e.downcast_ref::<SessionError>() {
+                Some(SessionError::MissingInput { input, .. }) => Some(*input),
+                _ => panic!("unexpected error: {}", e),
+            },
+            Ok(_) => None,
+        };
+
+        assert_eq!(
+            missing(config(false, true, false, false)),
+            Some("a FW DnX path")
+        );
+        assert_eq!(
+            missing(config(false, false, true, false)),
+            Some("an OS image path")
+        );
+        assert_eq!(
+            missing(config(false, true, false, true)),
+            Some("a FW DnX path")
+        );
+        assert_eq!(missing(config(true, false, false, false)), None);
+        assert_eq!(missing(config(true, true, false, false)), None);
+        assert_eq!(missing(config(true, false, false, true)), None);
+        assert_eq!(missing(config(false, false, false, true)), None);
+
+        let wipe = SessionConfig {
+            ifwi_wipe_enable: true,
+            ..config(false, true, false, false)
+        };
+        assert_eq!(missing(wipe), Some("a FW DnX path"));
+    }
+
+    #[test]
+    fn test_cancelled_wait_returns_cancelled() {
+        let session = DnxSession::new(SessionConfig::default());
+        let token = session.cancel_token();
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(
+            session.wait_for_device(),
+            Err(WaitError::Cancelled)
+        ));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_run_async_cancelled_while_waiting() {
+        let dir = TempDir::new("async");
+        let mut session = DnxSession::new(os_only_config(&dir));
+        session.cancel_token().cancel();
+
+        // Spawning also checks the future is Send
+        let err = tokio::spawn(async move { session.run_async().await })
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(err, SessionError::Cancelled));
+    }
+
+    /// OS-only config backed by a tiny OS DnX and OSIP image written to `dir`.
+    fn os_only_config(dir: &std::path::Path) -> SessionConfig {
+        let os_dnx = dir.join("os_dnx.bin");
+        std::fs::write(&os_dnx, [0xA5u8; 64]).unwrap();
+        let mut image = vec![0u8; OSIP_PARTITIONTABLE_SIZE + 1024];
+        image[0..4].copy_from_slice(b"$OS$");
+        let os_image = dir.join("os_image.bin");
+        std::fs::write(&os_image, &image).unwrap();
+
+        SessionConfig {
+            os_dnx_path: Some(os_dnx.to_string_lossy().into_owned()),
+            os_image_path: Some(os_image.to_string_lossy().into_owned()),
+            operation: Some(Operation::OsOnly),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_written_during_session() {
+        let dir = TempDir::new("checkpoint");
+        let checkpoint = dir.join("session.json");
+        let config = SessionConfig {
+            chunk_size: 256,
+            checkpoint_path: Some(checkpoint.to_string_lossy().into_owned()),
+            ..os_only_config(&dir)
+        };
+
+        let mock = MockTransport::new();
+        queue_os_session(&mock, 3);
+
+        let mut session = DnxSession::new(config.clone());
+        session.run_with_transport(&mock).unwrap();
+
+        let saved = Checkpoint::load(&checkpoint).unwrap().unwrap();
+        let raw: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&checkpoint).unwrap()).unwrap();
+        assert!(!dir.join("session.json.tmp").exists());
+
+        assert!(saved.matches(&config));
+        assert_eq!(saved.phase, DnxPhase::Complete);
+        assert_eq!(saved.last_ack.as_deref(), Some("DONE"));
+        assert_eq!(saved.components["OS Image"], 3 * 256);
+        assert_eq!(saved.components["OSIP"], OSIP_PARTITIONTABLE_SIZE as u64);
+        assert_eq!(saved.bytes_sent, saved.components.values().sum::<u64>());
+        assert_eq!(raw["last_ack"], "DONE");
+    }
+
+    #[test]
+    fn test_resume_os_after_disconnect() {
+        let run = |resume_os: bool| {
+            let dir = TempDir::new(&format!("resume-os-{resume_os}"));
+            let config = SessionConfig {
+                chunk_size: 256,
+                resume_os,
+                ..os_only_config(&dir)
+            };
+            // Four 256-byte chunks, each filled with its index
+            let mut image = vec![0u8; OSIP_PARTITIONTABLE_SIZE];
+            image[0..4].copy_from_slice(b"$OS$");
+            (0..4u8).for_each(|i| image.extend([i; 256]));
+            std::fs::write(config.os_image_path.as_ref().unwrap(), &image).unwrap();
+
+            // Device drops after two chunks were written but only the first
+            // was acknowledged (by the second RIMG), then asks again
+            let resent = if resume_os { 3 } else { 4 };
+            let mock = MockTransport::new();
+            mock.queue_ack_u32(BULK_ACK_DXBL);
+            mock.queue_ack_u64(BULK_ACK_ROSIP, 5);
+            mock.queue_ack_u32(BULK_ACK_RIMG);
+            mock.queue_ack_u32(BULK_ACK_RIMG);
+            mock.queue_disconnect();
+            mock.queue_ack_u64(BULK_ACK_ROSIP, 5);
+            for _ in 0..resent {
+                mock.queue_ack_u32(BULK_ACK_RIMG);
+            }
+            mock.queue_ack_u32(BULK_ACK_DONE);
+
+            let mut session = DnxSession::new(config);
+            let summary = session.run_with_transport(&mock).unwrap();
+            assert_eq!(summary.reenumerations, 1);
+
+            // Chunk index of every OS image chunk written
+            mock.get_writes()
+                .iter()
+                .filter(|w| w.len() == 256)
+                .map(|w| w[0])
+                .collect::<Vec<_>>()
+        };
+
+        // The unacknowledged chunk 1 is sent again
+        assert_eq!(run(true), [0, 1, 1, 2, 3]);
+        assert_eq!(run(false), [0, 1, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_overall_total_counts_only_selected_partitions() {
+        let dir = TempDir::new("partition-total");
+        let config = SessionConfig {
+            chunk_size: 512,
+            os_partitions: Some(vec![1]),
+            ..os_only_config(&dir)
+        };
+        // Two one-block partitions
+        let mut image = vec![0u8; OSIP_PARTITIONTABLE_SIZE + 2 * 512];
+        image[0..4].copy_from_slice(b"$OS$");
+        image[0x08] = 2;
+        image[0x30] = 1;
+        image[0x48] = 1;
+        std::fs::write(config.os_image_path.as_ref().unwrap(), &image).unwrap();
+
+        let mock = MockTransport::new();
+        queue_os_session(&mock, 1);
+        let observer = Arc::new(EventRecorder::default());
+        let mut session = DnxSession::with_observer(config, observer.clone());
+        session.run_with_transport(&mock).unwrap();
+
+        let overall = observer.collect(|e| match e {
+            DnxEvent::OverallProgress {
+                bytes_sent,
+                bytes_total,
+            } => Some((*bytes_sent, *bytes_total)),
+            _ => None,
+        });
+        let expected = (64 + OSIP_PARTITIONTABLE_SIZE + 512) as u64;
+        assert_eq!(overall.last(), Some(&(expected, expected)));
+    }
+
+    #[test]
+    fn test_os_only_phase_sequence() {
+        let dir = TempDir::new("session");
+        let config = os_only_config(&dir);
+
+        let mock = MockTransport::new();
+        queue_os_session(&mock, 1);
+
+        let observer = Arc::new(EventRecorder::default());
+        let mut session = DnxSession::with_observer(config, observer.clone());
+        session.run_with_transport(&mock).unwrap();
+
+        assert_eq!(
+            phases(&observer),
+            [
+                (DnxPhase::WaitingForDevice, DnxPhase::Handshake),
+                (DnxPhase::Handshake, DnxPhase::OsDownload),
+                (DnxPhase::OsDownload, DnxPhase::Complete),
+            ]
+        );
+        let completes = observer.collect(|e| matches!(e, DnxEvent::Complete).then_some(()));
+        assert_eq!(completes.len(), 1);
+    }
+
+    #[test]
+    fn test_device_error_ack_is_typed() {
+        let dir = TempDir::new("er10");
+        let run = |code: u32| {
+            let mock = MockTransport::new();
+            mock.queue_ack_u32(BULK_ACK_DXBL);
+            mock.queue_ack_u32(code);
+            let mut session =
+                DnxSession::with_observer(os_only_config(&dir), Arc::new(EventRecorder::default()));
+            session.run_with_transport(&mock).unwrap_err()
+        };
+
+        let err = run(BULK_ACK_ER10);
+        assert!(matches!(&err, SessionError::DeviceError { ack, .. } if ack == "ER10"));
+        assert_eq!(err.to_string(), "Device error: ER10 (undocumented code)");
+        assert_eq!(
+            run(BULK_ACK_INVALID_PING).to_string(),
+            "Device error: ER00 (invalid ping: the handshake preamble was not recognized)"
+        );
+    }
+
+    #[test]
+    fn test_session_errors_keep_their_kind() {
+        let config = SessionConfig {
+            os_dnx_path: Some("/nonexistent/dnx_osr.bin".into()),
+            os_image_path: Some("/nonexistent/dnx_osr.img".into()),
+            ..Default::default()
+        };
+        let mut session = DnxSession::new(config);
+        let err = SessionError::from(session.load_files().unwrap_err());
+        assert!(matches!(
+            &err,
+            SessionError::Io { path, source }
+                if path == "/nonexistent/dnx_osr.bin"
+                    && source.kind() == std::io::ErrorKind::NotFound
+        ));
+
+        let transport = anyhow::Error::from(TransportError::Disconnected);
+        assert!(matches!(
+            SessionError::from(transport),
+            SessionError::Transport(TransportError::Disconnected)
+        ));
+        let wait = anyhow::Error::from(WaitError::Timeout { secs: 60 });
+        assert!(matches!(
+            SessionError::from(wait),
+            SessionError::Timeout { secs: 60 }
+        ));
+        assert!(matches!(
+            SessionError::from(anyhow!("something else")),
+            SessionError::Other(_)
+        ));
+    }
+
+    /// Cancels the session once ROSIP has been acknowledged.
+    struct CancelAfterRosip(CancelToken);
+
+    impl DnxObserver for CancelAfterRosip {
+        fn on_event(&self, event: &DnxEvent) {
+            if matches!(event, DnxEvent::AckReceived { ack } if ack == "ROSIP") {
+                self.0.cancel();
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancel_mid_stream_stops_run() {
+        let dir = TempDir::new("cancel");
+        let config = os_only_config(&dir);
+
+        // No ACKs after ROSIP: without cancellation the session would keep
+        // polling a silent device.
+        let mock = MockTransport::new();
+        mock.queue_ack_u32(BULK_ACK_DXBL);
+        mock.queue_ack_u64(BULK_ACK_ROSIP, 5);
+
+        let token = CancelToken::new();
+        let recorder = Arc::new(EventRecorder::default());
+        let observer = crate::events::CompositeObserver::new()
+            .with(Arc::new(CancelAfterRosip(token.clone())))
+            .with(recorder.clone());
+        let mut session =
+            DnxSession::with_observer(config, Arc::new(observer)).with_cancel_token(token);
+        let err = session.run_with_transport(&mock).unwrap_err();
+
+        assert!(matches!(err, SessionError::Cancelled));
+        // Handshake, DnX binary and OSIP were sent; nothing after the cancel
+        assert_eq!(mock.get_writes().len(), 3);
+        let cancelled = recorder.collect(|e| matches!(e, DnxEvent::Cancelled).then_some(()));
+        assert_eq!(cancelled.len(), 1);
+    }
+
+    #[test]
+    fn test_reenumerate_resets_device() {
+        let dir = TempDir::new("usb-reset");
+        let fw_dnx = dir.join("fw_dnx.bin");
+        let config = SessionConfig {
+            fw_dnx_path: Some(fw_dnx.to_string_lossy().into_owned()),
+            operation: Some(Operation::FwThenOs),
+            ..os_only_config(&dir)
+        };
+        std::fs::write(&fw_dnx, [0x5Au8; 64]).unwrap();
+
+        let mock = MockTransport::new();
+        mock.queue_ack_u64(BULK_ACK_GPP_RESET, 5);
+        queue_os_session(&mock, 1);
+
+        let mut session = DnxSession::with_observer(config, Arc::new(EventRecorder::default()));
+        let summary = session.run_with_transport(&mock).unwrap();
+
+        assert_eq!(summary.reenumerations, 1);
+        assert_eq!(mock.reset_count(), 1);
+    }
+
+    /// Every `RetryExhausted` event `recorder` saw.
+    fn exhausted(recorder: &EventRecorder) -> Vec<(u32, String)> {
+        recorder.collect(|e| match e {
+            DnxEvent::RetryExhausted {
+                retries,
+                last_error,
+            } => Some((*retries, last_error.clone())),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_read_errors_abort_after_max_retries() {
+        let dir = TempDir::new("retries");
+        let mock = MockTransport::new().with_error_rate(1.0);
+        let config = SessionConfig {
+            max_read_retries: 3,
+            ..os_only_config(&dir)
+        };
+        let observer = Arc::new(EventRecorder::default());
+        let mut session = DnxSession::with_observer(config, observer.clone());
+        let err = session.run_with_transport(&mock).unwrap_err();
+
+        assert!(matches!(
+            err,
+            SessionError::TooManyRetries { retries: 3, .. }
+        ));
+        let exhausted = exhausted(&observer);
+        assert_eq!(exhausted.len(), 1);
+        assert_eq!(exhausted[0].0, 3);
+        assert!(exhausted[0].1.contains("Injected error"));
+    }
+
+    #[test]
+    fn test_read_errors_below_cap_recover() {
+        let dir = TempDir::new("retries-ok");
+        let mock = MockTransport::new();
+        mock.fail_next_reads(3);
+        queue_os_session(&mock, 1);
+        let config = SessionConfig {
+            max_read_retries: 3,
+            ..os_only_config(&dir)
+        };
+        let observer = Arc::new(EventRecorder::default());
+        let mut session = DnxSession::with_observer(config, observer.clone());
+        session.run_with_transport(&mock).unwrap();
+        assert!(exhausted(&observer).is_empty());
+    }
+
+    #[test]
+    fn test_read_timeouts_are_retried() {
+        let dir = TempDir::new("read-timeout");
+        let mock = MockTransport::new().with_latency(Duration::from_millis(20));
+        mock.queue_ack(b"DFRM");
+        let config = SessionConfig {
+            max_read_retries: 2,
+            read_timeout_ms: 5,
+            ..os_only_config(&dir)
+        };
+        let mut session = DnxSession::with_observer(config, Arc::new(EventRecorder::default()));
+        let err = session.run_with_transport(&mock).unwrap_err();
+
+        assert!(matches!(
+            err,
+            SessionError::TooManyRetries {
+                retries: 2,
+                last: TransportError::Timeout { timeout_ms: 5 },
+            }
+        ));
+    }
+
+    #[test]
+    fn test_strict_os_validation_rejects_bad_signature() {
+        let dir = TempDir::new("strict-os");
+        let config = os_only_config(&dir);
+        let os_image = config.os_image_path.clone().unwrap();
+        let mut image = std::fs::read(&os_image).unwrap();
+        image[0..4].copy_from_slice(b"BAD!");
+        std::fs::write(&os_image, &image).unwrap();
+
+        let mut lenient =
+            DnxSession::with_observer(config.clone(), Arc::new(EventRecorder::default()));
+        assert!(lenient.load_files().is_ok());
+
+        let config = SessionConfig {
+            strict_os_validation: true,
+            ..config
+        };
+        let mut strict = DnxSession::with_observer(config, Arc::new(EventRecorder::default()));
+        let err = strict.load_files().unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<crate::payload::OsImageError>(),
+            Some(crate::payload::OsImageError::InvalidSignature { .. })
+        ));
+    }
+
+    #[test]
+    fn test_packet_trace_captures_full_payloads() {
+        let dir = TempDir::new("trace");
+        let config = os_only_config(&dir);
+        let trace = dir.join("trace.jsonl");
+        let observer = crate::events::CompositeObserver::new()
+            .with(Arc::new(EventRecorder::default()))
+            .with(Arc::new(crate::events::PacketLogger::new(&trace).unwrap()));
+
+        let mock = MockTransport::new();
+        queue_os_session(&mock, 1);
+        let mut session = DnxSession::with_observer(config, Arc::new(observer));
+        session.run_with_transport(&mock).unwrap();
+
+        let content = std::fs::read_to_string(&trace).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        let tx: Vec<_> = lines.iter().filter(|l| l["direction"] == "Tx").collect();
+        assert_eq!(tx.len(), mock.get_writes().len());
+        assert!(lines.iter().any(|l| l["direction"] == "Rx"));
+        // The 64-byte OS DnX is traced whole, not as a 32-byte preview
+        let dnx = tx.iter().find(|l| l["length"] == 64).unwrap();
+        assert_eq!(dnx["data"].as_str().unwrap(), "a5".repeat(64));
+    }
+
+    #[test]
+    fn test_packet_capture_len() {
+        let previews = |capture_len: Option<usize>| {
+            let dir = TempDir::new(&format!("capture-{capture_len:?}"));
+            let config = SessionConfig {
+                packet_capture_len: capture_len,
+                ..os_only_config(&dir)
+            };
+            let mock = MockTransport::new();
+            queue_os_session(&mock, 1);
+            let observer = Arc::new(EventRecorder::default());
+            let mut session = DnxSession::with_observer(config, observer.clone());
+            session.run_with_transport(&mock).unwrap();
+            // Length and preview size of every packet
+            observer.collect(|e| match e {
+                DnxEvent::Packet { length, data, .. } => {
+                    Some((*length, data.as_ref().map(Vec::len)))
+                }
+                _ => None,
+            })
+        };
+
+        let default = previews(None);
+        assert!(
+            default
+                .iter()
+                .any(|&(len, _)| len > DEFAULT_PACKET_CAPTURE_LEN)
+        );
+        for (len, data) in default {
+            assert_eq!(data, Some(len.min(DEFAULT_PACKET_CAPTURE_LEN)));
+        }
+        for (len, data) in previews(Some(8)) {
+            assert_eq!(data, Some(len.min(8)));
+        }
+        for (len, data) in previews(Some(usize::MAX)) {
+            assert_eq!(data, Some(len));
+        }
+        assert!(previews(Some(0)).iter().all(|(_, data)| data.is_none()));
+    }
+
+    #[test]
+    fn test_dry_run_plans_without_a_device() {
+        let dir = TempDir::new("dry-run");
+        let config = SessionConfig {
+            dry_run: true,
+            chunk_size: 512,
+            ..os_only_config(&dir)
+        };
+        let observer = Arc::new(EventRecorder::default());
+        let mut session = DnxSession::with_observer(config, observer.clone());
+        let summary = session.run_with_summary().unwrap();
+
+        assert_eq!(summary.operation, Operation::OsOnly);
+        assert_eq!(summary.device, None);
+        let messages = observer.collect(|e| match e {
+            DnxEvent::Log { message, .. } => Some(message.clone()),
+            _ => None,
+        });
+        assert_eq!(
+            messages,
+            [
+                "Plan 1: [OS Download] OS DnX: 64 bytes in 1 chunk(s)",
+                "Plan 2: [OS Download] OSIP: 512 bytes in 1 chunk(s)",
+                "Plan 3: [OS Download] OS Image: 1024 bytes in 2 chunk(s)",
+                "Dry run of os-only: 1600 bytes in 4 chunk(s) would be sent",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let dir = TempDir::new("validate");
+        let good = os_only_config(&dir);
+        let tiny = dir.join("tiny.bin");
+        std::fs::write(&tiny, [0u8; 16]).unwrap();
+        let tiny = tiny.to_string_lossy().into_owned();
+        let missing = dir.join("missing.bin").to_string_lossy().into_owned();
+        let errors = |config: &SessionConfig| config.validate().unwrap_err();
+
+        assert_eq!(good.validate(), Ok(()));
+        assert_eq!(errors(&SessionConfig::default()), [ConfigError::NoInputs]);
+
+        // A FW image with no FW DnX, which is also too small to parse
+        let fw_image_only = SessionConfig {
+            fw_image_path: Some(tiny.clone()),
+            ..Default::default()
+        };
+        let found = errors(&fw_image_only);
+        assert!(matches!(
+            found[..],
+            [
+                ConfigError::MissingInput {
+                    input: "a FW DnX path",
+                    ..
+                },
+                ConfigError::InvalidFirmwareImage { .. }
+            ]
+        ));
+
+        let unreadable = SessionConfig {
+            os_dnx_path: Some(missing.clone()),
+            ..good.clone()
+        };
+        assert!(matches!(
+            &errors(&unreadable)[..],
+            [ConfigError::Unreadable { path, .. }] if *path == missing
+        ));
+
+        let bad_os = SessionConfig {
+            os_image_path: Some(tiny),
+            ..good.clone()
+        };
+        assert!(matches!(
+            errors(&bad_os)[..],
+            [ConfigError::InvalidOsImage { .. }]
+        ));
+        // A session parses the image once, while loading it
+        let mut session = DnxSession::with_observer(bad_os, Arc::new(EventRecorder::default()));
+        assert!(matches!(
+            session.run_with_transport(&MockTransport::new()),
+            Err(SessionError::OsImage(_))
+        ));
+
+        let bad_flags = SessionConfig {
+            gp_flags: 0x8000_0107,
+            ..good.clone()
+        };
+        assert_eq!(
+            errors(&bad_flags),
+            [ConfigError::UnknownGpFlags {
+                flags: 0x8000_0107,
+                unknown: 0x100
+            }]
+        );
+
+        // Problems are reported together, and stop the session before it starts
+        let mut session = DnxSession::with_observer(
+            SessionConfig {
+                gp_flags: 0x100,
+                os_dnx_path: Some(missing),
+                ..good
+            },
+            Arc::new(EventRecorder::default()),
+        );
+        let err = session
+            .run_with_transport(&MockTransport::new())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SessionError::InvalidConfig(found) if found.len() == 2
+        ));
+    }
+
+    #[test]
+    fn test_step_drives_single_transitions() {
+        let dir = TempDir::new("step");
+        let os_only = os_only_config(&dir);
+        let config = SessionConfig {
+            operation: Some(Operation::FwThenOs),
+            fw_dnx_path: os_only.os_dnx_path.clone(),
+            ..os_only
+        };
+        let observer = Arc::new(EventRecorder::default());
+        let mut session = DnxSession::with_observer(config, observer.clone());
+        session.load_files().unwrap();
+
+        let mock = MockTransport::new();
+        let mut state = session.new_state().unwrap();
+        assert_eq!(state.operation, Operation::FwThenOs);
+
+        let result = session
+            .step(&mock, &mut state, &AckCode::from_u32(BULK_ACK_DFRM))
+            .unwrap();
+        assert!(matches!(result, HandleResult::Continue));
+        assert_eq!(state.state, crate::state::DldrState::FwNormal);
+        assert!(mock.get_writes().is_empty());
+        assert_eq!(
+            phases(&observer),
+            [(DnxPhase::WaitingForDevice, DnxPhase::FirmwareDownload)]
+        );
+
+        let result = session
+            .step(&mock, &mut state, &AckCode::from_u32(BULK_ACK_DONE))
+            .unwrap();
+        assert!(matches!(result, HandleResult::Complete));
+        assert!(state.os_done);
+    }
+}
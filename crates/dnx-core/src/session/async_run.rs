@@ -0,0 +1,179 @@
+//! Async session runner, behind the `async` feature.
+//!
+//! Mirrors `run_with_summary`, but ACK reads use nusb's native async
+//! transfers and every wait is a Tokio timer, so the session yields to the
+//! runtime while the device is busy and can be cancelled by dropping the
+//! future (e.g. from `tokio::select!` or `tokio::time::timeout`).
+
+use super::*;
+use crate::transport::traits::reassemble_ack_async;
+
+impl<O: DnxObserver + 'static> DnxSession<O> {
+    /// Async `run_with_summary`. Needs a Tokio runtime with timers enabled.
+    ///
+    /// Waiting for the device and for each ACK awaits; the handlers' payload
+    /// writes and observer calls still run synchronously between awaits.
+    #[instrument(skip(self))]
+    pub async fn run_async(&mut self) -> Result<SessionSummary, SessionError> {
+        if self.config.dry_run {
+            return Ok(self.dry_run()?);
+        }
+        let start = std::time::Instant::now();
+        let mut state = self.prepare()?;
+        let mut reenumerating = false;
+        let mut summary = SessionSummary {
+            operation: state.operation,
+            device: None,
+            reenumerations: 0,
+            elapsed: Duration::ZERO,
+        };
+
+        loop {
+            if reenumerating {
+                self.enter_phase(&mut state, DnxPhase::WaitingForDevice);
+            }
+
+            let transport = match self.config.expected_reenumerated_pid {
+                Some(pid) if reenumerating => {
+                    info!(
+                        expected_pid = format!("{:04X}", pid),
+                        "Waiting for re-enumerated device..."
+                    );
+                    self.poll_until_open_async(
+                        &mut DeviceWait::new(&self.config),
+                        self.reenumerated_opener(pid),
+                    )
+                    .await?
+                }
+                _ if reenumerating => {
+                    self.wait_for_selected_async(self.reenumerated_selector())
+                        .await?
+                }
+                _ => {
+                    self.wait_for_selected_async(self.config.device_selector.as_ref())
+                        .await?
+                }
+            }
+            .with_write_queue_depth(self.config.write_queue_depth);
+
+            self.observer.on_event(&DnxEvent::DeviceConnected {
+                vid: transport.vendor_id(),
+                pid: transport.product_id(),
+            });
+            summary.device = Some((transport.vendor_id(), transport.product_id()));
+
+            let obs_transport = ObservableTransport {
+                inner: &transport,
+                observer: &self.observer,
+                capture_len: self.packet_capture_len(),
+            };
+
+            match self
+                .run_state_machine_async(&obs_transport, &mut state)
+                .await?
+            {
+                HandleResult::NeedReEnumerate if state.is_complete() => {
+                    info!("Requested operation complete, not waiting for re-enumeration");
+                    break;
+                }
+                HandleResult::NeedReEnumerate => {
+                    info!("Device resetting, waiting for re-enumeration...");
+                    state.reset_transfers();
+                    self.reset_device(&transport);
+                    drop(transport);
+                    tokio::time::sleep(RESET_SETTLE).await;
+                    reenumerating = true;
+                    summary.reenumerations += 1;
+                }
+                _ => break,
+            }
+        }
+
+        self.finish(&mut state);
+        summary.elapsed = start.elapsed();
+        Ok(summary)
+    }
+
+    async fn wait_for_selected_async(
+        &self,
+        selector: Option<&DeviceSelector>,
+    ) -> Result<DeviceTransport, WaitError> {
+        match selector {
+            Some(selector) => info!("Waiting for device ({})...", selector),
+            None => info!("Waiting for device..."),
+        }
+        let mut wait = DeviceWait::new(&self.config);
+        let t = self
+            .poll_until_open_async(&mut wait, self.selected_opener(selector))
+            .await?;
+        Ok(self.opened_device(t, &wait))
+    }
+
+    /// Async `poll_until_open`, sleeping on a Tokio timer between polls.
+    async fn poll_until_open_async(
+        &self,
+        wait: &mut DeviceWait,
+        mut open: impl FnMut() -> Result<DeviceTransport, TransportError>,
+    ) -> Result<DeviceTransport, WaitError> {
+        loop {
+            if let Some(t) = self.poll_once(wait, &mut open)? {
+                return Ok(t);
+            }
+            tokio::time::sleep(DEVICE_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn run_state_machine_async<'a>(
+        &self,
+        transport: &ObservableTransport<'a, DeviceTransport, O>,
+        state: &mut StateMachineContext,
+    ) -> Result<HandleResult> {
+        self.start_state_machine(transport, state)?;
+        let (max_read_retries, read_timeout) = self.read_limits();
+        let mut read_retries = 0u32;
+
+        loop {
+            if self.cancel.is_cancelled() {
+                self.observer.on_event(&DnxEvent::Cancelled);
+                self.save_checkpoint(state, true);
+                return Err(SessionError::Cancelled.into());
+            }
+
+            let ack = match transport.read_ack_async(read_timeout).await {
+                Ok(a) => {
+                    read_retries = 0;
+                    a
+                }
+                Err(TransportError::Disconnected) => {
+                    self.observer.on_event(&DnxEvent::DeviceDisconnected);
+                    warn!("Device disconnected");
+                    return Ok(HandleResult::NeedReEnumerate);
+                }
+                Err(e) => {
+                    read_retries += 1;
+                    let delay = self.read_retry_delay(state, read_retries, max_read_retries, e)?;
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            };
+
+            let result = self.step(transport, state, &ack)?;
+            if let Some(done) = self.after_step(state, &ack, result) {
+                return done;
+            }
+        }
+    }
+}
+
+impl<O: DnxObserver> ObservableTransport<'_, DeviceTransport, O> {
+    /// Read one ACK asynchronously, reporting each read as a packet.
+    async fn read_ack_async(&self, timeout: Duration) -> Result<AckCode, TransportError> {
+        let bytes = reassemble_ack_async(|| async move {
+            let res = self.inner.read_async(512, timeout).await;
+            self.observe_read(&res);
+            res
+        })
+        .await?;
+        Ok(AckCode::from_bytes(&bytes))
+    }
+}
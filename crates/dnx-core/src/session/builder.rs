@@ -0,0 +1,240 @@
+//! Fluent construction of a `SessionConfig`.
+
+use std::time::Duration;
+
+use super::{ErrorPolicy, Operation, Preamble, SessionConfig};
+use crate::transport::{DeviceSelector, UsbBackend};
+
+/// Builds a [`SessionConfig`] one setting at a time.
+///
+/// Unset settings keep their `SessionConfig::default()` values, so a
+/// builder with nothing set builds the default config.
+///
+/// ```
+/// use std::time::Duration;
+/// use dnx_core::session::SessionConfig;
+///
+/// let config = SessionConfig::builder()
+///     .fw_dnx("dnx_fwr.bin")
+///     .os_image("dnx_osr.img")
+///     .retry_timeout(Duration::from_secs(120))
+///     .build();
+/// assert_eq!(config.retry_timeout_secs, 120);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct SessionConfigBuilder {
+    config: SessionConfig,
+}
+
+impl From<SessionConfig> for SessionConfigBuilder {
+    /// Start from an existing config, e.g. one loaded from a file.
+    fn from(config: SessionConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl SessionConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// FW DnX binary.
+    pub fn fw_dnx(mut self, path: impl Into<String>) -> Self {
+        self.config.fw_dnx_path = Some(path.into());
+        self
+    }
+
+    /// FW image (IFWI).
+    pub fn fw_image(mut self, path: impl Into<String>) -> Self {
+        self.config.fw_image_path = Some(path.into());
+        self
+    }
+
+    /// OS DnX binary.
+    pub fn os_dnx(mut self, path: impl Into<String>) -> Self {
+        self.config.os_dnx_path = Some(path.into());
+        self
+    }
+
+    /// OS image.
+    pub fn os_image(mut self, path: impl Into<String>) -> Self {
+        self.config.os_image_path = Some(path.into());
+        self
+    }
+
+    /// Misc DnX binary.
+    pub fn misc_dnx(mut self, path: impl Into<String>) -> Self {
+        self.config.misc_dnx_path = Some(path.into());
+        self
+    }
+
+    pub fn gp_flags(mut self, flags: u32) -> Self {
+        self.config.gp_flags = flags;
+        self
+    }
+
+    pub fn ifwi_wipe(mut self, enable: bool) -> Self {
+        self.config.ifwi_wipe_enable = enable;
+        self
+    }
+
+    /// How long to wait for the device, in whole seconds.
+    pub fn retry_timeout(mut self, timeout: Duration) -> Self {
+        self.config.retry_timeout_secs = timeout.as_secs();
+        self
+    }
+
+    /// Chunk size for IFWI/OS transfers.
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.config.chunk_size = bytes;
+        self
+    }
+
+    /// Operation to run instead of inferring it from the inputs.
+    pub fn operation(mut self, operation: Operation) -> Self {
+        self.config.operation = Some(operation);
+        self
+    }
+
+    /// Open exactly this VID/PID.
+    pub fn device_filter(mut self, vid: u16, pid: u16) -> Self {
+        self.config.device_filter = Some((vid, pid));
+        self
+    }
+
+    /// Which device to use when several are attached.
+    pub fn device_selector(mut self, selector: DeviceSelector) -> Self {
+        self.config.device_selector = Some(selector);
+        self
+    }
+
+    pub fn interface_hint(mut self, interface: u8) -> Self {
+        self.config.interface_hint = Some(interface);
+        self
+    }
+
+    pub fn backend(mut self, backend: UsbBackend) -> Self {
+        self.config.backend = backend;
+        self
+    }
+
+    /// PID the device must come back with after a reset.
+    pub fn expected_reenumerated_pid(mut self, pid: u16) -> Self {
+        self.config.expected_reenumerated_pid = Some(pid);
+        self
+    }
+
+    /// OS partitions to flash, by OSIP index.
+    pub fn os_partitions(mut self, partitions: Vec<usize>) -> Self {
+        self.config.os_partitions = Some(partitions);
+        self
+    }
+
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.config.error_policy = policy;
+        self
+    }
+
+    pub fn max_read_retries(mut self, retries: u32) -> Self {
+        self.config.max_read_retries = retries;
+        self
+    }
+
+    /// Time to wait for each ACK, in whole milliseconds.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.config.read_timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    pub fn write_queue_depth(mut self, depth: usize) -> Self {
+        self.config.write_queue_depth = depth;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    pub fn strict_os_validation(mut self, strict: bool) -> Self {
+        self.config.strict_os_validation = strict;
+        self
+    }
+
+    pub fn packet_capture_len(mut self, len: usize) -> Self {
+        self.config.packet_capture_len = Some(len);
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.config.dry_run = dry_run;
+        self
+    }
+
+    /// Preambles sent to open the handshake.
+    pub fn handshake(mut self, preambles: Vec<Preamble>) -> Self {
+        self.config.handshake = preambles;
+        self
+    }
+
+    pub fn resume_os(mut self, resume: bool) -> Self {
+        self.config.resume_os = resume;
+        self
+    }
+
+    /// File to keep a progress [`Checkpoint`](super::Checkpoint) in.
+    pub fn checkpoint_path(mut self, path: impl Into<String>) -> Self {
+        self.config.checkpoint_path = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> SessionConfig {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        let built = SessionConfigBuilder::new().build();
+        let default = SessionConfig::default();
+        assert_eq!(
+            toml::to_string(&built).unwrap(),
+            toml::to_string(&default).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_builder_overrides() {
+        let config = SessionConfig::builder()
+            .fw_dnx("fw_dnx.bin")
+            .fw_image("ifwi.bin")
+            .os_image("os.img")
+            .gp_flags(0x80)
+            .ifwi_wipe(true)
+            .retry_timeout(Duration::from_secs(90))
+            .read_timeout(Duration::from_millis(1500))
+            .device_filter(0x8086, 0xE005)
+            .operation(Operation::FwThenOs)
+            .build();
+
+        assert_eq!(config.fw_dnx_path.as_deref(), Some("fw_dnx.bin"));
+        assert_eq!(config.fw_image_path.as_deref(), Some("ifwi.bin"));
+        assert_eq!(config.os_image_path.as_deref(), Some("os.img"));
+        assert_eq!(config.os_dnx_path, None);
+        assert_eq!(config.gp_flags, 0x80);
+        assert!(config.ifwi_wipe_enable);
+        assert_eq!(config.retry_timeout_secs, 90);
+        assert_eq!(config.read_timeout_ms, 1500);
+        assert_eq!(config.device_filter, Some((0x8086, 0xE005)));
+        assert_eq!(config.operation, Some(Operation::FwThenOs));
+
+        // Starting from a config keeps what the builder does not touch
+        let rebuilt = SessionConfigBuilder::from(config).gp_flags(0).build();
+        assert_eq!(rebuilt.gp_flags, 0);
+        assert_eq!(rebuilt.retry_timeout_secs, 90);
+    }
+}
@@ -0,0 +1,150 @@
+//! Checkpoint file recording how far a session got.
+//!
+//! Written to `SessionConfig::checkpoint_path` while the session runs and
+//! once more when it ends, so an interrupted session leaves a record behind.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::SessionConfig;
+use crate::events::DnxPhase;
+use crate::state::machine::{Operation, StateMachineContext};
+
+/// Snapshot of a session's progress, stored as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Operation the session ran.
+    pub operation: Operation,
+    /// Input files the session ran with.
+    pub fw_dnx_path: Option<String>,
+    pub fw_image_path: Option<String>,
+    pub os_dnx_path: Option<String>,
+    pub os_image_path: Option<String>,
+    /// Phase at the time of the snapshot.
+    pub phase: DnxPhase,
+    /// Payload bytes written, and planned, across all phases.
+    pub bytes_sent: u64,
+    pub bytes_total: u64,
+    /// Payload bytes written per component (e.g. `"OS Image"`).
+    pub components: BTreeMap<String, u64>,
+    /// Last ACK received, as ASCII.
+    pub last_ack: Option<String>,
+    /// Unix time of the snapshot, in milliseconds.
+    pub timestamp_ms: u64,
+}
+
+impl Checkpoint {
+    /// Snapshot `state` for a session running `config`.
+    pub fn capture(config: &SessionConfig, state: &StateMachineContext) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Self {
+            operation: state.operation,
+            fw_dnx_path: config.fw_dnx_path.clone(),
+            fw_image_path: config.fw_image_path.clone(),
+            os_dnx_path: config.os_dnx_path.clone(),
+            os_image_path: config.os_image_path.clone(),
+            phase: state.phase,
+            bytes_sent: state.progress.bytes_sent,
+            bytes_total: state.progress.bytes_total,
+            components: state.component_bytes.clone(),
+            last_ack: state.last_ack.map(|ack| ack.as_ascii()),
+            timestamp_ms,
+        }
+    }
+
+    /// Whether this checkpoint was written by a session with the same
+    /// operation and inputs as `config`.
+    pub fn matches(&self, config: &SessionConfig) -> bool {
+        config.validate_operation().ok() == Some(self.operation)
+            && self.fw_dnx_path == config.fw_dnx_path
+            && self.fw_image_path == config.fw_image_path
+            && self.os_dnx_path == config.os_dnx_path
+            && self.os_image_path == config.os_image_path
+    }
+
+    /// Read the checkpoint at `path`, if there is one.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let checkpoint = serde_json::from_str(&text)
+            .with_context(|| format!("Invalid checkpoint {}", path.display()))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Write to `path` atomically: a temporary file next to it, then a rename,
+    /// so readers never see a half-written checkpoint.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&tmp, json)
+            .with_context(|| format!("Failed to write {}", Path::new(&tmp).display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_matches_inputs() {
+        let config = SessionConfig {
+            os_image_path: Some("os.img".into()),
+            operation: Some(Operation::OsOnly),
+            ..Default::default()
+        };
+        let checkpoint = Checkpoint::capture(
+            &config,
+            &StateMachineContext {
+                operation: Operation::OsOnly,
+                ..Default::default()
+            },
+        );
+        assert!(checkpoint.matches(&config));
+
+        let other_image = SessionConfig {
+            os_image_path: Some("other.img".into()),
+            ..config.clone()
+        };
+        assert!(!checkpoint.matches(&other_image));
+        let other_operation = SessionConfig {
+            fw_dnx_path: Some("fw.bin".into()),
+            operation: Some(Operation::FwThenOs),
+            ..config
+        };
+        assert!(!checkpoint.matches(&other_operation));
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("dnx-checkpoint-rt-{}.json", std::process::id()));
+        assert_eq!(Checkpoint::load(&path).unwrap(), None);
+
+        let mut state = StateMachineContext::default();
+        state.component_bytes.insert("OS Image".into(), 4096);
+        state.last_ack = Some(crate::protocol::AckCode::from_bytes(b"RIMG"));
+        let checkpoint = Checkpoint::capture(&SessionConfig::default(), &state);
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Some(checkpoint));
+    }
+}
@@ -0,0 +1,37 @@
+//! Human-readable byte sizes.
+
+const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+
+/// Format `bytes` with binary (1024) units: `512 B`, `1.50 KB`, `2.00 MB`.
+///
+/// Values below 1 KB are shown as whole bytes; larger values use two
+/// decimals in the largest unit that keeps the number at or above 1.
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size_boundaries() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.00 KB");
+        assert_eq!(format_size(1536), "1.50 KB");
+        assert_eq!(format_size(1024 * 1024 - 1), "1024.00 KB");
+        assert_eq!(format_size(1024 * 1024), "1.00 MB");
+        assert_eq!(format_size(1024 * 1024 * 1024), "1.00 GB");
+        assert_eq!(format_size(2048 * 1024 * 1024 * 1024), "2048.00 GB");
+    }
+}
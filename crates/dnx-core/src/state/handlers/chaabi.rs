@@ -1,26 +1,17 @@
 //! Chaabi firmware helper functions.
 
+use crate::util::first_positions;
+
 /// Helper to find Chaabi range in DnX binary.
 /// Returns (start, end) offsets for the Token+FW section (NOT including CDPH).
 pub fn find_chaabi_range(data: &[u8]) -> Option<(usize, usize)> {
-    let ch00_magic = b"CH00";
-    let cdph_magic = b"CDPH";
-    let dtkn_magic = b"DTKN";
-
-    let find = |needle: &[u8]| -> Option<usize> {
-        data.windows(needle.len())
-            .position(|window| window == needle)
-    };
-
-    let ch00_pos = find(ch00_magic)?;
-    let cdph_pos = find(cdph_magic)?;
+    let [ch00, cdph, dtkn] = first_positions(data, [b"CH00", b"CDPH", b"DTKN"]);
+    let ch00_pos = ch00?;
+    let cdph_pos = cdph?;
 
-    // Token+FW start: CH00 - 0x80, or DTKN if found
+    // Token+FW start: CH00 - 0x80, or DTKN if found before CH00
     let mut start = ch00_pos.checked_sub(0x80)?;
-    if let Some(dtkn_pos) = data[..ch00_pos]
-        .windows(dtkn_magic.len())
-        .position(|w| w == dtkn_magic)
-    {
+    if let Some(dtkn_pos) = dtkn.filter(|&pos| pos + 4 <= ch00_pos) {
         start = dtkn_pos;
     }
 
@@ -41,19 +32,11 @@ pub fn find_chaabi_range(data: &[u8]) -> Option<(usize, usize)> {
 /// **NOTE**: This file has 488 extra bytes after CDPH, so we use magic string positions
 /// instead of xFSTK's (file_size - token - fw - 24) calculation.
 pub fn build_chaabi_payload(data: &[u8]) -> Option<Vec<u8>> {
-    let ch00_magic = b"CH00";
-    let cdph_magic = b"CDPH";
-    let dtkn_magic = b"DTKN";
-    let cht_magic = b"$CHT"; // TNG A0
-    let chpr_magic = b"ChPr"; // TNG B0/ANN
-
-    let find = |needle: &[u8]| -> Option<usize> {
-        data.windows(needle.len())
-            .position(|window| window == needle)
-    };
-
-    let ch00_pos = find(ch00_magic)?;
-    let cdph_pos = find(cdph_magic)?;
+    // $CHT marks TNG A0 tokens, ChPr TNG B0/ANN ones
+    let [ch00, cdph, dtkn, cht, chpr] =
+        first_positions(data, [b"CH00", b"CDPH", b"DTKN", b"$CHT", b"ChPr"]);
+    let ch00_pos = ch00?;
+    let cdph_pos = cdph?;
     let file_size = data.len();
 
     // Calculate CH00 adjusted position (used for fallback)
@@ -61,14 +44,14 @@ pub fn build_chaabi_payload(data: &[u8]) -> Option<Vec<u8>> {
 
     // Determine Token+FW start position based on magic string markers
     // Priority: DTKN > $CHT > ChPr > CH00-0x80
-    let token_fw_start = if let Some(dtkn_pos) = find(dtkn_magic) {
+    let token_fw_start = if let Some(dtkn_pos) = dtkn {
         if dtkn_pos < ch00_pos {
             tracing::info!("Using DTKN marker at 0x{:x} for Token start", dtkn_pos);
             dtkn_pos
         } else {
             ch00_adjusted
         }
-    } else if let Some(cht_pos) = find(cht_magic) {
+    } else if let Some(cht_pos) = cht {
         if cht_pos < ch00_pos {
             let start = cht_pos.checked_sub(0x80)?;
             tracing::info!(
@@ -80,7 +63,7 @@ pub fn build_chaabi_payload(data: &[u8]) -> Option<Vec<u8>> {
         } else {
             ch00_adjusted
         }
-    } else if let Some(chpr_pos) = find(chpr_magic) {
+    } else if let Some(chpr_pos) = chpr {
         if chpr_pos < ch00_pos {
             tracing::info!("Using ChPr marker at 0x{:x} for Token start", chpr_pos);
             chpr_pos
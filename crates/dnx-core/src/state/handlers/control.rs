@@ -1,6 +1,7 @@
-//! Control flow handlers (reset, halt, done).
+//! Control flow handlers (reset, halt, done, platform identity).
 
 use crate::events::{DnxEvent, DnxObserver, LogLevel};
+use crate::protocol::Platform;
 use crate::transport::UsbTransport;
 use anyhow::Result;
 use tracing::info;
@@ -46,6 +47,16 @@ pub fn handle_done<T: UsbTransport, O: DnxObserver>(
     info!("DONE: All operations complete");
     ctx.log(LogLevel::Info, "All operations complete");
     ctx.state.os_done = true;
-    ctx.emit(DnxEvent::Complete);
     Ok(HandleResult::Complete)
 }
+
+/// MFLD/CLVT - Platform identity.
+pub fn handle_platform<T: UsbTransport, O: DnxObserver>(
+    ctx: &mut HandlerContext<'_, T, O>,
+    platform: Platform,
+) -> Result<HandleResult> {
+    info!("Device reports platform {}", platform);
+    ctx.state.platform = Some(platform);
+    ctx.emit(DnxEvent::PlatformDetected { platform });
+    Ok(HandleResult::Continue)
+}
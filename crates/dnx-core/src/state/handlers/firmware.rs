@@ -1,13 +1,15 @@
 //! Firmware download handlers (DFRM, DxxM, DCFI, DIFWI, DXBL, RUPH, DMIP, LOFW, HIFW).
 
 use crate::events::{DnxEvent, DnxObserver, DnxPhase, LogLevel};
-use crate::state::machine::DldrState;
+use crate::protocol::DnxHeader;
+use crate::protocol::GpFlags;
+use crate::state::machine::{DldrState, StateMachineContext};
 use crate::transport::UsbTransport;
 use anyhow::Result;
 use tracing::{debug, info, warn};
 
 use super::chaabi::{build_chaabi_payload, find_chaabi_range};
-use super::{HandleResult, HandlerContext};
+use super::{HandleResult, HandlerContext, missing_input};
 
 /// DFRM - Virgin part DnX.
 pub fn handle_dfrm<T: UsbTransport, O: DnxObserver>(
@@ -26,10 +28,7 @@ pub fn handle_dfrm<T: UsbTransport, O: DnxObserver>(
         return Ok(HandleResult::FwDone);
     }
 
-    ctx.emit(DnxEvent::PhaseChanged {
-        from: DnxPhase::Handshake,
-        to: DnxPhase::FirmwareDownload,
-    });
+    ctx.enter_phase(DnxPhase::FirmwareDownload);
     ctx.state.goto_state(DldrState::FwNormal);
     Ok(HandleResult::Continue)
 }
@@ -41,7 +40,7 @@ pub fn handle_dxxm<T: UsbTransport, O: DnxObserver>(
     info!("DxxM: Non-virgin part detected");
     ctx.log(LogLevel::Info, "Non-virgin part detected");
 
-    let is_dnx_os = (ctx.state.gp_flags & 0x20) != 0;
+    let is_dnx_os = GpFlags::from_u32(ctx.state.gp_flags).contains(GpFlags::DNX_OS);
 
     if ctx.state.ifwi_wipe_enable {
         ctx.state.goto_state(DldrState::FwWipe);
@@ -51,35 +50,24 @@ pub fn handle_dxxm<T: UsbTransport, O: DnxObserver>(
         ctx.state.goto_state(DldrState::FwNormal);
     }
 
-    ctx.emit(DnxEvent::PhaseChanged {
-        from: DnxPhase::Handshake,
-        to: DnxPhase::FirmwareDownload,
-    });
+    ctx.enter_phase(DnxPhase::FirmwareDownload);
 
     // Critical fix for Non-Virgin devices (like Z3580 Moorefield):
     // Based on xFSTK's EmmcFW::InitDnxHdr logic, the device expects a dynamic 24-byte header:
-    // [0..4]   - File Size (u32 LE)
-    // [4..8]   - GP Flags (u32 LE)
-    // [8..20]  - Reserved (3x u32 LE, all 0)
-    // [20..24] - Checksum (u32 LE) = File Size ^ GP Flags
+    // size, GP flags, 3 reserved words, size ^ GP flags (see DnxHeader)
     if let Some(dnx_data) = ctx.fw_dnx_data {
-        let file_size = dnx_data.len() as u32;
-        let gp_flags = ctx.state.gp_flags;
-        let checksum = file_size ^ gp_flags;
-
-        let mut header = [0u8; 24];
-        header[0..4].copy_from_slice(&file_size.to_le_bytes());
-        header[4..8].copy_from_slice(&gp_flags.to_le_bytes());
-        // 8..12, 12..16, 16..20 are 0 (already zeroed by initiation)
-        header[20..24].copy_from_slice(&checksum.to_le_bytes());
+        let header = DnxHeader::new(dnx_data.len() as u32, ctx.state.gp_flags);
 
         info!(
-            "DxxM: Sending dynamic DnX header (Size: {}, GP: 0x{:08X}, CS: 0x{:08X})",
-            file_size, gp_flags, checksum
+            "DxxM: Sending dynamic DnX header (Size: {}, GP: 0x{:08X} [{}], CS: 0x{:08X})",
+            header.size,
+            header.gp_flags,
+            GpFlags::from_u32(header.gp_flags),
+            header.checksum
         );
-        ctx.transport.write(&header)?;
+        ctx.send("DnX Header", &header.to_bytes())?;
     } else {
-        warn!("DxxM: No FW DnX data available to construct header!");
+        return missing_input(ctx, "the DnX header (DxxM)", "FW DnX");
     }
 
     Ok(HandleResult::Continue)
@@ -100,7 +88,7 @@ pub fn handle_dcfi00<T: UsbTransport, O: DnxObserver>(
                 LogLevel::Info,
                 format!("Sending Chaabi FW: {} bytes", chaabi_payload.len()),
             );
-            ctx.transport.write(&chaabi_payload)?;
+            ctx.send("Chaabi FW", &chaabi_payload)?;
             ctx.emit(DnxEvent::Progress {
                 phase: DnxPhase::FirmwareDownload,
                 operation: "Chaabi FW".to_string(),
@@ -111,11 +99,10 @@ pub fn handle_dcfi00<T: UsbTransport, O: DnxObserver>(
 
             // Prepare IFWI state for next phase
             // IFWI is everything BEFORE the Token+FW section.
-            // Use find_chaabi_range to get the start offset.
-            if let Some((chaabi_start, _)) = find_chaabi_range(dnx_data) {
+            if let Some((chaabi_start, _)) = chaabi_range(ctx.state, dnx_data) {
                 let ifwi_len = chaabi_start;
                 ctx.state.ifwi_state =
-                    crate::payload::ChunkState::new(ifwi_len, crate::protocol::constants::ONE28_K);
+                    crate::payload::ChunkState::new(ifwi_len, ctx.state.effective_chunk_size());
                 info!(
                     "Prepared IFWI state: size={} chunks={}",
                     ifwi_len, ctx.state.ifwi_state.total
@@ -129,8 +116,7 @@ pub fn handle_dcfi00<T: UsbTransport, O: DnxObserver>(
             return Ok(HandleResult::Error(msg.to_string()));
         }
     } else {
-        warn!("DCFI00: No FW data available!");
-        ctx.log(LogLevel::Warn, "No FW data available for DCFI00");
+        return missing_input(ctx, "Chaabi FW (DCFI00)", "FW DnX");
     }
 
     Ok(HandleResult::Continue)
@@ -149,21 +135,20 @@ pub fn handle_difwi<T: UsbTransport, O: DnxObserver>(
     if ctx.state.ifwi_state.total == 0 {
         // Not initialized? Try to find boundaries again.
         if let Some(dnx_data) = ctx.fw_dnx_data
-            && let Some((start, _)) = find_chaabi_range(dnx_data)
+            && let Some((start, _)) = chaabi_range(ctx.state, dnx_data)
         {
             let ifwi_len = start;
             ctx.state.ifwi_state =
-                crate::payload::ChunkState::new(ifwi_len, crate::protocol::constants::ONE28_K);
+                crate::payload::ChunkState::new(ifwi_len, ctx.state.effective_chunk_size());
         }
     }
 
     if let Some(dnx_data) = ctx.fw_dnx_data {
-        // Efficient way: re-find range (it's fast)
-        if let Some((chaabi_start, _)) = find_chaabi_range(dnx_data) {
+        if let Some((chaabi_start, _)) = chaabi_range(ctx.state, dnx_data) {
             let ifwi_data = &dnx_data[0..chaabi_start];
 
             if let Some(chunk) = ctx.state.ifwi_state.next_chunk(ifwi_data) {
-                ctx.transport.write(chunk)?;
+                ctx.send("IFWI", chunk)?;
                 ctx.emit(DnxEvent::Progress {
                     phase: DnxPhase::FirmwareDownload,
                     operation: "IFWI".to_string(),
@@ -188,30 +173,31 @@ pub fn handle_difwi<T: UsbTransport, O: DnxObserver>(
 }
 
 /// DXBL - Download Execute Bootloader.
+///
+/// Sends the misc DnX in the DnX OS recovery flow (`FwMisc`), the FW DnX in
+/// the other firmware states and the OS DnX otherwise.
 pub fn handle_dxbl<T: UsbTransport, O: DnxObserver>(
     ctx: &mut HandlerContext<'_, T, O>,
 ) -> Result<HandleResult> {
     info!("DXBL: Sending DnX binary");
     ctx.log(LogLevel::Info, "Sending DnX binary");
 
-    let data = if ctx.state.state.is_fw() {
-        ctx.fw_dnx_data
-    } else {
-        ctx.os_dnx_data
+    let (data, component, input) = match ctx.state.state {
+        DldrState::FwMisc => (ctx.misc_dnx_data, "Misc DnX binary", "misc DnX"),
+        state if state.is_fw() => (ctx.fw_dnx_data, "DnX binary", "FW DnX"),
+        _ => (ctx.os_dnx_data, "DnX binary", "OS DnX"),
     };
 
-    if let Some(dnx_data) = data {
-        ctx.transport.write(dnx_data)?;
-        ctx.emit(DnxEvent::Progress {
-            phase: DnxPhase::FirmwareDownload,
-            operation: "DnX binary".to_string(),
-            current: dnx_data.len() as u64,
-            total: dnx_data.len() as u64,
-        });
-    } else {
-        warn!("No DnX data available for current state");
-        ctx.log(LogLevel::Warn, "No DnX data available");
-    }
+    let Some(dnx_data) = data else {
+        return missing_input(ctx, "the DnX binary (DXBL)", input);
+    };
+    ctx.send(component, dnx_data)?;
+    ctx.emit(DnxEvent::Progress {
+        phase: DnxPhase::FirmwareDownload,
+        operation: component.to_string(),
+        current: dnx_data.len() as u64,
+        total: dnx_data.len() as u64,
+    });
 
     Ok(HandleResult::Continue)
 }
@@ -225,15 +211,18 @@ pub fn handle_ruphs<T: UsbTransport, O: DnxObserver>(
 
     if let Some(fw) = ctx.fw_image {
         let size_bytes = fw.profile_header_size_bytes();
-        ctx.transport.write(&size_bytes)?;
+        ctx.send("Profile Header Size", &size_bytes)?;
         debug!(
             "Sent profile header size: {} bytes",
             u32::from_le_bytes(size_bytes)
         );
     } else {
-        // Fallback to default D0 size
-        let header_size: u32 = crate::protocol::constants::D0_FW_UPDATE_PROFILE_HDR_SIZE as u32;
-        ctx.transport.write(&header_size.to_le_bytes())?;
+        // No image to read it from: go by the reported platform, else D0
+        let header_size = ctx.state.platform.map_or(
+            crate::protocol::constants::D0_FW_UPDATE_PROFILE_HDR_SIZE,
+            |p| p.profile_header_size(),
+        ) as u32;
+        ctx.send("Profile Header Size", &header_size.to_le_bytes())?;
     }
 
     Ok(HandleResult::Continue)
@@ -248,11 +237,10 @@ pub fn handle_ruph<T: UsbTransport, O: DnxObserver>(
 
     if let Some(fw) = ctx.fw_image {
         let header = fw.profile_header_bytes();
-        ctx.transport.write(header)?;
+        ctx.send("Profile Header", header)?;
         debug!("Sent profile header: {} bytes", header.len());
     } else {
-        warn!("No FW image available for RUPH");
-        ctx.log(LogLevel::Warn, "No FW image available");
+        return missing_input(ctx, "the profile header (RUPH)", "FW image");
     }
 
     Ok(HandleResult::Continue)
@@ -265,13 +253,24 @@ pub fn handle_dmip<T: UsbTransport, O: DnxObserver>(
     info!("DMIP: Sending MIP (Module Info Pointer)");
     ctx.log(LogLevel::Debug, "Sending MIP");
 
-    // MIP is typically embedded in the DnX header region
-    // For now, we acknowledge but the actual MIP extraction may need refinement
-    if let Some(fw) = ctx.fw_image {
-        let dnx_header = fw.dnx_header_bytes();
-        ctx.transport.write(dnx_header)?;
-        debug!("Sent DnX header as MIP: {} bytes", dnx_header.len());
-    }
+    let Some(fw) = ctx.fw_image else {
+        return missing_input(ctx, "the MIP (DMIP)", "FW image");
+    };
+    let Some(mip) = fw.mip_bytes() else {
+        let msg = "DMIP: FW image has no FUPH MIP region; not sending a MIP";
+        warn!("{}", msg);
+        ctx.log(LogLevel::Warn, msg);
+        return Ok(HandleResult::Continue);
+    };
+
+    ctx.send("MIP", mip)?;
+    ctx.emit(DnxEvent::Progress {
+        phase: DnxPhase::FirmwareDownload,
+        operation: "MIP".to_string(),
+        current: mip.len() as u64,
+        total: mip.len() as u64,
+    });
+    debug!("Sent MIP: {} bytes", mip.len());
 
     Ok(HandleResult::Continue)
 }
@@ -286,7 +285,7 @@ pub fn handle_lofw<T: UsbTransport, O: DnxObserver>(
     if let Some(fw) = ctx.fw_image {
         let lofw = fw.lofw_bytes();
         if !lofw.is_empty() {
-            ctx.transport.write(lofw)?;
+            ctx.send("LOFW", lofw)?;
             ctx.emit(DnxEvent::Progress {
                 phase: DnxPhase::FirmwareDownload,
                 operation: "LOFW".to_string(),
@@ -298,7 +297,7 @@ pub fn handle_lofw<T: UsbTransport, O: DnxObserver>(
             warn!("LOFW data is empty");
         }
     } else {
-        warn!("No FW image available for LOFW");
+        return missing_input(ctx, "LOFW", "FW image");
     }
 
     Ok(HandleResult::Continue)
@@ -314,7 +313,7 @@ pub fn handle_hifw<T: UsbTransport, O: DnxObserver>(
     if let Some(fw) = ctx.fw_image {
         let hifw = fw.hifw_bytes();
         if !hifw.is_empty() {
-            ctx.transport.write(hifw)?;
+            ctx.send("HIFW", hifw)?;
             ctx.emit(DnxEvent::Progress {
                 phase: DnxPhase::FirmwareDownload,
                 operation: "HIFW".to_string(),
@@ -326,8 +325,235 @@ pub fn handle_hifw<T: UsbTransport, O: DnxObserver>(
             warn!("HIFW data is empty");
         }
     } else {
-        warn!("No FW image available for HIFW");
+        return missing_input(ctx, "HIFW", "FW image");
     }
 
     Ok(HandleResult::Continue)
 }
+
+/// Chaabi range of `dnx_data`, scanned for on first use and then cached in
+/// the state so per-chunk requests don't re-scan the binary.
+fn chaabi_range(state: &mut StateMachineContext, dnx_data: &[u8]) -> Option<(usize, usize)> {
+    if state.chaabi_range.is_none() {
+        state.chaabi_range = find_chaabi_range(dnx_data);
+    }
+    state.chaabi_range
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::NullObserver;
+    use crate::protocol::AckCode;
+    use crate::protocol::constants::BULK_ACK_DIFWI;
+    use crate::state::handlers::handle_ack;
+    use crate::state::handlers::tests::test_ctx;
+    use crate::transport::MockTransport;
+
+    #[test]
+    fn test_dxxm_sends_dnx_header() {
+        let transport = MockTransport::new();
+        let fw_dnx = vec![0u8; 109812];
+        let mut state = StateMachineContext {
+            gp_flags: 0x20,
+            ..Default::default()
+        };
+        let mut ctx = HandlerContext {
+            fw_dnx_data: Some(&fw_dnx),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        handle_dxxm(&mut ctx).unwrap();
+        assert_eq!(ctx.state.state, DldrState::FwMisc);
+
+        let writes = transport.get_writes();
+        assert_eq!(writes.len(), 1);
+        let header = DnxHeader::from_bytes(&writes[0]).unwrap();
+        assert_eq!(header, DnxHeader::new(109812, 0x20));
+        assert_eq!(header.checksum, 109812 ^ 0x20);
+    }
+
+    #[test]
+    fn test_dxxm_branch_follows_dnx_os_flag() {
+        let fw_dnx = vec![0u8; 64];
+        for (flags, expected) in [
+            (GpFlags::empty(), DldrState::FwNormal),
+            (GpFlags::XFSTK_DEFAULT, DldrState::FwNormal),
+            (GpFlags::DNX_OS, DldrState::FwMisc),
+            (GpFlags::XFSTK_DEFAULT | GpFlags::DNX_OS, DldrState::FwMisc),
+        ] {
+            let transport = MockTransport::new();
+            let mut state = StateMachineContext {
+                gp_flags: flags.bits(),
+                ..Default::default()
+            };
+            let mut ctx = HandlerContext {
+                fw_dnx_data: Some(&fw_dnx),
+                ..test_ctx(&transport, &NullObserver, &mut state)
+            };
+            handle_dxxm(&mut ctx).unwrap();
+            assert_eq!(ctx.state.state, expected, "flags {flags}");
+        }
+    }
+
+    #[test]
+    fn test_dnx_os_flow_sends_misc_dnx() {
+        let transport = MockTransport::new();
+        let fw_dnx = vec![0xF0u8; 64];
+        let misc_dnx = vec![0x3Cu8; 48];
+        let mut state = StateMachineContext {
+            gp_flags: GpFlags::DNX_OS.bits(),
+            ..Default::default()
+        };
+        let mut ctx = HandlerContext {
+            fw_dnx_data: Some(&fw_dnx),
+            misc_dnx_data: Some(&misc_dnx),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        handle_dxxm(&mut ctx).unwrap();
+        assert_eq!(ctx.state.state, DldrState::FwMisc);
+        assert!(matches!(
+            handle_dxbl(&mut ctx).unwrap(),
+            HandleResult::Continue
+        ));
+
+        let writes = transport.get_writes();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[1], misc_dnx);
+
+        // Without a misc DnX the flow stops instead of sending the FW DnX
+        ctx.misc_dnx_data = None;
+        let HandleResult::Error(msg) = handle_dxbl(&mut ctx).unwrap() else {
+            panic!("DXBL in FwMisc without a misc DnX should fail");
+        };
+        assert!(msg.contains("misc DnX"));
+        assert_eq!(transport.get_writes().len(), 2);
+    }
+
+    #[test]
+    fn test_missing_inputs_fail_immediately() {
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext::default();
+        let mut ctx = test_ctx(&transport, &NullObserver, &mut state);
+
+        for handler in [
+            handle_dxxm,
+            handle_dcfi00,
+            handle_ruph,
+            handle_lofw,
+            handle_hifw,
+        ] {
+            let result = handler(&mut ctx).unwrap();
+            assert!(matches!(result, HandleResult::Error(_)));
+        }
+        ctx.state.goto_state(DldrState::FwNormal);
+        let HandleResult::Error(msg) = handle_dxbl(&mut ctx).unwrap() else {
+            panic!("DXBL without FW DnX should fail");
+        };
+        assert!(msg.contains("FW DnX"));
+        assert!(transport.get_writes().is_empty());
+    }
+
+    #[test]
+    fn test_dmip_sends_mip_region() {
+        use crate::fuph::{FUPH_HDR_LEN, FUPH_MAGIC};
+        use crate::protocol::constants::ONE28_K;
+        use crate::protocol::header::FwUpdateProfileHeader;
+
+        // MIP (0x3D) and IFWI after the headers, then a FUPH trailer with
+        // only those two sizes (in dwords)
+        let (mip, ifwi) = (0x40, 2 * ONE28_K);
+        let base = DnxHeader::SIZE + FwUpdateProfileHeader::D0_SIZE;
+        let mut data = vec![0u8; base + mip + ifwi + FUPH_HDR_LEN];
+        data[base..base + mip].fill(0x3D);
+        let fuph = base + mip + ifwi;
+        data[fuph..fuph + 4].copy_from_slice(FUPH_MAGIC);
+        data[fuph + 4..fuph + 8].copy_from_slice(&(mip as u32 / 4).to_le_bytes());
+        data[fuph + 8..fuph + 12].copy_from_slice(&(ifwi as u32 / 4).to_le_bytes());
+        let image = crate::payload::FirmwareImage::from_bytes(data).unwrap();
+        // Without a FUPH there is no MIP to send
+        let plain = crate::payload::FirmwareImage::from_bytes(vec![0u8; base + ifwi]).unwrap();
+
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext::default();
+        let mut ctx = HandlerContext {
+            fw_image: Some(&image),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+        handle_dmip(&mut ctx).unwrap();
+        assert_eq!(transport.get_writes(), vec![vec![0x3D; mip]]);
+
+        ctx.fw_image = Some(&plain);
+        let result = handle_dmip(&mut ctx).unwrap();
+        assert!(matches!(result, HandleResult::Continue));
+        assert_eq!(transport.get_writes().len(), 1);
+    }
+
+    #[test]
+    fn test_difwi_scans_for_chaabi_once() {
+        // 16 chunks of IFWI, then a Chaabi block (CH00 at +0x80) and CDPH
+        let mut fw_dnx: Vec<u8> = (0..0x1000).map(|i| (i / 0x100) as u8).collect();
+        let mut chaabi = vec![0xEEu8; 0x100];
+        chaabi[0x80..0x84].copy_from_slice(b"CH00");
+        fw_dnx.extend(chaabi);
+        fw_dnx.extend_from_slice(b"CDPH");
+        // Same IFWI without the markers: a re-scan would find no range
+        let mut unmarked = fw_dnx.clone();
+        unmarked[0x1080..].fill(0);
+
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext {
+            chunk_size: 0x100,
+            ..Default::default()
+        };
+        let mut ctx = HandlerContext {
+            fw_dnx_data: Some(&fw_dnx),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        let difwi = AckCode::from_u64(BULK_ACK_DIFWI);
+        handle_ack(&difwi, &mut ctx).unwrap();
+        assert_eq!(ctx.state.chaabi_range, Some((0x1000, 0x1100)));
+
+        ctx.fw_dnx_data = Some(&unmarked);
+        for _ in 1..16 {
+            handle_ack(&difwi, &mut ctx).unwrap();
+        }
+        assert!(ctx.state.ifwi_state.is_done());
+        assert_eq!(transport.get_writes().concat(), &fw_dnx[..0x1000]);
+    }
+
+    #[test]
+    fn test_ifwi_restarts_after_reset_transfers() {
+        // 0x300 bytes of IFWI, then a Chaabi block (CH00 at +0x80) and CDPH
+        let mut fw_dnx: Vec<u8> = (0..0x300).map(|i| (i / 0x100) as u8).collect();
+        let mut chaabi = vec![0xEEu8; 0x100];
+        chaabi[0x80..0x84].copy_from_slice(b"CH00");
+        fw_dnx.extend(chaabi);
+        fw_dnx.extend_from_slice(b"CDPH");
+
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext {
+            chunk_size: 0x100,
+            ..Default::default()
+        };
+        let mut ctx = HandlerContext {
+            fw_dnx_data: Some(&fw_dnx),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        handle_difwi(&mut ctx).unwrap();
+        handle_difwi(&mut ctx).unwrap();
+        assert_eq!(ctx.state.ifwi_state.offset, 0x200);
+
+        ctx.state.reset_transfers();
+        handle_difwi(&mut ctx).unwrap();
+        assert_eq!(ctx.state.ifwi_state.offset, 0x100);
+
+        let writes = transport.get_writes();
+        assert_eq!(writes.len(), 3);
+        assert_eq!(writes[2], writes[0]);
+        assert_eq!(writes[0], vec![0u8; 0x100]);
+    }
+}
@@ -5,7 +5,7 @@
 //! - `control`: Control flow handlers (reset, halt, done)
 //! - `firmware`: Firmware download handlers
 //! - `os`: OS download handlers
-//! - `security`: Security firmware handlers
+//! - `security`: Security firmware handlers (PSFW, SSFW, SuCP, VEDFW)
 
 mod chaabi;
 mod control;
@@ -13,22 +13,23 @@ mod firmware;
 mod os;
 mod security;
 
-use crate::events::{DnxEvent, DnxObserver, LogLevel};
-use crate::protocol::AckCode;
+use crate::events::{DnxEvent, DnxObserver, DnxPhase, LogLevel};
 use crate::protocol::constants::*;
-use crate::state::machine::StateMachineContext;
+use crate::protocol::{AckCode, Platform};
+use crate::state::machine::{DldrState, SentComponent, StateMachineContext};
 use crate::transport::UsbTransport;
 use anyhow::Result;
+use std::time::Instant;
 use tracing::warn;
 
 // Re-export submodule handlers for internal use
-use control::{handle_done, handle_hlt_success, handle_hlt0, handle_reset};
+use control::{handle_done, handle_hlt_success, handle_hlt0, handle_platform, handle_reset};
 use firmware::{
     handle_dcfi00, handle_dfrm, handle_difwi, handle_dmip, handle_dxbl, handle_dxxm, handle_hifw,
     handle_lofw, handle_ruph, handle_ruphs,
 };
-use os::{handle_dorm, handle_eoiu, handle_rimg, handle_rosip};
-use security::{handle_psfw1, handle_psfw2, handle_ssfw, handle_vedfw};
+use os::{handle_dorm, handle_eoiu, handle_osipsz, handle_rimg, handle_rosip};
+use security::{handle_psfw1, handle_psfw2, handle_ssfw, handle_sucp, handle_vedfw};
 
 /// Result of handling an ACK.
 #[derive(Debug)]
@@ -58,6 +59,8 @@ pub struct HandlerContext<'a, T: UsbTransport, O: DnxObserver> {
     pub fw_image: Option<&'a crate::payload::FirmwareImage>,
     /// OS DnX binary data.
     pub os_dnx_data: Option<&'a [u8]>,
+    /// Misc DnX binary data, sent instead of the FW DnX in `FwMisc`.
+    pub misc_dnx_data: Option<&'a [u8]>,
     /// Parsed OS image.
     pub os_image: Option<&'a crate::payload::OsImage>,
 }
@@ -67,6 +70,37 @@ impl<'a, T: UsbTransport, O: DnxObserver> HandlerContext<'a, T, O> {
         self.observer.on_event(&event);
     }
 
+    /// Write `data`, remember it as the last component sent when the error
+    /// policy may retry it, and report overall progress.
+    pub(crate) fn send(&mut self, component: &str, data: &[u8]) -> Result<()> {
+        self.transport.write(data)?;
+        // Without retryable codes the copy would never be read
+        if !self.state.error_policy.retryable.is_empty() {
+            self.state.last_sent = Some(SentComponent {
+                component: component.to_string(),
+                data: data.to_vec(),
+            });
+        }
+        *self
+            .state
+            .component_bytes
+            .entry(component.to_string())
+            .or_default() += data.len() as u64;
+        let event = self.state.progress.record(data.len());
+        self.emit(event);
+        let progress = self.state.progress;
+        if let Some(event) = self.state.throughput.sample(Instant::now(), &progress) {
+            self.emit(event);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn enter_phase(&mut self, to: DnxPhase) {
+        if let Some(event) = self.state.enter_phase(to) {
+            self.emit(event);
+        }
+    }
+
     pub(crate) fn log(&self, level: LogLevel, message: impl Into<String>) {
         self.emit(DnxEvent::Log {
             level,
@@ -75,6 +109,109 @@ impl<'a, T: UsbTransport, O: DnxObserver> HandlerContext<'a, T, O> {
     }
 }
 
+/// Stop the session when the device asks for an input that was not provided,
+/// instead of writing nothing and stalling.
+fn missing_input<T: UsbTransport, O: DnxObserver>(
+    ctx: &mut HandlerContext<'_, T, O>,
+    request: &str,
+    input: &str,
+) -> Result<HandleResult> {
+    let msg = format!("Device requested {} but no {} was provided", request, input);
+    warn!("{}", msg);
+    ctx.log(LogLevel::Error, msg.clone());
+    Ok(HandleResult::Error(msg))
+}
+
+/// Whether `ack` asks for part of the OS stage. DXBL asks for the OS DnX
+/// outside the firmware states, as in `handle_dxbl`.
+fn is_os_request(ack: &AckCode, state: DldrState) -> bool {
+    (ack.matches_u32(BULK_ACK_DXBL) && !state.is_fw())
+        || [BULK_ACK_ROSIP, BULK_ACK_OSIPSZ]
+            .into_iter()
+            .any(|code| ack.matches_u64(code))
+        || [BULK_ACK_DORM, BULK_ACK_RIMG, BULK_ACK_EOIU]
+            .into_iter()
+            .any(|code| ack.matches_u32(code))
+}
+
+/// Whether `ack` asks for part of the FW stage (FW, IFWI or security firmware).
+/// DXBL asks for the FW or misc DnX in the firmware states.
+fn is_fw_request(ack: &AckCode, state: DldrState) -> bool {
+    (ack.matches_u32(BULK_ACK_DXBL) && state.is_fw())
+        || [
+            BULK_ACK_READY_UPH_SIZE,
+            BULK_ACK_DCFI00,
+            BULK_ACK_DIFWI,
+            BULK_ACK_PSFW1,
+            BULK_ACK_PSFW2,
+            BULK_ACK_VEDFW,
+        ]
+        .into_iter()
+        .any(|code| ack.matches_u64(code))
+        || [
+            BULK_ACK_DFRM,
+            BULK_ACK_DxxM,
+            BULK_ACK_READY_UPH,
+            BULK_ACK_DMIP,
+            BULK_ACK_LOFW,
+            BULK_ACK_HIFW,
+            BULK_ACK_SSFW,
+            BULK_ACK_PATCH,
+        ]
+        .into_iter()
+        .any(|code| ack.matches_u32(code))
+}
+
+/// Stop the session when the device asks for a stage the operation leaves
+/// out, e.g. an OS image during a FW-only session.
+fn outside_operation<T: UsbTransport, O: DnxObserver>(
+    ack: &AckCode,
+    ctx: &mut HandlerContext<'_, T, O>,
+) -> Result<HandleResult> {
+    let msg = format!(
+        "Device requested {} but the operation is {}",
+        ack.as_ascii(),
+        ctx.state.operation
+    );
+    warn!("{}", msg);
+    ctx.log(LogLevel::Error, msg.clone());
+    Ok(HandleResult::Error(msg))
+}
+
+/// Re-send the last component for an error the policy marks retryable.
+///
+/// Returns `None` when the error should abort the session.
+fn retry_last_sent<T: UsbTransport, O: DnxObserver>(
+    ack: &AckCode,
+    ctx: &mut HandlerContext<'_, T, O>,
+) -> Result<Option<HandleResult>> {
+    let code = ack.as_ascii();
+    let policy = &ctx.state.error_policy;
+    if !policy.is_retryable(&code) || ctx.state.retries >= policy.max_retries {
+        return Ok(None);
+    }
+    let Some(last) = ctx.state.last_sent.take() else {
+        return Ok(None);
+    };
+
+    ctx.state.retries += 1;
+    warn!(
+        code = %code,
+        component = %last.component,
+        attempt = ctx.state.retries,
+        "Retrying after device error"
+    );
+    ctx.emit(DnxEvent::Retry {
+        code,
+        component: last.component.clone(),
+        attempt: ctx.state.retries,
+        max_retries: ctx.state.error_policy.max_retries,
+    });
+    ctx.transport.write(&last.data)?;
+    ctx.state.last_sent = Some(last);
+    Ok(Some(HandleResult::Continue))
+}
+
 /// Handle an ACK code and perform the appropriate action.
 pub fn handle_ack<T: UsbTransport, O: DnxObserver>(
     ack: &AckCode,
@@ -86,7 +223,13 @@ pub fn handle_ack<T: UsbTransport, O: DnxObserver>(
 
     // First check for error codes
     if ack.is_error() {
-        let msg = format!("Device error: {}", ack.as_ascii());
+        if let Some(result) = retry_last_sent(ack, ctx)? {
+            return Ok(result);
+        }
+        let msg = match ack.error_description() {
+            Some(description) => format!("Device error: {} ({})", ack.as_ascii(), description),
+            None => format!("Device error: {} (undocumented code)", ack.as_ascii()),
+        };
         ctx.emit(DnxEvent::Error {
             code: ack.value() as u32,
             message: msg.clone(),
@@ -94,7 +237,17 @@ pub fn handle_ack<T: UsbTransport, O: DnxObserver>(
         return Ok(HandleResult::Error(msg));
     }
 
-    // Match 5+ byte ACKs first (to avoid prefix collisions with 4-byte ones)
+    ctx.state.retries = 0;
+
+    let (operation, state) = (ctx.state.operation, ctx.state.state);
+    if (is_os_request(ack, state) && !operation.includes_os())
+        || (is_fw_request(ack, state) && !operation.includes_fw())
+    {
+        return outside_operation(ack, ctx);
+    }
+
+    // Every check compares length too, so RUPHS never matches RUPH and the
+    // order below does not matter
     if ack.matches_u64(BULK_ACK_READY_UPH_SIZE) {
         return handle_ruphs(ctx);
     }
@@ -120,9 +273,7 @@ pub fn handle_ack<T: UsbTransport, O: DnxObserver>(
         return handle_rosip(ctx);
     }
     if ack.matches_u64(BULK_ACK_OSIPSZ) {
-        // Just log it for now
-        ctx.log(LogLevel::Debug, "Received OSIP Sz request");
-        return Ok(HandleResult::Continue);
+        return handle_osipsz(ctx);
     }
 
     // Match 4-byte ACKs
@@ -150,6 +301,9 @@ pub fn handle_ack<T: UsbTransport, O: DnxObserver>(
     if ack.matches_u32(BULK_ACK_SSFW) {
         return handle_ssfw(ctx);
     }
+    if ack.matches_u32(BULK_ACK_PATCH) {
+        return handle_sucp(ctx);
+    }
     if ack.matches_u32(BULK_ACK_UPDATE_SUCCESSFUL) {
         return handle_hlt_success(ctx);
     }
@@ -159,6 +313,12 @@ pub fn handle_ack<T: UsbTransport, O: DnxObserver>(
     if ack.matches_u32(BULK_ACK_DONE) {
         return handle_done(ctx);
     }
+    if ack.matches_u32(BULK_ACK_MFLD) {
+        return handle_platform(ctx, Platform::Medfield);
+    }
+    if ack.matches_u32(BULK_ACK_CLVT) {
+        return handle_platform(ctx, Platform::Clovertrail);
+    }
     if ack.matches_u32(BULK_ACK_DORM) {
         return handle_dorm(ctx);
     }
@@ -169,8 +329,333 @@ pub fn handle_ack<T: UsbTransport, O: DnxObserver>(
         return handle_eoiu(ctx);
     }
 
-    // Unknown ACK
-    warn!(ack = %ack.as_ascii(), "Unhandled ACK code");
-    ctx.log(LogLevel::Warn, format!("Unhandled ACK: {}", ack.as_ascii()));
+    // Unknown here, though it may still be a recognized code (e.g. a
+    // registry ACK with no handler, or a preamble echo)
+    let kind = match ack.known_name() {
+        Some(name) => format!("recognized as {} but not handled", name),
+        None => "not a known code".to_string(),
+    };
+    warn!(ack = %ack.as_ascii(), kind = %kind, "Unhandled ACK code");
+    ctx.log(
+        LogLevel::Warn,
+        format!("Unhandled ACK: {:?} ({})", ack, kind),
+    );
     Ok(HandleResult::Continue)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventRecorder, NullObserver};
+    use crate::state::machine::{ErrorPolicy, Operation};
+    use crate::transport::MockTransport;
+
+    /// Context over `transport`, `observer` and `state` with no inputs loaded.
+    pub(crate) fn test_ctx<'a, O: DnxObserver>(
+        transport: &'a MockTransport,
+        observer: &'a O,
+        state: &'a mut StateMachineContext,
+    ) -> HandlerContext<'a, MockTransport, O> {
+        HandlerContext {
+            transport,
+            observer,
+            state,
+            fw_dnx_data: None,
+            fw_image: None,
+            os_dnx_data: None,
+            misc_dnx_data: None,
+            os_image: None,
+        }
+    }
+
+    fn retry_state(max_retries: u32) -> StateMachineContext {
+        StateMachineContext {
+            error_policy: ErrorPolicy {
+                retryable: vec!["ER04".to_string()],
+                max_retries,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn ack(code: &[u8]) -> AckCode {
+        AckCode::from_bytes(code)
+    }
+
+    #[test]
+    fn test_sucp_ack_sends_rom_patch() {
+        use crate::protocol::header::{DnxHeader, FwUpdateProfileHeader};
+
+        let sucp = 0x40;
+        let base = DnxHeader::SIZE + FwUpdateProfileHeader::D0_SIZE + 2 * ONE28_K;
+        let mut data = vec![0u8; base + sucp];
+        let profile = DnxHeader::SIZE;
+        data[profile + 0x18..profile + 0x1C].copy_from_slice(&(sucp as u32).to_le_bytes());
+        data[base..].fill(0x5C);
+        let image = crate::payload::FirmwareImage::from_bytes(data).unwrap();
+
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext::default();
+        let mut ctx = HandlerContext {
+            fw_image: Some(&image),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        let result = handle_ack(&ack(b"SuCP"), &mut ctx).unwrap();
+        assert!(matches!(result, HandleResult::Continue));
+        assert_eq!(transport.get_writes(), vec![vec![0x5C; sucp]]);
+        assert!(ctx.state.sucp_state.is_done());
+    }
+
+    /// FW image with a FUPH trailer declaring these component sizes: MIP,
+    /// IFWI, PSFW1, PSFW2, SSFW, SuCP and VEDFW.
+    fn fuph_image(sizes: [usize; 7]) -> crate::payload::FirmwareImage {
+        use crate::fuph::{FUPH_HDR_LEN, FUPH_MAGIC};
+        use crate::protocol::header::{DnxHeader, FwUpdateProfileHeader};
+
+        let base = DnxHeader::SIZE + FwUpdateProfileHeader::D0_SIZE;
+        let fuph = base + sizes.iter().sum::<usize>();
+        let mut data = vec![0u8; fuph + FUPH_HDR_LEN];
+        data[fuph..fuph + 4].copy_from_slice(FUPH_MAGIC);
+        for (i, size) in sizes.into_iter().enumerate() {
+            let offset = fuph + 4 + i * 4;
+            data[offset..offset + 4].copy_from_slice(&(size as u32 / 4).to_le_bytes());
+        }
+        crate::payload::FirmwareImage::from_bytes(data).unwrap()
+    }
+
+    #[test]
+    fn test_security_fw_acks_send_their_component() {
+        let image = fuph_image([0, 2 * ONE28_K, 0x100, 0xC0, 0x80, 0, 0x20]);
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext::default();
+        let mut ctx = HandlerContext {
+            fw_image: Some(&image),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        // The chunk states start empty; each handler sizes its own from the
+        // image, or it would find nothing to send
+        for code in [&b"PSFW1"[..], b"PSFW2", b"SSFW", b"VEDFW"] {
+            handle_ack(&ack(code), &mut ctx).unwrap();
+        }
+
+        let sizes: Vec<_> = transport.get_writes().iter().map(Vec::len).collect();
+        assert_eq!(sizes, [0x100, 0xC0, 0x80, 0x20]);
+        assert!(ctx.state.psfw1_state.is_done());
+        assert!(ctx.state.vedfw_state.is_done());
+    }
+
+    #[test]
+    fn test_overall_progress_accumulates_fuph_components() {
+        let image = fuph_image([0x40, 2 * ONE28_K, 0x100, 0, 0x80, 0x40, 0x20]);
+
+        let transport = MockTransport::new();
+        let observer = EventRecorder::default();
+        let mut state = StateMachineContext::default();
+        state.progress.bytes_total = image.total_transfer_size() as u64;
+        let mut ctx = HandlerContext {
+            fw_image: Some(&image),
+            ..test_ctx(&transport, &observer, &mut state)
+        };
+
+        for code in [
+            &b"DMIP"[..],
+            b"RUPH",
+            b"LOFW",
+            b"HIFW",
+            b"PSFW1",
+            b"SSFW",
+            b"SuCP",
+            b"VEDFW",
+        ] {
+            handle_ack(&ack(code), &mut ctx).unwrap();
+        }
+
+        let sent = observer.collect(|e| match e {
+            DnxEvent::OverallProgress { bytes_sent, .. } => Some(*bytes_sent),
+            _ => None,
+        });
+        assert_eq!(sent.len(), 8);
+        assert!(sent.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            ctx.state.progress.bytes_sent,
+            ctx.state.progress.bytes_total
+        );
+        assert_eq!(ctx.state.progress.percent(), 100);
+    }
+
+    #[test]
+    fn test_retryable_error_resends_last_component() {
+        let transport = MockTransport::new();
+        let mut state = retry_state(2);
+        let mut ctx = test_ctx(&transport, &NullObserver, &mut state);
+
+        ctx.send("Chunk", &[1, 2, 3]).unwrap();
+        for _ in 0..2 {
+            let result = handle_ack(&ack(b"ER04"), &mut ctx).unwrap();
+            assert!(matches!(result, HandleResult::Continue));
+        }
+        let result = handle_ack(&ack(b"ER04"), &mut ctx).unwrap();
+        assert!(matches!(result, HandleResult::Error(_)));
+
+        assert_eq!(transport.get_writes(), vec![vec![1, 2, 3]; 3]);
+    }
+
+    #[test]
+    fn test_send_keeps_payload_only_for_retry_policy() {
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext::default();
+        let mut ctx = test_ctx(&transport, &NullObserver, &mut state);
+        ctx.send("Chunk", &[1, 2, 3]).unwrap();
+        assert_eq!(ctx.state.last_sent, None);
+
+        let mut state = retry_state(1);
+        let mut ctx = test_ctx(&transport, &NullObserver, &mut state);
+        ctx.send("Chunk", &[1, 2, 3]).unwrap();
+        assert_eq!(
+            ctx.state.last_sent.as_ref().map(|s| s.data.as_slice()),
+            Some(&[1, 2, 3][..])
+        );
+    }
+
+    #[test]
+    fn test_retry_budget_resets_after_progress() {
+        let transport = MockTransport::new();
+        let mut state = retry_state(1);
+        let mut ctx = test_ctx(&transport, &NullObserver, &mut state);
+
+        ctx.send("Chunk", &[1]).unwrap();
+        handle_ack(&ack(b"ER04"), &mut ctx).unwrap();
+        handle_ack(&ack(b"HLT$"), &mut ctx).unwrap();
+        assert_eq!(ctx.state.retries, 0);
+        let result = handle_ack(&ack(b"ER04"), &mut ctx).unwrap();
+        assert!(matches!(result, HandleResult::Continue));
+    }
+
+    #[test]
+    fn test_non_retryable_error_aborts() {
+        let transport = MockTransport::new();
+        let mut state = retry_state(3);
+        let mut ctx = test_ctx(&transport, &NullObserver, &mut state);
+
+        ctx.send("Chunk", &[1]).unwrap();
+        let result = handle_ack(&ack(b"ER10"), &mut ctx).unwrap();
+        assert!(
+            matches!(result, HandleResult::Error(msg) if msg == "Device error: ER10 (undocumented code)")
+        );
+        assert_eq!(transport.get_writes().len(), 1);
+
+        let result = handle_ack(&ack(b"ER00"), &mut ctx).unwrap();
+        assert!(matches!(
+            result,
+            HandleResult::Error(msg) if msg.ends_with("(invalid ping: the handshake preamble was not recognized)")
+        ));
+    }
+
+    #[test]
+    fn test_platform_ack_picks_profile_header_size() {
+        let transport = MockTransport::new();
+        transport.queue_ack_u32(BULK_ACK_MFLD);
+        transport.queue_ack_u64(BULK_ACK_READY_UPH_SIZE, 5);
+        let observer = EventRecorder::default();
+        let mut state = StateMachineContext::default();
+        let mut ctx = test_ctx(&transport, &observer, &mut state);
+
+        while let Ok(code) = transport.read_ack() {
+            let result = handle_ack(&code, &mut ctx).unwrap();
+            assert!(matches!(result, HandleResult::Continue));
+        }
+        assert_eq!(ctx.state.platform, Some(Platform::Medfield));
+        let detected = observer.collect(|e| match e {
+            DnxEvent::PlatformDetected { platform } => Some(*platform),
+            _ => None,
+        });
+        assert_eq!(detected, [Platform::Medfield]);
+        // Without a FW image, RUPHS announces the old Medfield header size
+        assert_eq!(transport.get_writes(), vec![0x1Cu32.to_le_bytes().to_vec()]);
+    }
+
+    #[test]
+    fn test_undispatched_registry_ack_warns() {
+        let transport = MockTransport::new();
+        let observer = EventRecorder::default();
+        let mut state = StateMachineContext::default();
+        let mut ctx = test_ctx(&transport, &observer, &mut state);
+
+        let result = handle_ack(&AckCode::from_u32(BULK_ACK_RTBD), &mut ctx).unwrap();
+        assert!(matches!(result, HandleResult::Continue));
+        let warnings = observer.collect(|e| match e {
+            DnxEvent::Log {
+                level: LogLevel::Warn,
+                message,
+            } => Some(message.clone()),
+            _ => None,
+        });
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("recognized as RTBD but not handled"));
+    }
+
+    #[test]
+    fn test_requests_outside_the_operation_are_refused() {
+        let fw_dnx = [0xA5u8; 16];
+        for (operation, code) in [
+            (Operation::FwOnly, &b"ROSIP"[..]),
+            (Operation::FwOnly, b"RIMG"),
+            (Operation::OsOnly, b"DFRM"),
+            (Operation::OsOnly, b"PSFW1"),
+        ] {
+            let transport = MockTransport::new();
+            let mut state = StateMachineContext {
+                operation,
+                ..Default::default()
+            };
+            let mut ctx = HandlerContext {
+                fw_dnx_data: Some(&fw_dnx),
+                os_dnx_data: Some(&fw_dnx),
+                ..test_ctx(&transport, &NullObserver, &mut state)
+            };
+
+            let result = handle_ack(&AckCode::from_bytes(code), &mut ctx).unwrap();
+            assert!(
+                matches!(&result, HandleResult::Error(msg) if msg.ends_with(&format!("but the operation is {}", operation))),
+                "{operation} answered {}: {result:?}",
+                String::from_utf8_lossy(code)
+            );
+            assert!(transport.get_writes().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_fw_operations_answer_dxbl_with_the_fw_dnx() {
+        let fw_dnx = [0xA5u8; 16];
+        let os_dnx = [0x5Au8; 16];
+        // DFRM for a FW flash, DxxM (with the IFWI wipe) for a wipe
+        for (operation, wipe, entry) in [
+            (Operation::FwOnly, false, &b"DFRM"[..]),
+            (Operation::Wipe, true, b"DxxM"),
+        ] {
+            let transport = MockTransport::new();
+            let mut state = StateMachineContext {
+                operation,
+                ifwi_wipe_enable: wipe,
+                ..Default::default()
+            };
+            let mut ctx = HandlerContext {
+                fw_dnx_data: Some(&fw_dnx),
+                os_dnx_data: Some(&os_dnx),
+                ..test_ctx(&transport, &NullObserver, &mut state)
+            };
+
+            for code in [entry, b"DXBL"] {
+                let result = handle_ack(&AckCode::from_bytes(code), &mut ctx).unwrap();
+                assert!(
+                    matches!(result, HandleResult::Continue),
+                    "{operation}: {result:?}"
+                );
+            }
+            let writes = transport.get_writes();
+            assert_eq!(writes.last(), Some(&fw_dnx.to_vec()), "{operation}");
+        }
+    }
+}
@@ -1,12 +1,13 @@
-//! OS download handlers (DORM, ROSIP, RIMG, EOIU).
+//! OS download handlers (DORM, OSIP Sz, ROSIP, RIMG, EOIU).
 
 use crate::events::{DnxEvent, DnxObserver, DnxPhase, LogLevel};
-use crate::state::machine::DldrState;
+use crate::payload::OsImage;
+use crate::state::machine::{DldrState, StateMachineContext};
 use crate::transport::UsbTransport;
 use anyhow::Result;
-use tracing::{debug, info, warn};
+use tracing::{debug, info};
 
-use super::{HandleResult, HandlerContext};
+use super::{HandleResult, HandlerContext, missing_input};
 
 /// DORM - OS Recovery Mode.
 pub fn handle_dorm<T: UsbTransport, O: DnxObserver>(
@@ -18,26 +19,67 @@ pub fn handle_dorm<T: UsbTransport, O: DnxObserver>(
     Ok(HandleResult::Continue)
 }
 
+/// OSIP Sz - Device asks for the OSIP partition table size.
+pub fn handle_osipsz<T: UsbTransport, O: DnxObserver>(
+    ctx: &mut HandlerContext<'_, T, O>,
+) -> Result<HandleResult> {
+    info!("OSIP Sz: Sending OSIP size");
+
+    let Some(os) = ctx.os_image else {
+        return missing_input(ctx, "the OSIP size (OSIP Sz)", "OS image");
+    };
+    let size = os.osip_size();
+    ctx.send("OSIP Size", &size.to_le_bytes())?;
+    debug!("Sent OSIP size: {} bytes", size);
+
+    Ok(HandleResult::Continue)
+}
+
 /// ROSIP - Ready for OSIP.
 pub fn handle_rosip<T: UsbTransport, O: DnxObserver>(
     ctx: &mut HandlerContext<'_, T, O>,
 ) -> Result<HandleResult> {
     info!("ROSIP: Sending OSIP data");
+    ctx.enter_phase(DnxPhase::OsDownload);
     ctx.log(LogLevel::Debug, "Sending OSIP partition table");
 
-    if let Some(os) = ctx.os_image {
-        let osip = os.osip_bytes();
-        ctx.transport.write(osip)?;
-        debug!("Sent OSIP: {} bytes", osip.len());
-
-        // Initialize OS image chunk state for subsequent RIMG requests
-        let image_data = os.image_data();
-        ctx.state.os_image_state = crate::payload::OsChunkState::new(
-            image_data.len(),
-            crate::protocol::constants::ONE28_K,
+    let Some(os) = ctx.os_image else {
+        return missing_input(ctx, "the OSIP (ROSIP)", "OS image");
+    };
+    let osip = os.osip_bytes();
+    ctx.send("OSIP", osip)?;
+    debug!("Sent OSIP: {} bytes", osip.len());
+
+    // Initialize OS image chunk state for subsequent RIMG requests
+    if let Some(partitions) = &ctx.state.os_partitions {
+        if let Some(&bad) = partitions.iter().find(|&&i| os.partition(i).is_err()) {
+            return Ok(HandleResult::Error(format!(
+                "OS partition {} not present in image ({} partitions)",
+                bad,
+                os.num_partitions()
+            )));
+        }
+        ctx.log(
+            LogLevel::Info,
+            format!("Flashing OS partitions {:?}", partitions),
         );
+    }
+    if let Some((cursor, chunks)) = ctx.state.os_resume.take() {
+        ctx.log(
+            LogLevel::Info,
+            format!(
+                "Resuming OS image at chunk {}/{}",
+                chunks.current + 1,
+                chunks.total
+            ),
+        );
+        ctx.state.os_partition_cursor = cursor;
+        ctx.state.os_image_state = chunks;
     } else {
-        warn!("No OS image available for ROSIP");
+        ctx.state.os_partition_cursor = 0;
+        let image_data = selected_os_data(os, ctx.state).unwrap_or_default();
+        ctx.state.os_image_state =
+            crate::payload::OsChunkState::new(image_data.len(), ctx.state.effective_chunk_size());
     }
 
     Ok(HandleResult::Continue)
@@ -49,13 +91,38 @@ pub fn handle_rimg<T: UsbTransport, O: DnxObserver>(
 ) -> Result<HandleResult> {
     debug!("RIMG: Sending OS image chunk");
 
+    // Asking for the next chunk acknowledges the previous one; the chunk
+    // about to be sent is not confirmed until the following RIMG
+    if ctx.state.resume_os {
+        ctx.state.os_resume = Some((
+            ctx.state.os_partition_cursor,
+            ctx.state.os_image_state.clone(),
+        ));
+    }
+
     if let Some(os) = ctx.os_image {
-        let image_data = os.image_data();
-        if let Some(chunk) = ctx.state.os_image_state.next_chunk(image_data) {
-            ctx.transport.write(chunk)?;
+        let mut chunk = None;
+        while let Some(data) = selected_os_data(os, ctx.state) {
+            chunk = ctx.state.os_image_state.next_chunk(data);
+            if chunk.is_some() || ctx.state.os_partitions.is_none() {
+                break;
+            }
+            // Selected partition exhausted, move to the next one
+            ctx.state.os_partition_cursor += 1;
+            let next_len = selected_os_data(os, ctx.state).map_or(0, <[u8]>::len);
+            ctx.state.os_image_state =
+                crate::payload::OsChunkState::new(next_len, ctx.state.effective_chunk_size());
+        }
+
+        if let Some(chunk) = chunk {
+            let operation = match &ctx.state.os_partitions {
+                Some(p) => format!("OS Partition {}", p[ctx.state.os_partition_cursor]),
+                None => "OS Image".to_string(),
+            };
+            ctx.send("OS Image", chunk)?;
             ctx.emit(DnxEvent::Progress {
                 phase: DnxPhase::OsDownload,
-                operation: "OS Image".to_string(),
+                operation,
                 current: ctx.state.os_image_state.current as u64,
                 total: ctx.state.os_image_state.total as u64,
             });
@@ -71,11 +138,118 @@ pub fn handle_rimg<T: UsbTransport, O: DnxObserver>(
     Ok(HandleResult::Continue)
 }
 
+/// Data RIMG currently streams: the whole image, or the selected partition.
+fn selected_os_data<'a>(os: &'a OsImage, state: &StateMachineContext) -> Option<&'a [u8]> {
+    match &state.os_partitions {
+        None => Some(os.image_data()),
+        Some(partitions) => {
+            let index = *partitions.get(state.os_partition_cursor)?;
+            os.partition(index).ok()
+        }
+    }
+}
+
 /// EOIU - End of Image Update.
 pub fn handle_eoiu<T: UsbTransport, O: DnxObserver>(
     ctx: &mut HandlerContext<'_, T, O>,
 ) -> Result<HandleResult> {
     info!("EOIU: OS image transfer complete");
+    ctx.state.os_resume = None;
     ctx.log(LogLevel::Info, "OS image transfer complete");
     Ok(HandleResult::Continue)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::NullObserver;
+    use crate::protocol::AckCode;
+    use crate::state::handlers::handle_ack;
+    use crate::state::handlers::tests::test_ctx;
+    use crate::state::machine::Operation;
+    use crate::transport::MockTransport;
+
+    /// OSIP with three sequential partitions of 1, 2 and 3 blocks,
+    /// filled with 1, 2 and 3 respectively.
+    fn three_partition_image() -> OsImage {
+        let blocks = [1u32, 2, 3];
+        let mut data = vec![0u8; 0x200];
+        data[0..4].copy_from_slice(b"$OS$");
+        data[0x08] = blocks.len() as u8;
+        for (i, n) in blocks.iter().enumerate() {
+            let off = 0x30 + i * 0x18;
+            data[off..off + 4].copy_from_slice(&n.to_le_bytes());
+        }
+        for (i, n) in blocks.iter().enumerate() {
+            data.extend(std::iter::repeat_n(i as u8 + 1, *n as usize * 512));
+        }
+        OsImage::from_bytes(data).unwrap()
+    }
+
+    #[test]
+    fn test_osipsz_sends_table_size() {
+        let os = three_partition_image();
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext {
+            operation: Operation::OsOnly,
+            ..Default::default()
+        };
+        let mut ctx = HandlerContext {
+            os_image: Some(&os),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        let result = handle_ack(&AckCode::from_bytes(b"OSIP Sz"), &mut ctx).unwrap();
+        assert!(matches!(result, HandleResult::Continue));
+        assert_eq!(
+            transport.get_writes(),
+            vec![0x200u32.to_le_bytes().to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_osip_requests_need_an_os_image() {
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext {
+            operation: Operation::OsOnly,
+            ..Default::default()
+        };
+        let mut ctx = test_ctx(&transport, &NullObserver, &mut state);
+
+        for code in [&b"OSIP Sz"[..], b"ROSIP"] {
+            let result = handle_ack(&AckCode::from_bytes(code), &mut ctx).unwrap();
+            assert!(
+                matches!(result, HandleResult::Error(msg) if msg.ends_with("but no OS image was provided"))
+            );
+        }
+        assert!(transport.get_writes().is_empty());
+    }
+
+    #[test]
+    fn test_rimg_sends_only_selected_partitions() {
+        let os = three_partition_image();
+        let transport = MockTransport::new();
+        let mut state = StateMachineContext {
+            chunk_size: 512,
+            os_partitions: Some(vec![0, 2]),
+            ..Default::default()
+        };
+        let mut ctx = HandlerContext {
+            os_image: Some(&os),
+            ..test_ctx(&transport, &NullObserver, &mut state)
+        };
+
+        handle_rosip(&mut ctx).unwrap();
+        for _ in 0..6 {
+            handle_rimg(&mut ctx).unwrap();
+        }
+
+        let writes = transport.get_writes();
+        assert_eq!(writes[0].len(), 0x200);
+        let sent: Vec<u8> = writes[1..].concat();
+        let mut expected = vec![1u8; 512];
+        expected.extend(vec![3u8; 3 * 512]);
+        assert_eq!(writes.len(), 1 + 1 + 3);
+        assert_eq!(sent, expected);
+    }
+}
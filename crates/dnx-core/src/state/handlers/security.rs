@@ -1,6 +1,8 @@
-//! Security firmware handlers (PSFW, SSFW, VEDFW).
+//! Security firmware handlers (PSFW, SSFW, SuCP, VEDFW).
 
 use crate::events::{DnxEvent, DnxObserver, DnxPhase};
+use crate::payload::ChunkState;
+use crate::protocol::constants::ONE28_K;
 use crate::transport::UsbTransport;
 use anyhow::Result;
 use tracing::debug;
@@ -15,10 +17,13 @@ pub fn handle_psfw1<T: UsbTransport, O: DnxObserver>(
 
     if let Some(fw) = ctx.fw_image {
         let psfw1 = fw.psfw1_bytes();
+        if ctx.state.psfw1_state.data_size == 0 {
+            ctx.state.psfw1_state = ChunkState::new(psfw1.len(), ONE28_K);
+        }
         if !psfw1.is_empty() {
             // Get next chunk using state
             if let Some(chunk) = ctx.state.psfw1_state.next_chunk(psfw1) {
-                ctx.transport.write(chunk)?;
+                ctx.send("PSFW1", chunk)?;
                 ctx.emit(DnxEvent::Progress {
                     phase: DnxPhase::FirmwareDownload,
                     operation: "PSFW1".to_string(),
@@ -46,10 +51,13 @@ pub fn handle_psfw2<T: UsbTransport, O: DnxObserver>(
 
     if let Some(fw) = ctx.fw_image {
         let psfw2 = fw.psfw2_bytes();
+        if ctx.state.psfw2_state.data_size == 0 {
+            ctx.state.psfw2_state = ChunkState::new(psfw2.len(), ONE28_K);
+        }
         if !psfw2.is_empty()
             && let Some(chunk) = ctx.state.psfw2_state.next_chunk(psfw2)
         {
-            ctx.transport.write(chunk)?;
+            ctx.send("PSFW2", chunk)?;
             ctx.emit(DnxEvent::Progress {
                 phase: DnxPhase::FirmwareDownload,
                 operation: "PSFW2".to_string(),
@@ -76,10 +84,13 @@ pub fn handle_ssfw<T: UsbTransport, O: DnxObserver>(
 
     if let Some(fw) = ctx.fw_image {
         let ssfw = fw.ssfw_bytes();
+        if ctx.state.ssfw_state.data_size == 0 {
+            ctx.state.ssfw_state = ChunkState::new(ssfw.len(), ONE28_K);
+        }
         if !ssfw.is_empty()
             && let Some(chunk) = ctx.state.ssfw_state.next_chunk(ssfw)
         {
-            ctx.transport.write(chunk)?;
+            ctx.send("SSFW", chunk)?;
             ctx.emit(DnxEvent::Progress {
                 phase: DnxPhase::FirmwareDownload,
                 operation: "SSFW".to_string(),
@@ -98,6 +109,39 @@ pub fn handle_ssfw<T: UsbTransport, O: DnxObserver>(
     Ok(HandleResult::Continue)
 }
 
+/// SuCP - SCU patch.
+pub fn handle_sucp<T: UsbTransport, O: DnxObserver>(
+    ctx: &mut HandlerContext<'_, T, O>,
+) -> Result<HandleResult> {
+    debug!("SuCP: Sending SCU patch chunk");
+
+    if let Some(fw) = ctx.fw_image {
+        let sucp = fw.sucp_bytes();
+        if ctx.state.sucp_state.data_size == 0 {
+            ctx.state.sucp_state = ChunkState::new(sucp.len(), ONE28_K);
+        }
+        if !sucp.is_empty()
+            && let Some(chunk) = ctx.state.sucp_state.next_chunk(sucp)
+        {
+            ctx.send("SuCP", chunk)?;
+            ctx.emit(DnxEvent::Progress {
+                phase: DnxPhase::FirmwareDownload,
+                operation: "SuCP".to_string(),
+                current: ctx.state.sucp_state.current as u64,
+                total: ctx.state.sucp_state.total as u64,
+            });
+            debug!(
+                "SuCP chunk {}/{}: {} bytes",
+                ctx.state.sucp_state.current,
+                ctx.state.sucp_state.total,
+                chunk.len()
+            );
+        }
+    }
+
+    Ok(HandleResult::Continue)
+}
+
 /// VEDFW - Video Encoder/Decoder FW.
 pub fn handle_vedfw<T: UsbTransport, O: DnxObserver>(
     ctx: &mut HandlerContext<'_, T, O>,
@@ -106,10 +150,13 @@ pub fn handle_vedfw<T: UsbTransport, O: DnxObserver>(
 
     if let Some(fw) = ctx.fw_image {
         let vedfw = fw.vedfw_bytes();
+        if ctx.state.vedfw_state.data_size == 0 {
+            ctx.state.vedfw_state = ChunkState::new(vedfw.len(), ONE28_K);
+        }
         if !vedfw.is_empty()
             && let Some(chunk) = ctx.state.vedfw_state.next_chunk(vedfw)
         {
-            ctx.transport.write(chunk)?;
+            ctx.send("VEDFW", chunk)?;
             ctx.emit(DnxEvent::Progress {
                 phase: DnxPhase::FirmwareDownload,
                 operation: "VEDFW".to_string(),
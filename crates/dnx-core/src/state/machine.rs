@@ -1,6 +1,144 @@
 //! State machine implementation for DnX protocol.
 
+use std::collections::VecDeque;
 use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::{DnxEvent, DnxPhase};
+
+/// What a session is expected to accomplish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    /// Download firmware only; done after the FW stage.
+    #[default]
+    FwOnly,
+    /// Download the OS image only.
+    OsOnly,
+    /// Download firmware, wait for re-enumeration, then the OS image.
+    FwThenOs,
+    /// Wipe the IFWI.
+    Wipe,
+}
+
+impl Operation {
+    /// Infer the operation from which inputs are present.
+    pub fn infer(has_fw: bool, has_os: bool, wipe: bool) -> Self {
+        match (wipe, has_fw, has_os) {
+            (true, _, _) => Operation::Wipe,
+            (false, true, true) => Operation::FwThenOs,
+            (false, false, true) => Operation::OsOnly,
+            (false, _, false) => Operation::FwOnly,
+        }
+    }
+
+    /// Whether this operation includes a firmware stage.
+    pub fn includes_fw(&self) -> bool {
+        !matches!(self, Operation::OsOnly)
+    }
+
+    /// Whether this operation includes an OS stage.
+    pub fn includes_os(&self) -> bool {
+        matches!(self, Operation::OsOnly | Operation::FwThenOs)
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::FwOnly => write!(f, "fw-only"),
+            Operation::OsOnly => write!(f, "os-only"),
+            Operation::FwThenOs => write!(f, "fw-then-os"),
+            Operation::Wipe => write!(f, "wipe"),
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "fw-only" | "fw" => Ok(Operation::FwOnly),
+            "os-only" | "os" => Ok(Operation::OsOnly),
+            "fw-then-os" | "all" => Ok(Operation::FwThenOs),
+            "wipe" => Ok(Operation::Wipe),
+            _ => Err(format!(
+                "unknown operation '{}' (expected fw-only, os-only, fw-then-os, wipe)",
+                s
+            )),
+        }
+    }
+}
+
+/// How to react to device error ACKs (`ER**`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErrorPolicy {
+    /// Error ACKs (e.g. `"ER04"`) answered by re-sending the last component.
+    pub retryable: Vec<String>,
+    /// Consecutive re-sends allowed before the session aborts.
+    pub max_retries: u32,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        Self {
+            retryable: Vec::new(),
+            max_retries: 3,
+        }
+    }
+}
+
+impl ErrorPolicy {
+    /// Whether `ack` (e.g. `"ER04"`) may be retried.
+    pub fn is_retryable(&self, ack: &str) -> bool {
+        self.retryable
+            .iter()
+            .any(|code| code.eq_ignore_ascii_case(ack))
+    }
+}
+
+/// The last payload written to the device, kept for retries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentComponent {
+    pub component: String,
+    pub data: Vec<u8>,
+}
+
+/// Payload bytes written so far against the bytes the session plans to send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OverallProgress {
+    pub bytes_sent: u64,
+    pub bytes_total: u64,
+}
+
+impl OverallProgress {
+    /// Count `len` more bytes written and return the event to report it.
+    pub fn record(&mut self, len: usize) -> DnxEvent {
+        self.bytes_sent += len as u64;
+        self.event()
+    }
+
+    pub fn event(&self) -> DnxEvent {
+        DnxEvent::OverallProgress {
+            bytes_sent: self.bytes_sent,
+            bytes_total: self.bytes_total,
+        }
+    }
+
+    /// Percentage complete, capped at 100 since protocol framing (dynamic
+    /// headers, size words) is not part of the plan.
+    pub fn percent(&self) -> u8 {
+        (self.bytes_sent * 100)
+            .checked_div(self.bytes_total)
+            .unwrap_or(0)
+            .min(100) as u8
+    }
+}
 
 /// Internal state of the DnX downloader.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -96,11 +234,58 @@ impl ChunkTracker {
     }
 }
 
+/// Transfer rate over the last few seconds of writes.
+///
+/// Samples are `(time, bytes_sent)` pairs; the rate is taken between the
+/// oldest sample inside [`ThroughputMeter::WINDOW`] and the newest.
+#[derive(Debug, Clone, Default)]
+pub struct ThroughputMeter {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl ThroughputMeter {
+    /// How far back samples are kept.
+    pub const WINDOW: Duration = Duration::from_secs(5);
+
+    /// Record `progress` as of `now` and return the event to report the rate,
+    /// or `None` until two samples are far enough apart to measure.
+    pub fn sample(&mut self, now: Instant, progress: &OverallProgress) -> Option<DnxEvent> {
+        while self
+            .samples
+            .front()
+            .is_some_and(|&(t, _)| now.duration_since(t) > Self::WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, progress.bytes_sent));
+
+        let &(start, start_bytes) = self.samples.front()?;
+        let elapsed = now.duration_since(start).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        let bytes_per_sec =
+            (progress.bytes_sent.saturating_sub(start_bytes) as f64 / elapsed) as u64;
+        let eta_secs = progress
+            .bytes_total
+            .saturating_sub(progress.bytes_sent)
+            .checked_div(bytes_per_sec);
+        Some(DnxEvent::Throughput {
+            bytes_per_sec,
+            eta_secs,
+        })
+    }
+}
+
 /// State machine context holding all runtime state.
 #[derive(Debug, Default)]
 pub struct StateMachineContext {
+    /// Requested operation (drives completion).
+    pub operation: Operation,
     /// Current downloader state.
     pub state: DldrState,
+    /// Phase last reported to observers.
+    pub phase: DnxPhase,
     /// Whether FW download is complete.
     pub fw_done: bool,
     /// Whether IFWI is done.
@@ -111,10 +296,33 @@ pub struct StateMachineContext {
     pub abort: bool,
     /// Whether GPP reset was received.
     pub gpp_reset: bool,
+    /// Platform the device identified itself as (`MFLD`/`CLVT`), if it did.
+    pub platform: Option<crate::protocol::Platform>,
     /// Flags from GP (General Purpose).
     pub gp_flags: u32,
     /// IFWI wipe enabled.
     pub ifwi_wipe_enable: bool,
+    /// Chunk size for IFWI/OS transfers (0 selects the 128 KB protocol default).
+    pub chunk_size: usize,
+    /// OS partitions to send on RIMG (None = whole image after the OSIP).
+    pub os_partitions: Option<Vec<usize>>,
+    /// Index into `os_partitions` of the partition being sent.
+    pub os_partition_cursor: usize,
+    /// Reaction to device error ACKs.
+    pub error_policy: ErrorPolicy,
+    /// Last payload written, re-sent on a retryable error. Only kept when
+    /// `error_policy` has retryable codes.
+    pub last_sent: Option<SentComponent>,
+    /// Re-sends of `last_sent` since the last non-error ACK.
+    pub retries: u32,
+    /// Bytes written across all phases.
+    pub progress: OverallProgress,
+    /// Bytes written per component, keyed by the name passed to `send`.
+    pub component_bytes: std::collections::BTreeMap<String, u64>,
+    /// Last ACK handed to the state machine.
+    pub last_ack: Option<crate::protocol::AckCode>,
+    /// Recent write rate, for throughput and ETA reporting.
+    pub throughput: ThroughputMeter,
 
     // Chunk state for FW components (using payload::ChunkState)
     /// PSFW1 chunk state.
@@ -125,15 +333,24 @@ pub struct StateMachineContext {
     pub ssfw_state: crate::payload::ChunkState,
     /// VEDFW chunk state.
     pub vedfw_state: crate::payload::ChunkState,
-    /// ROM Patch chunk state.
-    pub rom_patch_state: crate::payload::ChunkState,
+    /// SuCP (SCU patch) chunk state.
+    pub sucp_state: crate::payload::ChunkState,
 
     /// IFWI chunk state (manual tracker for DIFWI).
     pub ifwi_state: crate::payload::ChunkState,
+    /// Token+FW (Chaabi) byte range of the FW DnX, found once and reused;
+    /// the IFWI is everything before it. Kept across `reset_transfers`.
+    pub chaabi_range: Option<(usize, usize)>,
 
     // OS chunk state
     /// OS image chunk state.
     pub os_image_state: crate::payload::OsChunkState,
+    /// Resume the OS image after a re-enumeration instead of restarting it.
+    pub resume_os: bool,
+    /// Partition cursor and chunk state right after the last OS chunk the
+    /// device acknowledged (by requesting the next one). Kept across
+    /// `reset_transfers` when `resume_os` is set.
+    pub os_resume: Option<(usize, crate::payload::OsChunkState)>,
 }
 
 impl StateMachineContext {
@@ -141,19 +358,144 @@ impl StateMachineContext {
         Self::default()
     }
 
+    /// Chunk size to use, falling back to the protocol default.
+    pub fn effective_chunk_size(&self) -> usize {
+        if self.chunk_size == 0 {
+            crate::protocol::constants::ONE28_K
+        } else {
+            self.chunk_size
+        }
+    }
+
+    /// Forget per-transfer progress before a re-enumerated device is driven again.
+    ///
+    /// Clears every chunk state, the OS partition cursor and the retry
+    /// bookkeeping. Flow state (`state`, `phase`, `fw_done`, `ifwi_done`,
+    /// `os_done`, `gpp_reset`), overall `progress` and configuration survive,
+    /// since they decide where the session resumes. So do the per-component
+    /// byte counts, and the OS resume point when `resume_os` is set.
+    pub fn reset_transfers(&mut self) {
+        self.psfw1_state = Default::default();
+        self.psfw2_state = Default::default();
+        self.ssfw_state = Default::default();
+        self.vedfw_state = Default::default();
+        self.sucp_state = Default::default();
+        self.ifwi_state = Default::default();
+        self.os_image_state = Default::default();
+        self.os_partition_cursor = 0;
+        if !self.resume_os {
+            self.os_resume = None;
+        }
+        self.last_sent = None;
+        self.retries = 0;
+    }
+
     /// Transition to a new state.
     pub fn goto_state(&mut self, new_state: DldrState) {
         tracing::info!(from = %self.state, to = %new_state, "State transition");
         self.state = new_state;
     }
 
+    /// Move to `to`, returning the `PhaseChanged` event to emit (None if already there).
+    pub fn enter_phase(&mut self, to: DnxPhase) -> Option<DnxEvent> {
+        if self.phase == to {
+            return None;
+        }
+        let from = std::mem::replace(&mut self.phase, to);
+        Some(DnxEvent::PhaseChanged { from, to })
+    }
+
     /// Check if operation should continue.
     pub fn should_continue(&self) -> bool {
         !self.abort && !self.is_complete()
     }
 
-    /// Check if all operations are complete.
+    /// Check if all stages required by the operation are complete.
     pub fn is_complete(&self) -> bool {
-        (self.fw_done || self.gpp_reset) && self.os_done
+        match self.operation {
+            Operation::FwOnly | Operation::Wipe => self.fw_done,
+            Operation::OsOnly => self.os_done,
+            Operation::FwThenOs => (self.fw_done || self.gpp_reset) && self.os_done,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_meter() {
+        let mut meter = ThroughputMeter::default();
+        let t0 = Instant::now();
+        let mut progress = OverallProgress {
+            bytes_sent: 0,
+            bytes_total: 3_000_000,
+        };
+        let at = |secs: f64| t0 + Duration::from_secs_f64(secs);
+        let rate = |event: Option<DnxEvent>| match event {
+            Some(DnxEvent::Throughput {
+                bytes_per_sec,
+                eta_secs,
+            }) => (bytes_per_sec, eta_secs),
+            other => panic!("unexpected {:?}", other),
+        };
+
+        // A single sample, or a repeat at the same instant, has no rate
+        assert!(meter.sample(t0, &progress).is_none());
+        assert!(meter.sample(t0, &progress).is_none());
+
+        progress.bytes_sent = 500_000;
+        assert_eq!(rate(meter.sample(at(1.0), &progress)), (500_000, Some(5)));
+
+        // Samples older than the window stop counting: 2 MB/s over the last 5 s
+        progress.bytes_sent = 1_000_000;
+        meter.sample(at(6.0), &progress);
+        progress.bytes_sent = 3_000_000;
+        assert_eq!(rate(meter.sample(at(7.0), &progress)), (2_000_000, Some(0)));
+
+        // A stall reports no ETA rather than dividing by zero
+        let mut stalled = ThroughputMeter::default();
+        stalled.sample(t0, &progress);
+        progress.bytes_total = 4_000_000;
+        assert_eq!(rate(stalled.sample(at(1.0), &progress)), (0, None));
+    }
+
+    #[test]
+    fn test_operation_infer() {
+        assert_eq!(Operation::infer(true, false, false), Operation::FwOnly);
+        assert_eq!(Operation::infer(false, true, false), Operation::OsOnly);
+        assert_eq!(Operation::infer(true, true, false), Operation::FwThenOs);
+        assert_eq!(Operation::infer(true, true, true), Operation::Wipe);
+        assert_eq!("fw-then-os".parse::<Operation>(), Ok(Operation::FwThenOs));
+    }
+
+    #[test]
+    fn test_completion_follows_operation() {
+        let mut ctx = StateMachineContext {
+            operation: Operation::FwOnly,
+            ..Default::default()
+        };
+        ctx.fw_done = true;
+        assert!(ctx.is_complete());
+
+        ctx.operation = Operation::FwThenOs;
+        assert!(!ctx.is_complete());
+        ctx.os_done = true;
+        assert!(ctx.is_complete());
+    }
+
+    #[test]
+    fn test_enter_phase_skips_self_transition() {
+        let mut ctx = StateMachineContext::new();
+        assert!(ctx.enter_phase(DnxPhase::WaitingForDevice).is_none());
+        assert!(matches!(
+            ctx.enter_phase(DnxPhase::Handshake),
+            Some(DnxEvent::PhaseChanged {
+                from: DnxPhase::WaitingForDevice,
+                to: DnxPhase::Handshake
+            })
+        ));
+        assert!(ctx.enter_phase(DnxPhase::Handshake).is_none());
     }
 }
@@ -4,4 +4,6 @@ pub mod handlers;
 pub mod machine;
 
 pub use handlers::{HandleResult, HandlerContext, handle_ack};
-pub use machine::{ChunkTracker, DldrState, StateMachineContext};
+pub use machine::{
+    ChunkTracker, DldrState, Operation, OverallProgress, StateMachineContext, ThroughputMeter,
+};
@@ -0,0 +1,256 @@
+//! USB backend selection: nusb, libusb, or nusb with a libusb fallback.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "libusb")]
+use tracing::warn;
+
+#[cfg(feature = "libusb")]
+use super::libusb::LibusbTransport;
+use super::nusb::{DeviceSelector, NusbTransport};
+use super::traits::{TransportError, UsbTransport};
+use crate::protocol::AckCode;
+
+/// Which USB library opens and drives the device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsbBackend {
+    /// nusb, falling back to libusb when nusb cannot open the device (and
+    /// the `libusb` feature is enabled).
+    #[default]
+    Auto,
+    Nusb,
+    /// libusb via rusb. Needs the `libusb` feature.
+    Libusb,
+}
+
+impl UsbBackend {
+    /// Whether this build can use the backend.
+    pub fn is_available(self) -> bool {
+        self != Self::Libusb || cfg!(feature = "libusb")
+    }
+}
+
+impl fmt::Display for UsbBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Auto => "auto",
+            Self::Nusb => "nusb",
+            Self::Libusb => "libusb",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for UsbBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "nusb" => Ok(Self::Nusb),
+            "libusb" | "rusb" => Ok(Self::Libusb),
+            _ => Err(format!(
+                "unknown USB backend '{}' (expected auto, nusb, libusb)",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether `Auto` retries with libusb after nusb failed with `e`.
+///
+/// Only failures to open or claim a device that was found: not finding it,
+/// ambiguity and missing permissions would fail the same way under libusb.
+pub fn should_fall_back(e: &TransportError) -> bool {
+    matches!(
+        e,
+        TransportError::OpenFailed(_) | TransportError::ClaimInterfaceFailed { .. }
+    )
+}
+
+/// A device opened through one of the `UsbBackend`s.
+pub enum DeviceTransport {
+    Nusb(NusbTransport),
+    #[cfg(feature = "libusb")]
+    Libusb(LibusbTransport),
+}
+
+impl DeviceTransport {
+    /// Open one device with `backend`; see `NusbTransport::open_selected`.
+    pub fn open_selected(
+        backend: UsbBackend,
+        ids: Option<(u16, u16)>,
+        selector: Option<&DeviceSelector>,
+        interface_hint: Option<u8>,
+    ) -> Result<Self, TransportError> {
+        let nusb = || NusbTransport::open_selected(ids, selector, interface_hint).map(Self::Nusb);
+        #[cfg(feature = "libusb")]
+        let libusb =
+            || LibusbTransport::open_selected(ids, selector, interface_hint).map(Self::Libusb);
+
+        match backend {
+            UsbBackend::Nusb => nusb(),
+            #[cfg(feature = "libusb")]
+            UsbBackend::Libusb => libusb(),
+            #[cfg(not(feature = "libusb"))]
+            UsbBackend::Libusb => Err(libusb_unavailable()),
+            UsbBackend::Auto => match nusb() {
+                #[cfg(feature = "libusb")]
+                Err(e) if should_fall_back(&e) => {
+                    warn!("nusb failed ({}), trying libusb", e);
+                    libusb().map_err(|fallback| {
+                        warn!("libusb fallback failed: {}", fallback);
+                        e
+                    })
+                }
+                result => result,
+            },
+        }
+    }
+
+    /// Product IDs of all attached supported devices, listed with `backend`.
+    pub fn attached_supported_pids(backend: UsbBackend) -> Result<Vec<u16>, TransportError> {
+        match backend {
+            UsbBackend::Nusb => NusbTransport::attached_supported_pids(),
+            #[cfg(feature = "libusb")]
+            UsbBackend::Libusb => LibusbTransport::attached_supported_pids(),
+            #[cfg(not(feature = "libusb"))]
+            UsbBackend::Libusb => Err(libusb_unavailable()),
+            UsbBackend::Auto => match NusbTransport::attached_supported_pids() {
+                #[cfg(feature = "libusb")]
+                Err(e) if should_fall_back(&e) => {
+                    LibusbTransport::attached_supported_pids().map_err(|_| e)
+                }
+                result => result,
+            },
+        }
+    }
+
+    /// See `NusbTransport::with_write_queue_depth`; libusb writes one
+    /// transfer at a time regardless.
+    pub fn with_write_queue_depth(self, depth: usize) -> Self {
+        match self {
+            Self::Nusb(t) => Self::Nusb(t.with_write_queue_depth(depth)),
+            #[cfg(feature = "libusb")]
+            other => other,
+        }
+    }
+
+    /// The backend that opened the device.
+    pub fn backend(&self) -> UsbBackend {
+        match self {
+            Self::Nusb(_) => UsbBackend::Nusb,
+            #[cfg(feature = "libusb")]
+            Self::Libusb(_) => UsbBackend::Libusb,
+        }
+    }
+
+    fn inner(&self) -> &dyn UsbTransport {
+        match self {
+            Self::Nusb(t) => t,
+            #[cfg(feature = "libusb")]
+            Self::Libusb(t) => t,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl DeviceTransport {
+    /// Async `read_with_timeout`: native transfers on nusb; libusb has no
+    /// async path here and blocks for the read.
+    pub async fn read_async(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        match self {
+            Self::Nusb(t) => t.read_async(max_len, timeout).await,
+            #[cfg(feature = "libusb")]
+            Self::Libusb(t) => t.read_with_timeout(max_len, timeout),
+        }
+    }
+}
+
+#[cfg(not(feature = "libusb"))]
+fn libusb_unavailable() -> TransportError {
+    TransportError::OpenFailed("built without the `libusb` feature".into())
+}
+
+impl UsbTransport for DeviceTransport {
+    fn write(&self, data: &[u8]) -> Result<usize, TransportError> {
+        self.inner().write(data)
+    }
+
+    fn read(&self, max_len: usize) -> Result<Vec<u8>, TransportError> {
+        self.inner().read(max_len)
+    }
+
+    fn read_with_timeout(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        self.inner().read_with_timeout(max_len, timeout)
+    }
+
+    fn read_ack(&self) -> Result<AckCode, TransportError> {
+        self.inner().read_ack()
+    }
+
+    fn read_ack_with_timeout(&self, timeout: Duration) -> Result<AckCode, TransportError> {
+        self.inner().read_ack_with_timeout(timeout)
+    }
+
+    fn reset(&self) -> Result<(), TransportError> {
+        self.inner().reset()
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner().is_connected()
+    }
+
+    fn vendor_id(&self) -> u16 {
+        self.inner().vendor_id()
+    }
+
+    fn product_id(&self) -> u16 {
+        self.inner().product_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_parse_and_display() {
+        for backend in [UsbBackend::Auto, UsbBackend::Nusb, UsbBackend::Libusb] {
+            assert_eq!(backend.to_string().parse::<UsbBackend>(), Ok(backend));
+        }
+        assert_eq!("rusb".parse::<UsbBackend>(), Ok(UsbBackend::Libusb));
+        assert!("winusb".parse::<UsbBackend>().is_err());
+        assert_eq!(UsbBackend::Libusb.is_available(), cfg!(feature = "libusb"));
+    }
+
+    #[test]
+    fn test_auto_falls_back_only_on_open_failures() {
+        assert!(should_fall_back(&TransportError::OpenFailed("EIO".into())));
+        assert!(should_fall_back(&TransportError::ClaimInterfaceFailed {
+            interface: 0,
+            message: "busy".into(),
+        }));
+        assert!(!should_fall_back(&TransportError::DeviceNotFound {
+            vid: 0x8086,
+            pid: 0
+        }));
+        assert!(!should_fall_back(&TransportError::PermissionDenied {
+            vid: 0x8086,
+            pid: 0,
+            hint: String::new(),
+        }));
+    }
+}
@@ -0,0 +1,273 @@
+//! libusb-based USB transport, behind the `libusb` feature.
+//!
+//! A fallback for setups where nusb cannot open or claim the device. Device
+//! selection and endpoint discovery go through the same `select_device` and
+//! `select_endpoints` as `NusbTransport`.
+
+use std::time::Duration;
+
+use nusb::descriptors::TransferType;
+use rusb::{Device, DeviceHandle, GlobalContext, UsbContext};
+use tracing::{debug, info, instrument};
+
+use super::WRITE_TRANSFER_SIZE;
+use super::nusb::{
+    DeviceDescriptor, DeviceSelector, EndpointInfo, InterfaceEndpoints, describe_interfaces,
+    is_supported_device, permission_hint, select_device, select_endpoints,
+};
+use super::traits::{TransportError, UsbTransport};
+use crate::protocol::constants::INTEL_VENDOR_ID;
+use crate::protocol::platform_name;
+
+/// libusb timeout for transfers that should wait indefinitely.
+const NO_TIMEOUT: Duration = Duration::ZERO;
+
+/// libusb-based USB transport.
+pub struct LibusbTransport {
+    handle: DeviceHandle<GlobalContext>,
+    in_endpoint: u8,
+    in_transfer_type: TransferType,
+    out_endpoint: u8,
+    vid: u16,
+    pid: u16,
+}
+
+impl LibusbTransport {
+    /// Open one device among those with `ids` (any supported device when
+    /// unset), picked by `selector`. See `select_device`.
+    #[instrument(level = "info")]
+    pub fn open_selected(
+        ids: Option<(u16, u16)>,
+        selector: Option<&DeviceSelector>,
+        interface_hint: Option<u8>,
+    ) -> Result<Self, TransportError> {
+        let mut devices: Vec<_> = list_matching(|vid, pid| match ids {
+            Some(ids) => (vid, pid) == ids,
+            None => is_supported_device(vid, pid),
+        })?;
+
+        let candidates: Vec<_> = devices.iter().map(|(_, d)| d.clone()).collect();
+        let index = select_device(&candidates, selector, ids.unwrap_or((INTEL_VENDOR_ID, 0)))?;
+
+        let (device, descriptor) = devices.swap_remove(index);
+        Self::open_device(device, descriptor.vid, descriptor.pid, interface_hint)
+    }
+
+    /// All currently attached Intel devices in `SUPPORTED_PIDS`.
+    pub fn list() -> Result<Vec<DeviceDescriptor>, TransportError> {
+        Ok(list_matching(is_supported_device)?
+            .into_iter()
+            .map(|(_, d)| d)
+            .collect())
+    }
+
+    /// Product IDs of all currently attached Intel devices in `SUPPORTED_PIDS`.
+    pub fn attached_supported_pids() -> Result<Vec<u16>, TransportError> {
+        Ok(list_matching(is_supported_device)?
+            .into_iter()
+            .map(|(_, d)| d.pid)
+            .collect())
+    }
+
+    fn open_device(
+        device: Device<GlobalContext>,
+        vid: u16,
+        pid: u16,
+        interface_hint: Option<u8>,
+    ) -> Result<Self, TransportError> {
+        info!(
+            vendor_id = %format!("{:04X}", vid),
+            product_id = %format!("{:04X}", pid),
+            "Found device (libusb)"
+        );
+
+        let handle = device.open().map_err(|e| match e {
+            rusb::Error::Access => TransportError::PermissionDenied {
+                vid,
+                pid,
+                hint: permission_hint(vid),
+            },
+            _ => TransportError::OpenFailed(e.to_string()),
+        })?;
+
+        let interfaces = match device.active_config_descriptor() {
+            Ok(config) => collect_interfaces(&config),
+            Err(_) => device
+                .config_descriptor(0)
+                .map(|config| collect_interfaces(&config))
+                .unwrap_or_default(),
+        };
+        debug!(interfaces = %describe_interfaces(&interfaces), "Descriptors read");
+
+        let selection = select_endpoints(&interfaces, interface_hint)?;
+        let claim_failed = |e: rusb::Error| TransportError::ClaimInterfaceFailed {
+            interface: selection.interface,
+            message: e.to_string(),
+        };
+
+        // Not supported everywhere (e.g. Windows); claiming reports real conflicts
+        let _ = handle.set_auto_detach_kernel_driver(true);
+        handle
+            .claim_interface(selection.interface)
+            .map_err(claim_failed)?;
+        if selection.alt_setting != 0 {
+            handle
+                .set_alternate_setting(selection.interface, selection.alt_setting)
+                .map_err(claim_failed)?;
+        }
+
+        info!(
+            interface = selection.interface,
+            alt_setting = selection.alt_setting,
+            in_ep = %format!("0x{:02X}", selection.in_endpoint),
+            in_type = ?selection.in_transfer_type,
+            out_ep = %format!("0x{:02X}", selection.out_endpoint),
+            "Device opened successfully (libusb)"
+        );
+
+        Ok(Self {
+            handle,
+            in_endpoint: selection.in_endpoint,
+            in_transfer_type: selection.in_transfer_type,
+            out_endpoint: selection.out_endpoint,
+            vid,
+            pid,
+        })
+    }
+
+    fn read_in(&self, max_len: usize, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        let mut buf = vec![0u8; max_len];
+        let n = match self.in_transfer_type {
+            TransferType::Interrupt => {
+                self.handle
+                    .read_interrupt(self.in_endpoint, &mut buf, timeout)
+            }
+            _ => self.handle.read_bulk(self.in_endpoint, &mut buf, timeout),
+        }
+        .map_err(|e| match e {
+            rusb::Error::Timeout => TransportError::Timeout {
+                timeout_ms: timeout.as_millis() as u64,
+            },
+            rusb::Error::NoDevice => TransportError::Disconnected,
+            _ => TransportError::ReadFailed(e.to_string()),
+        })?;
+
+        buf.truncate(n);
+        debug!(bytes_read = n, "Read complete");
+        Ok(buf)
+    }
+}
+
+/// Attached devices whose IDs pass `filter`, with their descriptors.
+fn list_matching(
+    filter: impl Fn(u16, u16) -> bool,
+) -> Result<Vec<(Device<GlobalContext>, DeviceDescriptor)>, TransportError> {
+    let devices = GlobalContext::default()
+        .devices()
+        .map_err(|e| TransportError::OpenFailed(e.to_string()))?;
+
+    Ok(devices
+        .iter()
+        .filter_map(|device| {
+            let desc = device.device_descriptor().ok()?;
+            let (vid, pid) = (desc.vendor_id(), desc.product_id());
+            if !filter(vid, pid) {
+                return None;
+            }
+            // libusb needs an open handle to read the serial string
+            let serial = device
+                .open()
+                .ok()
+                .and_then(|h| h.read_serial_number_string_ascii(&desc).ok());
+            let descriptor = DeviceDescriptor {
+                vid,
+                pid,
+                serial,
+                bus: Some(device.bus_number()),
+                address: device.address(),
+                platform: platform_name(pid),
+            };
+            Some((device, descriptor))
+        })
+        .collect())
+}
+
+/// Endpoint info for every interface/alt setting in a configuration.
+fn collect_interfaces(config: &rusb::ConfigDescriptor) -> Vec<InterfaceEndpoints> {
+    config
+        .interfaces()
+        .flat_map(|iface| iface.descriptors())
+        .map(|alt| InterfaceEndpoints {
+            interface: alt.interface_number(),
+            alt_setting: alt.setting_number(),
+            endpoints: alt
+                .endpoint_descriptors()
+                .map(|ep| EndpointInfo {
+                    address: ep.address(),
+                    transfer_type: transfer_type(ep.transfer_type()),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn transfer_type(t: rusb::TransferType) -> TransferType {
+    match t {
+        rusb::TransferType::Control => TransferType::Control,
+        rusb::TransferType::Isochronous => TransferType::Isochronous,
+        rusb::TransferType::Bulk => TransferType::Bulk,
+        rusb::TransferType::Interrupt => TransferType::Interrupt,
+    }
+}
+
+impl UsbTransport for LibusbTransport {
+    #[instrument(skip(self, data), fields(len = data.len()))]
+    fn write(&self, data: &[u8]) -> Result<usize, TransportError> {
+        for chunk in data.chunks(WRITE_TRANSFER_SIZE) {
+            self.handle
+                .write_bulk(self.out_endpoint, chunk, NO_TIMEOUT)
+                .map_err(|e| match e {
+                    rusb::Error::NoDevice => TransportError::Disconnected,
+                    _ => TransportError::WriteFailed(e.to_string()),
+                })?;
+        }
+
+        debug!(bytes_written = data.len(), "Write complete");
+        Ok(data.len())
+    }
+
+    fn read(&self, max_len: usize) -> Result<Vec<u8>, TransportError> {
+        self.read_in(max_len, NO_TIMEOUT)
+    }
+
+    fn read_with_timeout(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        // libusb takes whole milliseconds, and 0 means no timeout
+        let ms = timeout.as_millis().clamp(1, u32::MAX as u128) as u64;
+        self.read_in(max_len, Duration::from_millis(ms))
+    }
+
+    #[instrument(skip(self))]
+    fn reset(&self) -> Result<(), TransportError> {
+        self.handle
+            .reset()
+            .map_err(|e| TransportError::ResetFailed(e.to_string()))?;
+        debug!("USB reset issued");
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn vendor_id(&self) -> u16 {
+        self.vid
+    }
+
+    fn product_id(&self) -> u16 {
+        self.pid
+    }
+}
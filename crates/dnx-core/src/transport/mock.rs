@@ -2,14 +2,16 @@
 
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
+use super::WRITE_TRANSFER_SIZE;
 use super::traits::{TransportError, UsbTransport};
-use crate::protocol::AckCode;
 
 /// Mock transport for unit testing state machine logic.
 pub struct MockTransport {
-    /// Queued ACKs to return on read.
-    ack_queue: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    /// Queued ACKs to return on read (None = a disconnect).
+    ack_queue: Arc<Mutex<VecDeque<Option<Vec<u8>>>>>,
     /// Captured writes.
     write_log: Arc<Mutex<Vec<Vec<u8>>>>,
     /// Simulated VID/PID.
@@ -17,6 +19,22 @@ pub struct MockTransport {
     pid: u16,
     /// Whether device is "connected".
     connected: Arc<Mutex<bool>>,
+    /// Simulated per-transfer latency.
+    latency: Duration,
+    /// Simulated link bandwidth in bytes/sec (None = unlimited).
+    bandwidth_bps: Option<u64>,
+    /// Simulated OUT queue depth; None = latency charged once per write.
+    write_queue_depth: Option<usize>,
+    /// Most OUT transfers the simulated queue had in flight at once.
+    peak_in_flight: Arc<Mutex<usize>>,
+    /// Probability (0.0..=1.0) that a read fails with an injected error.
+    error_rate: f64,
+    /// xorshift state for error injection.
+    rng: Arc<Mutex<u64>>,
+    /// Reads that fail before the error rate applies.
+    failing_reads: Arc<Mutex<usize>>,
+    /// Number of `reset` calls.
+    resets: Arc<Mutex<usize>>,
 }
 
 impl MockTransport {
@@ -27,12 +45,107 @@ impl MockTransport {
             vid: 0x8086,
             pid: 0xE004,
             connected: Arc::new(Mutex::new(true)),
+            latency: Duration::ZERO,
+            bandwidth_bps: None,
+            write_queue_depth: None,
+            peak_in_flight: Arc::new(Mutex::new(0)),
+            error_rate: 0.0,
+            rng: Arc::new(Mutex::new(0x2545_F491_4F6C_DD1D)),
+            failing_reads: Arc::new(Mutex::new(0)),
+            resets: Arc::new(Mutex::new(0)),
         }
     }
 
+    /// Simulate a fixed latency on every read and write.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Simulate a link limited to `bytes_per_sec` (applies to writes).
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.bandwidth_bps = Some(bytes_per_sec).filter(|&b| b > 0);
+        self
+    }
+
+    /// Model writes as `WRITE_TRANSFER_SIZE` transfers with up to `depth` in
+    /// flight: latency is paid once per round of `depth` transfers.
+    pub fn with_write_queue_depth(mut self, depth: usize) -> Self {
+        self.write_queue_depth = Some(depth.max(1));
+        self
+    }
+
+    /// Make reads fail with a transient error at the given rate (0.0..=1.0).
+    pub fn with_error_rate(mut self, rate: f64) -> Self {
+        self.error_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seed the error-injection generator for reproducible runs.
+    pub fn with_seed(self, seed: u64) -> Self {
+        *self.rng.lock().unwrap() = seed.max(1);
+        self
+    }
+
+    fn simulate_transfer(&self, len: usize) {
+        let rounds = match self.write_queue_depth {
+            Some(depth) if len > 0 => {
+                let transfers = len.div_ceil(WRITE_TRANSFER_SIZE);
+                let mut peak = self.peak_in_flight.lock().unwrap();
+                *peak = (*peak).max(transfers.min(depth));
+                transfers.div_ceil(depth)
+            }
+            _ => 1,
+        };
+        let mut delay = self.latency * rounds as u32;
+        if let Some(bps) = self.bandwidth_bps {
+            delay += Duration::from_secs_f64(len as f64 / bps as f64);
+        }
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+
+    /// Most OUT transfers in flight at once so far, with a write queue depth
+    /// set; 0 without one.
+    pub fn peak_in_flight(&self) -> usize {
+        *self.peak_in_flight.lock().unwrap()
+    }
+
+    /// Make the next `count` reads fail with a transient error.
+    pub fn fail_next_reads(&self, count: usize) {
+        *self.failing_reads.lock().unwrap() = count;
+    }
+
+    fn inject_error(&self) -> bool {
+        let mut failing = self.failing_reads.lock().unwrap();
+        if *failing > 0 {
+            *failing -= 1;
+            return true;
+        }
+        drop(failing);
+        if self.error_rate <= 0.0 {
+            return false;
+        }
+        let mut x = self.rng.lock().unwrap();
+        *x ^= *x << 13;
+        *x ^= *x >> 7;
+        *x ^= *x << 17;
+        ((*x >> 11) as f64 / (1u64 << 53) as f64) < self.error_rate
+    }
+
     /// Queue an ACK response to be returned on next read.
     pub fn queue_ack(&self, ack_bytes: &[u8]) {
-        self.ack_queue.lock().unwrap().push_back(ack_bytes.to_vec());
+        self.ack_queue
+            .lock()
+            .unwrap()
+            .push_back(Some(ack_bytes.to_vec()));
+    }
+
+    /// Queue a read that fails with `Disconnected`, as if the device dropped
+    /// and came back before the next read.
+    pub fn queue_disconnect(&self) {
+        self.ack_queue.lock().unwrap().push_back(None);
     }
 
     /// Queue an ACK from a u32 constant.
@@ -67,6 +180,11 @@ impl MockTransport {
         *self.connected.lock().unwrap() = true;
     }
 
+    /// Number of times `reset` was called.
+    pub fn reset_count(&self) -> usize {
+        *self.resets.lock().unwrap()
+    }
+
     /// Set VID/PID for re-enumeration testing.
     pub fn set_ids(&mut self, vid: u16, pid: u16) {
         self.vid = vid;
@@ -85,6 +203,7 @@ impl UsbTransport for MockTransport {
         if !*self.connected.lock().unwrap() {
             return Err(TransportError::Disconnected);
         }
+        self.simulate_transfer(data.len());
         self.write_log.lock().unwrap().push(data.to_vec());
         Ok(data.len())
     }
@@ -93,16 +212,36 @@ impl UsbTransport for MockTransport {
         if !*self.connected.lock().unwrap() {
             return Err(TransportError::Disconnected);
         }
+        self.simulate_transfer(0);
+        if self.inject_error() {
+            return Err(TransportError::ReadFailed("Injected error".into()));
+        }
         self.ack_queue
             .lock()
             .unwrap()
             .pop_front()
-            .ok_or(TransportError::Timeout { timeout_ms: 5000 })
+            .ok_or(TransportError::Timeout { timeout_ms: 5000 })?
+            .ok_or(TransportError::Disconnected)
     }
 
-    fn read_ack(&self) -> Result<AckCode, TransportError> {
-        let bytes = self.read(512)?;
-        Ok(AckCode::from_bytes(&bytes))
+    fn read_with_timeout(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        // A device slower than the timeout never answers in time
+        if self.latency > timeout {
+            thread::sleep(timeout);
+            return Err(TransportError::Timeout {
+                timeout_ms: timeout.as_millis() as u64,
+            });
+        }
+        self.read(max_len)
+    }
+
+    fn reset(&self) -> Result<(), TransportError> {
+        *self.resets.lock().unwrap() += 1;
+        Ok(())
     }
 
     fn is_connected(&self) -> bool {
@@ -160,4 +299,81 @@ mod tests {
         assert!(!mock.is_connected());
         assert!(mock.write(b"test").is_err());
     }
+
+    #[test]
+    fn test_mock_write_queue_depth() {
+        let data = vec![0x5Au8; 16 * WRITE_TRANSFER_SIZE];
+        let peak = |depth: usize, writes: &[&[u8]]| {
+            let mock = MockTransport::new().with_write_queue_depth(depth);
+            for data in writes {
+                mock.write(data).unwrap();
+            }
+            assert_eq!(mock.get_writes().concat(), writes.concat());
+            mock.peak_in_flight()
+        };
+
+        assert_eq!(peak(1, &[&data]), 1);
+        assert_eq!(peak(8, &[&data]), 8);
+        assert_eq!(peak(32, &[&data]), 16);
+        // Separate writes never share the queue, however small they are
+        assert_eq!(peak(8, &[b"DnER", b"OSIP", &[0u8; 64]]), 1);
+        assert_eq!(MockTransport::new().peak_in_flight(), 0);
+    }
+
+    #[test]
+    fn test_mock_error_injection() {
+        let mock = MockTransport::new().with_error_rate(1.0);
+        mock.queue_ack_u32(BULK_ACK_DONE);
+        assert!(matches!(mock.read(512), Err(TransportError::ReadFailed(_))));
+
+        // Queued ACK is not consumed by an injected failure
+        let mock = MockTransport::new().with_error_rate(0.0);
+        mock.queue_ack_u32(BULK_ACK_DONE);
+        assert!(mock.read_ack().unwrap().matches_u32(BULK_ACK_DONE));
+    }
+
+    #[test]
+    fn test_mock_slow_read_times_out() {
+        let mock = MockTransport::new().with_latency(Duration::from_millis(20));
+        mock.queue_ack(b"DFRM");
+
+        assert!(matches!(
+            mock.read_with_timeout(512, Duration::from_millis(5)),
+            Err(TransportError::Timeout { timeout_ms: 5 })
+        ));
+        // The ACK is still there once the timeout allows for the latency
+        assert_eq!(
+            mock.read_with_timeout(512, Duration::from_millis(100))
+                .unwrap(),
+            b"DFRM".to_vec()
+        );
+        assert!(matches!(
+            mock.read_with_timeout(512, Duration::from_millis(100)),
+            Err(TransportError::Timeout { timeout_ms: 5000 })
+        ));
+    }
+
+    #[test]
+    fn test_mock_split_ack_reassembled() {
+        let mock = MockTransport::new();
+        mock.queue_ack(b"RUP");
+        mock.queue_ack(b"HS");
+        mock.queue_ack_u32(BULK_ACK_DONE);
+
+        let ack = mock.read_ack().unwrap();
+        assert!(ack.matches_u64(BULK_ACK_READY_UPH_SIZE));
+        assert_eq!(ack.len(), 5);
+
+        // Complete ACKs are not merged with what follows
+        assert!(mock.read_ack().unwrap().matches_u32(BULK_ACK_DONE));
+
+        // 7-byte size request split mid-token
+        mock.queue_ack(b"OSIP");
+        mock.queue_ack(b" Sz");
+        assert!(mock.read_ack().unwrap().matches_u64(BULK_ACK_OSIPSZ));
+
+        // A truncated ACK with nothing following is returned as-is
+        mock.queue_ack(b"DO");
+        assert_eq!(mock.read_ack().unwrap().as_ascii(), "DO");
+    }
 }
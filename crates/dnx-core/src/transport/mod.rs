@@ -1,9 +1,80 @@
 //! Transport layer module.
 
+pub mod backend;
+#[cfg(feature = "libusb")]
+pub mod libusb;
 pub mod mock;
 pub mod nusb;
 pub mod traits;
 
+pub use backend::{DeviceTransport, UsbBackend};
+#[cfg(feature = "libusb")]
+pub use libusb::LibusbTransport;
 pub use mock::MockTransport;
-pub use nusb::NusbTransport;
-pub use traits::{TransportError, UsbTransport};
+pub use nusb::{
+    DeviceDescriptor, DeviceSelector, EndpointSelection, NusbTransport, is_supported_device,
+    select_device, udev_rule,
+};
+pub use traits::{TransportError, UsbTransport, read_ack_bytes, read_ack_bytes_with_timeout};
+
+/// Size of each bulk OUT transfer a write is split into.
+///
+/// A multiple of every max packet size, so the packet stream the device sees
+/// does not depend on how many transfers are queued at once.
+pub const WRITE_TRANSFER_SIZE: usize = 4096;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::AckCode;
+    use crate::protocol::constants::*;
+    use std::time::Duration;
+
+    fn assert_transport<T: UsbTransport>() {}
+
+    /// Every backend is usable wherever the session takes a transport.
+    #[test]
+    fn test_backends_implement_transport() {
+        assert_transport::<NusbTransport>();
+        assert_transport::<DeviceTransport>();
+        #[cfg(feature = "libusb")]
+        assert_transport::<LibusbTransport>();
+    }
+
+    /// Behaviour the state machine relies on from any `UsbTransport`,
+    /// checked against the scripted device behind `t`.
+    fn check_conformance(t: &dyn UsbTransport, device: &MockTransport) {
+        // Writes report the full length and arrive unsplit
+        assert_eq!(t.write(b"DnER").unwrap(), 4);
+        assert_eq!(device.get_writes().last().unwrap(), b"DnER");
+
+        // A split ACK is reassembled into one code
+        device.queue_ack(b"RUP");
+        device.queue_ack(b"HS");
+        assert_eq!(t.read_ack().unwrap(), AckCode::from_bytes(b"RUPHS"));
+
+        // No answer is a timeout, which the session retries
+        assert!(matches!(
+            t.read_ack_with_timeout(Duration::from_millis(10)),
+            Err(TransportError::Timeout { .. })
+        ));
+
+        // Unplugging surfaces as Disconnected on both directions
+        device.disconnect();
+        assert!(matches!(t.read(512), Err(TransportError::Disconnected)));
+        assert!(matches!(t.write(&[0]), Err(TransportError::Disconnected)));
+        device.reconnect();
+
+        assert_eq!(
+            (t.vendor_id(), t.product_id()),
+            (device.vendor_id(), device.product_id())
+        );
+    }
+
+    #[test]
+    fn test_mock_transport_conformance() {
+        let device = MockTransport::new();
+        check_conformance(&device, &device);
+        assert_eq!(device.vendor_id(), INTEL_VENDOR_ID);
+    }
+}
@@ -1,59 +1,392 @@
 //! nusb-based USB transport implementation.
 
-use nusb::transfer::{Bulk, In, Out};
-use nusb::{Interface, MaybeFuture, list_devices};
-use std::io::{Read, Write};
+use nusb::descriptors::{ConfigurationDescriptor, TransferType};
+use nusb::transfer::{Bulk, BulkOrInterrupt, In, Interrupt, Out};
+use nusb::{Device, DeviceInfo, Interface, MaybeFuture, list_devices};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
 use tracing::{debug, info, instrument};
 
-use super::traits::{TransportError, UsbTransport};
-use crate::protocol::AckCode;
+use super::WRITE_TRANSFER_SIZE;
+use super::traits::{TransportError, UsbTransport, read_ack_bytes, read_ack_bytes_with_timeout};
 use crate::protocol::constants::{INTEL_VENDOR_ID, SUPPORTED_PIDS};
+use crate::protocol::{AckCode, platform_name};
+
+/// A single endpoint as reported by the interface descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointInfo {
+    /// Endpoint address (bit 7 set for IN).
+    pub address: u8,
+    /// Transfer type from bmAttributes.
+    pub transfer_type: TransferType,
+}
+
+impl EndpointInfo {
+    /// Check if this is a device-to-host endpoint.
+    pub fn is_in(&self) -> bool {
+        self.address & 0x80 != 0
+    }
+}
+
+impl fmt::Display for EndpointInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:02X} {:?} {}",
+            self.address,
+            self.transfer_type,
+            if self.is_in() { "IN" } else { "OUT" }
+        )
+    }
+}
+
+/// Endpoints exposed by one interface alternate setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceEndpoints {
+    pub interface: u8,
+    pub alt_setting: u8,
+    pub endpoints: Vec<EndpointInfo>,
+}
+
+/// Interface and endpoint pair chosen for DnX traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointSelection {
+    pub interface: u8,
+    pub alt_setting: u8,
+    pub in_endpoint: u8,
+    /// Bulk, or Interrupt when the stage exposes no bulk IN.
+    pub in_transfer_type: TransferType,
+    pub out_endpoint: u8,
+}
+
+/// Picks one device when several boards are attached.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceSelector {
+    /// USB serial number (`iSerial`).
+    Serial(String),
+    /// Bus number and device address. Only valid until the device re-enumerates.
+    BusAddr { bus: u8, address: u8 },
+}
+
+impl DeviceSelector {
+    /// Whether `device` is the one this selector names.
+    pub fn matches(&self, device: &DeviceDescriptor) -> bool {
+        match self {
+            Self::Serial(serial) => device.serial.as_deref() == Some(serial.as_str()),
+            Self::BusAddr { bus, address } => {
+                device.bus == Some(*bus) && device.address == *address
+            }
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serial(serial) => write!(f, "serial {}", serial),
+            Self::BusAddr { bus, address } => write!(f, "bus {} address {}", bus, address),
+        }
+    }
+}
+
+/// An attached device: its IDs and where it sits on the bus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceDescriptor {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial: Option<String>,
+    /// Bus number, when the platform's bus ID is numeric.
+    pub bus: Option<u8>,
+    pub address: u8,
+    /// Platform derived from the PID, e.g. "Moorefield 0A2C".
+    pub platform: String,
+}
+
+impl DeviceDescriptor {
+    fn from_info(info: &DeviceInfo) -> Self {
+        Self {
+            vid: info.vendor_id(),
+            pid: info.product_id(),
+            serial: info.serial_number().map(str::to_owned),
+            bus: info.bus_id().parse().ok(),
+            address: info.device_address(),
+            platform: platform_name(info.product_id()),
+        }
+    }
+}
+
+impl fmt::Display for DeviceDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:04X}:{:04X}",
+            self.serial.as_deref().unwrap_or("no serial"),
+            self.vid,
+            self.pid
+        )?;
+        if let Some(bus) = self.bus {
+            write!(f, " at {}:{}", bus, self.address)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Pick the index of the device to open among `candidates`.
+///
+/// With a selector, the first match wins. Without one, exactly one candidate
+/// must be attached; several give `MultipleDevices` so the user can choose.
+/// No match gives `DeviceNotFound` with `not_found` as the reported IDs, so
+/// callers polling for the device keep waiting.
+pub fn select_device(
+    candidates: &[DeviceDescriptor],
+    selector: Option<&DeviceSelector>,
+    not_found: (u16, u16),
+) -> Result<usize, TransportError> {
+    let (vid, pid) = not_found;
+    match selector {
+        Some(selector) => candidates
+            .iter()
+            .position(|c| selector.matches(c))
+            .ok_or(TransportError::DeviceNotFound { vid, pid }),
+        None => match candidates.len() {
+            0 => Err(TransportError::DeviceNotFound { vid, pid }),
+            1 => Ok(0),
+            _ => Err(TransportError::MultipleDevices {
+                candidates: candidates.iter().map(ToString::to_string).collect(),
+            }),
+        },
+    }
+}
+
+/// Collect endpoint info for every interface/alt setting in a configuration.
+pub fn collect_interfaces(config: &ConfigurationDescriptor<'_>) -> Vec<InterfaceEndpoints> {
+    config
+        .interface_alt_settings()
+        .map(|alt| InterfaceEndpoints {
+            interface: alt.interface_number(),
+            alt_setting: alt.alternate_setting(),
+            endpoints: alt
+                .endpoints()
+                .map(|ep| EndpointInfo {
+                    address: ep.address(),
+                    transfer_type: ep.transfer_type(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Pick the interface/alt setting to use for DnX traffic.
+///
+/// Prefers the first one exposing both a bulk IN and a bulk OUT endpoint.
+/// Failing that, accepts a bulk OUT paired with an interrupt IN, which some
+/// stages use for the ACK channel. None of the PIDs in `SUPPORTED_PIDS` are
+/// currently known to need the fallback; it only kicks in when no bulk IN is
+/// exposed at all.
+///
+/// If `interface_hint` is set, only that interface number is considered.
+pub fn select_endpoints(
+    interfaces: &[InterfaceEndpoints],
+    interface_hint: Option<u8>,
+) -> Result<EndpointSelection, TransportError> {
+    let find = |iface: &InterfaceEndpoints, transfer_type: TransferType, want_in: bool| {
+        iface
+            .endpoints
+            .iter()
+            .find(|ep| ep.transfer_type == transfer_type && ep.is_in() == want_in)
+            .map(|ep| ep.address)
+    };
+
+    for in_transfer_type in [TransferType::Bulk, TransferType::Interrupt] {
+        let candidates = interfaces
+            .iter()
+            .filter(|i| interface_hint.is_none_or(|hint| i.interface == hint));
+
+        for iface in candidates {
+            if let (Some(in_endpoint), Some(out_endpoint)) = (
+                find(iface, in_transfer_type, true),
+                find(iface, TransferType::Bulk, false),
+            ) {
+                return Ok(EndpointSelection {
+                    interface: iface.interface,
+                    alt_setting: iface.alt_setting,
+                    in_endpoint,
+                    in_transfer_type,
+                    out_endpoint,
+                });
+            }
+        }
+    }
+
+    Err(TransportError::NoBulkEndpointPair {
+        found: describe_interfaces(interfaces),
+    })
+}
+
+pub(crate) fn describe_interfaces(interfaces: &[InterfaceEndpoints]) -> String {
+    if interfaces.is_empty() {
+        return "no interfaces".into();
+    }
+    interfaces
+        .iter()
+        .map(|i| {
+            let eps = i
+                .endpoints
+                .iter()
+                .map(|ep| ep.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("if{}/alt{}: [{}]", i.interface, i.alt_setting, eps)
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Whether `vid:pid` is an Intel DnX device from `SUPPORTED_PIDS`.
+pub fn is_supported_device(vid: u16, pid: u16) -> bool {
+    vid == INTEL_VENDOR_ID && SUPPORTED_PIDS.contains(&pid)
+}
+
+/// udev rule granting non-root access to every device with `vid`.
+pub fn udev_rule(vid: u16) -> String {
+    format!(
+        "SUBSYSTEM==\"usb\", ATTR{{idVendor}}==\"{:04x}\", MODE=\"0666\"",
+        vid
+    )
+}
+
+/// What to do when the OS refuses to open the device.
+pub(crate) fn permission_hint(vid: u16) -> String {
+    if cfg!(target_os = "linux") {
+        format!(
+            "Run as root or add a udev rule, e.g. `echo '{}' | sudo tee /etc/udev/rules.d/51-dnx.rules && sudo udevadm control --reload-rules`, then replug the device.",
+            udev_rule(vid)
+        )
+    } else {
+        "Run with administrator rights, or bind the device to a WinUSB/libusb driver.".to_string()
+    }
+}
 
 /// nusb-based USB transport.
 pub struct NusbTransport {
+    device: Device,
     interface: Interface,
     in_endpoint: u8,
+    in_transfer_type: TransferType,
     out_endpoint: u8,
     vid: u16,
     pid: u16,
+    /// Bulk OUT transfers kept in flight per write (1 = one at a time).
+    write_queue_depth: usize,
 }
 
 impl NusbTransport {
+    /// Queue up to `depth` OUT transfers per write so the host prepares the
+    /// next transfer while the previous one is on the bus. 0 is treated as 1.
+    ///
+    /// Only transfers of the same write overlap: every write is flushed
+    /// before it returns, since the device answers each one with an ACK
+    /// before it takes the next. The depth helps large writes (IFWI and OS
+    /// chunks), not the small command packets between them.
+    pub fn with_write_queue_depth(mut self, depth: usize) -> Self {
+        self.write_queue_depth = depth.max(1);
+        self
+    }
+
     /// Open any matching Intel DnX device (tries all supported PIDs).
     #[instrument(level = "info")]
     pub fn open() -> Result<Self, TransportError> {
+        Self::open_with_interface(None)
+    }
+
+    /// Open any matching Intel DnX device, restricting endpoint search to
+    /// `interface_hint` if given.
+    #[instrument(level = "info")]
+    pub fn open_with_interface(interface_hint: Option<u8>) -> Result<Self, TransportError> {
+        Self::open_selected(None, None, interface_hint)
+    }
+
+    /// Open the supported device with this USB serial number.
+    #[instrument(level = "info")]
+    pub fn open_with_serial(serial: &str) -> Result<Self, TransportError> {
+        Self::open_selected(None, Some(&DeviceSelector::Serial(serial.to_owned())), None)
+    }
+
+    /// Open the supported device at this bus number and device address.
+    #[instrument(level = "info")]
+    pub fn open_at(bus: u8, address: u8) -> Result<Self, TransportError> {
+        Self::open_selected(None, Some(&DeviceSelector::BusAddr { bus, address }), None)
+    }
+
+    /// Open one device among those with `ids` (any supported device when
+    /// unset), picked by `selector`. See `select_device`.
+    pub fn open_selected(
+        ids: Option<(u16, u16)>,
+        selector: Option<&DeviceSelector>,
+        interface_hint: Option<u8>,
+    ) -> Result<Self, TransportError> {
+        let mut devices: Vec<DeviceInfo> = list_devices()
+            .wait()
+            .map_err(|e| TransportError::OpenFailed(e.to_string()))?
+            .filter(|d| match ids {
+                Some((vid, pid)) => d.vendor_id() == vid && d.product_id() == pid,
+                None => is_supported_device(d.vendor_id(), d.product_id()),
+            })
+            .collect();
+
+        let candidates: Vec<_> = devices.iter().map(DeviceDescriptor::from_info).collect();
+        let index = select_device(&candidates, selector, ids.unwrap_or((INTEL_VENDOR_ID, 0)))?;
+
+        Self::open_device_info(devices.swap_remove(index), interface_hint)
+    }
+
+    /// All currently attached Intel devices in `SUPPORTED_PIDS`.
+    pub fn list() -> Result<Vec<DeviceDescriptor>, TransportError> {
         let devices = list_devices()
             .wait()
             .map_err(|e| TransportError::OpenFailed(e.to_string()))?;
 
-        // Try to find any Intel device with a supported PID
-        for device_info in devices {
-            if device_info.vendor_id() == INTEL_VENDOR_ID
-                && SUPPORTED_PIDS.contains(&device_info.product_id())
-            {
-                return Self::open_device_info(device_info);
-            }
-        }
-
-        Err(TransportError::DeviceNotFound {
-            vid: INTEL_VENDOR_ID,
-            pid: 0,
-        })
+        Ok(devices
+            .filter(|d| is_supported_device(d.vendor_id(), d.product_id()))
+            .map(|d| DeviceDescriptor::from_info(&d))
+            .collect())
     }
 
-    /// Open a device with specific VID/PID.
-    #[instrument(level = "info", fields(vid = format!("{:04X}", vid), pid = format!("{:04X}", pid)))]
-    pub fn open_with_ids(vid: u16, pid: u16) -> Result<Self, TransportError> {
-        let device_info = list_devices()
+    /// Product IDs of all currently attached Intel devices in `SUPPORTED_PIDS`.
+    pub fn attached_supported_pids() -> Result<Vec<u16>, TransportError> {
+        let devices = list_devices()
             .wait()
-            .map_err(|e| TransportError::OpenFailed(e.to_string()))?
-            .find(|d| d.vendor_id() == vid && d.product_id() == pid)
-            .ok_or(TransportError::DeviceNotFound { vid, pid })?;
+            .map_err(|e| TransportError::OpenFailed(e.to_string()))?;
 
-        Self::open_device_info(device_info)
+        Ok(devices
+            .filter(|d| is_supported_device(d.vendor_id(), d.product_id()))
+            .map(|d| d.product_id())
+            .collect())
     }
 
-    fn open_device_info(device_info: nusb::DeviceInfo) -> Result<Self, TransportError> {
+    /// Open the Intel device with the given PID, restricted to `interface_hint` if set.
+    pub fn open_pid(pid: u16, interface_hint: Option<u8>) -> Result<Self, TransportError> {
+        Self::open_selected(Some((INTEL_VENDOR_ID, pid)), None, interface_hint)
+    }
+
+    /// Open a device with specific VID/PID, bypassing the `SUPPORTED_PIDS` allowlist,
+    /// restricted to `interface_hint` if set.
+    #[instrument(level = "info", fields(vid = format!("{:04X}", vid), pid = format!("{:04X}", pid)))]
+    pub fn open_with_ids(
+        vid: u16,
+        pid: u16,
+        interface_hint: Option<u8>,
+    ) -> Result<Self, TransportError> {
+        Self::open_selected(Some((vid, pid)), None, interface_hint)
+    }
+
+    fn open_device_info(
+        device_info: DeviceInfo,
+        interface_hint: Option<u8>,
+    ) -> Result<Self, TransportError> {
         let vid = device_info.vendor_id();
         let pid = device_info.product_id();
 
@@ -63,71 +396,204 @@ impl NusbTransport {
             "Found device"
         );
 
-        let device = device_info
-            .open()
+        let device = device_info.open().wait().map_err(|e| match e.kind() {
+            nusb::ErrorKind::PermissionDenied => TransportError::PermissionDenied {
+                vid,
+                pid,
+                hint: permission_hint(vid),
+            },
+            _ => TransportError::OpenFailed(e.to_string()),
+        })?;
+
+        // Search all interfaces of the active configuration for the DnX bulk pair
+        let interfaces = match device.active_configuration() {
+            Ok(config) => collect_interfaces(&config),
+            Err(_) => device
+                .configurations()
+                .next()
+                .map(|config| collect_interfaces(&config))
+                .unwrap_or_default(),
+        };
+        debug!(interfaces = %describe_interfaces(&interfaces), "Descriptors read");
+
+        let selection = select_endpoints(&interfaces, interface_hint)?;
+
+        let interface = device
+            .claim_interface(selection.interface)
             .wait()
-            .map_err(|e| TransportError::OpenFailed(e.to_string()))?;
+            .map_err(|e| TransportError::ClaimInterfaceFailed {
+                interface: selection.interface,
+                message: e.to_string(),
+            })?;
 
-        let interface =
-            device
-                .claim_interface(0)
+        if selection.alt_setting != 0 {
+            interface
+                .set_alt_setting(selection.alt_setting)
                 .wait()
                 .map_err(|e| TransportError::ClaimInterfaceFailed {
-                    interface: 0,
+                    interface: selection.interface,
                     message: e.to_string(),
                 })?;
-
-        // Find BULK endpoints
-        let mut in_endpoint: u8 = 0;
-        let mut out_endpoint: u8 = 0;
-
-        for config in device.configurations() {
-            for iface in config.interfaces() {
-                if iface.interface_number() == 0 {
-                    for alt in iface.alt_settings() {
-                        for ep in alt.endpoints() {
-                            if ep.transfer_type() == nusb::descriptors::TransferType::Bulk {
-                                if ep.direction() == nusb::transfer::Direction::In {
-                                    in_endpoint = ep.address();
-                                } else {
-                                    out_endpoint = ep.address();
-                                }
-                            }
-                        }
-                    }
-                }
-            }
         }
 
-        if in_endpoint == 0 {
-            return Err(TransportError::EndpointNotFound {
-                ep_type: "Bulk".into(),
-                direction: "In".into(),
-            });
-        }
-        if out_endpoint == 0 {
-            return Err(TransportError::EndpointNotFound {
-                ep_type: "Bulk".into(),
-                direction: "Out".into(),
-            });
-        }
+        let in_endpoint = selection.in_endpoint;
+        let out_endpoint = selection.out_endpoint;
 
         info!(
+            interface = selection.interface,
+            alt_setting = selection.alt_setting,
             in_ep = %format!("0x{:02X}", in_endpoint),
+            in_type = ?selection.in_transfer_type,
             out_ep = %format!("0x{:02X}", out_endpoint),
             "Device opened successfully"
         );
 
         Ok(Self {
+            device,
             interface,
             in_endpoint,
+            in_transfer_type: selection.in_transfer_type,
             out_endpoint,
             vid,
             pid,
+            write_queue_depth: 1,
         })
     }
 }
 
+fn read_endpoint<E: BulkOrInterrupt>(
+    ep: nusb::Endpoint<E, In>,
+    max_len: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>, TransportError> {
+    let mut reader = ep.reader(4096).with_read_timeout(timeout);
+    let mut buf = vec![0u8; max_len];
+
+    let n = reader.read(&mut buf).map_err(|e| match e.kind() {
+        ErrorKind::TimedOut => TransportError::Timeout {
+            timeout_ms: timeout.as_millis() as u64,
+        },
+        _ => TransportError::ReadFailed(e.to_string()),
+    })?;
+
+    buf.truncate(n);
+    Ok(buf)
+}
+
+impl NusbTransport {
+    fn read_in(&self, max_len: usize, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        let buf = match self.in_transfer_type {
+            TransferType::Interrupt => read_endpoint(
+                self.interface
+                    .endpoint::<Interrupt, In>(self.in_endpoint)
+                    .map_err(|e| TransportError::ReadFailed(e.to_string()))?,
+                max_len,
+                timeout,
+            )?,
+            _ => read_endpoint(
+                self.interface
+                    .endpoint::<Bulk, In>(self.in_endpoint)
+                    .map_err(|e| TransportError::ReadFailed(e.to_string()))?,
+                max_len,
+                timeout,
+            )?,
+        };
+
+        debug!(bytes_read = buf.len(), "Read complete");
+        Ok(buf)
+    }
+}
+
+/// Async `read_endpoint` on one native IN transfer.
+///
+/// Dropping the future drops the endpoint, which cancels the transfer.
+#[cfg(feature = "async")]
+async fn read_endpoint_async<E: BulkOrInterrupt>(
+    mut ep: nusb::Endpoint<E, In>,
+    max_len: usize,
+    timeout: Duration,
+) -> Result<Vec<u8>, TransportError> {
+    // IN transfers must request whole packets
+    let packet = ep.max_packet_size().max(1);
+    ep.submit(nusb::transfer::Buffer::new(
+        max_len.div_ceil(packet).max(1) * packet,
+    ));
+
+    let completion = match tokio::time::timeout(timeout, ep.next_complete()).await {
+        Ok(completion) => completion,
+        Err(_) => {
+            ep.cancel_all();
+            // Keep whatever arrived before the cancel took effect
+            let late = ep.next_complete().await;
+            if late.buffer.is_empty() {
+                return Err(TransportError::Timeout {
+                    timeout_ms: timeout.as_millis() as u64,
+                });
+            }
+            late
+        }
+    };
+
+    let mut buf = match completion.status {
+        Ok(()) | Err(nusb::transfer::TransferError::Cancelled) => completion.buffer.into_vec(),
+        Err(e) => return Err(TransportError::ReadFailed(e.to_string())),
+    };
+    buf.truncate(max_len);
+    Ok(buf)
+}
+
+#[cfg(feature = "async")]
+impl NusbTransport {
+    /// Async `read_with_timeout` using nusb's native transfers, so waiting
+    /// for the device yields to the runtime. Needs a Tokio runtime.
+    pub async fn read_async(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        let buf = match self.in_transfer_type {
+            TransferType::Interrupt => {
+                read_endpoint_async(
+                    self.interface
+                        .endpoint::<Interrupt, In>(self.in_endpoint)
+                        .map_err(|e| TransportError::ReadFailed(e.to_string()))?,
+                    max_len,
+                    timeout,
+                )
+                .await?
+            }
+            _ => {
+                read_endpoint_async(
+                    self.interface
+                        .endpoint::<Bulk, In>(self.in_endpoint)
+                        .map_err(|e| TransportError::ReadFailed(e.to_string()))?,
+                    max_len,
+                    timeout,
+                )
+                .await?
+            }
+        };
+
+        debug!(bytes_read = buf.len(), "Read complete");
+        Ok(buf)
+    }
+
+    /// Async `read_ack_with_timeout`.
+    pub async fn read_ack_async(&self, timeout: Duration) -> Result<AckCode, TransportError> {
+        let bytes = super::traits::reassemble_ack_async(|| self.read_async(512, timeout)).await?;
+        log_raw_ack(&bytes);
+        Ok(AckCode::from_bytes(&bytes))
+    }
+}
+
+fn log_raw_ack(bytes: &[u8]) {
+    debug!(
+        raw_bytes = ?bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
+        ascii = ?String::from_utf8_lossy(bytes),
+        "Raw ACK received"
+    );
+}
+
 impl UsbTransport for NusbTransport {
     #[instrument(skip(self, data), fields(len = data.len()))]
     fn write(&self, data: &[u8]) -> Result<usize, TransportError> {
@@ -136,7 +602,9 @@ impl UsbTransport for NusbTransport {
             .endpoint::<Bulk, Out>(self.out_endpoint)
             .map_err(|e| TransportError::WriteFailed(e.to_string()))?;
 
-        let mut writer = ep.writer(4096);
+        let mut writer = ep
+            .writer(WRITE_TRANSFER_SIZE)
+            .with_num_transfers(self.write_queue_depth);
         writer
             .write_all(data)
             .map_err(|e| TransportError::WriteFailed(e.to_string()))?;
@@ -150,37 +618,40 @@ impl UsbTransport for NusbTransport {
 
     #[instrument(skip(self), fields(max_len))]
     fn read(&self, max_len: usize) -> Result<Vec<u8>, TransportError> {
-        let ep = self
-            .interface
-            .endpoint::<Bulk, In>(self.in_endpoint)
-            .map_err(|e| TransportError::ReadFailed(e.to_string()))?;
-
-        let mut reader = ep.reader(4096);
-        let mut buf = vec![0u8; max_len];
-
-        let n = reader
-            .read(&mut buf)
-            .map_err(|e| TransportError::ReadFailed(e.to_string()))?;
+        self.read_in(max_len, Duration::MAX)
+    }
 
-        buf.truncate(n);
-        debug!(bytes_read = n, "Read complete");
-        Ok(buf)
+    #[instrument(skip(self), fields(max_len, timeout_ms = timeout.as_millis() as u64))]
+    fn read_with_timeout(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        self.read_in(max_len, timeout)
     }
 
     fn read_ack(&self) -> Result<AckCode, TransportError> {
-        let bytes = self.read(512)?;
-        if bytes.is_empty() {
-            return Err(TransportError::ReadFailed("Empty ACK response".into()));
-        }
-        // Debug: print raw ACK bytes
-        debug!(
-            raw_bytes = ?bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
-            ascii = ?String::from_utf8_lossy(&bytes),
-            "Raw ACK received"
-        );
+        let bytes = read_ack_bytes(self)?;
+        log_raw_ack(&bytes);
         Ok(AckCode::from_bytes(&bytes))
     }
 
+    fn read_ack_with_timeout(&self, timeout: Duration) -> Result<AckCode, TransportError> {
+        let bytes = read_ack_bytes_with_timeout(self, timeout)?;
+        log_raw_ack(&bytes);
+        Ok(AckCode::from_bytes(&bytes))
+    }
+
+    #[instrument(skip(self))]
+    fn reset(&self) -> Result<(), TransportError> {
+        self.device
+            .reset()
+            .wait()
+            .map_err(|e| TransportError::ResetFailed(e.to_string()))?;
+        debug!("USB reset issued");
+        Ok(())
+    }
+
     fn is_connected(&self) -> bool {
         // nusb doesn't provide a direct "is connected" check.
         // We could try a zero-length read, but for now just return true.
@@ -195,3 +666,142 @@ impl UsbTransport for NusbTransport {
         self.pid
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(serial: Option<&str>, bus: u8, address: u8) -> DeviceDescriptor {
+        DeviceDescriptor {
+            vid: INTEL_VENDOR_ID,
+            pid: 0xE005,
+            serial: serial.map(str::to_owned),
+            bus: Some(bus),
+            address,
+            platform: platform_name(0xE005),
+        }
+    }
+
+    #[test]
+    fn test_select_device() {
+        let one = [candidate(Some("A1"), 1, 4)];
+        let two = [candidate(Some("A1"), 1, 4), candidate(None, 2, 7)];
+        let not_found = (INTEL_VENDOR_ID, 0);
+
+        assert_eq!(select_device(&one, None, not_found).unwrap(), 0);
+        assert!(matches!(
+            select_device(&[], None, not_found),
+            Err(TransportError::DeviceNotFound { pid: 0, .. })
+        ));
+        match select_device(&two, None, not_found) {
+            Err(TransportError::MultipleDevices { candidates }) => assert_eq!(
+                candidates,
+                ["A1 (8086:E005 at 1:4)", "no serial (8086:E005 at 2:7)"]
+            ),
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let serial = DeviceSelector::Serial("A1".into());
+        assert_eq!(select_device(&two, Some(&serial), not_found).unwrap(), 0);
+        let at = DeviceSelector::BusAddr { bus: 2, address: 7 };
+        assert_eq!(select_device(&two, Some(&at), not_found).unwrap(), 1);
+        let missing = DeviceSelector::Serial("B2".into());
+        assert!(matches!(
+            select_device(&two, Some(&missing), not_found),
+            Err(TransportError::DeviceNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_udev_rule_for_intel() {
+        assert_eq!(
+            udev_rule(INTEL_VENDOR_ID),
+            r#"SUBSYSTEM=="usb", ATTR{idVendor}=="8086", MODE="0666""#
+        );
+    }
+
+    #[test]
+    fn test_is_supported_device() {
+        assert!(is_supported_device(INTEL_VENDOR_ID, SUPPORTED_PIDS[0]));
+        assert!(!is_supported_device(INTEL_VENDOR_ID, 0xFFFF));
+        assert!(!is_supported_device(0x1234, SUPPORTED_PIDS[0]));
+    }
+
+    fn ep(address: u8, transfer_type: TransferType) -> EndpointInfo {
+        EndpointInfo {
+            address,
+            transfer_type,
+        }
+    }
+
+    #[test]
+    fn test_select_endpoints_skips_interfaces_without_pair() {
+        let interfaces = vec![
+            InterfaceEndpoints {
+                interface: 0,
+                alt_setting: 0,
+                endpoints: vec![ep(0x83, TransferType::Interrupt)],
+            },
+            InterfaceEndpoints {
+                interface: 1,
+                alt_setting: 0,
+                endpoints: vec![ep(0x81, TransferType::Bulk), ep(0x01, TransferType::Bulk)],
+            },
+        ];
+
+        let sel = select_endpoints(&interfaces, None).unwrap();
+        assert_eq!(sel.interface, 1);
+        assert_eq!(sel.in_endpoint, 0x81);
+        assert_eq!(sel.out_endpoint, 0x01);
+    }
+
+    #[test]
+    fn test_select_endpoints_interrupt_in_fallback() {
+        let interfaces = vec![InterfaceEndpoints {
+            interface: 0,
+            alt_setting: 0,
+            endpoints: vec![
+                ep(0x81, TransferType::Interrupt),
+                ep(0x01, TransferType::Bulk),
+            ],
+        }];
+
+        let sel = select_endpoints(&interfaces, None).unwrap();
+        assert_eq!(sel.in_endpoint, 0x81);
+        assert_eq!(sel.in_transfer_type, TransferType::Interrupt);
+
+        // A bulk IN anywhere is still preferred over the fallback
+        let mut with_bulk = interfaces.clone();
+        with_bulk.push(InterfaceEndpoints {
+            interface: 1,
+            alt_setting: 0,
+            endpoints: vec![ep(0x82, TransferType::Bulk), ep(0x02, TransferType::Bulk)],
+        });
+        let sel = select_endpoints(&with_bulk, None).unwrap();
+        assert_eq!(sel.interface, 1);
+        assert_eq!(sel.in_transfer_type, TransferType::Bulk);
+    }
+
+    #[test]
+    fn test_select_endpoints_reports_found_descriptors() {
+        let interfaces = vec![InterfaceEndpoints {
+            interface: 0,
+            alt_setting: 0,
+            endpoints: vec![ep(0x81, TransferType::Bulk)],
+        }];
+
+        let err = select_endpoints(&interfaces, None).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("if0/alt0"));
+        assert!(msg.contains("0x81 Bulk IN"));
+
+        // Hint excludes the only interface with a pair
+        let interfaces = vec![InterfaceEndpoints {
+            interface: 2,
+            alt_setting: 0,
+            endpoints: vec![ep(0x82, TransferType::Bulk), ep(0x02, TransferType::Bulk)],
+        }];
+        assert!(select_endpoints(&interfaces, Some(0)).is_err());
+        assert!(select_endpoints(&interfaces, Some(2)).is_ok());
+    }
+}
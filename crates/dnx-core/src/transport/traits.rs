@@ -3,7 +3,9 @@
 //! Defines the `UsbTransport` trait for USB communication,
 //! allowing different implementations (nusb, mock, etc.).
 
-use crate::protocol::AckCode;
+use std::time::Duration;
+
+use crate::protocol::{AckCode, AckDef};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,15 +13,27 @@ pub enum TransportError {
     #[error("Device not found: VID={vid:04X} PID={pid:04X}")]
     DeviceNotFound { vid: u16, pid: u16 },
 
+    #[error(
+        "Several devices attached ({}); select one by serial number or bus/address",
+        candidates.join(", ")
+    )]
+    MultipleDevices { candidates: Vec<String> },
+
     #[error("Failed to open device: {0}")]
     OpenFailed(String),
 
+    #[error("Permission denied opening device {vid:04X}:{pid:04X}. {hint}")]
+    PermissionDenied { vid: u16, pid: u16, hint: String },
+
     #[error("Failed to claim interface {interface}: {message}")]
     ClaimInterfaceFailed { interface: u8, message: String },
 
     #[error("Endpoint not found: type={ep_type}, direction={direction}")]
     EndpointNotFound { ep_type: String, direction: String },
 
+    #[error("No interface with a bulk OUT and bulk/interrupt IN endpoint (found: {found})")]
+    NoBulkEndpointPair { found: String },
+
     #[error("Write failed: {0}")]
     WriteFailed(String),
 
@@ -29,6 +43,9 @@ pub enum TransportError {
     #[error("Device disconnected")]
     Disconnected,
 
+    #[error("Reset failed: {0}")]
+    ResetFailed(String),
+
     #[error("Timeout after {timeout_ms}ms")]
     Timeout { timeout_ms: u64 },
 
@@ -49,13 +66,33 @@ pub trait UsbTransport: Send + Sync {
     /// Read raw bytes from the IN endpoint.
     fn read(&self, max_len: usize) -> Result<Vec<u8>, TransportError>;
 
+    /// Read raw bytes, failing with `TransportError::Timeout` after `timeout`.
+    ///
+    /// The default ignores the timeout and falls back to `read`.
+    fn read_with_timeout(
+        &self,
+        max_len: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, TransportError> {
+        let _ = timeout;
+        self.read(max_len)
+    }
+
     /// Read and parse ACK code from device.
     fn read_ack(&self) -> Result<AckCode, TransportError> {
-        let bytes = self.read(512)?;
-        if bytes.is_empty() {
-            return Err(TransportError::ReadFailed("Empty response".into()));
-        }
-        Ok(AckCode::from_bytes(&bytes))
+        read_ack_bytes(self).map(|bytes| AckCode::from_bytes(&bytes))
+    }
+
+    /// Read and parse an ACK code, giving up on each read after `timeout`.
+    fn read_ack_with_timeout(&self, timeout: Duration) -> Result<AckCode, TransportError> {
+        read_ack_bytes_with_timeout(self, timeout).map(|bytes| AckCode::from_bytes(&bytes))
+    }
+
+    /// Issue a USB port reset so the device re-enumerates sooner.
+    ///
+    /// The handle is unusable afterwards. The default does nothing.
+    fn reset(&self) -> Result<(), TransportError> {
+        Ok(())
     }
 
     /// Check if device is still connected.
@@ -67,3 +104,71 @@ pub trait UsbTransport: Send + Sync {
     /// Get the current PID.
     fn product_id(&self) -> u16;
 }
+
+/// Maximum number of reads used to reassemble one split ACK.
+const MAX_ACK_READS: usize = 4;
+
+/// Read one ACK, reassembling it if the device split it across bulk packets.
+///
+/// Keeps reading while the bytes so far are a strict prefix of a known ACK
+/// (e.g. `RUP` of `RUPHS`), until the ACK is complete, a read times out or
+/// comes back empty, or `MAX_ACK_READS` is reached.
+pub fn read_ack_bytes<T: UsbTransport + ?Sized>(transport: &T) -> Result<Vec<u8>, TransportError> {
+    reassemble_ack(|| transport.read(512))
+}
+
+/// Like `read_ack_bytes`, but every read gives up after `timeout`.
+pub fn read_ack_bytes_with_timeout<T: UsbTransport + ?Sized>(
+    transport: &T,
+    timeout: Duration,
+) -> Result<Vec<u8>, TransportError> {
+    reassemble_ack(|| transport.read_with_timeout(512, timeout))
+}
+
+/// Async `reassemble_ack`: `read` yields one read per call.
+#[cfg(feature = "async")]
+pub(crate) async fn reassemble_ack_async<F, Fut>(mut read: F) -> Result<Vec<u8>, TransportError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<u8>, TransportError>>,
+{
+    let mut bytes = read().await?;
+    if bytes.is_empty() {
+        return Err(TransportError::ReadFailed("Empty response".into()));
+    }
+
+    for _ in 1..MAX_ACK_READS {
+        if !AckDef::is_partial(&bytes) {
+            break;
+        }
+        match read().await {
+            Ok(more) if !more.is_empty() => bytes.extend_from_slice(&more),
+            Ok(_) | Err(TransportError::Timeout { .. }) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn reassemble_ack(
+    mut read: impl FnMut() -> Result<Vec<u8>, TransportError>,
+) -> Result<Vec<u8>, TransportError> {
+    let mut bytes = read()?;
+    if bytes.is_empty() {
+        return Err(TransportError::ReadFailed("Empty response".into()));
+    }
+
+    for _ in 1..MAX_ACK_READS {
+        if !AckDef::is_partial(&bytes) {
+            break;
+        }
+        match read() {
+            Ok(more) if !more.is_empty() => bytes.extend_from_slice(&more),
+            Ok(_) | Err(TransportError::Timeout { .. }) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(bytes)
+}
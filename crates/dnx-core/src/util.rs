@@ -0,0 +1,146 @@
+//! Small helpers for inspecting binary data.
+
+/// Bytes shown per hex-dump row.
+const HEXDUMP_WIDTH: usize = 16;
+
+/// Format `data` as classic hex-dump rows, numbering offsets from `base_offset`:
+///
+/// ```text
+/// 00000100  24 44 6E 58 00 00 00 00  01 02 03 04 05 06 07 08  |$DnX............|
+/// ```
+///
+/// A short final row is padded so its ASCII column lines up.
+pub fn hexdump(data: &[u8], base_offset: usize) -> String {
+    let mut out = String::new();
+    for (row, chunk) in data.chunks(HEXDUMP_WIDTH).enumerate() {
+        out.push_str(&format!("{:08X} ", base_offset + row * HEXDUMP_WIDTH));
+        for i in 0..HEXDUMP_WIDTH {
+            if i % 8 == 0 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(b) => out.push_str(&format!("{:02X} ", b)),
+                None => out.push_str("   "),
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(" |{}|\n", ascii));
+    }
+    out
+}
+
+/// First offset of each of `patterns` in `data`, found in a single pass.
+///
+/// Equivalent to a `windows().position()` search per pattern, but the buffer
+/// is traversed once however many patterns there are, and only offsets whose
+/// byte starts some pattern are compared. Empty patterns are never found.
+pub fn first_positions<const N: usize>(data: &[u8], patterns: [&[u8]; N]) -> [Option<usize>; N] {
+    let mut found = [None; N];
+    let mut starts = [false; 256];
+    let mut remaining = 0;
+    for first in patterns.iter().filter_map(|p| p.first()) {
+        starts[*first as usize] = true;
+        remaining += 1;
+    }
+
+    for (i, &byte) in data.iter().enumerate() {
+        if remaining == 0 {
+            break;
+        }
+        if !starts[byte as usize] {
+            continue;
+        }
+        let rest = &data[i..];
+        for (slot, pattern) in found.iter_mut().zip(&patterns) {
+            if slot.is_none() && !pattern.is_empty() && rest.starts_with(pattern) {
+                *slot = Some(i);
+                remaining -= 1;
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The per-pattern scan `first_positions` replaces.
+    fn naive_positions<const N: usize>(data: &[u8], patterns: [&[u8]; N]) -> [Option<usize>; N] {
+        patterns.map(|p| data.windows(p.len()).position(|w| w == p))
+    }
+
+    const PATTERNS: [&[u8]; 6] = [b"$DnX", b"CH00", b"CDPH", b"DTKN", b"ANDROID!", b"$OS$"];
+
+    #[test]
+    fn test_first_positions_matches_naive_scan() {
+        let mut data: Vec<u8> = (0..0x4000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+        for (pos, marker) in [
+            (0x80, b"$DnX".as_slice()),
+            (0x900, b"CH00"),
+            (0x100, b"CH00"),
+            (0x3FFC, b"CDPH"),
+            (0x2000, b"ANDROID"), // prefix only
+            (0x2100, b"ANDROID!"),
+        ] {
+            data[pos..pos + marker.len()].copy_from_slice(marker);
+        }
+
+        assert_eq!(
+            first_positions(&data, PATTERNS),
+            naive_positions(&data, PATTERNS)
+        );
+        assert_eq!(
+            first_positions(&data, PATTERNS),
+            [
+                Some(0x80),
+                Some(0x100),
+                Some(0x3FFC),
+                None,
+                Some(0x2100),
+                None
+            ]
+        );
+        assert_eq!(first_positions(&data[..3], PATTERNS), [None; 6]);
+        assert_eq!(
+            first_positions(b"ab", [b"".as_slice(), b"b"]),
+            [None, Some(1)]
+        );
+    }
+
+    /// `cargo test -p dnx-core --release bench_first_positions -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_first_positions() {
+        let data: Vec<u8> = (0..64 << 20).map(|i: u32| (i % 251) as u8).collect();
+        let time = |f: &dyn Fn() -> [Option<usize>; 6]| {
+            let start = std::time::Instant::now();
+            let result = f();
+            (start.elapsed(), result)
+        };
+        let (single, a) = time(&|| first_positions(&data, PATTERNS));
+        let (naive, b) = time(&|| naive_positions(&data, PATTERNS));
+        assert_eq!(a, b);
+        println!("64 MiB, 6 patterns: single pass {single:?}, per-pattern {naive:?}");
+    }
+
+    #[test]
+    fn test_hexdump_rows() {
+        let data = b"$DnX\x00\x01\x02\x03Hello, DnX!\xFF\x7F\n\x00";
+        assert_eq!(
+            hexdump(data, 0x100),
+            "00000100  24 44 6E 58 00 01 02 03  48 65 6C 6C 6F 2C 20 44  |$DnX....Hello, D|\n\
+             00000110  6E 58 21 FF 7F 0A 00                              |nX!....|\n"
+        );
+        assert_eq!(hexdump(&[], 0), "");
+    }
+}
@@ -5,6 +5,8 @@
 
 use anyhow::Result;
 use clap::{Parser, Subcommand, ValueEnum};
+use dnx_core::format_size;
+use dnx_core::profiles::{PROFILES_FILE, ProfileRegistry};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
@@ -26,6 +28,16 @@ fn firmware_dir() -> PathBuf {
     assets_dir().join("firmware")
 }
 
+/// Built-in profiles plus the project's `profiles.toml`, if any.
+fn profile_registry() -> Result<ProfileRegistry> {
+    let mut registry = ProfileRegistry::builtin();
+    let file = project_root().join(PROFILES_FILE);
+    if file.exists() {
+        registry.load_file(file)?;
+    }
+    Ok(registry)
+}
+
 #[derive(Parser)]
 #[command(name = "xtask")]
 #[command(about = "Development tasks for dnx-rs", long_about = None)]
@@ -60,7 +72,7 @@ enum Commands {
 
     /// Run the CLI
     Run {
-        /// Profile to use (eaglespeak, blackburn)
+        /// Profile to use (see `cargo xtask firmware list`)
         #[arg(short, long)]
         profile: Option<String>,
 
@@ -120,6 +132,10 @@ enum Commands {
         /// Also clean downloaded firmware
         #[arg(long)]
         all: bool,
+
+        /// Also delete the TUI logs in the user's log directory
+        #[arg(long)]
+        logs: bool,
     },
 
     /// Setup development environment
@@ -156,14 +172,22 @@ enum FirmwareCommands {
     /// List available firmware profiles
     List,
 
-    /// Download firmware from source
+    /// Download firmware from source (resumes partial downloads)
     Download {
         /// Profile name
         profile: String,
 
         /// Source URL
         #[arg(long)]
-        url: Option<String>,
+        url: String,
+
+        /// Which profile file the download is; inferred from the URL if omitted
+        #[arg(long, value_enum)]
+        component: Option<FirmwareComponent>,
+
+        /// Expected SHA-256 of the file, in hex
+        #[arg(long)]
+        sha256: Option<String>,
     },
 
     /// Validate firmware integrity
@@ -181,7 +205,7 @@ enum FirmwareCommands {
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Component to extract (token, chaabi, ifwi, all)
+        /// Component to extract (token, chaabi, ifwi, cdph, header, all)
         #[arg(short, long, default_value = "all")]
         component: String,
     },
@@ -197,6 +221,14 @@ enum FirmwareCommands {
         /// Show detailed diff
         #[arg(long)]
         detailed: bool,
+
+        /// Ignore the RSA signature block and FUPH trailer
+        #[arg(long)]
+        ignore_rsa: bool,
+
+        /// Output format (text, json, markdown)
+        #[arg(short, long, default_value = "text")]
+        format: OutputFormat,
     },
 }
 
@@ -209,6 +241,14 @@ enum BuildTarget {
     Core,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum FirmwareComponent {
+    FwDnx,
+    FwImage,
+    OsDnx,
+    OsImage,
+}
+
 #[derive(Clone, Copy, ValueEnum, Default)]
 enum OutputFormat {
     #[default]
@@ -237,12 +277,17 @@ fn main() -> Result<()> {
         Commands::Doc { open } => cmd_doc(open)?,
         Commands::Check { fix } => cmd_check(fix)?,
         Commands::Release { bump, github } => cmd_release(bump, github)?,
-        Commands::Clean { all } => cmd_clean(all)?,
+        Commands::Clean { all, logs } => cmd_clean(all, logs)?,
         Commands::Setup => cmd_setup()?,
         Commands::Report { output, all } => cmd_report(output, all)?,
         Commands::Firmware { cmd } => match cmd {
             FirmwareCommands::List => cmd_firmware_list()?,
-            FirmwareCommands::Download { profile, url } => cmd_firmware_download(&profile, url)?,
+            FirmwareCommands::Download {
+                profile,
+                url,
+                component,
+                sha256,
+            } => cmd_firmware_download(&profile, &url, component, sha256.as_deref())?,
             FirmwareCommands::Validate { target } => cmd_firmware_validate(&target)?,
             FirmwareCommands::Extract {
                 source,
@@ -253,7 +298,9 @@ fn main() -> Result<()> {
                 file1,
                 file2,
                 detailed,
-            } => cmd_firmware_compare(&file1, &file2, detailed)?,
+                ignore_rsa,
+                format,
+            } => cmd_firmware_compare(&file1, &file2, detailed, ignore_rsa, format)?,
         },
         Commands::GenerateTest { name } => cmd_generate_test(&name)?,
     }
@@ -332,6 +379,7 @@ fn cmd_run(profile: Option<String>, args: Vec<String>) -> Result<()> {
     cmd.args(["run", "-p", "dnx-cli", "--"]);
 
     if let Some(p) = profile {
+        profile_registry()?.get(&p)?;
         cmd.args(["-p", &p]);
     }
 
@@ -490,7 +538,7 @@ fn cmd_release(bump: Option<VersionBump>, github: bool) -> Result<()> {
     Ok(())
 }
 
-fn cmd_clean(all: bool) -> Result<()> {
+fn cmd_clean(all: bool, logs: bool) -> Result<()> {
     let root = project_root();
     println!("🧹 Cleaning build artifacts...");
 
@@ -505,9 +553,16 @@ fn cmd_clean(all: bool) -> Result<()> {
 
     if all {
         println!("  → Cleaning additional artifacts...");
+        // Older TUI builds logged to the working directory
         let _ = std::fs::remove_file(root.join("dnx-tui.log"));
     }
 
+    if logs {
+        let dir = dnx_core::paths::log_dir();
+        println!("  → Removing logs in {}...", dir.display());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
     println!("✅ Clean complete");
     Ok(())
 }
@@ -601,30 +656,33 @@ fn test_{}() {{
 // ============================================================================
 
 fn cmd_firmware_list() -> Result<()> {
-    let fw_dir = firmware_dir();
+    let root = project_root();
     println!("📦 Available firmware profiles:\n");
 
-    for entry in std::fs::read_dir(&fw_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if path.is_dir() {
-            let name = path.file_name().unwrap().to_string_lossy();
-            let dnx_fwr = path.join("dnx_fwr.bin");
-            let dnx_osr = path.join("dnx_osr.img");
-
-            println!("  {} {}", if dnx_fwr.exists() { "✅" } else { "❌" }, name);
-
-            if dnx_fwr.exists() {
-                let size = std::fs::metadata(&dnx_fwr)?.len();
-                println!("     └─ dnx_fwr.bin ({} bytes)", size);
-            }
-            if dnx_osr.exists() {
-                let size = std::fs::metadata(&dnx_osr)?.len();
-                println!(
-                    "     └─ dnx_osr.img ({:.2} MB)",
-                    size as f64 / 1024.0 / 1024.0
-                );
+    for profile in profile_registry()?.iter() {
+        let files: Vec<_> = [
+            &profile.fw_dnx,
+            &profile.fw_image,
+            &profile.os_dnx,
+            &profile.os_image,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|f| root.join(f))
+        .collect();
+        let complete = files.iter().all(|f| f.exists());
+
+        println!(
+            "  {} {} - {}",
+            if complete { "✅" } else { "❌" },
+            profile.name,
+            profile.description
+        );
+        for file in &files {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            match std::fs::metadata(file) {
+                Ok(meta) => println!("     └─ {} ({})", name, format_size(meta.len())),
+                Err(_) => println!("     └─ {} (missing)", name),
             }
         }
     }
@@ -632,16 +690,133 @@ fn cmd_firmware_list() -> Result<()> {
     Ok(())
 }
 
-fn cmd_firmware_download(profile: &str, url: Option<String>) -> Result<()> {
+fn cmd_firmware_download(
+    profile: &str,
+    url: &str,
+    component: Option<FirmwareComponent>,
+    sha256: Option<&str>,
+) -> Result<()> {
     println!("📥 Downloading firmware for profile: {}", profile);
+    println!("  Source: {}", url);
 
-    if let Some(url) = url {
-        println!("  Source: {}", url);
-        // TODO: Implement download
+    // OS images are .img; anything else is taken to be the FW DnX
+    let component = component.unwrap_or(if url.ends_with(".img") {
+        FirmwareComponent::OsImage
     } else {
-        println!("⚠️  No download URL specified");
+        FirmwareComponent::FwDnx
+    });
+    let dest = download_destination(profile, url, component)?;
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    println!("  Destination: {}", dest.display());
+
+    // Download into a .part file so an interrupted run can resume
+    let mut part = dest.clone().into_os_string();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    let offset = std::fs::metadata(&part).map_or(0, |meta| meta.len());
+    if offset > 0 {
+        println!("  Resuming from {}", format_size(offset));
+    }
+    fetch(url, &part, offset)?;
+
+    if let Some(expected) = sha256 {
+        let actual = dnx_core::firmware::sha256_hex(&std::fs::read(&part)?);
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            std::fs::remove_file(&part)?;
+            anyhow::bail!(
+                "SHA-256 mismatch for {}: expected {}, got {}",
+                url,
+                expected,
+                actual
+            );
+        }
+        println!("  ✅ SHA-256 verified");
+    }
+
+    std::fs::rename(&part, &dest)?;
+    let size = std::fs::metadata(&dest)?.len();
+    println!("✅ Saved {} ({})", dest.display(), format_size(size));
+    Ok(())
+}
+
+/// Where a downloaded `component` goes: the profile's path for it, or
+/// `assets/firmware/<profile>/` under the URL's file name.
+fn download_destination(profile: &str, url: &str, component: FirmwareComponent) -> Result<PathBuf> {
+    let registry = profile_registry()?;
+    let known = registry.get(profile).ok().and_then(|p| match component {
+        FirmwareComponent::FwDnx => p.fw_dnx.clone(),
+        FirmwareComponent::FwImage => p.fw_image.clone(),
+        FirmwareComponent::OsDnx => p.os_dnx.clone(),
+        FirmwareComponent::OsImage => p.os_image.clone(),
+    });
+    if let Some(path) = known {
+        return Ok(project_root().join(path));
     }
 
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|u| u.rsplit('/').next())
+        .filter(|n| !n.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Cannot derive a file name from {}", url))?;
+    Ok(firmware_dir().join(profile).join(name))
+}
+
+/// Download `url` into `part`, continuing after its first `offset` bytes
+/// when the server honours range requests.
+fn fetch(url: &str, part: &Path, offset: u64) -> Result<()> {
+    use std::io::{Read, Write};
+
+    let mut request = ureq::get(url);
+    if offset > 0 {
+        request = request.header("Range", format!("bytes={}-", offset));
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        // Nothing past `offset`: an earlier run already got the whole file
+        Err(ureq::Error::StatusCode(416)) if offset > 0 => return Ok(()),
+        Err(ureq::Error::StatusCode(code)) => {
+            anyhow::bail!("HTTP error {} downloading {}", code, url)
+        }
+        Err(e) => anyhow::bail!("Download of {} failed ({}); re-run to resume", url, e),
+    };
+
+    // A 200 to a range request is the whole file again
+    let resumed = response.status() == 206;
+    let mut written = if resumed { offset } else { 0 };
+    let total = response.body().content_length().map(|len| len + written);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part)?;
+
+    let mut reader = response.into_body().into_reader();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .map_err(|e| anyhow::anyhow!("Download of {} failed ({}); re-run to resume", url, e))?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n])?;
+        written += n as u64;
+        match total {
+            Some(total) => print!(
+                "\r  {} / {} ({}%)",
+                format_size(written),
+                format_size(total),
+                written * 100 / total.max(1)
+            ),
+            None => print!("\r  {}", format_size(written)),
+        }
+        std::io::stdout().flush()?;
+    }
+    println!();
     Ok(())
 }
 
@@ -651,7 +826,13 @@ fn cmd_firmware_validate(target: &str) -> Result<()> {
     let path = if Path::new(target).exists() {
         PathBuf::from(target)
     } else {
-        firmware_dir().join(target).join("dnx_fwr.bin")
+        let registry = profile_registry()?;
+        let profile = registry.get(target)?;
+        let fw_dnx = profile
+            .fw_dnx
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Profile '{}' has no FW DnX image", target))?;
+        project_root().join(fw_dnx)
     };
 
     if !path.exists() {
@@ -662,7 +843,11 @@ fn cmd_firmware_validate(target: &str) -> Result<()> {
     let analysis = dnx_core::FirmwareAnalysis::analyze(&path)?;
 
     println!("  File: {}", analysis.filename);
-    println!("  Size: {} bytes", analysis.size);
+    println!(
+        "  Size: {} bytes ({})",
+        analysis.size,
+        format_size(analysis.size)
+    );
     println!("  Type: {}", analysis.file_type);
     println!();
     println!("  Validation checks:");
@@ -695,47 +880,22 @@ fn cmd_firmware_extract(source: &Path, output: Option<PathBuf>, component: &str)
     println!("  Output: {}", output_dir.display());
 
     let data = std::fs::read(source)?;
-
-    // Find markers
-    let find_marker = |pattern: &[u8]| -> Option<usize> {
-        data.windows(pattern.len()).position(|w| w == pattern)
-    };
-
-    let cht_pos = find_marker(b"$CHT");
-    let ch00_pos = find_marker(b"CH00");
-    let cdph_pos = find_marker(b"CDPH");
+    let parts = dnx_core::extract_components(&data);
 
     let extract_all = component == "all";
 
-    if (component == "token" || extract_all)
-        && let (Some(cht), Some(ch00)) = (cht_pos, ch00_pos)
-    {
-        let start = cht.saturating_sub(0x80);
-        let end = ch00.saturating_sub(0x80);
-        let token_data = &data[start..end];
-        let path = output_dir.join("token.bin");
-        std::fs::write(&path, token_data)?;
-        println!("  [Done] Extracted token: {} bytes", token_data.len());
-    }
-
-    if (component == "chaabi" || extract_all)
-        && let (Some(ch00), Some(cdph)) = (ch00_pos, cdph_pos)
-    {
-        let start = ch00.saturating_sub(0x80);
-        let chaabi_data = &data[start..cdph];
-        let path = output_dir.join("chaabi.bin");
-        std::fs::write(&path, chaabi_data)?;
-        println!("  [Done] Extracted chaabi: {} bytes", chaabi_data.len());
-    }
-
-    if (component == "ifwi" || extract_all)
-        && let Some(cht) = cht_pos
-    {
-        let end = cht.saturating_sub(0x80);
-        let ifwi_data = &data[..end];
-        let path = output_dir.join("ifwi.bin");
-        std::fs::write(&path, ifwi_data)?;
-        println!("  [Done] Extracted ifwi: {} bytes", ifwi_data.len());
+    if let Some(parts) = &parts {
+        for (name, bytes) in [
+            ("token", &parts.token),
+            ("chaabi", &parts.chaabi),
+            ("ifwi", &parts.ifwi),
+            ("cdph", &parts.cdph),
+        ] {
+            if (component == name || extract_all) && !bytes.is_empty() {
+                std::fs::write(output_dir.join(format!("{}.bin", name)), bytes)?;
+                println!("  [Done] Extracted {}: {} bytes", name, bytes.len());
+            }
+        }
     }
 
     if component == "header" || extract_all {
@@ -745,7 +905,7 @@ fn cmd_firmware_extract(source: &Path, output: Option<PathBuf>, component: &str)
         println!("  [Done] Extracted header: {} bytes", header.len());
     }
 
-    if !extract_all && !matches!(component, "token" | "chaabi" | "ifwi" | "header") {
+    if !extract_all && !matches!(component, "token" | "chaabi" | "ifwi" | "cdph" | "header") {
         println!("  ⚠️  Unknown component: {}", component);
     }
 
@@ -753,29 +913,70 @@ fn cmd_firmware_extract(source: &Path, output: Option<PathBuf>, component: &str)
     Ok(())
 }
 
-fn cmd_firmware_compare(file1: &Path, file2: &Path, detailed: bool) -> Result<()> {
-    println!("🔄 Comparing firmware files...");
-
-    let result = dnx_core::FirmwareComparison::compare(file1, file2)?;
-    println!("{}", result.to_text());
-
-    if detailed && !result.diff_regions.is_empty() {
-        // Detailed binary diff if requested
-        if result.diff_count > 0 && result.diff_count < 1000 {
-            let data1 = std::fs::read(file1)?;
-            let data2 = std::fs::read(file2)?;
-            println!("\n  Detailed differences (first 50):");
-            let mut shown = 0;
-            for (i, (a, b)) in data1.iter().zip(data2.iter()).enumerate() {
-                if a != b {
-                    println!("    0x{:05X}: {:02X} -> {:02X}", i, a, b);
-                    shown += 1;
-                    if shown >= 50 {
-                        println!("    ... ({} more differences)", result.diff_count - 50);
-                        break;
-                    }
-                }
+fn cmd_firmware_compare(
+    file1: &Path,
+    file2: &Path,
+    detailed: bool,
+    ignore_rsa: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let text = matches!(format, OutputFormat::Text);
+    if text {
+        println!("🔄 Comparing firmware files...");
+    }
+
+    let ignore = if ignore_rsa {
+        dnx_core::FirmwareComparison::signature_ranges(file1)?
+    } else {
+        Vec::new()
+    };
+    let mut last_pct = None;
+    let result = dnx_core::FirmwareComparison::compare_with_mask_and_progress(
+        file1,
+        file2,
+        &ignore,
+        |done, total| {
+            let pct = (done * 100).checked_div(total).unwrap_or(100);
+            if last_pct != Some(pct) {
+                eprint!(
+                    "\r  {:>3}% ({} / {})",
+                    pct,
+                    format_size(done),
+                    format_size(total)
+                );
+                last_pct = Some(pct);
             }
+        },
+    )?;
+    if last_pct.is_some() {
+        eprintln!();
+    }
+    match format {
+        OutputFormat::Text => println!("{}", result.to_text()),
+        OutputFormat::Json => println!("{}", result.to_json()),
+        OutputFormat::Markdown => println!("{}", result.to_markdown()),
+    }
+
+    // JSON and markdown already list every region
+    if text && detailed && !result.diff_regions.is_empty() {
+        const MAX_REGIONS: usize = 10;
+        const MAX_BYTES: usize = 64;
+
+        let data1 = std::fs::read(file1)?;
+        let data2 = std::fs::read(file2)?;
+        println!("\n  Detailed differences (first {} regions):", MAX_REGIONS);
+        for r in result.diff_regions.iter().take(MAX_REGIONS) {
+            // Whole 16-byte rows around the region, capped per region
+            let start = r.start & !0xF;
+            let end = (r.end + 1)
+                .min(start + MAX_BYTES)
+                .min(data1.len())
+                .min(data2.len());
+            println!("\n  0x{:05X}-0x{:05X} ({})", r.start, r.end, r.description);
+            println!("  {}:", file1.display());
+            print!("{}", dnx_core::hexdump(&data1[start..end], start));
+            println!("  {}:", file2.display());
+            print!("{}", dnx_core::hexdump(&data2[start..end], start));
         }
     }
 